@@ -5,9 +5,403 @@ extern crate engine;
 extern crate nalgebra_glm as glm;
 extern crate specs;
 extern crate specs_derive;
+extern crate winit;
 
-use engine::start_event_loop;
+use engine::camera::Camera;
+use engine::fps_counter::FpsCounter;
+use engine::gui::{Gui, Inspector};
+use engine::hud::{Hud, Layout, NodeId, Widget};
+use engine::input::{Input, InputBindings};
+use engine::mesh::mesh::Mesh;
+use engine::renderer::debug_draw;
+use engine::renderer::{
+    gather_lights, DrawList, Light, Material, Renderable, Renderer, RendererConfig, ShadowSettings,
+    VisibilityIndex,
+};
+use engine::scene::{self, load_scene, Name, SceneAssets};
+use engine::text::{Alignment, LayoutOptions, SdfStyle, Text};
+use engine::time::Time;
+use engine::utils::file::executable_dir;
+use glm::*;
+use specs::{Builder, Join, World, WorldExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+};
+
+const FLASH_DURATION: f32 = 0.15;
+const MAX_AMMO: u32 = 30;
+/// Units per second the camera moves at.
+const CAMERA_SPEED: f32 = 3.0;
+/// Replaces the bindings in client/input.cfg if it exists next to the executable.
+const BINDINGS_FILE: &str = "input.cfg";
+/// Loaded at startup if it exists next to the executable, and written there by the inspector's
+/// Save button.
+const SCENE_FILE: &str = "scene.xml";
 
 fn main() {
-    start_event_loop();
+    let event_loop = EventLoop::new();
+    let config = RendererConfig {
+        title: "Client".to_string(),
+        msaa_samples: 4,
+        ..RendererConfig::default()
+    };
+    let mut renderer = Renderer::with_config(&event_loop, &config);
+
+    let box_mesh = renderer.register_mesh(&Mesh::create_box(&None));
+    let box_material = renderer.register_material(Material::default()).unwrap();
+
+    let mut world = World::new();
+    scene::register_components(&mut world);
+    let mut visibility = VisibilityIndex::new(&mut world);
+    let mut assets = SceneAssets::new();
+    assets.add_mesh("box", box_mesh);
+    assets.add_material("default", box_material);
+
+    let scene_path = beside_executable(SCENE_FILE);
+    if scene_path.is_file() {
+        if let Err(e) = load_scene(&mut world, &scene_path, &assets) {
+            println!("Failed to load the scene:\n{}", e);
+        }
+    } else {
+        world
+            .create_entity()
+            .with(Name("box".to_string()))
+            .with(Renderable::new(box_mesh, box_material, identity()))
+            .build();
+        world
+            .create_entity()
+            .with(Name("sun".to_string()))
+            .with(
+                Light::directional(vec3(-0.5, -1.0, -0.5), vec3(1.0, 0.95, 0.9), 0.6)
+                    .with_shadows(ShadowSettings::default()),
+            )
+            .build();
+        world
+            .create_entity()
+            .with(Name("lamp".to_string()))
+            .with(Light::point(
+                vec3(5.0, 10.0, 15.0),
+                vec3(1.0, 1.0, 1.0),
+                4.0,
+                40.0,
+            ))
+            .build();
+    }
+    // The first camera in the world is the one looked through
+    if world.read_storage::<Camera>().is_empty() {
+        world
+            .create_entity()
+            .with(Name("camera".to_string()))
+            .with(Camera::new(vec3(0.0, 0.0, 4.0)))
+            .build();
+    }
+
+    let mut gui = Gui::new();
+    let mut inspector = Inspector::new(&scene_path);
+    renderer.set_gui_font(gui.font_atlas());
+
+    let mut hud = Hud::new();
+    let hud_nodes = create_hud(&mut hud);
+    let mut ammo = MAX_AMMO;
+    let mut score = 0;
+
+    let mut input = Input::new(load_bindings());
+
+    let mut fps_text = Text::new("FPS: -", vec2(10.0, 10.0));
+    let mut fps_counter = FpsCounter::new();
+    let mut time = Time::new();
+    // Seconds left of the flash fired with space, standing in for a muzzle flash
+    let mut flash_time = 0.0;
+
+    event_loop.run(move |event, _, control_flow| {
+        let mut hud_used = false;
+        if let Event::WindowEvent { ref event, .. } = event {
+            gui.handle_event(event);
+            if !gui.want_capture_mouse() && !gui.want_capture_keyboard() {
+                hud_used = hud.handle_event(event);
+            }
+        }
+        // Events the HUD used, like clicking a menu button, don't reach gameplay
+        if !hud_used {
+            input.handle_event(&event);
+        }
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(_),
+                ..
+            } => {
+                renderer.resize();
+            }
+            Event::RedrawEventsCleared => {
+                let delta_time = time.delta_time() as f32;
+                flash_time = (flash_time - delta_time).max(0.0);
+                if let Some(fps) = fps_counter.update(delta_time) {
+                    fps_text.set_value(&fps);
+                }
+                fps_text.draw(renderer.text());
+
+                // Actions are ignored while a gui widget has the keyboard
+                if !gui.want_capture_keyboard() {
+                    if input.action_pressed("pause") {
+                        let paused = !hud.is_visible(hud_nodes.menu);
+                        hud.set_visible(hud_nodes.menu, paused);
+                        hud.set_focus(if paused { Some(hud_nodes.resume) } else { None });
+                    }
+                    if input.action_pressed("screenshot") {
+                        let secs = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs();
+                        renderer.save_screenshot(Path::new(&format!("screenshot_{}.png", secs)));
+                    }
+                    if input.action_pressed("capture") && !renderer.is_capturing_frames() {
+                        std::fs::create_dir_all("capture").unwrap();
+                        renderer.capture_frames(Path::new("capture"), 300);
+                    }
+                    let settings = renderer.post_settings();
+                    if input.action_pressed("toggle_tonemapping") {
+                        settings.tonemapping = !settings.tonemapping;
+                    }
+                    if input.action_pressed("toggle_bloom") {
+                        settings.bloom = !settings.bloom;
+                    }
+                    if input.action_pressed("toggle_vignette") {
+                        settings.vignette = !settings.vignette;
+                    }
+                    if input.action_pressed("toggle_color_grading") {
+                        settings.color_grading = !settings.color_grading;
+                    }
+                    if input.action_pressed("toggle_debug_draw") {
+                        debug_draw::set_enabled(!debug_draw::is_enabled());
+                    }
+
+                    if !hud.is_visible(hud_nodes.menu) {
+                        if input.action_pressed("reload") {
+                            ammo = MAX_AMMO;
+                        }
+                        if input.action_pressed("fire") && ammo > 0 {
+                            ammo -= 1;
+                            score += 10;
+                            flash_time = FLASH_DURATION;
+                            debug_draw::sphere(
+                                vec3(0.0, 0.0, -1.5),
+                                0.2,
+                                vec4(1.0, 0.7, 0.3, 1.0),
+                                Some(1.0),
+                            );
+                        }
+                        let movement = vec3(
+                            input.axis("move_x"),
+                            0.0,
+                            -input.axis("move_y") - input.axis("zoom"),
+                        );
+                        if let Some(camera) = (&mut world.write_storage::<Camera>()).join().next() {
+                            camera.move_local(movement * CAMERA_SPEED * delta_time);
+                        }
+                    }
+                }
+                input.end_frame();
+
+                for clicked in hud.take_clicks() {
+                    if clicked == hud_nodes.resume {
+                        hud.set_visible(hud_nodes.menu, false);
+                        hud.set_focus(None);
+                    } else if clicked == hud_nodes.quit {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+                // Health drains while firing, standing in for taking damage
+                let health = 1.0 - 0.5 * (MAX_AMMO - ammo) as f32 / MAX_AMMO as f32;
+                hud.set_progress(hud_nodes.health, health);
+                hud.set_text(hud_nodes.ammo, &format!("{} / {}", ammo, MAX_AMMO));
+                hud.set_text(hud_nodes.score, &format!("Score: {}", score));
+                renderer.queue_hud(&mut hud);
+
+                // The gui goes first so its edits show up in this frame
+                let ui = gui.frame(renderer.dimensions(), delta_time);
+                let stats = renderer.stats();
+                ui.window("Renderer")
+                    .position(vec2(10.0, 40.0))
+                    .size(vec2(260.0, 200.0))
+                    .build(|| {
+                        ui.text(&format!(
+                            "{} draw calls, {} for shadows",
+                            stats.draw_calls, stats.shadow_draw_calls
+                        ));
+                        let settings = renderer.post_settings();
+                        ui.checkbox("Tonemapping", &mut settings.tonemapping);
+                        ui.slider_float("Exposure", &mut settings.exposure, 0.1, 4.0);
+                        ui.checkbox("Bloom", &mut settings.bloom);
+                        ui.slider_float("Bloom intensity", &mut settings.bloom_intensity, 0.0, 2.0);
+                        ui.checkbox("Vignette", &mut settings.vignette);
+                        ui.checkbox("Color grading", &mut settings.color_grading);
+                    });
+                inspector.draw(&ui, &mut world, &assets);
+                renderer.gui().queue(ui.render());
+                world.maintain();
+
+                // The inspector can delete every camera, which leaves the view where it started
+                let camera = world
+                    .read_storage::<Camera>()
+                    .join()
+                    .next()
+                    .cloned()
+                    .unwrap_or_else(|| Camera::new(vec3(0.0, 0.0, 4.0)));
+                let mut draw_list =
+                    DrawList::new(camera.view(), camera.projection(renderer.aspect_ratio()));
+                gather_lights(&world, &mut draw_list);
+                if flash_time > 0.0 {
+                    draw_list.add_light(Light::point(
+                        vec3(0.0, 0.0, -1.5),
+                        vec3(1.0, 0.7, 0.3),
+                        8.0 * flash_time / FLASH_DURATION,
+                        6.0,
+                    ));
+                }
+                // After the lights, which decide what casts shadows into view
+                visibility.update(&world, |mesh| renderer.mesh_bounds(mesh));
+                visibility.gather_visible(&world, &mut draw_list);
+
+                // F5 shows the bounds of the entity selected in the inspector
+                if let Some(entity) = inspector.selected() {
+                    if let Some(renderable) = world.read_storage::<Renderable>().get(entity) {
+                        let bounds = renderer.mesh_bounds(renderable.mesh);
+                        debug_draw::oriented_box(
+                            &translate(&renderable.transform, &((bounds.min + bounds.max) * 0.5)),
+                            (bounds.max - bounds.min) * 0.5,
+                            vec4(0.0, 1.0, 0.0, 1.0),
+                            None,
+                        );
+                    }
+                }
+
+                renderer.render(&draw_list);
+            }
+            _ => (),
+        }
+    });
+}
+
+/// Where the files the game reads and writes live. Looking beside the executable rather than in
+/// the working directory finds them however the game is started.
+fn beside_executable(file_name: &str) -> PathBuf {
+    executable_dir().unwrap_or_default().join(file_name)
+}
+
+/// The bindings in input.cfg next to the executable, or the built in ones if it doesn't exist or
+/// fails to load.
+fn load_bindings() -> InputBindings {
+    let path = beside_executable(BINDINGS_FILE);
+    if path.is_file() {
+        match InputBindings::load(&path) {
+            Ok(bindings) => return bindings,
+            Err(e) => println!("Failed to load the input bindings:\n{}", e),
+        }
+    }
+    InputBindings::parse(include_str!("../input.cfg"), Path::new("client/input.cfg")).unwrap()
+}
+
+/// The HUD nodes the game updates.
+struct HudNodes {
+    health: NodeId,
+    ammo: NodeId,
+    score: NodeId,
+    menu: NodeId,
+    resume: NodeId,
+    quit: NodeId,
+}
+
+/// A health bar in the bottom left, ammo in the bottom right, the score at the top, a crosshair
+/// and a pause menu toggled with escape.
+fn create_hud(hud: &mut Hud) -> HudNodes {
+    let root = hud.root();
+    let right = LayoutOptions {
+        alignment: Alignment::Right,
+        ..LayoutOptions::default()
+    };
+    let centered = LayoutOptions {
+        alignment: Alignment::Center,
+        ..LayoutOptions::default()
+    };
+
+    let health = hud.add(
+        root,
+        Layout::anchored(vec2(0.0, 1.0), vec2(300.0, 24.0)).offset(vec2(20.0, -20.0)),
+        Widget::progress_bar(1.0, vec4(0.8, 0.1, 0.1, 0.9)),
+    );
+    let ammo = hud.add(
+        root,
+        Layout::anchored(vec2(1.0, 1.0), vec2(200.0, 40.0)).offset(vec2(-20.0, -20.0)),
+        Widget::text("", right),
+    );
+    let score = hud.add(
+        root,
+        Layout::anchored(vec2(0.5, 0.0), vec2(400.0, 60.0)).offset(vec2(0.0, 10.0)),
+        Widget::Label {
+            text: String::new(),
+            options: centered.clone(),
+            size: 36.0,
+            style: SdfStyle {
+                outline_width: 2.0,
+                shadow_offset: vec2(2.0, 3.0),
+                shadow_softness: 2.0,
+                shadow_color: vec4(0.0, 0.0, 0.0, 0.6),
+                ..SdfStyle::default()
+            },
+        },
+    );
+
+    let crosshair = vec4(1.0, 1.0, 1.0, 0.8);
+    let center = vec2(0.5, 0.5);
+    hud.add(
+        root,
+        Layout::anchored(center, vec2(16.0, 2.0)),
+        Widget::panel(crosshair),
+    );
+    hud.add(
+        root,
+        Layout::anchored(center, vec2(2.0, 16.0)),
+        Widget::panel(crosshair),
+    );
+
+    // The menu takes the middle third of the screen, with its buttons stacked inside
+    let menu = hud.add(
+        root,
+        Layout::percent(vec2(1.0 / 3.0, 1.0 / 3.0), vec2(2.0 / 3.0, 2.0 / 3.0)),
+        Widget::panel(vec4(0.0, 0.0, 0.0, 0.7)),
+    );
+    hud.add(
+        menu,
+        Layout::percent(vec2(0.0, 0.0), vec2(1.0, 0.3)),
+        Widget::text("Paused", centered),
+    );
+    let resume = hud.add(
+        menu,
+        Layout::percent(vec2(0.1, 0.35), vec2(0.9, 0.6)).margin(4.0),
+        Widget::button("Resume"),
+    );
+    let quit = hud.add(
+        menu,
+        Layout::percent(vec2(0.1, 0.65), vec2(0.9, 0.9)).margin(4.0),
+        Widget::button("Quit"),
+    );
+    hud.set_visible(menu, false);
+
+    HudNodes {
+        health: health,
+        ammo: ammo,
+        score: score,
+        menu: menu,
+        resume: resume,
+        quit: quit,
+    }
 }
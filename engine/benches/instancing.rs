@@ -0,0 +1,112 @@
+//! Draws a shooter-like scene of bullets and debris with and without instancing, and prints the
+//! draw calls and frame time of each. Renders headless, so it runs on lavapipe too:
+//!
+//! `cargo bench -p engine --bench instancing`
+
+extern crate engine;
+extern crate nalgebra_glm as glm;
+
+use engine::mesh::mesh::Mesh;
+use engine::renderer::{DrawList, Light, Material, Renderer, ShadowSettings};
+use glm::{identity, look_at, perspective, rotate_y, scale, translate, vec3, vec4};
+use std::time::Instant;
+
+const BULLETS: usize = 1000;
+const DEBRIS: usize = 500;
+const WARMUP_FRAMES: u32 = 10;
+const FRAMES: u32 = 100;
+
+fn main() {
+    let mut renderer = match Renderer::new_headless([640, 480]) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            println!(
+                "No Vulkan device, skipping the instancing benchmark ({:?})",
+                e
+            );
+            return;
+        }
+    };
+    let draw_list = build_scene(&mut renderer);
+
+    for &instancing in &[false, true] {
+        renderer.set_instancing(instancing);
+        for _ in 0..WARMUP_FRAMES {
+            renderer.render(&draw_list);
+        }
+
+        // Reading the last frame back waits for the GPU to finish all of them
+        let start = Instant::now();
+        for _ in 0..FRAMES - 1 {
+            renderer.render(&draw_list);
+        }
+        renderer.render_to_pixels(&draw_list);
+        let frame_time = start.elapsed().as_secs_f64() * 1000.0 / FRAMES as f64;
+
+        let stats = renderer.stats();
+        println!(
+            "instancing {:>3}: {} commands, {} draw calls, {} shadow draw calls, {:.2} ms/frame",
+            if instancing { "on" } else { "off" },
+            stats.commands,
+            stats.draw_calls,
+            stats.shadow_draw_calls,
+            frame_time
+        );
+    }
+}
+
+/// Bullets in a grid flying away from the camera, and debris scattered in a ring around them,
+/// in a few materials and tints.
+fn build_scene(renderer: &mut Renderer) -> DrawList {
+    let bullet_mesh = renderer.register_mesh(&Mesh::create_box(&None).scaled(0.05, 0.05, 0.3));
+    let debris_mesh = renderer.register_mesh(&Mesh::create_box(&None).scaled(0.2, 0.2, 0.2));
+    let bullet_material = renderer.register_material(Material::default()).unwrap();
+    let debris_materials = [
+        renderer.register_material(Material::default()).unwrap(),
+        renderer
+            .register_material(Material::for_shader("pbr"))
+            .unwrap(),
+    ];
+
+    let view = look_at(
+        &vec3(0.0, 4.0, 12.0),
+        &vec3(0.0, 0.0, 0.0),
+        &vec3(0.0, 1.0, 0.0),
+    );
+    let mut draw_list = DrawList::new(view, perspective(640.0 / 480.0, 1.0, 0.1, 100.0));
+    draw_list.add_light(
+        Light::directional(vec3(-1.0, -2.0, -1.5), vec3(1.0, 1.0, 1.0), 1.0)
+            .with_shadows(ShadowSettings::default()),
+    );
+
+    for i in 0..BULLETS {
+        let x = (i % 40) as f32 * 0.25 - 5.0;
+        let z = -((i / 40) as f32) * 0.8;
+        let transform = translate(&identity(), &vec3(x, 0.5, z));
+        draw_list.draw_tinted(
+            bullet_mesh,
+            bullet_material,
+            transform,
+            vec4(1.0, 0.8, 0.3, 1.0),
+        );
+    }
+
+    for i in 0..DEBRIS {
+        let angle = i as f32 * 0.37;
+        let radius = 3.0 + (i % 7) as f32 * 0.5;
+        let transform = translate(
+            &identity(),
+            &vec3(angle.cos() * radius, 0.0, angle.sin() * radius - 5.0),
+        );
+        let transform = scale(&rotate_y(&transform, angle), &vec3(1.0, 0.5, 1.0));
+        let shade = 0.5 + (i % 5) as f32 * 0.1;
+        draw_list.draw_tinted(
+            debris_mesh,
+            debris_materials[i % debris_materials.len()],
+            transform,
+            vec4(shade, shade, shade, 1.0),
+        );
+    }
+
+    draw_list
+}
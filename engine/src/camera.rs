@@ -1,96 +1,106 @@
-/*use num::Num;
-use glm::Vec3;
+use glm::*;
+use specs::{Component, VecStorage};
 
-#[derive(Debug)]
-pub struct Camera<T: Num> {
-    pub pos: Vec3<T>,
-    pub pitch: T,
-    pub yaw: T,
-
-    pub aspect: T,
-    pub fov: T,
-    pub near: T,
-    pub far: T,
+/// A perspective camera turned by yaw around y, then pitch around x, both in radians. With both
+/// at 0 it looks down -z.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    /// The vertical field of view in radians.
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
 
-    pub projection: Mat4<T>,
+impl Component for Camera {
+    type Storage = VecStorage<Self>;
 }
 
-impl<T: Num> Camera<T> {
-    pub fn new_perspective(aspect: T, fov: T, near: T, far: T, pos: TVec3<T>) -> Camera<T> {
+impl Camera {
+    pub fn new(position: Vec3) -> Camera {
         Camera {
-            projection: perspective(aspect, fov, near, far),
-
-            aspect: aspect,
-            fov: fov,
-            near: near,
-            far: far,
-
-            pos: pos,
-            pitch: T::from_f64(0.0).unwrap(),
-            yaw: T::from_f64(0.0).unwrap(),
+            position: position,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: 1.0,
+            near: 0.01,
+            far: 1000.0,
         }
     }
 
-    pub fn set_aspect(&mut self, aspect: T) {
-        self.aspect = aspect;
-        self.projection = perspective(self.aspect, self.fov, self.near, self.far);
+    /// Turns directions in camera space into world space.
+    pub fn orientation(&self) -> Mat4 {
+        rotation(self.yaw, &vec3(0.0, 1.0, 0.0)) * rotation(self.pitch, &vec3(1.0, 0.0, 0.0))
     }
 
-    pub fn rotation(&self) -> Qua<T> {
-        let ret = quat_angle_axis(
-            self.pitch,
-            &vec3(
-                T::from_f64(1.0).unwrap(),
-                T::from_f64(0.0).unwrap(),
-                T::from_f64(0.0).unwrap(),
-            ),
-        ) * quat_angle_axis(
-            self.yaw,
-            &vec3(
-                T::from_f64(0.0).unwrap(),
-                T::from_f64(1.0).unwrap(),
-                T::from_f64(0.0).unwrap(),
-            ),
-        );
-        quat_normalize(&ret)
+    pub fn view(&self) -> Mat4 {
+        transpose(&self.orientation()) * translation(&-self.position)
     }
 
-    pub fn move_dir(&mut self, dir: TVec3<T>) {
-        let scaled_vec = dir;
-        let rotated = quat_rotate_vec3(&self.rotation(), &scaled_vec);
-        self.pos += rotated;
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        perspective(aspect, self.fov, self.near, self.far)
     }
 
-    pub fn move_forward(&mut self, d: T) {
-        self.move_dir(vec3(
-            T::from_f64(0.0).unwrap(),
-            T::from_f64(0.0).unwrap(),
-            d,
-        ));
+    /// Moves by `offset` in camera space, so -z is forward whichever way the camera faces.
+    pub fn move_local(&mut self, offset: Vec3) {
+        let offset = self.orientation() * vec4(offset.x, offset.y, offset.z, 0.0);
+        self.position += offset.xyz();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn move_right(&mut self, d: T) {
-        self.move_dir(vec3(
-            d,
-            T::from_f64(0.0).unwrap(),
-            T::from_f64(0.0).unwrap(),
-        ));
+    use std::f32::consts::FRAC_PI_2;
+
+    fn view_point(camera: &Camera, point: Vec3) -> Vec3 {
+        (camera.view() * vec4(point.x, point.y, point.z, 1.0)).xyz()
+    }
+
+    #[test]
+    fn view_moves_the_camera_to_the_origin() {
+        let camera = Camera::new(vec3(1.0, 2.0, 3.0));
+        let point = view_point(&camera, vec3(1.0, 2.0, 3.0));
+        assert!(length(&point) < 1e-5, "{:?}", point);
     }
 
-    pub fn move_up(&mut self, d: T) {
-        self.move_dir(vec3(
-            T::from_f64(0.0).unwrap(),
-            d,
-            T::from_f64(0.0).unwrap(),
-        ));
+    #[test]
+    fn yaw_turns_to_the_left() {
+        let mut camera = Camera::new(vec3(0.0, 0.0, 0.0));
+        camera.yaw = FRAC_PI_2;
+        // A quarter turn to the left looks down -x, which is -z in view space
+        let point = view_point(&camera, vec3(-5.0, 0.0, 0.0));
+        assert!(
+            length(&(point - vec3(0.0, 0.0, -5.0))) < 1e-5,
+            "{:?}",
+            point
+        );
     }
 
-    pub fn view(&self) -> TMat4<T> {
-        quat_cast(&self.rotation())
+    #[test]
+    fn pitch_looks_up() {
+        let mut camera = Camera::new(vec3(0.0, 0.0, 0.0));
+        camera.pitch = FRAC_PI_2;
+        let point = view_point(&camera, vec3(0.0, 5.0, 0.0));
+        assert!(
+            length(&(point - vec3(0.0, 0.0, -5.0))) < 1e-5,
+            "{:?}",
+            point
+        );
     }
 
-    pub fn camera_matrix(&self) -> TMat4<T> {
-        self.projection * self.view()
+    #[test]
+    fn move_local_follows_the_view() {
+        let mut camera = Camera::new(vec3(0.0, 0.0, 0.0));
+        camera.yaw = FRAC_PI_2;
+        camera.move_local(vec3(0.0, 0.0, -2.0));
+        assert!(length(&(camera.position - vec3(-2.0, 0.0, 0.0))) < 1e-5);
+
+        let before = camera.position;
+        camera.move_local(vec3(0.0, 0.0, 0.0));
+        assert_eq!(camera.position, before);
     }
 }
-*/
@@ -0,0 +1,277 @@
+//! Golden image testing: render a scene headless and compare it against a stored reference PNG.
+//!
+//! References live in `engine/tests/golden/<name>.png`. When a comparison fails, the rendered image
+//! and a diff image (differing pixels in red) are written to `target/golden/`. Run with
+//! `UPDATE_GOLDEN=1` to overwrite the references with what is rendered now.
+//!
+//! The scene tests in `engine/tests/golden.rs` are ignored by a plain `cargo test`, since they need
+//! a Vulkan device and a committed reference. Run them with
+//! `cargo test -p engine --test golden -- --ignored`.
+
+use glm::{look_at, perspective, vec3, Mat4};
+use img::{self, ColorType};
+use renderer::{DrawList, Light, Renderer, ShadowSettings};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub const GOLDEN_SIZE: [u32; 2] = [256, 256];
+const UPDATE_ENV_VAR: &str = "UPDATE_GOLDEN";
+
+#[derive(Clone, Copy, Debug)]
+pub struct GoldenSettings {
+    /// Largest per channel difference that still counts as the same pixel.
+    pub tolerance: u8,
+    /// How many pixels may differ by more than `tolerance` before the comparison fails.
+    pub max_differing_pixels: usize,
+}
+
+impl Default for GoldenSettings {
+    fn default() -> GoldenSettings {
+        GoldenSettings {
+            tolerance: 2,
+            max_differing_pixels: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageDiff {
+    pub differing_pixels: usize,
+    pub max_difference: u8,
+    /// RGBA image with differing pixels in red over a faded copy of the actual image.
+    pub diff_image: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    MissingReference(PathBuf),
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Mismatch {
+        differing_pixels: usize,
+        max_difference: u8,
+        actual_path: PathBuf,
+        diff_path: PathBuf,
+    },
+}
+
+/// The camera every golden scene is rendered with, looking at the origin from (3, 3, 5).
+pub fn golden_camera() -> (Mat4, Mat4) {
+    let view = look_at(
+        &vec3(3.0, 3.0, 5.0),
+        &vec3(0.0, 0.0, 0.0),
+        &vec3(0.0, 1.0, 0.0),
+    );
+    let aspect = GOLDEN_SIZE[0] as f32 / GOLDEN_SIZE[1] as f32;
+    (view, perspective(aspect, 1.0, 0.01, 100.0))
+}
+
+/// A shadow casting light from above and behind the golden camera, for scenes that test lighting
+/// or shadows.
+pub fn golden_sun() -> Light {
+    Light::directional(vec3(-1.0, -2.0, -1.5), vec3(1.0, 1.0, 1.0), 1.0)
+        .with_shadows(ShadowSettings::default())
+}
+
+/// Renders the scene built by `build_scene` at `GOLDEN_SIZE` and asserts it matches reference `name`.
+///
+/// `build_scene` registers whatever resources it needs and returns the draw list to render;
+/// use `golden_camera` for its view and projection.
+pub fn assert_golden_scene<F>(name: &str, settings: GoldenSettings, build_scene: F)
+where
+    F: FnOnce(&mut Renderer) -> DrawList,
+{
+    // Skipped rather than failed, so machines without Vulkan can still run the whole suite
+    let mut renderer = match Renderer::new_headless(GOLDEN_SIZE) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            println!(
+                "Skipping golden test '{}', no Vulkan device ({:?}). \
+                 Without a GPU, install lavapipe and set VK_ICD_FILENAMES to its ICD json.",
+                name, e
+            );
+            return;
+        }
+    };
+    let draw_list = build_scene(&mut renderer);
+    let pixels = renderer.render_to_pixels(&draw_list);
+    assert_golden(name, &pixels, GOLDEN_SIZE[0], GOLDEN_SIZE[1], settings);
+}
+
+pub fn assert_golden(name: &str, pixels: &[u8], width: u32, height: u32, settings: GoldenSettings) {
+    match check_golden(name, pixels, width, height, settings) {
+        Ok(()) => (),
+        Err(GoldenError::MissingReference(path)) => panic!(
+            "No reference image {:?} for golden test '{}', run with {}=1 to create it",
+            path, name, UPDATE_ENV_VAR
+        ),
+        Err(e) => panic!("Golden image '{}' does not match: {:?}", name, e),
+    }
+}
+
+pub fn check_golden(
+    name: &str,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    settings: GoldenSettings,
+) -> Result<(), GoldenError> {
+    let reference_path = reference_dir().join(format!("{}.png", name));
+
+    if update_requested(env::var(UPDATE_ENV_VAR).ok()) {
+        fs::create_dir_all(reference_dir()).unwrap();
+        img::save_buffer(&reference_path, pixels, width, height, ColorType::Rgba8).unwrap();
+        println!("Updated golden image {:?}", reference_path);
+        return Ok(());
+    }
+
+    let reference = match img::open(&reference_path) {
+        Ok(reference) => reference.to_rgba(),
+        Err(_) => return Err(GoldenError::MissingReference(reference_path)),
+    };
+    if reference.dimensions() != (width, height) {
+        return Err(GoldenError::SizeMismatch {
+            expected: reference.dimensions(),
+            actual: (width, height),
+        });
+    }
+
+    let diff = compare_images(pixels, &reference.into_raw(), settings.tolerance);
+    if diff.differing_pixels <= settings.max_differing_pixels {
+        return Ok(());
+    }
+
+    fs::create_dir_all(failure_dir()).unwrap();
+    let actual_path = failure_dir().join(format!("{}.actual.png", name));
+    let diff_path = failure_dir().join(format!("{}.diff.png", name));
+    img::save_buffer(&actual_path, pixels, width, height, ColorType::Rgba8).unwrap();
+    img::save_buffer(
+        &diff_path,
+        &diff.diff_image,
+        width,
+        height,
+        ColorType::Rgba8,
+    )
+    .unwrap();
+
+    Err(GoldenError::Mismatch {
+        differing_pixels: diff.differing_pixels,
+        max_difference: diff.max_difference,
+        actual_path: actual_path,
+        diff_path: diff_path,
+    })
+}
+
+/// Compares two RGBA8 images of the same size pixel by pixel.
+pub fn compare_images(actual: &[u8], expected: &[u8], tolerance: u8) -> ImageDiff {
+    assert_eq!(actual.len(), expected.len());
+
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff_image = Vec::with_capacity(actual.len());
+    for (a, e) in actual.chunks(4).zip(expected.chunks(4)) {
+        let difference = a
+            .iter()
+            .zip(e.iter())
+            .map(|(a, e)| (*a as i16 - *e as i16).abs() as u8)
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance {
+            differing_pixels += 1;
+            diff_image.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let faded = |c: u8| c / 4;
+            diff_image.extend_from_slice(&[faded(a[0]), faded(a[1]), faded(a[2]), 255]);
+        }
+    }
+
+    ImageDiff {
+        differing_pixels: differing_pixels,
+        max_difference: max_difference,
+        diff_image: diff_image,
+    }
+}
+
+/// Only `UPDATE_GOLDEN=1` overwrites the references, so `UPDATE_GOLDEN=0` can't do it by accident.
+fn update_requested(value: Option<String>) -> bool {
+    value.map_or(false, |value| value == "1")
+}
+
+fn reference_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn failure_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("target")
+        .join("golden")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.iter().flat_map(|p| p.iter().cloned()).collect()
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let pixels = image(&[[10, 20, 30, 255], [200, 100, 0, 255]]);
+        let diff = compare_images(&pixels, &pixels, 0);
+        assert_eq!(diff.differing_pixels, 0);
+        assert_eq!(diff.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let actual = image(&[[10, 20, 30, 255], [200, 100, 0, 255]]);
+        let expected = image(&[[12, 20, 30, 255], [200, 99, 0, 254]]);
+        let diff = compare_images(&actual, &expected, 2);
+        assert_eq!(diff.differing_pixels, 0);
+        assert_eq!(diff.max_difference, 2);
+    }
+
+    #[test]
+    fn differing_pixels_are_counted_and_red_in_the_diff() {
+        let actual = image(&[[0, 0, 0, 255], [100, 100, 100, 255], [40, 80, 120, 255]]);
+        let expected = image(&[[0, 0, 0, 255], [100, 150, 100, 255], [40, 80, 120, 0]]);
+        let diff = compare_images(&actual, &expected, 2);
+        assert_eq!(diff.differing_pixels, 2);
+        assert_eq!(diff.max_difference, 255);
+        assert_eq!(
+            diff.diff_image,
+            image(&[[0, 0, 0, 255], [255, 0, 0, 255], [255, 0, 0, 255]])
+        );
+    }
+
+    #[test]
+    fn matching_pixels_are_faded_in_the_diff() {
+        let pixels = image(&[[40, 80, 120, 255]]);
+        let diff = compare_images(&pixels, &pixels, 0);
+        assert_eq!(diff.diff_image, image(&[[10, 20, 30, 255]]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn images_of_different_sizes_panic() {
+        compare_images(&[0; 8], &[0; 4], 0);
+    }
+
+    #[test]
+    fn only_one_updates_the_references() {
+        assert!(update_requested(Some("1".to_string())));
+        assert!(!update_requested(Some("0".to_string())));
+        assert!(!update_requested(Some(String::new())));
+        assert!(!update_requested(Some("true".to_string())));
+        assert!(!update_requested(None));
+    }
+}
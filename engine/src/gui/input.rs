@@ -0,0 +1,265 @@
+use gui::Gui;
+use imgui::{
+    ImGuiIO, ImGuiIO_AddInputCharacter, ImGuiKey_A, ImGuiKey_Backspace, ImGuiKey_C,
+    ImGuiKey_Delete, ImGuiKey_DownArrow, ImGuiKey_End, ImGuiKey_Enter, ImGuiKey_Escape,
+    ImGuiKey_Home, ImGuiKey_Insert, ImGuiKey_LeftArrow, ImGuiKey_PageDown, ImGuiKey_PageUp,
+    ImGuiKey_RightArrow, ImGuiKey_Space, ImGuiKey_Tab, ImGuiKey_UpArrow, ImGuiKey_V, ImGuiKey_X,
+    ImGuiKey_Y, ImGuiKey_Z, ImVec2,
+};
+use input::PIXELS_PER_LINE;
+
+use std::f32;
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+impl Gui {
+    /// Passes mouse, keyboard and text input on to ImGui. Call it with every window event.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let io = self.io_mut();
+        match *event {
+            WindowEvent::CursorMoved { position, .. } => {
+                io.MousePos = ImVec2 {
+                    x: position.x as f32,
+                    y: position.y as f32,
+                };
+            }
+            WindowEvent::CursorLeft { .. } => {
+                io.MousePos = ImVec2 {
+                    x: -f32::MAX,
+                    y: -f32::MAX,
+                };
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let index = match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Right => 1,
+                    MouseButton::Middle => 2,
+                    MouseButton::Other(i) if i < 5 => i as usize,
+                    MouseButton::Other(_) => return,
+                };
+                io.MouseDown[index] = state == ElementState::Pressed;
+            }
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => {
+                    io.MouseWheelH += x;
+                    io.MouseWheel += y;
+                }
+                MouseScrollDelta::PixelDelta(position) => {
+                    io.MouseWheelH += position.x as f32 / PIXELS_PER_LINE;
+                    io.MouseWheel += position.y as f32 / PIXELS_PER_LINE;
+                }
+            },
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => {
+                io.KeysDown[key as usize] = state == ElementState::Pressed;
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                io.KeyCtrl = modifiers.ctrl();
+                io.KeyShift = modifiers.shift();
+                io.KeyAlt = modifiers.alt();
+                io.KeySuper = modifiers.logo();
+            }
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => unsafe {
+                ImGuiIO_AddInputCharacter(io, c as u32);
+            },
+            // ImGui would otherwise keep keys that were let go of in another window held
+            WindowEvent::Focused(false) => {
+                for down in io.KeysDown.iter_mut() {
+                    *down = false;
+                }
+                for down in io.MouseDown.iter_mut() {
+                    *down = false;
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Tells ImGui which entries of `KeysDown` are its navigation and shortcut keys. `KeysDown` is
+/// indexed by `VirtualKeyCode`.
+pub fn set_key_map(io: &mut ImGuiIO) {
+    let keys = [
+        (ImGuiKey_Tab, VirtualKeyCode::Tab),
+        (ImGuiKey_LeftArrow, VirtualKeyCode::Left),
+        (ImGuiKey_RightArrow, VirtualKeyCode::Right),
+        (ImGuiKey_UpArrow, VirtualKeyCode::Up),
+        (ImGuiKey_DownArrow, VirtualKeyCode::Down),
+        (ImGuiKey_PageUp, VirtualKeyCode::PageUp),
+        (ImGuiKey_PageDown, VirtualKeyCode::PageDown),
+        (ImGuiKey_Home, VirtualKeyCode::Home),
+        (ImGuiKey_End, VirtualKeyCode::End),
+        (ImGuiKey_Insert, VirtualKeyCode::Insert),
+        (ImGuiKey_Delete, VirtualKeyCode::Delete),
+        (ImGuiKey_Backspace, VirtualKeyCode::Back),
+        (ImGuiKey_Space, VirtualKeyCode::Space),
+        (ImGuiKey_Enter, VirtualKeyCode::Return),
+        (ImGuiKey_Escape, VirtualKeyCode::Escape),
+        (ImGuiKey_A, VirtualKeyCode::A),
+        (ImGuiKey_C, VirtualKeyCode::C),
+        (ImGuiKey_V, VirtualKeyCode::V),
+        (ImGuiKey_X, VirtualKeyCode::X),
+        (ImGuiKey_Y, VirtualKeyCode::Y),
+        (ImGuiKey_Z, VirtualKeyCode::Z),
+    ];
+    for &(imgui_key, key) in &keys {
+        io.KeyMap[imgui_key as usize] = key as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use winit::dpi::{LogicalPosition, PhysicalPosition};
+    use winit::event::{DeviceId, ModifiersState, TouchPhase};
+
+    lazy_static! {
+        /// Only one `Gui` can exist at a time, so the tests take turns.
+        static ref GUI_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    fn gui() -> (MutexGuard<'static, ()>, Gui) {
+        // A failed test poisons the lock without leaving its Gui alive
+        let guard = GUI_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        (guard, Gui::new())
+    }
+
+    #[allow(deprecated)]
+    fn click(button: MouseButton, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state: state,
+            button: button,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[allow(deprecated)]
+    fn scroll(delta: MouseScrollDelta) -> WindowEvent<'static> {
+        WindowEvent::MouseWheel {
+            device_id: unsafe { DeviceId::dummy() },
+            delta: delta,
+            phase: TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    fn key(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 0,
+            state: state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        };
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: input,
+            is_synthetic: false,
+        }
+    }
+
+    #[test]
+    fn mouse_buttons_go_to_their_slots() {
+        let (_guard, mut gui) = gui();
+        let buttons = [
+            (MouseButton::Left, 0),
+            (MouseButton::Right, 1),
+            (MouseButton::Middle, 2),
+            (MouseButton::Other(3), 3),
+            (MouseButton::Other(4), 4),
+        ];
+        for &(button, slot) in &buttons {
+            gui.handle_event(&click(button, ElementState::Pressed));
+            assert!(gui.io().MouseDown[slot], "{:?}", button);
+        }
+        for &(button, slot) in &buttons {
+            gui.handle_event(&click(button, ElementState::Released));
+            assert!(!gui.io().MouseDown[slot], "{:?}", button);
+        }
+
+        // ImGui only knows 5 buttons
+        gui.handle_event(&click(MouseButton::Other(5), ElementState::Pressed));
+        gui.handle_event(&click(MouseButton::Other(255), ElementState::Pressed));
+        assert_eq!(gui.io().MouseDown, [false; 5]);
+    }
+
+    #[test]
+    fn cursor_position_is_passed_on() {
+        let (_guard, mut gui) = gui();
+        #[allow(deprecated)]
+        gui.handle_event(&WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(12.0, 34.5),
+            modifiers: ModifiersState::empty(),
+        });
+        assert_eq!((gui.io().MousePos.x, gui.io().MousePos.y), (12.0, 34.5));
+
+        gui.handle_event(&WindowEvent::CursorLeft {
+            device_id: unsafe { DeviceId::dummy() },
+        });
+        assert_eq!(gui.io().MousePos.x, -f32::MAX);
+    }
+
+    #[test]
+    fn pixel_scrolling_is_counted_in_lines() {
+        let (_guard, mut gui) = gui();
+        gui.handle_event(&scroll(MouseScrollDelta::LineDelta(1.0, -2.0)));
+        assert_eq!((gui.io().MouseWheelH, gui.io().MouseWheel), (1.0, -2.0));
+
+        let pixels = PIXELS_PER_LINE as f64;
+        gui.handle_event(&scroll(MouseScrollDelta::PixelDelta(LogicalPosition::new(
+            pixels / 2.0,
+            pixels * 3.0,
+        ))));
+        assert_eq!((gui.io().MouseWheelH, gui.io().MouseWheel), (1.5, 1.0));
+    }
+
+    #[test]
+    fn key_map_points_into_keys_down() {
+        let (_guard, mut gui) = gui();
+        let key_map = gui.io().KeyMap;
+        assert_eq!(key_map[ImGuiKey_Tab as usize], VirtualKeyCode::Tab as i32);
+        assert_eq!(
+            key_map[ImGuiKey_Enter as usize],
+            VirtualKeyCode::Return as i32
+        );
+        assert_eq!(
+            key_map[ImGuiKey_Backspace as usize],
+            VirtualKeyCode::Back as i32
+        );
+
+        gui.handle_event(&key(VirtualKeyCode::Back, ElementState::Pressed));
+        assert!(gui.io().KeysDown[key_map[ImGuiKey_Backspace as usize] as usize]);
+        gui.handle_event(&key(VirtualKeyCode::Back, ElementState::Released));
+        assert!(!gui.io().KeysDown[key_map[ImGuiKey_Backspace as usize] as usize]);
+    }
+
+    #[test]
+    fn losing_focus_lets_go_of_everything() {
+        let (_guard, mut gui) = gui();
+        gui.handle_event(&key(VirtualKeyCode::A, ElementState::Pressed));
+        gui.handle_event(&key(VirtualKeyCode::LShift, ElementState::Pressed));
+        gui.handle_event(&click(MouseButton::Left, ElementState::Pressed));
+        assert!(gui.io().KeysDown[VirtualKeyCode::A as usize]);
+
+        gui.handle_event(&WindowEvent::Focused(false));
+        assert!(gui.io().KeysDown.iter().all(|&down| !down));
+        assert_eq!(gui.io().MouseDown, [false; 5]);
+    }
+
+    #[test]
+    fn nothing_is_saved_to_an_ini_file() {
+        let (_guard, gui) = gui();
+        assert!(gui.io().IniFilename.is_null());
+    }
+}
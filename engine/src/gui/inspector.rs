@@ -0,0 +1,348 @@
+use camera::Camera;
+use glm::{identity, length, normalize, vec2, vec3};
+use renderer::{Light, LightKind, Renderable, ShadowSettings};
+use scene::{save_scene, Name, SceneAssets};
+use specs::{Builder, Entity, Join, World, WorldExt};
+use transform::Transform;
+
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+
+use super::ui::Ui;
+
+const LIGHT_KINDS: [&str; 3] = ["Point", "Spot", "Directional"];
+
+/// A developer window listing every entity by `Name`, with editors for the selected entity's
+/// components and buttons to spawn, duplicate and delete entities and to save the scene.
+///
+/// Edits are written back to the world as they happen, so they show up while the game runs.
+pub struct Inspector {
+    scene_path: PathBuf,
+    selected: Option<Entity>,
+    /// What the last save did.
+    status: String,
+    spawned: u32,
+}
+
+impl Inspector {
+    /// `scene_path` is where Save writes the scene to.
+    pub fn new(scene_path: &Path) -> Inspector {
+        Inspector {
+            scene_path: scene_path.to_path_buf(),
+            selected: None,
+            status: String::new(),
+            spawned: 0,
+        }
+    }
+
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+
+    /// The world needs the components of `scene::register_components`.
+    pub fn draw(&mut self, ui: &Ui, world: &mut World, assets: &SceneAssets) {
+        if let Some(entity) = self.selected {
+            if !world.is_alive(entity) {
+                self.selected = None;
+            }
+        }
+
+        ui.window("Inspector")
+            .position(vec2(10.0, 250.0))
+            .size(vec2(320.0, 460.0))
+            .build(|| {
+                self.draw_toolbar(ui, world, assets);
+                ui.separator();
+                self.draw_entity_list(ui, world);
+                if let Some(entity) = self.selected {
+                    ui.separator();
+                    edit_name(ui, world, entity);
+                    edit_renderable(ui, world, entity, assets);
+                    edit_light(ui, world, entity);
+                    edit_camera(ui, world, entity);
+                }
+            });
+    }
+
+    fn draw_toolbar(&mut self, ui: &Ui, world: &mut World, assets: &SceneAssets) {
+        if ui.button("Spawn") {
+            self.spawned += 1;
+            let name = Name(format!("Entity {}", self.spawned));
+            self.selected = Some(world.create_entity().with(name).build());
+        }
+        if let Some(entity) = self.selected {
+            ui.same_line();
+            if ui.button("Duplicate") {
+                self.selected = Some(duplicate(world, entity));
+            }
+            ui.same_line();
+            if ui.button("Delete") {
+                world.delete_entity(entity).unwrap();
+                self.selected = None;
+            }
+        }
+        ui.same_line();
+        if ui.button("Save") {
+            self.status = match save_scene(world, &self.scene_path, assets) {
+                Ok(()) => format!("Saved {}", self.scene_path.display()),
+                Err(e) => format!("Failed to save: {}", e),
+            };
+        }
+        if !self.status.is_empty() {
+            ui.text(&self.status);
+        }
+    }
+
+    fn draw_entity_list(&mut self, ui: &Ui, world: &World) {
+        let entities = world.entities();
+        let names = world.read_storage::<Name>();
+        for (entity, name) in (&entities, names.maybe()).join() {
+            let label = match name {
+                Some(name) => name.0.clone(),
+                None => format!("Entity {}", entity.id()),
+            };
+            let selected = self.selected == Some(entity);
+            ui.with_id(entity.id() as i32, || {
+                if ui.selectable(&label, selected) {
+                    self.selected = Some(entity);
+                }
+            });
+        }
+    }
+}
+
+/// Copies the components of `entity` onto a new entity.
+fn duplicate(world: &mut World, entity: Entity) -> Entity {
+    let name = world
+        .read_storage::<Name>()
+        .get(entity)
+        .map(|name| Name(format!("{} copy", name.0)));
+    let renderable = world.read_storage::<Renderable>().get(entity).cloned();
+    let light = world.read_storage::<Light>().get(entity).cloned();
+    let camera = world.read_storage::<Camera>().get(entity).cloned();
+
+    let mut builder = world.create_entity();
+    if let Some(name) = name {
+        builder = builder.with(name);
+    }
+    if let Some(renderable) = renderable {
+        builder = builder.with(renderable);
+    }
+    if let Some(light) = light {
+        builder = builder.with(light);
+    }
+    if let Some(camera) = camera {
+        builder = builder.with(camera);
+    }
+    builder.build()
+}
+
+fn edit_name(ui: &Ui, world: &World, entity: Entity) {
+    let mut names = world.write_storage::<Name>();
+    let mut name = names
+        .get(entity)
+        .map_or(String::new(), |name| name.0.clone());
+    if ui.input_text("Name", &mut name, 64) {
+        names.insert(entity, Name(name)).unwrap();
+    }
+}
+
+// The editors change a copy and only write it back when something changed, since borrowing a
+// renderable mutably marks it as modified for the visibility index
+fn edit_renderable(ui: &Ui, world: &World, entity: Entity, assets: &SceneAssets) {
+    let mut renderables = world.write_storage::<Renderable>();
+    let mut renderable = match renderables.get(entity) {
+        Some(renderable) => renderable.clone(),
+        None => {
+            let first = (assets.meshes().first(), assets.materials().first());
+            if let (Some(&(_, mesh)), Some(&(_, material))) = first {
+                if ui.button("Add renderable") {
+                    let renderable = Renderable::new(mesh, material, identity());
+                    renderables.insert(entity, renderable).unwrap();
+                }
+            }
+            return;
+        }
+    };
+    if !ui.collapsing_header("Renderable") {
+        return;
+    }
+
+    let mut changed = false;
+    let mesh_names: Vec<&str> = assets.meshes().iter().map(|m| m.0.as_str()).collect();
+    match assets.meshes().iter().position(|m| m.1 == renderable.mesh) {
+        Some(mut index) => {
+            if ui.combo("Mesh", &mut index, &mesh_names) {
+                renderable.mesh = assets.meshes()[index].1;
+                changed = true;
+            }
+        }
+        None => ui.text("The mesh has no name in the scene assets"),
+    }
+    let material_names: Vec<&str> = assets.materials().iter().map(|m| m.0.as_str()).collect();
+    match assets
+        .materials()
+        .iter()
+        .position(|m| m.1 == renderable.material)
+    {
+        Some(mut index) => {
+            if ui.combo("Material", &mut index, &material_names) {
+                renderable.material = assets.materials()[index].1;
+                changed = true;
+            }
+        }
+        None => ui.text("The material has no name in the scene assets"),
+    }
+
+    let mut transform = Transform::from_matrix(&renderable.transform);
+    let mut degrees = transform.rotation * (180.0 / PI);
+    let mut moved = ui.drag_float3("Position", &mut transform.position, 0.05);
+    if ui.drag_float3("Rotation", &mut degrees, 1.0) {
+        transform.rotation = degrees * (PI / 180.0);
+        moved = true;
+    }
+    moved |= ui.drag_float3("Scale", &mut transform.scale, 0.01);
+    if moved {
+        renderable.transform = transform.matrix();
+        changed = true;
+    }
+    changed |= ui.color_edit("Tint", &mut renderable.tint);
+
+    if ui.button("Remove renderable") {
+        renderables.remove(entity);
+    } else if changed {
+        *renderables.get_mut(entity).unwrap() = renderable;
+    }
+}
+
+fn edit_light(ui: &Ui, world: &World, entity: Entity) {
+    let mut lights = world.write_storage::<Light>();
+    let mut light = match lights.get(entity) {
+        Some(light) => light.clone(),
+        None => {
+            if ui.button("Add light") {
+                let light = Light::point(vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0), 1.0, 10.0);
+                lights.insert(entity, light).unwrap();
+            }
+            return;
+        }
+    };
+    if !ui.collapsing_header("Light") {
+        return;
+    }
+
+    let mut changed = false;
+    let mut kind = match light.kind {
+        LightKind::Point => 0,
+        LightKind::Spot { .. } => 1,
+        LightKind::Directional => 2,
+    };
+    if ui.combo("Kind", &mut kind, &LIGHT_KINDS) {
+        light.kind = match kind {
+            0 => LightKind::Point,
+            1 => LightKind::Spot {
+                inner_angle: PI / 9.0,
+                outer_angle: PI / 6.0,
+            },
+            _ => LightKind::Directional,
+        };
+        changed = true;
+    }
+    changed |= ui.color_edit3("Color", &mut light.color);
+    changed |= ui.drag_float("Intensity", &mut light.intensity, 0.05);
+
+    if light.kind != LightKind::Directional {
+        changed |= ui.drag_float("Range", &mut light.range, 0.1);
+        // Labels after ## only tell the widget apart from the renderable's
+        changed |= ui.drag_float3("Position##light", &mut light.position, 0.05);
+    }
+    if light.kind != LightKind::Point {
+        let mut direction = light.direction;
+        if ui.drag_float3("Direction", &mut direction, 0.01) && length(&direction) > 0.0 {
+            light.direction = normalize(&direction);
+            changed = true;
+        }
+    }
+    if let LightKind::Spot {
+        ref mut inner_angle,
+        ref mut outer_angle,
+    } = light.kind
+    {
+        let mut inner = *inner_angle * (180.0 / PI);
+        let mut outer = *outer_angle * (180.0 / PI);
+        if ui.slider_float("Inner angle", &mut inner, 0.0, 90.0) {
+            *inner_angle = inner * (PI / 180.0);
+            changed = true;
+        }
+        if ui.slider_float("Outer angle", &mut outer, 0.0, 90.0) {
+            *outer_angle = outer * (PI / 180.0);
+            changed = true;
+        }
+    }
+    if light.kind != LightKind::Point {
+        let mut shadows = light.shadow.is_some();
+        if ui.checkbox("Shadows", &mut shadows) {
+            light.shadow = if shadows {
+                Some(ShadowSettings::default())
+            } else {
+                None
+            };
+            changed = true;
+        }
+    }
+
+    if ui.button("Remove light") {
+        lights.remove(entity);
+    } else if changed {
+        *lights.get_mut(entity).unwrap() = light;
+    }
+}
+
+fn edit_camera(ui: &Ui, world: &World, entity: Entity) {
+    let mut cameras = world.write_storage::<Camera>();
+    let mut camera = match cameras.get(entity) {
+        Some(camera) => *camera,
+        None => {
+            if ui.button("Add camera") {
+                cameras
+                    .insert(entity, Camera::new(vec3(0.0, 0.0, 0.0)))
+                    .unwrap();
+            }
+            return;
+        }
+    };
+    if !ui.collapsing_header("Camera") {
+        return;
+    }
+
+    let mut changed = ui.drag_float3("Position##camera", &mut camera.position, 0.05);
+    let mut yaw = camera.yaw * (180.0 / PI);
+    if ui.drag_float("Yaw", &mut yaw, 1.0) {
+        camera.yaw = yaw * (PI / 180.0);
+        changed = true;
+    }
+    let mut pitch = camera.pitch * (180.0 / PI);
+    if ui.slider_float("Pitch", &mut pitch, -89.0, 89.0) {
+        camera.pitch = pitch * (PI / 180.0);
+        changed = true;
+    }
+    let mut fov = camera.fov * (180.0 / PI);
+    if ui.slider_float("Field of view", &mut fov, 10.0, 120.0) {
+        camera.fov = fov * (PI / 180.0);
+        changed = true;
+    }
+    if ui.drag_float("Near", &mut camera.near, 0.001) {
+        camera.near = camera.near.max(0.001);
+        changed = true;
+    }
+    if ui.drag_float("Far", &mut camera.far, 1.0) {
+        camera.far = camera.far.max(camera.near + 0.001);
+        changed = true;
+    }
+
+    if ui.button("Remove camera") {
+        cameras.remove(entity);
+    } else if changed {
+        *cameras.get_mut(entity).unwrap() = camera;
+    }
+}
@@ -0,0 +1,115 @@
+pub mod input;
+pub mod inspector;
+pub mod renderer;
+pub mod ui;
+
+pub use self::inspector::*;
+pub use self::renderer::*;
+pub use self::ui::*;
+
+use image::Image;
+use imgui::{
+    igCreateContext, igDestroyContext, igGetIO, igNewFrame, ImFontAtlas_GetTexDataAsRGBA32,
+    ImGuiContext, ImGuiIO, ImVec2,
+};
+
+use std::os::raw::{c_int, c_uchar};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// ImGui keeps its state in one global context, so only one `Gui` can exist at a time.
+static CONTEXT_ALIVE: AtomicBool = AtomicBool::new(false);
+
+/// Owns the Dear ImGui context. Window events go in through `handle_event`, and each frame's
+/// widgets are built on the `Ui` returned by `frame`, whose draw data the `GuiRenderer` draws.
+pub struct Gui {
+    context: *mut ImGuiContext,
+    font_atlas: Image,
+}
+
+impl Gui {
+    /// Panics if another `Gui` is alive.
+    pub fn new() -> Gui {
+        if CONTEXT_ALIVE.swap(true, Ordering::SeqCst) {
+            panic!("Only one Gui can exist at a time");
+        }
+
+        let context = unsafe { igCreateContext(ptr::null_mut()) };
+        let io = unsafe { &mut *igGetIO() };
+        // Window positions come from the code, so there is nothing to remember in an imgui.ini
+        io.IniFilename = ptr::null();
+        input::set_key_map(io);
+
+        // Getting the pixels builds the atlas, which has to happen before the first frame
+        let font_atlas = unsafe {
+            let mut pixels: *mut c_uchar = ptr::null_mut();
+            let mut width: c_int = 0;
+            let mut height: c_int = 0;
+            let mut bytes_per_pixel: c_int = 0;
+            ImFontAtlas_GetTexDataAsRGBA32(
+                (*igGetIO()).Fonts,
+                &mut pixels,
+                &mut width,
+                &mut height,
+                &mut bytes_per_pixel,
+            );
+            let len = (width * height * bytes_per_pixel) as usize;
+            Image {
+                data: slice::from_raw_parts(pixels, len).to_vec(),
+                width: width as u32,
+                height: height as u32,
+            }
+        };
+
+        Gui {
+            context: context,
+            font_atlas: font_atlas,
+        }
+    }
+
+    /// The RGBA8 font texture, for `Renderer::set_gui_font`.
+    pub fn font_atlas(&self) -> &Image {
+        &self.font_atlas
+    }
+
+    /// True while the mouse is over a window, so the game should ignore it.
+    pub fn want_capture_mouse(&self) -> bool {
+        self.io().WantCaptureMouse
+    }
+
+    /// True while a widget has keyboard focus, so the game should ignore key presses.
+    pub fn want_capture_keyboard(&self) -> bool {
+        self.io().WantCaptureKeyboard
+    }
+
+    /// Starts a frame the size of a window of `dimensions`, `delta_time` seconds after the last.
+    pub fn frame(&mut self, dimensions: [u32; 2], delta_time: f32) -> Ui {
+        {
+            let io = self.io_mut();
+            io.DisplaySize = ImVec2 {
+                x: dimensions[0] as f32,
+                y: dimensions[1] as f32,
+            };
+            // ImGui asserts on a zero delta
+            io.DeltaTime = delta_time.max(1e-5);
+        }
+        unsafe { igNewFrame() };
+        Ui::new()
+    }
+
+    fn io(&self) -> &ImGuiIO {
+        unsafe { &*igGetIO() }
+    }
+
+    fn io_mut(&mut self) -> &mut ImGuiIO {
+        unsafe { &mut *igGetIO() }
+    }
+}
+
+impl Drop for Gui {
+    fn drop(&mut self) {
+        unsafe { igDestroyContext(self.context) };
+        CONTEXT_ALIVE.store(false, Ordering::SeqCst);
+    }
+}
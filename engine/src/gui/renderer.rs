@@ -0,0 +1,256 @@
+use glm::ortho;
+use image::Image;
+use renderer::GpuTexture;
+use shader::{ShaderError, ShaderProgram};
+
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::CpuBufferPool;
+use vulkano::buffer::{BufferSlice, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::viewport::{Scissor, Viewport};
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::Sampler;
+use vulkano::sync::GpuFuture;
+
+use super::ui::{GuiDrawData, GuiTexture, GuiVertex};
+
+pub const GUI_SHADER: &str = "gui";
+
+type GuiPipeline = GraphicsPipeline<
+    SingleBufferDefinition<GuiVertex>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+/// Draws the output of `Ui::render` and the HUD with gui.vs and gui.fs, one draw call per scissor
+/// rect and texture.
+///
+/// Both are drawn in the final post-processing pass: the HUD under text, ImGui last over
+/// everything.
+pub struct GuiRenderer {
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: ShaderProgram,
+    pipeline: Arc<GuiPipeline>,
+    vertex_pool: CpuBufferPool<GuiVertex>,
+    index_pool: CpuBufferPool<u32>,
+    sampler: Arc<Sampler>,
+    font: Option<Arc<ImmutableImage<Format>>>,
+    draw_data: Option<GuiDrawData>,
+    hud: Option<GuiDrawData>,
+}
+
+impl GuiRenderer {
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> GuiRenderer {
+        let program = match ShaderProgram::create_program(device.clone(), GUI_SHADER) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the gui shader:\n{}", e),
+        };
+        let pipeline = match create_pipeline(device.clone(), render_pass.clone(), &program) {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the gui pipeline:\n{}", e),
+        };
+
+        GuiRenderer {
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            index_pool: CpuBufferPool::new(device.clone(), BufferUsage::index_buffer()),
+            sampler: Sampler::simple_repeat_linear_no_mipmap(device.clone()),
+            device: device,
+            render_pass: render_pass,
+            program: program,
+            pipeline: pipeline,
+            font: None,
+            draw_data: None,
+            hud: None,
+        }
+    }
+
+    /// Recompiles gui.vs and gui.fs, keeping the current pipeline if that fails.
+    pub fn reload_shader(&mut self) -> Result<(), ShaderError> {
+        let program = self.program.reload(self.device.clone(), GUI_SHADER)?;
+        self.pipeline = create_pipeline(self.device.clone(), self.render_pass.clone(), &program)?;
+        self.program = program;
+        Ok(())
+    }
+
+    /// Uploads the font atlas of a `Gui`. The returned future has to be joined into the frame
+    /// before drawing.
+    pub fn set_font_atlas(&mut self, atlas: &Image, queue: Arc<Queue>) -> Box<dyn GpuFuture> {
+        let (image, future) = ImmutableImage::from_iter(
+            atlas.data.iter().cloned(),
+            Dimensions::Dim2d {
+                width: atlas.width,
+                height: atlas.height,
+            },
+            Format::R8G8B8A8Unorm,
+            queue,
+        )
+        .unwrap();
+        self.font = Some(image);
+        Box::new(future)
+    }
+
+    /// Replaces the ImGui output drawn this frame.
+    pub fn queue(&mut self, draw_data: GuiDrawData) {
+        self.draw_data = Some(draw_data);
+    }
+
+    /// Replaces the HUD drawn this frame, see `Renderer::queue_hud`.
+    pub fn queue_hud(&mut self, draw_data: GuiDrawData) {
+        self.hud = Some(draw_data);
+    }
+
+    /// Records the queued HUD into the current render pass and clears it. `textures` are what
+    /// `GuiTexture::Texture` handles point into.
+    pub fn draw_hud(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        dimensions: [u32; 2],
+        textures: &[GpuTexture],
+        white: &GpuTexture,
+    ) -> AutoCommandBufferBuilder {
+        match self.hud.take() {
+            Some(draw_data) => self.record(builder, draw_data, dimensions, textures, white),
+            None => builder,
+        }
+    }
+
+    /// Records the queued ImGui output into the current render pass and clears it. Commands
+    /// using the font atlas are skipped until one is set.
+    pub fn draw(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        dimensions: [u32; 2],
+        textures: &[GpuTexture],
+        white: &GpuTexture,
+    ) -> AutoCommandBufferBuilder {
+        match self.draw_data.take() {
+            Some(draw_data) => self.record(builder, draw_data, dimensions, textures, white),
+            None => builder,
+        }
+    }
+
+    fn record(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        draw_data: GuiDrawData,
+        dimensions: [u32; 2],
+        textures: &[GpuTexture],
+        white: &GpuTexture,
+    ) -> AutoCommandBufferBuilder {
+        // A buffer can't be empty
+        if draw_data.commands.is_empty() {
+            return builder;
+        }
+
+        let [width, height] = dimensions;
+        self.program
+            .set_mat4(
+                "proj",
+                &ortho(0.0, width as f32, 0.0, height as f32, -1.0, 1.0),
+            )
+            .unwrap();
+        let uniforms = self.program.uniform_buffer(0, 0).unwrap();
+        let layout = self.pipeline.descriptor_set_layout(0).unwrap().clone();
+
+        let vertex_buffer = Arc::new(self.vertex_pool.chunk(draw_data.vertices).unwrap());
+        let index_buffer = Arc::new(self.index_pool.chunk(draw_data.indices).unwrap());
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [width as f32, height as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        let mut builder = builder;
+        // Consecutive commands mostly share a texture, so the set is only rebuilt when it changes
+        let mut current: Option<(GuiTexture, Arc<dyn DescriptorSet + Send + Sync>)> = None;
+        for command in &draw_data.commands {
+            let min_x = command.clip_min.x.max(0.0);
+            let min_y = command.clip_min.y.max(0.0);
+            let max_x = command.clip_max.x.min(width as f32);
+            let max_y = command.clip_max.y.min(height as f32);
+            if max_x <= min_x || max_y <= min_y {
+                continue;
+            }
+
+            let set = match current {
+                Some((texture, ref set)) if texture == command.texture => set.clone(),
+                _ => {
+                    let image = match command.texture {
+                        GuiTexture::Font => match self.font {
+                            Some(ref font) => font.clone(),
+                            None => continue,
+                        },
+                        GuiTexture::White => white.clone(),
+                        GuiTexture::Texture(handle) => textures[handle.0].clone(),
+                    };
+                    let set: Arc<dyn DescriptorSet + Send + Sync> = Arc::new(
+                        PersistentDescriptorSet::start(layout.clone())
+                            .add_buffer(uniforms.clone())
+                            .unwrap()
+                            .add_sampled_image(image, self.sampler.clone())
+                            .unwrap()
+                            .build()
+                            .unwrap(),
+                    );
+                    current = Some((command.texture, set.clone()));
+                    set
+                }
+            };
+
+            let dynamic_state = DynamicState {
+                viewports: Some(vec![viewport.clone()]),
+                scissors: Some(vec![Scissor {
+                    origin: [min_x as i32, min_y as i32],
+                    dimensions: [(max_x - min_x) as u32, (max_y - min_y) as u32],
+                }]),
+                ..DynamicState::none()
+            };
+
+            let start = command.first_index as usize;
+            let indices = BufferSlice::from_typed_buffer_access(index_buffer.clone())
+                .slice(start..start + command.index_count as usize)
+                .unwrap();
+            builder = builder
+                .draw_indexed(
+                    self.pipeline.clone(),
+                    &dynamic_state,
+                    vertex_buffer.clone(),
+                    indices,
+                    set,
+                    (),
+                )
+                .unwrap();
+        }
+        builder
+    }
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<GuiPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(SingleBufferDefinition::<GuiVertex>::new())
+        .vertex_shader(program.vertex_entry_point(), ())
+        .triangle_list()
+        .viewports_scissors_dynamic(1)
+        .fragment_shader(program.fragment_entry_point(), ())
+        .depth_stencil_disabled()
+        .blend_alpha_blending()
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
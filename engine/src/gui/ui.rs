@@ -0,0 +1,624 @@
+use drawing::{Color3, Color4};
+use glm::{vec2, Vec2, Vec3};
+use imgui::{
+    igBegin, igButton, igCheckbox, igCollapsingHeader, igColorEdit3, igColorEdit4, igCombo,
+    igDragFloat, igDragFloat3, igEnd, igEndFrame, igGetDrawData, igInputText, igPlotLines, igPopID,
+    igPushIDInt, igRender, igSameLine, igSelectable, igSeparator, igSetNextWindowPos,
+    igSetNextWindowSize, igSliderFloat, igTextUnformatted, igTreeNodeStr, igTreePop, ImDrawData,
+    ImDrawList, ImGuiCond_FirstUseEver, ImVec2,
+};
+use renderer::TextureHandle;
+
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+#[derive(Default, Clone, Debug)]
+pub struct GuiVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+vulkano::impl_vertex!(GuiVertex, pos, uv, color);
+
+impl GuiVertex {
+    pub fn new(pos: Vec2, uv: Vec2, color: Color4) -> GuiVertex {
+        GuiVertex {
+            pos: [pos.x, pos.y],
+            uv: [uv.x, uv.y],
+            color: [color.x, color.y, color.z, color.w],
+        }
+    }
+}
+
+/// What a `GuiDrawCommand` samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GuiTexture {
+    /// The ImGui font atlas.
+    Font,
+    /// A 1x1 white texture, for plain colors.
+    White,
+    Texture(TextureHandle),
+}
+
+/// A run of indices drawn with one scissor rect, in pixels from the top left corner.
+#[derive(Clone, Copy, Debug)]
+pub struct GuiDrawCommand {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub clip_min: Vec2,
+    pub clip_max: Vec2,
+    pub texture: GuiTexture,
+}
+
+/// Vertices and indices to draw with the gui shaders. A frame of ImGui output is copied out of
+/// ImGui with the indices of every draw list made to point into one vertex list.
+#[derive(Clone, Debug, Default)]
+pub struct GuiDrawData {
+    pub vertices: Vec<GuiVertex>,
+    pub indices: Vec<u32>,
+    pub commands: Vec<GuiDrawCommand>,
+}
+
+impl GuiDrawData {
+    /// Adds a rectangle from `min` to `max` in pixels, cut to the clip rect `clip_min`..`clip_max`.
+    /// It joins the last command when that uses the same texture and clip rect.
+    pub fn add_quad(
+        &mut self,
+        min: Vec2,
+        max: Vec2,
+        uv_min: Vec2,
+        uv_max: Vec2,
+        color: Color4,
+        texture: GuiTexture,
+        clip: (Vec2, Vec2),
+    ) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&[
+            GuiVertex::new(min, uv_min, color),
+            GuiVertex::new(vec2(max.x, min.y), vec2(uv_max.x, uv_min.y), color),
+            GuiVertex::new(max, uv_max, color),
+            GuiVertex::new(vec2(min.x, max.y), vec2(uv_min.x, uv_max.y), color),
+        ]);
+        let first_index = self.indices.len() as u32;
+        self.indices
+            .extend([0, 1, 2, 0, 2, 3].iter().map(|&i| base + i));
+
+        if let Some(last) = self.commands.last_mut() {
+            if last.texture == texture && (last.clip_min, last.clip_max) == clip {
+                last.index_count += 6;
+                return;
+            }
+        }
+        self.commands.push(GuiDrawCommand {
+            first_index: first_index,
+            index_count: 6,
+            clip_min: clip.0,
+            clip_max: clip.1,
+            texture: texture,
+        });
+    }
+}
+
+/// Builds the widgets of one frame. Widgets are drawn in the order they are called and the frame
+/// ends with `render`.
+pub struct Ui<'gui> {
+    rendered: bool,
+    _gui: PhantomData<&'gui mut ()>,
+}
+
+impl<'gui> Ui<'gui> {
+    pub(super) fn new() -> Ui<'gui> {
+        Ui {
+            rendered: false,
+            _gui: PhantomData,
+        }
+    }
+
+    /// A window to put widgets in, see `Window::build`.
+    pub fn window(&self, name: &str) -> Window {
+        Window {
+            name: c_string(name),
+            position: None,
+            size: None,
+            _ui: PhantomData,
+        }
+    }
+
+    pub fn text(&self, text: &str) {
+        let start = text.as_ptr() as *const c_char;
+        unsafe { igTextUnformatted(start, start.add(text.len())) };
+    }
+
+    /// True the frame the button is clicked.
+    pub fn button(&self, label: &str) -> bool {
+        let label = c_string(label);
+        unsafe { igButton(label.as_ptr(), ImVec2 { x: 0.0, y: 0.0 }) }
+    }
+
+    /// These return true when the user changed `value`.
+    pub fn checkbox(&self, label: &str, value: &mut bool) -> bool {
+        let label = c_string(label);
+        unsafe { igCheckbox(label.as_ptr(), value) }
+    }
+
+    pub fn slider_float(&self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let label = c_string(label);
+        let format = c_string("%.3f");
+        unsafe { igSliderFloat(label.as_ptr(), value, min, max, format.as_ptr(), 1.0) }
+    }
+
+    /// Changes `value` by `speed` per pixel the mouse is dragged.
+    pub fn drag_float(&self, label: &str, value: &mut f32, speed: f32) -> bool {
+        let label = c_string(label);
+        let format = c_string("%.3f");
+        unsafe { igDragFloat(label.as_ptr(), value, speed, 0.0, 0.0, format.as_ptr(), 1.0) }
+    }
+
+    pub fn drag_float3(&self, label: &str, value: &mut Vec3, speed: f32) -> bool {
+        let label = c_string(label);
+        let format = c_string("%.3f");
+        unsafe {
+            igDragFloat3(
+                label.as_ptr(),
+                value.as_mut_ptr(),
+                speed,
+                0.0,
+                0.0,
+                format.as_ptr(),
+                1.0,
+            )
+        }
+    }
+
+    pub fn color_edit(&self, label: &str, color: &mut Color4) -> bool {
+        let label = c_string(label);
+        unsafe { igColorEdit4(label.as_ptr(), color.as_mut_ptr(), 0) }
+    }
+
+    pub fn color_edit3(&self, label: &str, color: &mut Color3) -> bool {
+        let label = c_string(label);
+        unsafe { igColorEdit3(label.as_ptr(), color.as_mut_ptr(), 0) }
+    }
+
+    /// Edits `value` in place; it can't grow past `capacity` bytes.
+    pub fn input_text(&self, label: &str, value: &mut String, capacity: usize) -> bool {
+        let label = c_string(label);
+        let mut buffer = value.clone().into_bytes();
+        buffer.truncate(capacity);
+        buffer.resize(capacity + 1, 0);
+        let changed = unsafe {
+            igInputText(
+                label.as_ptr(),
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len(),
+                0,
+                None,
+                ptr::null_mut(),
+            )
+        };
+        if changed {
+            let len = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+            *value = String::from_utf8_lossy(&buffer[..len]).into_owned();
+        }
+        changed
+    }
+
+    /// A dropdown choosing one of `items` into `current`.
+    pub fn combo(&self, label: &str, current: &mut usize, items: &[&str]) -> bool {
+        let label = c_string(label);
+        let items: Vec<CString> = items.iter().map(|item| c_string(item)).collect();
+        let item_ptrs: Vec<*const c_char> = items.iter().map(|item| item.as_ptr()).collect();
+        let mut index = *current as i32;
+        let changed = unsafe {
+            igCombo(
+                label.as_ptr(),
+                &mut index,
+                item_ptrs.as_ptr(),
+                item_ptrs.len() as i32,
+                -1,
+            )
+        };
+        *current = index as usize;
+        changed
+    }
+
+    /// True the frame it is clicked. `selected` only decides how it is highlighted.
+    pub fn selectable(&self, label: &str, selected: bool) -> bool {
+        let label = c_string(label);
+        unsafe { igSelectable(label.as_ptr(), selected, 0, ImVec2 { x: 0.0, y: 0.0 }) }
+    }
+
+    /// A graph of `values`, e.g. recent frame times.
+    pub fn plot_lines(&self, label: &str, values: &[f32]) {
+        let label = c_string(label);
+        unsafe {
+            igPlotLines(
+                label.as_ptr(),
+                values.as_ptr(),
+                values.len() as i32,
+                0,
+                ptr::null(),
+                ::std::f32::MAX,
+                ::std::f32::MAX,
+                ImVec2 { x: 0.0, y: 40.0 },
+                4,
+            )
+        };
+    }
+
+    /// True while the header is open, when whatever is under it should be built.
+    pub fn collapsing_header(&self, label: &str) -> bool {
+        let label = c_string(label);
+        unsafe { igCollapsingHeader(label.as_ptr(), 0) }
+    }
+
+    /// Builds `f` inside the node while it is open.
+    pub fn tree_node<F: FnOnce()>(&self, label: &str, f: F) {
+        let label = c_string(label);
+        if unsafe { igTreeNodeStr(label.as_ptr()) } {
+            f();
+            unsafe { igTreePop() };
+        }
+    }
+
+    /// Builds `f` with `id` mixed into the IDs of its widgets, so widgets with the same label,
+    /// e.g. one per list entry, are told apart.
+    pub fn with_id<F: FnOnce()>(&self, id: i32, f: F) {
+        unsafe { igPushIDInt(id) };
+        f();
+        unsafe { igPopID() };
+    }
+
+    /// Puts the next widget on the same line as the previous one.
+    pub fn same_line(&self) {
+        unsafe { igSameLine(0.0, -1.0) };
+    }
+
+    pub fn separator(&self) {
+        unsafe { igSeparator() };
+    }
+
+    /// Ends the frame and copies out what ImGui drew, for `GuiRenderer::queue`.
+    pub fn render(mut self) -> GuiDrawData {
+        self.rendered = true;
+        unsafe {
+            igRender();
+            copy_draw_data(&*igGetDrawData())
+        }
+    }
+}
+
+impl<'gui> Drop for Ui<'gui> {
+    fn drop(&mut self) {
+        if !self.rendered {
+            unsafe { igEndFrame() };
+        }
+    }
+}
+
+/// Set up with `Ui::window`. Position and size only apply the first time the window is shown;
+/// after that the user moves it.
+pub struct Window<'ui> {
+    name: CString,
+    position: Option<Vec2>,
+    size: Option<Vec2>,
+    _ui: PhantomData<&'ui ()>,
+}
+
+impl<'ui> Window<'ui> {
+    pub fn position(mut self, position: Vec2) -> Window<'ui> {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn size(mut self, size: Vec2) -> Window<'ui> {
+        self.size = Some(size);
+        self
+    }
+
+    /// Builds the window's widgets with `f`, which isn't called while the window is collapsed.
+    pub fn build<F: FnOnce()>(self, f: F) {
+        let condition = ImGuiCond_FirstUseEver as i32;
+        unsafe {
+            if let Some(position) = self.position {
+                igSetNextWindowPos(
+                    ImVec2 {
+                        x: position.x,
+                        y: position.y,
+                    },
+                    condition,
+                    ImVec2 { x: 0.0, y: 0.0 },
+                );
+            }
+            if let Some(size) = self.size {
+                igSetNextWindowSize(
+                    ImVec2 {
+                        x: size.x,
+                        y: size.y,
+                    },
+                    condition,
+                );
+            }
+            // End has to be called even when Begin returns false
+            if igBegin(self.name.as_ptr(), ptr::null_mut(), 0) {
+                f();
+            }
+            igEnd();
+        }
+    }
+}
+
+/// Labels with a nul in them are cut off there.
+fn c_string(s: &str) -> CString {
+    let end = s.find('\0').unwrap_or(s.len());
+    CString::new(&s[..end]).unwrap()
+}
+
+unsafe fn copy_draw_data(draw_data: &ImDrawData) -> GuiDrawData {
+    let mut data = GuiDrawData::default();
+    if !draw_data.Valid || draw_data.CmdListsCount <= 0 {
+        return data;
+    }
+    let origin = draw_data.DisplayPos;
+    let lists = slice::from_raw_parts(draw_data.CmdLists, draw_data.CmdListsCount as usize);
+    for &list in lists {
+        let list: &ImDrawList = &*list;
+        let base = data.vertices.len() as u32;
+        let vertices = vector(list.VtxBuffer.Data, list.VtxBuffer.Size);
+        let indices = vector(list.IdxBuffer.Data, list.IdxBuffer.Size);
+
+        data.vertices.extend(vertices.iter().map(|v| GuiVertex {
+            pos: [v.pos.x - origin.x, v.pos.y - origin.y],
+            uv: [v.uv.x, v.uv.y],
+            // Packed as ABGR
+            color: [
+                (v.col & 0xff) as f32 / 255.0,
+                ((v.col >> 8) & 0xff) as f32 / 255.0,
+                ((v.col >> 16) & 0xff) as f32 / 255.0,
+                ((v.col >> 24) & 0xff) as f32 / 255.0,
+            ],
+        }));
+
+        for command in vector(list.CmdBuffer.Data, list.CmdBuffer.Size) {
+            // Callbacks are for custom rendering, which nothing uses
+            if command.UserCallback.is_some() || command.ElemCount == 0 {
+                continue;
+            }
+            let start = command.IdxOffset as usize;
+            let end = start + command.ElemCount as usize;
+            let first_index = data.indices.len() as u32;
+            let vertex_offset = base + command.VtxOffset;
+            data.indices.extend(
+                indices[start..end]
+                    .iter()
+                    .map(|&index| vertex_offset + index as u32),
+            );
+            let clip = command.ClipRect;
+            data.commands.push(GuiDrawCommand {
+                first_index: first_index,
+                index_count: command.ElemCount,
+                clip_min: vec2(clip.x - origin.x, clip.y - origin.y),
+                clip_max: vec2(clip.z - origin.x, clip.w - origin.y),
+                texture: GuiTexture::Font,
+            });
+        }
+    }
+    data
+}
+
+/// The contents of an ImVector.
+unsafe fn vector<'a, T>(data: *const T, size: i32) -> &'a [T] {
+    if data.is_null() || size <= 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, size as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::vec4;
+    use imgui::{
+        ImDrawCmd, ImDrawIdx, ImDrawVert, ImVec4, ImVector_ImDrawCmd, ImVector_ImDrawIdx,
+        ImVector_ImDrawVert,
+    };
+    use std::mem;
+
+    fn vertex(x: f32, y: f32, col: u32) -> ImDrawVert {
+        ImDrawVert {
+            pos: ImVec2 { x: x, y: y },
+            uv: ImVec2 { x: 0.5, y: 0.25 },
+            col: col,
+        }
+    }
+
+    fn command(count: u32, idx_offset: u32, vtx_offset: u32, clip: [f32; 4]) -> ImDrawCmd {
+        ImDrawCmd {
+            ElemCount: count,
+            ClipRect: ImVec4 {
+                x: clip[0],
+                y: clip[1],
+                z: clip[2],
+                w: clip[3],
+            },
+            TextureId: ptr::null_mut(),
+            VtxOffset: vtx_offset,
+            IdxOffset: idx_offset,
+            UserCallback: None,
+            UserCallbackData: ptr::null_mut(),
+        }
+    }
+
+    /// A draw list pointing into the given buffers, which have to outlive it.
+    fn draw_list(
+        vertices: &mut Vec<ImDrawVert>,
+        indices: &mut Vec<ImDrawIdx>,
+        commands: &mut Vec<ImDrawCmd>,
+    ) -> ImDrawList {
+        let mut list: ImDrawList = unsafe { mem::zeroed() };
+        list.VtxBuffer = ImVector_ImDrawVert {
+            Size: vertices.len() as i32,
+            Capacity: vertices.len() as i32,
+            Data: vertices.as_mut_ptr(),
+        };
+        list.IdxBuffer = ImVector_ImDrawIdx {
+            Size: indices.len() as i32,
+            Capacity: indices.len() as i32,
+            Data: indices.as_mut_ptr(),
+        };
+        list.CmdBuffer = ImVector_ImDrawCmd {
+            Size: commands.len() as i32,
+            Capacity: commands.len() as i32,
+            Data: commands.as_mut_ptr(),
+        };
+        list
+    }
+
+    fn draw_data(lists: &mut Vec<*mut ImDrawList>, origin: Vec2) -> ImDrawData {
+        ImDrawData {
+            Valid: true,
+            CmdLists: lists.as_mut_ptr(),
+            CmdListsCount: lists.len() as i32,
+            TotalIdxCount: 0,
+            TotalVtxCount: 0,
+            DisplayPos: ImVec2 {
+                x: origin.x,
+                y: origin.y,
+            },
+            DisplaySize: ImVec2 { x: 800.0, y: 600.0 },
+            FramebufferScale: ImVec2 { x: 1.0, y: 1.0 },
+        }
+    }
+
+    #[test]
+    fn draw_lists_are_joined_into_one_vertex_list() {
+        let clip = [110.0, 60.0, 300.0, 250.0];
+        let mut vertices_a = vec![
+            vertex(100.0, 50.0, 0xff00_00ff),
+            vertex(110.0, 50.0, 0xff00_ff00),
+            vertex(110.0, 60.0, 0x80ff_0000),
+            vertex(100.0, 60.0, 0x0000_0000),
+        ];
+        let mut indices_a = vec![0, 1, 2, 0, 2, 3];
+        let mut commands_a = vec![
+            command(3, 0, 0, clip),
+            // Empty commands are left out
+            command(0, 3, 0, clip),
+            command(3, 3, 0, [100.0, 50.0, 900.0, 650.0]),
+        ];
+        // The second command starts at vertex 3, like ImGui does for lists of over 64k vertices
+        let mut vertices_b: Vec<ImDrawVert> =
+            (0..6).map(|i| vertex(200.0 + i as f32, 80.0, 0)).collect();
+        let mut indices_b = vec![0, 1, 2, 2, 1, 0];
+        let mut commands_b = vec![command(3, 0, 0, clip), command(3, 3, 3, clip)];
+
+        let mut list_a = draw_list(&mut vertices_a, &mut indices_a, &mut commands_a);
+        let mut list_b = draw_list(&mut vertices_b, &mut indices_b, &mut commands_b);
+        let mut lists = vec![&mut list_a as *mut _, &mut list_b as *mut _];
+        let data = unsafe { copy_draw_data(&draw_data(&mut lists, vec2(100.0, 50.0))) };
+
+        assert_eq!(data.vertices.len(), 10);
+        assert_eq!(data.indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 9, 8, 7]);
+        let firsts: Vec<(u32, u32)> = data
+            .commands
+            .iter()
+            .map(|c| (c.first_index, c.index_count))
+            .collect();
+        assert_eq!(firsts, vec![(0, 3), (3, 3), (6, 3), (9, 3)]);
+
+        // Positions and clip rects are moved so the display starts at 0, 0
+        assert_eq!(data.vertices[0].pos, [0.0, 0.0]);
+        assert_eq!(data.vertices[2].pos, [10.0, 10.0]);
+        assert_eq!(data.vertices[4].pos, [100.0, 30.0]);
+        assert_eq!(data.commands[0].clip_min, vec2(10.0, 10.0));
+        assert_eq!(data.commands[0].clip_max, vec2(200.0, 200.0));
+        assert_eq!(data.commands[1].clip_max, vec2(800.0, 600.0));
+        assert!(data.commands.iter().all(|c| c.texture == GuiTexture::Font));
+
+        // Colors are packed as ABGR
+        assert_eq!(data.vertices[0].color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(data.vertices[1].color, [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(data.vertices[2].color[2], 1.0);
+        assert!((data.vertices[2].color[3] - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!(data.vertices[0].uv, [0.5, 0.25]);
+    }
+
+    #[test]
+    fn invalid_draw_data_is_empty() {
+        let mut vertices = vec![vertex(0.0, 0.0, 0); 3];
+        let mut indices = vec![0, 1, 2];
+        let mut commands = vec![command(3, 0, 0, [0.0, 0.0, 10.0, 10.0])];
+        let mut list = draw_list(&mut vertices, &mut indices, &mut commands);
+        let mut lists = vec![&mut list as *mut _];
+        let mut draw_data = draw_data(&mut lists, vec2(0.0, 0.0));
+        draw_data.Valid = false;
+
+        let data = unsafe { copy_draw_data(&draw_data) };
+        assert!(data.vertices.is_empty());
+        assert!(data.commands.is_empty());
+    }
+
+    #[test]
+    fn quads_with_the_same_texture_and_clip_share_a_command() {
+        let white = vec4(1.0, 1.0, 1.0, 1.0);
+        let screen = (vec2(0.0, 0.0), vec2(800.0, 600.0));
+        let zero = vec2(0.0, 0.0);
+        let one = vec2(1.0, 1.0);
+        let mut data = GuiDrawData::default();
+        let quad = |data: &mut GuiDrawData, x: f32, texture, clip| {
+            data.add_quad(
+                vec2(x, 0.0),
+                vec2(x + 10.0, 10.0),
+                zero,
+                one,
+                white,
+                texture,
+                clip,
+            )
+        };
+        quad(&mut data, 0.0, GuiTexture::White, screen);
+        quad(&mut data, 10.0, GuiTexture::White, screen);
+        quad(&mut data, 20.0, GuiTexture::Font, screen);
+        quad(
+            &mut data,
+            30.0,
+            GuiTexture::Font,
+            (zero, vec2(100.0, 100.0)),
+        );
+        quad(
+            &mut data,
+            40.0,
+            GuiTexture::Font,
+            (zero, vec2(100.0, 100.0)),
+        );
+
+        assert_eq!(data.vertices.len(), 20);
+        assert_eq!(data.indices.len(), 30);
+        assert_eq!(&data.indices[6..12], &[4, 5, 6, 4, 6, 7]);
+        let commands: Vec<(u32, u32, GuiTexture)> = data
+            .commands
+            .iter()
+            .map(|c| (c.first_index, c.index_count, c.texture))
+            .collect();
+        assert_eq!(
+            commands,
+            vec![
+                (0, 12, GuiTexture::White),
+                (12, 6, GuiTexture::Font),
+                (18, 12, GuiTexture::Font),
+            ]
+        );
+
+        // Corners go clockwise from the top left
+        let corners: Vec<[f32; 2]> = data.vertices[4..8].iter().map(|v| v.pos).collect();
+        assert_eq!(
+            corners,
+            vec![[10.0, 0.0], [20.0, 0.0], [20.0, 10.0], [10.0, 10.0]]
+        );
+        assert_eq!(data.vertices[5].uv, [1.0, 0.0]);
+    }
+}
@@ -0,0 +1,340 @@
+use glm::vec2;
+use hud::Hud;
+
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+impl Hud {
+    /// Hovers and clicks buttons with the left mouse button, moves keyboard focus between the
+    /// visible buttons with Tab, Shift+Tab and the arrow keys, and presses the focused one with
+    /// Enter or Space.
+    ///
+    /// Returns true when the HUD used the event, so the game should ignore it. Cursor movement is
+    /// never used up, so the game always knows where the mouse is. Hover and focus use the
+    /// positions of the last `layout`.
+    ///
+    /// Only the first press of a held key does anything. Key repeats and the release are used up
+    /// exactly when that first press was.
+    pub fn handle_event(&mut self, event: &WindowEvent) -> bool {
+        match *event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = vec2(position.x as f32, position.y as f32);
+                self.cursor = Some(cursor);
+                self.hovered = self.button_at(cursor);
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.hovered = None;
+                false
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.pressed = self.hovered;
+                    if self.hovered.is_some() {
+                        self.focused = self.hovered;
+                    }
+                    self.pressed.is_some()
+                }
+                // A click needs the release over the same button as the press
+                ElementState::Released => match self.pressed.take() {
+                    Some(pressed) => {
+                        if self.hovered == Some(pressed) {
+                            self.clicks.push(pressed);
+                        }
+                        true
+                    }
+                    None => false,
+                },
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.shift = modifiers.shift();
+                false
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if let Some(&used) = self.held_keys.get(&key) {
+                        return used;
+                    }
+                    let used = self.press_key(key);
+                    self.held_keys.insert(key, used);
+                    used
+                }
+                ElementState::Released => self.held_keys.remove(&key).unwrap_or(false),
+            },
+            // A key let go of in another window sends no release, so it would stay held forever
+            WindowEvent::Focused(false) => {
+                self.pressed = None;
+                self.held_keys.clear();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Acts on the first press of `key`, true when it did something.
+    fn press_key(&mut self, key: VirtualKeyCode) -> bool {
+        match key {
+            VirtualKeyCode::Tab => {
+                let forward = !self.shift;
+                self.move_focus(forward)
+            }
+            VirtualKeyCode::Down => self.move_focus(true),
+            VirtualKeyCode::Up => self.move_focus(false),
+            VirtualKeyCode::Return | VirtualKeyCode::Space => match self.focused {
+                Some(focused) => {
+                    self.clicks.push(focused);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Moves focus to the next or previous visible button, wrapping around. False when there is
+    /// no button to focus.
+    fn move_focus(&mut self, forward: bool) -> bool {
+        let buttons = self.buttons();
+        if buttons.is_empty() {
+            return false;
+        }
+        let current = self
+            .focused
+            .and_then(|focused| buttons.iter().position(|&id| id == focused));
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % buttons.len(),
+            (Some(i), false) => (i + buttons.len() - 1) % buttons.len(),
+            (None, true) => 0,
+            (None, false) => buttons.len() - 1,
+        };
+        self.focused = Some(buttons[next]);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use glm::vec4;
+    use hud::{Layout, NodeId, Widget};
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{DeviceId, ModifiersState};
+
+    /// A panel in the middle of an 800x600 window with three buttons stacked in it.
+    fn menu() -> (Hud, NodeId, [NodeId; 3]) {
+        let mut hud = Hud::new();
+        let root = hud.root();
+        let panel = hud.add(
+            root,
+            Layout::anchored(vec2(0.5, 0.5), vec2(200.0, 150.0)),
+            Widget::panel(vec4(0.0, 0.0, 0.0, 0.5)),
+        );
+        let mut button = |i: usize, label: &str| {
+            let top = i as f32 / 3.0;
+            let layout = Layout::percent(vec2(0.0, top), vec2(1.0, top + 1.0 / 3.0)).margin(4.0);
+            hud.add(panel, layout, Widget::button(label))
+        };
+        let buttons = [button(0, "Resume"), button(1, "Options"), button(2, "Quit")];
+        hud.layout([800, 600]);
+        (hud, panel, buttons)
+    }
+
+    fn key(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 0,
+            state: state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        };
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: input,
+            is_synthetic: false,
+        }
+    }
+
+    /// Presses and releases `pressed`, returning whether the HUD used the press.
+    fn press(hud: &mut Hud, pressed: VirtualKeyCode) -> bool {
+        let used = hud.handle_event(&key(pressed, ElementState::Pressed));
+        hud.handle_event(&key(pressed, ElementState::Released));
+        used
+    }
+
+    fn shift(hud: &mut Hud, held: bool) {
+        let modifiers = if held {
+            ModifiersState::SHIFT
+        } else {
+            ModifiersState::empty()
+        };
+        hud.handle_event(&WindowEvent::ModifiersChanged(modifiers));
+    }
+
+    #[test]
+    fn tab_moves_forward_and_wraps_around() {
+        let (mut hud, _, buttons) = menu();
+        assert_eq!(hud.focused(), None);
+        for &expected in &[buttons[0], buttons[1], buttons[2], buttons[0]] {
+            assert!(press(&mut hud, VirtualKeyCode::Tab));
+            assert_eq!(hud.focused(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn shift_tab_moves_backward_and_wraps_around() {
+        let (mut hud, _, buttons) = menu();
+        shift(&mut hud, true);
+        for &expected in &[buttons[2], buttons[1], buttons[0], buttons[2]] {
+            assert!(press(&mut hud, VirtualKeyCode::Tab));
+            assert_eq!(hud.focused(), Some(expected));
+        }
+
+        shift(&mut hud, false);
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert_eq!(hud.focused(), Some(buttons[0]));
+    }
+
+    #[test]
+    fn arrow_keys_move_focus() {
+        let (mut hud, _, buttons) = menu();
+        press(&mut hud, VirtualKeyCode::Up);
+        assert_eq!(hud.focused(), Some(buttons[2]));
+        press(&mut hud, VirtualKeyCode::Down);
+        assert_eq!(hud.focused(), Some(buttons[0]));
+        press(&mut hud, VirtualKeyCode::Down);
+        assert_eq!(hud.focused(), Some(buttons[1]));
+        press(&mut hud, VirtualKeyCode::Up);
+        assert_eq!(hud.focused(), Some(buttons[0]));
+    }
+
+    #[test]
+    fn released_keys_do_nothing() {
+        let (mut hud, _, _) = menu();
+        assert!(!hud.handle_event(&key(VirtualKeyCode::Tab, ElementState::Released)));
+        assert_eq!(hud.focused(), None);
+    }
+
+    #[test]
+    fn hidden_buttons_are_skipped() {
+        let (mut hud, _, buttons) = menu();
+        hud.set_visible(buttons[1], false);
+        hud.layout([800, 600]);
+
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert_eq!(hud.focused(), Some(buttons[0]));
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert_eq!(hud.focused(), Some(buttons[2]));
+        press(&mut hud, VirtualKeyCode::Up);
+        assert_eq!(hud.focused(), Some(buttons[0]));
+    }
+
+    #[test]
+    fn hiding_the_parent_drops_focus() {
+        let (mut hud, panel, buttons) = menu();
+        hud.set_focus(Some(buttons[1]));
+        hud.set_visible(panel, false);
+        hud.layout([800, 600]);
+        assert_eq!(hud.focused(), None);
+
+        // With nothing to focus the keys are left to the game
+        assert!(!press(&mut hud, VirtualKeyCode::Tab));
+        assert!(!press(&mut hud, VirtualKeyCode::Return));
+        assert_eq!(hud.focused(), None);
+    }
+
+    #[test]
+    fn return_and_space_click_the_focused_button() {
+        let (mut hud, _, buttons) = menu();
+        assert!(!press(&mut hud, VirtualKeyCode::Return));
+        assert!(hud.take_clicks().is_empty());
+
+        hud.set_focus(Some(buttons[2]));
+        assert!(press(&mut hud, VirtualKeyCode::Return));
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert!(press(&mut hud, VirtualKeyCode::Space));
+        assert_eq!(hud.take_clicks(), vec![buttons[2], buttons[0]]);
+        assert!(hud.take_clicks().is_empty());
+    }
+
+    #[test]
+    fn clicking_a_button_focuses_it() {
+        let (mut hud, _, buttons) = menu();
+        let center = hud.rect(buttons[1]).center();
+        #[allow(deprecated)]
+        let moved = WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(center.x as f64, center.y as f64),
+            modifiers: ModifiersState::empty(),
+        };
+        #[allow(deprecated)]
+        let click = |state| WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state: state,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        };
+        // Hovering doesn't hide the cursor from the game
+        assert!(!hud.handle_event(&moved));
+        assert_eq!(hud.hovered(), Some(buttons[1]));
+        assert!(hud.handle_event(&click(ElementState::Pressed)));
+        assert!(hud.handle_event(&click(ElementState::Released)));
+        assert_eq!(hud.focused(), Some(buttons[1]));
+        assert_eq!(hud.take_clicks(), vec![buttons[1]]);
+
+        // Tab carries on from the clicked button
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert_eq!(hud.focused(), Some(buttons[2]));
+    }
+
+    #[test]
+    fn key_repeats_are_ignored() {
+        let (mut hud, _, buttons) = menu();
+        assert!(hud.handle_event(&key(VirtualKeyCode::Tab, ElementState::Pressed)));
+        // Repeats of a used key stay used, so the game doesn't see them as a new press
+        assert!(hud.handle_event(&key(VirtualKeyCode::Tab, ElementState::Pressed)));
+        assert!(hud.handle_event(&key(VirtualKeyCode::Tab, ElementState::Pressed)));
+        assert_eq!(hud.focused(), Some(buttons[0]));
+        assert!(hud.handle_event(&key(VirtualKeyCode::Tab, ElementState::Released)));
+
+        press(&mut hud, VirtualKeyCode::Tab);
+        assert_eq!(hud.focused(), Some(buttons[1]));
+    }
+
+    #[test]
+    fn a_key_held_before_the_menu_opened_does_not_click() {
+        let (mut hud, _, buttons) = menu();
+        // Space held for gameplay, then the menu opens with focus on its first button
+        assert!(!hud.handle_event(&key(VirtualKeyCode::Space, ElementState::Pressed)));
+        hud.set_focus(Some(buttons[0]));
+        assert!(!hud.handle_event(&key(VirtualKeyCode::Space, ElementState::Pressed)));
+        assert!(!hud.handle_event(&key(VirtualKeyCode::Space, ElementState::Released)));
+        assert!(hud.take_clicks().is_empty());
+
+        assert!(press(&mut hud, VirtualKeyCode::Space));
+        assert_eq!(hud.take_clicks(), vec![buttons[0]]);
+    }
+
+    #[test]
+    fn losing_focus_lets_go_of_held_keys() {
+        let (mut hud, _, buttons) = menu();
+        hud.set_focus(Some(buttons[1]));
+        hud.handle_event(&key(VirtualKeyCode::Return, ElementState::Pressed));
+        // The release happens in another window
+        hud.handle_event(&WindowEvent::Focused(false));
+        assert!(hud.handle_event(&key(VirtualKeyCode::Return, ElementState::Pressed)));
+        assert_eq!(hud.take_clicks(), vec![buttons[1], buttons[1]]);
+    }
+}
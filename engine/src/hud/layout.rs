@@ -0,0 +1,263 @@
+use glm::{vec2, Vec2};
+
+/// A rectangle in pixels from the top left corner of the window, y down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub fn new(min: Vec2, max: Vec2) -> Rect {
+        Rect { min: min, max: max }
+    }
+
+    /// The whole window.
+    pub fn screen(dimensions: [u32; 2]) -> Rect {
+        Rect::new(
+            vec2(0.0, 0.0),
+            vec2(dimensions[0] as f32, dimensions[1] as f32),
+        )
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec2 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x < self.max.x
+            && point.y >= self.min.y
+            && point.y < self.max.y
+    }
+
+    /// The part of `self` inside `other`, empty when they don't overlap.
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let min = vec2(self.min.x.max(other.min.x), self.min.y.max(other.min.y));
+        let max = vec2(self.max.x.min(other.max.x), self.max.y.min(other.max.y));
+        Rect::new(min, vec2(max.x.max(min.x), max.y.max(min.y)))
+    }
+
+    /// The point a fraction `anchor` of the way from `min` to `max`.
+    pub fn point(&self, anchor: Vec2) -> Vec2 {
+        self.min + self.size().component_mul(&anchor)
+    }
+}
+
+/// Where a node goes inside its parent.
+///
+/// Each corner is placed at a fraction of the parent (its anchor, 0 to 1 from the top left), then
+/// moved by an offset in pixels. Anchors that are the same keep the node a fixed size as the
+/// window changes, anchors that differ stretch it with the parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub anchor_min: Vec2,
+    pub anchor_max: Vec2,
+    pub offset_min: Vec2,
+    pub offset_max: Vec2,
+}
+
+impl Layout {
+    /// Covers the whole parent.
+    pub fn fill() -> Layout {
+        Layout::percent(vec2(0.0, 0.0), vec2(1.0, 1.0))
+    }
+
+    /// Covers the part of the parent between the fractions `min` and `max` of its size.
+    pub fn percent(min: Vec2, max: Vec2) -> Layout {
+        Layout {
+            anchor_min: min,
+            anchor_max: max,
+            offset_min: vec2(0.0, 0.0),
+            offset_max: vec2(0.0, 0.0),
+        }
+    }
+
+    /// `size` pixels, placed so that the point a fraction `anchor` into the node sits on the same
+    /// point of the parent. `vec2(1.0, 0.0)` puts it in the top right corner, `vec2(0.5, 0.5)` in
+    /// the middle.
+    pub fn anchored(anchor: Vec2, size: Vec2) -> Layout {
+        let offset_min = -size.component_mul(&anchor);
+        Layout {
+            anchor_min: anchor,
+            anchor_max: anchor,
+            offset_min: offset_min,
+            offset_max: offset_min + size,
+        }
+    }
+
+    /// Moves the node by `offset` pixels.
+    pub fn offset(mut self, offset: Vec2) -> Layout {
+        self.offset_min += offset;
+        self.offset_max += offset;
+        self
+    }
+
+    /// Shrinks the node by `margin` pixels on every side.
+    pub fn margin(mut self, margin: f32) -> Layout {
+        self.offset_min += vec2(margin, margin);
+        self.offset_max -= vec2(margin, margin);
+        self
+    }
+
+    /// Where the node ends up inside `parent`.
+    pub fn resolve(&self, parent: &Rect) -> Rect {
+        Rect::new(
+            parent.point(self.anchor_min) + self.offset_min,
+            parent.point(self.anchor_max) + self.offset_max,
+        )
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout::fill()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMALL: [u32; 2] = [800, 600];
+    const LARGE: [u32; 2] = [1920, 1080];
+
+    fn rect(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Rect {
+        Rect::new(vec2(min_x, min_y), vec2(max_x, max_y))
+    }
+
+    #[test]
+    fn fill_covers_the_window() {
+        for &dimensions in &[SMALL, LARGE] {
+            let screen = Rect::screen(dimensions);
+            assert_eq!(Layout::fill().resolve(&screen), screen);
+            assert_eq!(Layout::default().resolve(&screen), screen);
+        }
+    }
+
+    #[test]
+    fn anchored_keeps_its_size() {
+        let top_right = Layout::anchored(vec2(1.0, 0.0), vec2(200.0, 50.0));
+        assert_eq!(
+            top_right.resolve(&Rect::screen(SMALL)),
+            rect(600.0, 0.0, 800.0, 50.0)
+        );
+        assert_eq!(
+            top_right.resolve(&Rect::screen(LARGE)),
+            rect(1720.0, 0.0, 1920.0, 50.0)
+        );
+
+        let centered = Layout::anchored(vec2(0.5, 0.5), vec2(100.0, 40.0));
+        assert_eq!(
+            centered.resolve(&Rect::screen(SMALL)),
+            rect(350.0, 280.0, 450.0, 320.0)
+        );
+        assert_eq!(
+            centered.resolve(&Rect::screen(LARGE)),
+            rect(910.0, 520.0, 1010.0, 560.0)
+        );
+
+        let bottom_left = Layout::anchored(vec2(0.0, 1.0), vec2(300.0, 20.0));
+        assert_eq!(
+            bottom_left.resolve(&Rect::screen(LARGE)),
+            rect(0.0, 1060.0, 300.0, 1080.0)
+        );
+    }
+
+    #[test]
+    fn percent_stretches_with_the_window() {
+        let layout = Layout::percent(vec2(0.25, 0.5), vec2(0.75, 1.0));
+        assert_eq!(
+            layout.resolve(&Rect::screen(SMALL)),
+            rect(200.0, 300.0, 600.0, 600.0)
+        );
+        assert_eq!(
+            layout.resolve(&Rect::screen(LARGE)),
+            rect(480.0, 540.0, 1440.0, 1080.0)
+        );
+    }
+
+    #[test]
+    fn margin_shrinks_every_side() {
+        let layout = Layout::fill().margin(10.0);
+        assert_eq!(
+            layout.resolve(&Rect::screen(SMALL)),
+            rect(10.0, 10.0, 790.0, 590.0)
+        );
+        assert_eq!(
+            layout.resolve(&Rect::screen(LARGE)),
+            rect(10.0, 10.0, 1910.0, 1070.0)
+        );
+
+        let anchored = Layout::anchored(vec2(0.0, 0.0), vec2(100.0, 100.0)).margin(5.0);
+        assert_eq!(
+            anchored.resolve(&Rect::screen(SMALL)),
+            rect(5.0, 5.0, 95.0, 95.0)
+        );
+    }
+
+    #[test]
+    fn offset_moves_without_resizing() {
+        let layout = Layout::anchored(vec2(1.0, 1.0), vec2(100.0, 50.0)).offset(vec2(-20.0, -10.0));
+        assert_eq!(
+            layout.resolve(&Rect::screen(SMALL)),
+            rect(680.0, 540.0, 780.0, 590.0)
+        );
+        assert_eq!(
+            layout.resolve(&Rect::screen(LARGE)),
+            rect(1800.0, 1020.0, 1900.0, 1070.0)
+        );
+
+        let stretched = Layout::percent(vec2(0.0, 0.0), vec2(0.5, 0.5)).offset(vec2(5.0, 5.0));
+        assert_eq!(
+            stretched.resolve(&Rect::screen(SMALL)),
+            rect(5.0, 5.0, 405.0, 305.0)
+        );
+    }
+
+    #[test]
+    fn children_resolve_inside_their_parent() {
+        let panel = Layout::anchored(vec2(0.5, 0.5), vec2(400.0, 300.0));
+        let child = Layout::percent(vec2(0.0, 0.5), vec2(1.0, 1.0)).margin(10.0);
+        for &dimensions in &[SMALL, LARGE] {
+            let parent = panel.resolve(&Rect::screen(dimensions));
+            let resolved = child.resolve(&parent);
+            assert_eq!(resolved.size(), vec2(380.0, 130.0));
+            assert_eq!(resolved.min, parent.min + vec2(10.0, 160.0));
+        }
+    }
+
+    #[test]
+    fn rect_contains_its_min_but_not_its_max() {
+        let r = rect(10.0, 20.0, 30.0, 40.0);
+        assert!(r.contains(vec2(10.0, 20.0)));
+        assert!(r.contains(vec2(29.9, 39.9)));
+        assert!(!r.contains(vec2(30.0, 25.0)));
+        assert!(!r.contains(vec2(15.0, 40.0)));
+        assert!(!r.contains(vec2(5.0, 25.0)));
+    }
+
+    #[test]
+    fn rect_intersect() {
+        let a = rect(0.0, 0.0, 100.0, 100.0);
+        let b = rect(50.0, 25.0, 150.0, 75.0);
+        assert_eq!(a.intersect(&b), rect(50.0, 25.0, 100.0, 75.0));
+        assert_eq!(a.intersect(&b), b.intersect(&a));
+
+        let apart = a.intersect(&rect(200.0, 200.0, 300.0, 300.0));
+        assert_eq!(apart.size(), vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn rect_point_and_center() {
+        let r = rect(100.0, 200.0, 300.0, 600.0);
+        assert_eq!(r.point(vec2(0.0, 0.0)), r.min);
+        assert_eq!(r.point(vec2(1.0, 1.0)), r.max);
+        assert_eq!(r.point(vec2(0.5, 0.5)), r.center());
+        assert_eq!(r.point(vec2(0.25, 0.75)), vec2(150.0, 500.0));
+    }
+}
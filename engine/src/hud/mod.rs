@@ -0,0 +1,445 @@
+pub mod input;
+pub mod layout;
+
+pub use self::layout::*;
+
+use drawing::Color4;
+use glm::{vec2, vec4, Vec2};
+use gui::{GuiDrawData, GuiTexture};
+use renderer::TextureHandle;
+use text::{layout_text, Alignment, LayoutOptions, SdfStyle, TextRenderer};
+use winit::event::VirtualKeyCode;
+
+use std::collections::HashMap;
+
+/// Width in pixels of the outline around the focused button.
+const FOCUS_BORDER: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Debug)]
+pub struct ButtonStyle {
+    pub normal: Color4,
+    pub hovered: Color4,
+    pub pressed: Color4,
+    /// Outline drawn while the button has keyboard focus.
+    pub focus: Color4,
+    /// Used for the label, which is always centered.
+    pub label: LayoutOptions,
+}
+
+impl Default for ButtonStyle {
+    fn default() -> ButtonStyle {
+        ButtonStyle {
+            normal: vec4(0.1, 0.1, 0.1, 0.8),
+            hovered: vec4(0.25, 0.25, 0.25, 0.9),
+            pressed: vec4(0.05, 0.05, 0.05, 0.9),
+            focus: vec4(1.0, 0.8, 0.2, 1.0),
+            label: LayoutOptions::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Widget {
+    /// A plain rectangle, also used to group and place children. A transparent color draws
+    /// nothing.
+    Panel { color: Color4 },
+    /// A texture stretched over the node and multiplied by `tint`.
+    Image {
+        texture: TextureHandle,
+        tint: Color4,
+    },
+    /// Text wrapped to the width of the node and centered in it vertically.
+    Text {
+        text: String,
+        options: LayoutOptions,
+    },
+    /// Text drawn with the distance field font at `size` pixels, which stays sharp at any size
+    /// and can have an outline, shadow and glow. Placed like `Text`.
+    Label {
+        text: String,
+        options: LayoutOptions,
+        size: f32,
+        style: SdfStyle,
+    },
+    /// Clicks come out of `Hud::take_clicks`. Buttons can be focused with the keyboard.
+    Button { label: String, style: ButtonStyle },
+    /// Filled from the left for a `value` from 0 to 1.
+    ProgressBar {
+        value: f32,
+        background: Color4,
+        fill: Color4,
+    },
+}
+
+impl Widget {
+    pub fn panel(color: Color4) -> Widget {
+        Widget::Panel { color: color }
+    }
+
+    pub fn image(texture: TextureHandle) -> Widget {
+        Widget::Image {
+            texture: texture,
+            tint: vec4(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn text(text: &str, options: LayoutOptions) -> Widget {
+        Widget::Text {
+            text: text.to_string(),
+            options: options,
+        }
+    }
+
+    pub fn label(text: &str, size: f32, style: SdfStyle) -> Widget {
+        Widget::Label {
+            text: text.to_string(),
+            options: LayoutOptions::default(),
+            size: size,
+            style: style,
+        }
+    }
+
+    pub fn button(label: &str) -> Widget {
+        Widget::Button {
+            label: label.to_string(),
+            style: ButtonStyle::default(),
+        }
+    }
+
+    pub fn progress_bar(value: f32, fill: Color4) -> Widget {
+        Widget::ProgressBar {
+            value: value,
+            background: vec4(0.0, 0.0, 0.0, 0.5),
+            fill: fill,
+        }
+    }
+}
+
+struct Node {
+    layout: Layout,
+    widget: Widget,
+    children: Vec<NodeId>,
+    visible: bool,
+    /// Where `layout` placed the node the last time `Hud::layout` ran.
+    rect: Rect,
+    /// The part of the window the node can draw in: the rect of its parent, cut by the parent's
+    /// own clip rect.
+    clip: Rect,
+}
+
+/// A retained tree of HUD widgets, for health bars, ammo counters, crosshairs and menus.
+///
+/// Nodes are added once and updated in place through their `NodeId`. Each frame `layout` places
+/// them for the window size, which needs no GPU, and `Renderer::queue_hud` draws them with the
+/// gui shaders. Children draw over their parents and later siblings over earlier ones, while all
+/// text goes over all widgets and isn't clipped.
+pub struct Hud {
+    nodes: Vec<Node>,
+    cursor: Option<Vec2>,
+    hovered: Option<NodeId>,
+    pressed: Option<NodeId>,
+    focused: Option<NodeId>,
+    shift: bool,
+    /// The keys held down, and whether the HUD used their first press.
+    held_keys: HashMap<VirtualKeyCode, bool>,
+    clicks: Vec<NodeId>,
+}
+
+impl Hud {
+    /// An empty HUD whose root is a transparent panel covering the window.
+    pub fn new() -> Hud {
+        let screen = Rect::screen([0, 0]);
+        Hud {
+            nodes: vec![Node {
+                layout: Layout::fill(),
+                widget: Widget::panel(vec4(0.0, 0.0, 0.0, 0.0)),
+                children: Vec::new(),
+                visible: true,
+                rect: screen,
+                clip: screen,
+            }],
+            cursor: None,
+            hovered: None,
+            pressed: None,
+            focused: None,
+            shift: false,
+            held_keys: HashMap::new(),
+            clicks: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Adds a node as the last child of `parent`, so it draws over its siblings.
+    pub fn add(&mut self, parent: NodeId, layout: Layout, widget: Widget) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+            layout: layout,
+            widget: widget,
+            children: Vec::new(),
+            visible: true,
+            rect: self.nodes[parent.0].rect,
+            clip: self.nodes[parent.0].clip,
+        });
+        self.nodes[parent.0].children.push(id);
+        id
+    }
+
+    pub fn widget(&self, id: NodeId) -> &Widget {
+        &self.nodes[id.0].widget
+    }
+
+    pub fn widget_mut(&mut self, id: NodeId) -> &mut Widget {
+        &mut self.nodes[id.0].widget
+    }
+
+    /// Changes the string of a text or the label of a button.
+    pub fn set_text(&mut self, id: NodeId, value: &str) {
+        match self.nodes[id.0].widget {
+            Widget::Text { ref mut text, .. } | Widget::Label { ref mut text, .. } => {
+                set_string(text, value)
+            }
+            Widget::Button { ref mut label, .. } => set_string(label, value),
+            _ => panic!("{:?} is neither text nor a button", id),
+        }
+    }
+
+    /// Changes the value of a progress bar, clamped to 0..1.
+    pub fn set_progress(&mut self, id: NodeId, progress: f32) {
+        match self.nodes[id.0].widget {
+            Widget::ProgressBar { ref mut value, .. } => *value = progress.max(0.0).min(1.0),
+            _ => panic!("{:?} isn't a progress bar", id),
+        }
+    }
+
+    pub fn set_layout(&mut self, id: NodeId, layout: Layout) {
+        self.nodes[id.0].layout = layout;
+    }
+
+    /// Hidden nodes and their children are neither drawn nor take input.
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        self.nodes[id.0].visible = visible;
+    }
+
+    pub fn is_visible(&self, id: NodeId) -> bool {
+        self.nodes[id.0].visible
+    }
+
+    /// Where the node was placed by the last `layout`.
+    pub fn rect(&self, id: NodeId) -> Rect {
+        self.nodes[id.0].rect
+    }
+
+    /// The button under the mouse.
+    pub fn hovered(&self) -> Option<NodeId> {
+        self.hovered
+    }
+
+    /// The button that Enter and Space press.
+    pub fn focused(&self) -> Option<NodeId> {
+        self.focused
+    }
+
+    /// Gives keyboard focus to a button, or takes it away with `None`.
+    pub fn set_focus(&mut self, id: Option<NodeId>) {
+        if let Some(id) = id {
+            match self.nodes[id.0].widget {
+                Widget::Button { .. } => (),
+                _ => panic!("{:?} isn't a button", id),
+            }
+        }
+        self.focused = id;
+    }
+
+    /// The buttons clicked since the last call, in the order they were clicked.
+    pub fn take_clicks(&mut self) -> Vec<NodeId> {
+        self.clicks.drain(..).collect()
+    }
+
+    /// Places every node for a window of `dimensions`. Buttons that were hidden lose hover, press
+    /// and focus.
+    pub fn layout(&mut self, dimensions: [u32; 2]) {
+        let screen = Rect::screen(dimensions);
+        self.layout_node(self.root(), &screen, &screen);
+
+        let buttons = self.buttons();
+        let keep = |id: Option<NodeId>| id.filter(|id| buttons.contains(id));
+        self.pressed = keep(self.pressed);
+        self.focused = keep(self.focused);
+        self.hovered = self.cursor.and_then(|cursor| self.button_at(cursor));
+    }
+
+    fn layout_node(&mut self, id: NodeId, parent: &Rect, parent_clip: &Rect) {
+        let rect = self.nodes[id.0].layout.resolve(parent);
+        let clip = parent.intersect(parent_clip);
+        self.nodes[id.0].rect = rect;
+        self.nodes[id.0].clip = clip;
+        for i in 0..self.nodes[id.0].children.len() {
+            let child = self.nodes[id.0].children[i];
+            self.layout_node(child, &rect, &clip);
+        }
+    }
+
+    /// Every visible node, parents before children, in drawing order.
+    fn visible_nodes(&self) -> Vec<NodeId> {
+        let mut visible = Vec::new();
+        let mut stack = vec![self.root()];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id.0];
+            if node.visible {
+                visible.push(id);
+                stack.extend(node.children.iter().rev());
+            }
+        }
+        visible
+    }
+
+    /// The visible buttons in drawing order, which is also the order Tab moves focus in.
+    fn buttons(&self) -> Vec<NodeId> {
+        self.visible_nodes()
+            .into_iter()
+            .filter(|id| match self.nodes[id.0].widget {
+                Widget::Button { .. } => true,
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// The topmost visible button at `point`.
+    fn button_at(&self, point: Vec2) -> Option<NodeId> {
+        self.buttons().into_iter().rev().find(|id| {
+            let node = &self.nodes[id.0];
+            node.rect.intersect(&node.clip).contains(point)
+        })
+    }
+
+    /// Builds the quads of every visible node and queues their text into `text`. Positions come
+    /// from the last `layout`.
+    pub fn draw(&self, text: &mut TextRenderer) -> GuiDrawData {
+        let mut data = GuiDrawData::default();
+        for id in self.visible_nodes() {
+            let node = &self.nodes[id.0];
+            let rect = node.rect;
+            let clip = (node.clip.min, node.clip.max);
+            let mut quad = |rect: Rect, color: Color4, texture: GuiTexture| {
+                if color.w > 0.0 {
+                    data.add_quad(
+                        rect.min,
+                        rect.max,
+                        vec2(0.0, 0.0),
+                        vec2(1.0, 1.0),
+                        color,
+                        texture,
+                        clip,
+                    );
+                }
+            };
+
+            match node.widget {
+                Widget::Panel { color } => quad(rect, color, GuiTexture::White),
+                Widget::Image { texture, tint } => {
+                    quad(rect, tint, GuiTexture::Texture(texture));
+                }
+                Widget::Text {
+                    text: ref value,
+                    ref options,
+                } => queue_text(text, value, options.clone(), &rect),
+                Widget::Label {
+                    text: ref value,
+                    ref options,
+                    size,
+                    ref style,
+                } => {
+                    let options = LayoutOptions {
+                        max_width: Some(rect.size().x),
+                        ..options.clone()
+                    };
+                    let layout = text.layout_sdf_text(value, size, &options);
+                    let y = rect.center().y - layout.size.y * 0.5;
+                    text.queue_sdf_layout(&layout, vec2(rect.min.x, y), size, style);
+                }
+                Widget::Button {
+                    ref label,
+                    ref style,
+                } => {
+                    let color = if self.pressed == Some(id) && self.hovered == Some(id) {
+                        style.pressed
+                    } else if self.hovered == Some(id) {
+                        style.hovered
+                    } else {
+                        style.normal
+                    };
+                    quad(rect, color, GuiTexture::White);
+                    if self.focused == Some(id) {
+                        for edge in &outline(&rect, FOCUS_BORDER) {
+                            quad(*edge, style.focus, GuiTexture::White);
+                        }
+                    }
+                    let options = LayoutOptions {
+                        alignment: Alignment::Center,
+                        ..style.label.clone()
+                    };
+                    queue_text(text, label, options, &rect);
+                }
+                Widget::ProgressBar {
+                    value,
+                    background,
+                    fill,
+                } => {
+                    quad(rect, background, GuiTexture::White);
+                    let width = rect.size().x * value.max(0.0).min(1.0);
+                    let filled = Rect::new(rect.min, vec2(rect.min.x + width, rect.max.y));
+                    quad(filled, fill, GuiTexture::White);
+                }
+            }
+        }
+        data
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Hud {
+        Hud::new()
+    }
+}
+
+/// Assigns only when the string changed, to keep the allocation of the common case.
+fn set_string(string: &mut String, value: &str) {
+    if string != value {
+        string.clear();
+        string.push_str(value);
+    }
+}
+
+/// Lays out `value` wrapped to the width of `rect` and queues it centered vertically in `rect`.
+fn queue_text(renderer: &mut TextRenderer, value: &str, options: LayoutOptions, rect: &Rect) {
+    let options = LayoutOptions {
+        max_width: Some(rect.size().x),
+        ..options
+    };
+    let layout = layout_text(renderer.font(), value, &options);
+    let y = rect.center().y - layout.size.y * 0.5;
+    renderer.queue_layout(&layout, vec2(rect.min.x, y));
+}
+
+/// Four strips of `width` pixels along the inside of the edges of `rect`.
+fn outline(rect: &Rect, width: f32) -> [Rect; 4] {
+    let (min, max) = (rect.min, rect.max);
+    [
+        Rect::new(min, vec2(max.x, min.y + width)),
+        Rect::new(vec2(min.x, max.y - width), max),
+        Rect::new(
+            vec2(min.x, min.y + width),
+            vec2(min.x + width, max.y - width),
+        ),
+        Rect::new(
+            vec2(max.x - width, min.y + width),
+            vec2(max.x, max.y - width),
+        ),
+    ]
+}
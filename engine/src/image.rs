@@ -12,8 +12,9 @@ pub struct Image {
 pub fn load_texture(asset_name: &Path) -> Image {
     let reader = BufReader::new(File::open(asset_name).unwrap());
     match load(reader, ImageFormat::Png) {
+        // Always RGBA8 so the renderer can upload any PNG the same way
         Ok(dyn_img) => Image {
-            data: dyn_img.to_bytes(),
+            data: dyn_img.to_rgba().into_raw(),
             width: dyn_img.dimensions().0,
             height: dyn_img.dimensions().1,
         },
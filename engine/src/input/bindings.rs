@@ -0,0 +1,452 @@
+//! Input binding files, one `action` or `axis` per line with `#` starting a comment:
+//!
+//! ```text
+//! action fire = MouseLeft Space
+//! action reload = R
+//! axis move_x = D -A
+//! axis move_y = W -S
+//! axis look_x = MouseX*0.002
+//! axis zoom = -ScrollY
+//! ```
+//!
+//! An action is held while any of its buttons are. Buttons are `VirtualKeyCode` names, or
+//! `MouseLeft`, `MouseRight`, `MouseMiddle` and `Mouse4` and up. An axis adds up its bindings:
+//! buttons count 1 while held, clamped to -1..1 together, and `MouseX`, `MouseY`, `ScrollX` and
+//! `ScrollY` count the movement of the frame. A leading `-` flips a binding and `*` scales it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+#[derive(Debug)]
+pub enum InputError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InputError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            InputError::Parse {
+                ref path,
+                line,
+                ref message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisSource {
+    Button(Button),
+    MouseX,
+    MouseY,
+    ScrollX,
+    ScrollY,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AxisBinding {
+    pub source: AxisSource,
+    pub scale: f32,
+}
+
+/// Named actions and axes and what they are bound to.
+#[derive(Clone, Debug, Default)]
+pub struct InputBindings {
+    actions: HashMap<String, Vec<Button>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+}
+
+impl InputBindings {
+    pub fn new() -> InputBindings {
+        InputBindings::default()
+    }
+
+    pub fn load(path: &Path) -> Result<InputBindings, InputError> {
+        match fs::read_to_string(path) {
+            Ok(source) => InputBindings::parse(&source, path),
+            Err(e) => Err(InputError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    /// `path` is only used to point at errors.
+    pub fn parse(source: &str, path: &Path) -> Result<InputBindings, InputError> {
+        let mut bindings = InputBindings::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: String| InputError::Parse {
+                path: path.to_path_buf(),
+                line: i + 1,
+                message: message,
+            };
+
+            let (key, value) = match line.find('=') {
+                Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+                None => return Err(error(format!("expected `key = value`, got `{}`", line))),
+            };
+
+            let words: Vec<&str> = key.split_whitespace().collect();
+            match words.as_slice() {
+                ["action", name] => {
+                    for word in value.split_whitespace() {
+                        let button = parse_button(word)
+                            .ok_or_else(|| error(format!("unknown button `{}`", word)))?;
+                        bindings.bind_action(name, button);
+                    }
+                }
+                ["axis", name] => {
+                    for word in value.split_whitespace() {
+                        let binding = parse_axis_binding(word).map_err(&error)?;
+                        bindings.bind_axis(name, binding);
+                    }
+                }
+                _ => {
+                    return Err(error(format!(
+                        "expected `action <name>` or `axis <name>`, got `{}`",
+                        key
+                    )))
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// Adds `button` to the buttons that trigger `action`.
+    pub fn bind_action(&mut self, action: &str, button: Button) {
+        self.actions
+            .entry(action.to_string())
+            .or_insert_with(Vec::new)
+            .push(button);
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        self.axes
+            .entry(axis.to_string())
+            .or_insert_with(Vec::new)
+            .push(binding);
+    }
+
+    /// The buttons bound to `action`, empty if it isn't bound.
+    pub fn action(&self, action: &str) -> &[Button] {
+        self.actions
+            .get(action)
+            .map_or(&[][..], |buttons| &buttons[..])
+    }
+
+    pub fn axis(&self, axis: &str) -> &[AxisBinding] {
+        self.axes
+            .get(axis)
+            .map_or(&[][..], |bindings| &bindings[..])
+    }
+}
+
+fn parse_axis_binding(word: &str) -> Result<AxisBinding, String> {
+    let (sign, word) = if word.starts_with('-') {
+        (-1.0, &word[1..])
+    } else {
+        (1.0, word)
+    };
+    let (name, scale) = match word.find('*') {
+        Some(star) => {
+            let scale: f32 = word[star + 1..]
+                .parse()
+                .map_err(|_| format!("expected a number after `*`, got `{}`", &word[star + 1..]))?;
+            (&word[..star], scale)
+        }
+        None => (word, 1.0),
+    };
+    let source = match name {
+        "MouseX" => AxisSource::MouseX,
+        "MouseY" => AxisSource::MouseY,
+        "ScrollX" => AxisSource::ScrollX,
+        "ScrollY" => AxisSource::ScrollY,
+        _ => match parse_button(name) {
+            Some(button) => AxisSource::Button(button),
+            None => return Err(format!("unknown button or axis `{}`", name)),
+        },
+    };
+    Ok(AxisBinding {
+        source: source,
+        scale: sign * scale,
+    })
+}
+
+pub fn parse_button(name: &str) -> Option<Button> {
+    let mouse = match name {
+        "MouseLeft" => Some(MouseButton::Left),
+        "MouseRight" => Some(MouseButton::Right),
+        "MouseMiddle" => Some(MouseButton::Middle),
+        _ if name.starts_with("Mouse") => name[5..].parse().ok().map(MouseButton::Other),
+        _ => None,
+    };
+    match mouse {
+        Some(button) => Some(Button::Mouse(button)),
+        None => parse_key(name).map(Button::Key),
+    }
+}
+
+macro_rules! key_names {
+    ($($key:ident),*) => {
+        /// The key whose `VirtualKeyCode` variant is called `name`.
+        pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_names!(
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Key0,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Insert,
+    Home,
+    Delete,
+    End,
+    PageDown,
+    PageUp,
+    Left,
+    Up,
+    Right,
+    Down,
+    Back,
+    Return,
+    Space,
+    Tab,
+    Grave,
+    Minus,
+    Equals,
+    LBracket,
+    RBracket,
+    Backslash,
+    Semicolon,
+    Apostrophe,
+    Comma,
+    Period,
+    Slash,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    NumpadEnter,
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LAlt,
+    RAlt,
+    LWin,
+    RWin
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<InputBindings, InputError> {
+        InputBindings::parse(source, Path::new("test.cfg"))
+    }
+
+    /// The line and message of a parse error.
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse(source) {
+            Err(InputError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(bindings) => panic!("expected a parse error, got {:?}", bindings),
+        }
+    }
+
+    fn binding(source: AxisSource, scale: f32) -> AxisBinding {
+        AxisBinding {
+            source: source,
+            scale: scale,
+        }
+    }
+
+    #[test]
+    fn actions_take_keys_and_mouse_buttons() {
+        let bindings = parse("action fire = MouseLeft Space Mouse4\naction fire = F").unwrap();
+        assert_eq!(
+            bindings.action("fire"),
+            &[
+                Button::Mouse(MouseButton::Left),
+                Button::Key(VirtualKeyCode::Space),
+                Button::Mouse(MouseButton::Other(4)),
+                Button::Key(VirtualKeyCode::F),
+            ][..]
+        );
+        assert!(bindings.action("jump").is_empty());
+        assert!(bindings.axis("fire").is_empty());
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let source = "# Bindings\n\n   \naction reload = R # and not T\n\t# indented comment\n";
+        let bindings = parse(source).unwrap();
+        assert_eq!(
+            bindings.action("reload"),
+            &[Button::Key(VirtualKeyCode::R)][..]
+        );
+        assert!(parse("").unwrap().action("reload").is_empty());
+    }
+
+    #[test]
+    fn minus_flips_an_axis_binding() {
+        let bindings = parse("axis move_x = D -A\naxis zoom = -ScrollY").unwrap();
+        assert_eq!(
+            bindings.axis("move_x"),
+            &[
+                binding(AxisSource::Button(Button::Key(VirtualKeyCode::D)), 1.0),
+                binding(AxisSource::Button(Button::Key(VirtualKeyCode::A)), -1.0),
+            ][..]
+        );
+        assert_eq!(
+            bindings.axis("zoom"),
+            &[binding(AxisSource::ScrollY, -1.0)][..]
+        );
+    }
+
+    #[test]
+    fn star_scales_an_axis_binding() {
+        let bindings = parse("axis look_x = MouseX*0.002 -MouseY*2 ScrollX*-0.5").unwrap();
+        assert_eq!(
+            bindings.axis("look_x"),
+            &[
+                binding(AxisSource::MouseX, 0.002),
+                binding(AxisSource::MouseY, -2.0),
+                binding(AxisSource::ScrollX, -0.5),
+            ][..]
+        );
+    }
+
+    #[test]
+    fn unknown_buttons_are_errors() {
+        let (line, message) = parse_error("action fire = Space Trigger");
+        assert_eq!(line, 1);
+        assert_eq!(message, "unknown button `Trigger`");
+
+        let (_, message) = parse_error("axis move_x = -Stick");
+        assert_eq!(message, "unknown button or axis `Stick`");
+
+        // Mouse buttons past the named ones are numbers
+        let (_, message) = parse_error("action fire = MouseSide");
+        assert_eq!(message, "unknown button `MouseSide`");
+    }
+
+    #[test]
+    fn bad_scales_are_errors() {
+        let (_, message) = parse_error("axis look_x = MouseX*fast");
+        assert_eq!(message, "expected a number after `*`, got `fast`");
+        let (_, message) = parse_error("axis look_x = MouseX*");
+        assert_eq!(message, "expected a number after `*`, got ``");
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        let (_, message) = parse_error("action fire Space");
+        assert_eq!(message, "expected `key = value`, got `action fire Space`");
+        let (_, message) = parse_error("button fire = Space");
+        assert_eq!(
+            message,
+            "expected `action <name>` or `axis <name>`, got `button fire`"
+        );
+        let (_, message) = parse_error("action = Space");
+        assert_eq!(
+            message,
+            "expected `action <name>` or `axis <name>`, got `action`"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_their_line() {
+        let source = "# comment\n\naction fire = Space\naxis move_x = D -A\naction jump = Hop\n";
+        assert_eq!(parse_error(source).0, 5);
+
+        match parse("\naction fire = Nothing") {
+            Err(e) => assert_eq!(e.to_string(), "test.cfg:2: unknown button `Nothing`"),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
@@ -0,0 +1,389 @@
+pub mod bindings;
+
+pub use self::bindings::*;
+
+use glm::{vec2, Vec2};
+use std::collections::HashSet;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+/// Pixel scroll deltas, from touchpads, are turned into lines of about this many pixels. Shared
+/// with the gui so both scroll the same distance.
+pub const PIXELS_PER_LINE: f32 = 20.0;
+
+/// Keyboard and mouse state built from winit events, with named actions and axes on top so
+/// gameplay code doesn't match on key codes. Meant to live in the specs `World` as a resource.
+///
+/// Feed it every event with `handle_event` and call `end_frame` once the frame's gameplay code
+/// has run, which clears what only lasts a frame: presses, releases, mouse movement and scrolling.
+pub struct Input {
+    bindings: InputBindings,
+    down: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    mouse_position: Vec2,
+    mouse_delta: Vec2,
+    scroll: Vec2,
+}
+
+impl Input {
+    pub fn new(bindings: InputBindings) -> Input {
+        Input {
+            bindings: bindings,
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            mouse_position: vec2(0.0, 0.0),
+            mouse_delta: vec2(0.0, 0.0),
+            scroll: vec2(0.0, 0.0),
+        }
+    }
+
+    pub fn bindings(&self) -> &InputBindings {
+        &self.bindings
+    }
+
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
+    }
+
+    /// Mouse movement comes from raw device events, so it keeps counting while the cursor is
+    /// grabbed or stuck against the edge of the screen.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        match *event {
+            Event::WindowEvent { ref event, .. } => self.handle_window_event(event),
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                self.mouse_delta += vec2(delta.0 as f32, delta.1 as f32);
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_window_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => self.set_button(Button::Key(key), state),
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set_button(Button::Mouse(button), state)
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = vec2(position.x as f32, position.y as f32);
+            }
+            WindowEvent::MouseWheel { delta, .. } => match delta {
+                MouseScrollDelta::LineDelta(x, y) => self.scroll += vec2(x, y),
+                MouseScrollDelta::PixelDelta(position) => {
+                    self.scroll += vec2(position.x as f32, position.y as f32) / PIXELS_PER_LINE;
+                }
+            },
+            // Releases that happen while unfocused never arrive, so everything is let go
+            WindowEvent::Focused(false) => {
+                self.released.extend(self.down.drain());
+            }
+            _ => (),
+        }
+    }
+
+    fn set_button(&mut self, button: Button, state: ElementState) {
+        match state {
+            // Key repeat sends presses without releases, which aren't new presses
+            ElementState::Pressed => {
+                if self.down.insert(button) {
+                    self.pressed.insert(button);
+                }
+            }
+            ElementState::Released => {
+                if self.down.remove(&button) {
+                    self.released.insert(button);
+                }
+            }
+        }
+    }
+
+    /// Clears presses, releases, mouse movement and scrolling, ready for the next frame's events.
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = vec2(0.0, 0.0);
+        self.scroll = vec2(0.0, 0.0);
+    }
+
+    pub fn is_down(&self, button: Button) -> bool {
+        self.down.contains(&button)
+    }
+
+    /// True in the frame the button went down.
+    pub fn was_pressed(&self, button: Button) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    /// True in the frame the button came up.
+    pub fn was_released(&self, button: Button) -> bool {
+        self.released.contains(&button)
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.is_down(Button::Key(key))
+    }
+
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.is_down(Button::Mouse(button))
+    }
+
+    /// In pixels from the top left corner of the window.
+    pub fn mouse_position(&self) -> Vec2 {
+        self.mouse_position
+    }
+
+    /// How far the mouse moved this frame, in the device's units rather than pixels.
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    /// Lines scrolled this frame, positive y away from the user.
+    pub fn scroll(&self) -> Vec2 {
+        self.scroll
+    }
+
+    /// True while any button bound to `action` is held. Unbound actions are never held.
+    pub fn action(&self, action: &str) -> bool {
+        self.bindings
+            .action(action)
+            .iter()
+            .any(|&button| self.is_down(button))
+    }
+
+    /// True in the frame the first button bound to `action` went down.
+    pub fn action_pressed(&self, action: &str) -> bool {
+        let buttons = self.bindings.action(action);
+        buttons.iter().any(|&button| self.was_pressed(button))
+            && !buttons
+                .iter()
+                .any(|&button| self.is_down(button) && !self.was_pressed(button))
+    }
+
+    /// True in the frame the last held button bound to `action` came up.
+    pub fn action_released(&self, action: &str) -> bool {
+        let buttons = self.bindings.action(action);
+        buttons.iter().any(|&button| self.was_released(button)) && !self.action(action)
+    }
+
+    /// The value of `axis` this frame, see `input::bindings` for how bindings add up. Unbound
+    /// axes are 0.
+    pub fn axis(&self, axis: &str) -> f32 {
+        let mut buttons: f32 = 0.0;
+        let mut movement: f32 = 0.0;
+        for binding in self.bindings.axis(axis) {
+            match binding.source {
+                AxisSource::Button(button) => {
+                    if self.is_down(button) {
+                        buttons += binding.scale;
+                    }
+                }
+                AxisSource::MouseX => movement += self.mouse_delta.x * binding.scale,
+                AxisSource::MouseY => movement += self.mouse_delta.y * binding.scale,
+                AxisSource::ScrollX => movement += self.scroll.x * binding.scale,
+                AxisSource::ScrollY => movement += self.scroll.y * binding.scale,
+            }
+        }
+        buttons.max(-1.0).min(1.0) + movement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use winit::dpi::{LogicalPosition, PhysicalPosition};
+    use winit::event::{DeviceId, ModifiersState, TouchPhase};
+
+    const W: Button = Button::Key(VirtualKeyCode::W);
+    const SPACE: Button = Button::Key(VirtualKeyCode::Space);
+    const MOUSE_LEFT: Button = Button::Mouse(MouseButton::Left);
+
+    fn input(bindings: &str) -> Input {
+        Input::new(InputBindings::parse(bindings, Path::new("test.cfg")).unwrap())
+    }
+
+    fn press(input: &mut Input, button: Button) {
+        input.set_button(button, ElementState::Pressed);
+    }
+
+    fn release(input: &mut Input, button: Button) {
+        input.set_button(button, ElementState::Released);
+    }
+
+    #[allow(deprecated)]
+    fn scroll(delta: MouseScrollDelta) -> WindowEvent<'static> {
+        WindowEvent::MouseWheel {
+            device_id: unsafe { DeviceId::dummy() },
+            delta: delta,
+            phase: TouchPhase::Moved,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    fn mouse_motion(x: f64, y: f64) -> Event<'static, ()> {
+        Event::DeviceEvent {
+            device_id: unsafe { DeviceId::dummy() },
+            event: DeviceEvent::MouseMotion { delta: (x, y) },
+        }
+    }
+
+    #[test]
+    fn presses_and_releases_last_one_frame() {
+        let mut input = input("");
+        press(&mut input, W);
+        assert!(input.is_down(W) && input.was_pressed(W) && !input.was_released(W));
+        assert!(input.is_key_down(VirtualKeyCode::W));
+
+        input.end_frame();
+        assert!(input.is_down(W) && !input.was_pressed(W));
+
+        release(&mut input, W);
+        assert!(!input.is_down(W) && input.was_released(W));
+        input.end_frame();
+        assert!(!input.is_down(W) && !input.was_pressed(W) && !input.was_released(W));
+    }
+
+    #[test]
+    fn key_repeats_are_not_new_presses() {
+        let mut input = input("");
+        press(&mut input, W);
+        input.end_frame();
+        press(&mut input, W);
+        press(&mut input, W);
+        assert!(input.is_down(W) && !input.was_pressed(W));
+
+        release(&mut input, W);
+        input.end_frame();
+        // A release of a button that isn't down is ignored too
+        release(&mut input, W);
+        assert!(!input.was_released(W));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut input = input("action fire = Space MouseLeft");
+        press(&mut input, W);
+        press(&mut input, MOUSE_LEFT);
+        input.end_frame();
+
+        input.handle_window_event(&WindowEvent::Focused(false));
+        assert!(!input.is_down(W) && !input.is_mouse_down(MouseButton::Left));
+        assert!(input.was_released(W) && input.was_released(MOUSE_LEFT));
+        assert!(input.action_released("fire"));
+
+        // The press after coming back counts as new
+        input.end_frame();
+        press(&mut input, W);
+        assert!(input.was_pressed(W));
+    }
+
+    #[test]
+    fn actions_with_several_buttons() {
+        let mut input = input("action fire = Space MouseLeft");
+        press(&mut input, SPACE);
+        assert!(input.action("fire") && input.action_pressed("fire"));
+        input.end_frame();
+
+        // Already held through Space, so the mouse doesn't press it again
+        press(&mut input, MOUSE_LEFT);
+        assert!(input.action("fire") && !input.action_pressed("fire"));
+        input.end_frame();
+
+        // Still held through the mouse
+        release(&mut input, SPACE);
+        assert!(input.action("fire") && !input.action_released("fire"));
+        input.end_frame();
+
+        release(&mut input, MOUSE_LEFT);
+        assert!(!input.action("fire") && input.action_released("fire"));
+        input.end_frame();
+        assert!(!input.action_released("fire"));
+
+        // Both going down in the same frame is one press
+        press(&mut input, SPACE);
+        press(&mut input, MOUSE_LEFT);
+        assert!(input.action_pressed("fire"));
+    }
+
+    #[test]
+    fn unbound_actions_and_axes_do_nothing() {
+        let mut input = input("");
+        press(&mut input, SPACE);
+        input.handle_event(&mouse_motion(5.0, 5.0));
+        assert!(!input.action("fire") && !input.action_pressed("fire"));
+        assert!(!input.action_released("fire"));
+        assert_eq!(input.axis("move_x"), 0.0);
+    }
+
+    #[test]
+    fn button_axes_are_clamped_together() {
+        let mut input = input("axis move_x = D Right -A\naxis walk = D*0.5");
+        let d = Button::Key(VirtualKeyCode::D);
+        let right = Button::Key(VirtualKeyCode::Right);
+        let a = Button::Key(VirtualKeyCode::A);
+
+        press(&mut input, d);
+        press(&mut input, right);
+        assert_eq!(input.axis("move_x"), 1.0);
+        assert_eq!(input.axis("walk"), 0.5);
+
+        press(&mut input, a);
+        assert_eq!(input.axis("move_x"), 1.0);
+        release(&mut input, d);
+        assert_eq!(input.axis("move_x"), 0.0);
+        release(&mut input, right);
+        assert_eq!(input.axis("move_x"), -1.0);
+    }
+
+    #[test]
+    fn movement_axes_are_not_clamped_and_last_a_frame() {
+        let mut input = input("axis look_x = MouseX*0.5 D\naxis zoom = ScrollY");
+        input.handle_event(&mouse_motion(6.0, 1.0));
+        input.handle_event(&mouse_motion(4.0, 1.0));
+        press(&mut input, Button::Key(VirtualKeyCode::D));
+        assert_eq!(input.mouse_delta(), vec2(10.0, 2.0));
+        assert_eq!(input.axis("look_x"), 6.0);
+
+        input.handle_window_event(&scroll(MouseScrollDelta::LineDelta(0.0, 1.0)));
+        input.handle_window_event(&scroll(MouseScrollDelta::PixelDelta(LogicalPosition::new(
+            PIXELS_PER_LINE as f64,
+            PIXELS_PER_LINE as f64 * 2.0,
+        ))));
+        assert_eq!(input.scroll(), vec2(1.0, 3.0));
+        assert_eq!(input.axis("zoom"), 3.0);
+
+        input.end_frame();
+        assert_eq!(input.mouse_delta(), vec2(0.0, 0.0));
+        assert_eq!(input.scroll(), vec2(0.0, 0.0));
+        assert_eq!(input.axis("look_x"), 1.0);
+        assert_eq!(input.axis("zoom"), 0.0);
+    }
+
+    #[test]
+    fn cursor_position_outlasts_the_frame() {
+        let mut input = input("");
+        #[allow(deprecated)]
+        let moved = WindowEvent::CursorMoved {
+            device_id: unsafe { DeviceId::dummy() },
+            position: PhysicalPosition::new(120.0, 80.0),
+            modifiers: ModifiersState::empty(),
+        };
+        input.handle_window_event(&moved);
+        input.end_frame();
+        assert_eq!(input.mouse_position(), vec2(120.0, 80.0));
+    }
+}
@@ -12,427 +12,33 @@ extern crate itertools;
 
 extern crate nalgebra_glm as glm;
 extern crate ncollide3d as nc;
+extern crate notify;
 extern crate num;
 extern crate ordered_float as of;
 extern crate rusttype;
+extern crate shaderc;
 extern crate specs;
 extern crate specs_derive;
 extern crate time as t;
 extern crate vulkano;
 extern crate vulkano_win;
 extern crate winit;
+extern crate xml;
 
 pub mod camera;
 pub mod drawing;
 pub mod fps_counter;
+pub mod golden;
+pub mod gui;
+pub mod hud;
 pub mod image;
+pub mod input;
 pub mod mesh;
+pub mod renderer;
+pub mod scene;
 pub mod shader;
+pub mod text;
 pub mod time;
 pub mod transform;
 pub mod utils;
 pub mod window;
-
-use camera::*;
-use drawing::*;
-use glm::*;
-use mesh::{mesh::Mesh, model};
-use shader::*;
-use specs::prelude::*;
-
-use std::f32::consts::*;
-
-use std::sync::Arc;
-use vulkano::buffer::cpu_pool::CpuBufferPool;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
-use vulkano::descriptor::{descriptor_set::PersistentDescriptorSet, PipelineLayoutAbstract};
-use vulkano::device::{Device, DeviceExtensions};
-use vulkano::format::Format;
-use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
-use vulkano::image::attachment::AttachmentImage;
-use vulkano::image::SwapchainImage;
-use vulkano::instance::Instance;
-use vulkano::instance::PhysicalDevice;
-use vulkano::pipeline::depth_stencil::*;
-use vulkano::pipeline::vertex::SingleBufferDefinition;
-use vulkano::pipeline::viewport::Viewport;
-use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
-use vulkano::swapchain;
-use vulkano::swapchain::{
-    AcquireError, ColorSpace, FullscreenExclusive, PresentMode, SurfaceTransform, Swapchain,
-    SwapchainCreationError,
-};
-use vulkano::sync;
-use vulkano::sync::{FlushError, GpuFuture};
-use vulkano_win::VkSurfaceBuild;
-use window::init_vulkano_window;
-use winit::{
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
-};
-
-#[derive(Default, Clone, Debug)]
-struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
-}
-vulkano::impl_vertex!(Vertex, position, color);
-
-pub fn start_event_loop() {
-    // The start of this example is exactly the same as `triangle`. You should read the
-    // `triangle` example if you haven't done so yet.
-
-    let required_extensions = vulkano_win::required_extensions();
-    let instance = Instance::new(None, &required_extensions, None).unwrap();
-    let physical = PhysicalDevice::enumerate(&instance).next().unwrap();
-    println!(
-        "Using device: {} (type: {:?})",
-        physical.name(),
-        physical.ty()
-    );
-
-    let event_loop = EventLoop::new();
-    let surface = WindowBuilder::new()
-        .build_vk_surface(&event_loop, instance.clone())
-        .unwrap();
-    let dimensions: [u32; 2] = surface.window().inner_size().into();
-
-    let queue_family = physical
-        .queue_families()
-        .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
-        .unwrap();
-
-    let device_ext = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::none()
-    };
-
-    let (device, mut queues) = Device::new(
-        physical,
-        physical.supported_features(),
-        &device_ext,
-        [(queue_family, 0.5)].iter().cloned(),
-    )
-    .unwrap();
-
-    let queue = queues.next().unwrap();
-
-    let (mut swapchain, images) = {
-        let caps = surface.capabilities(physical).unwrap();
-        let usage = caps.supported_usage_flags;
-        let format = caps.supported_formats[0].0;
-        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
-
-        Swapchain::new(
-            device.clone(),
-            surface.clone(),
-            caps.min_image_count,
-            format,
-            dimensions,
-            1,
-            usage,
-            &queue,
-            SurfaceTransform::Identity,
-            alpha,
-            PresentMode::Fifo,
-            FullscreenExclusive::Default,
-            true,
-            ColorSpace::SrgbNonLinear,
-        )
-        .unwrap()
-    };
-
-    let mesh = Mesh::create_box(&None);
-
-    let vertices = mesh
-        .vertices
-        .iter()
-        .zip(mesh.colors.iter())
-        .map(|( v, c )| Vertex {
-            position: [v.x, v.y, v.z],
-            color: [c.x, c.y, c.z]
-        });
-    let vertex_buffer =
-        CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, vertices)
-            .unwrap();
-
-    // let normals = mesh.normals.iter().cloned();
-    // let normals_buffer =
-    //     CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, normals).unwrap();
-
-    let indices = mesh.indices.iter().cloned();
-    let index_buffer =
-        CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, indices).unwrap();
-
-    struct Uniforms {
-        pub model: Mat4,
-        pub view: Mat4,
-        pub projection: Mat4,
-    }
-
-    let uniform_buffer = CpuBufferPool::<Uniforms>::new(device.clone(), BufferUsage::all());
-
-    let vs = vs::Shader::load(device.clone()).unwrap();
-    let fs = fs::Shader::load(device.clone()).unwrap();
-
-    let render_pass = Arc::new(
-        vulkano::single_pass_renderpass!(device.clone(),
-            attachments: {
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: swapchain.format(),
-                    samples: 1,
-                },
-                depth: {
-                    load: Clear,
-                    store: DontCare,
-                    format: Format::D16Unorm,
-                    samples: 1,
-                }
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {depth}
-            }
-        )
-        .unwrap(),
-    );
-
-    let (mut pipeline, mut framebuffers) =
-        window_size_dependent_setup(device.clone(), &vs, &fs, &images, render_pass.clone());
-    let mut recreate_swapchain = false;
-
-    let mut previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<dyn GpuFuture>);
-
-    let mut rotation = 0.0;
-
-    event_loop.run(move |event, _, control_flow| match event {
-        Event::WindowEvent {
-            event: WindowEvent::CloseRequested,
-            ..
-        } => {
-            *control_flow = ControlFlow::Exit;
-        }
-        Event::WindowEvent {
-            event: WindowEvent::Resized(_),
-            ..
-        } => {
-            recreate_swapchain = true;
-        }
-        Event::RedrawEventsCleared => {
-            previous_frame_end.as_mut().unwrap().cleanup_finished();
-
-            if recreate_swapchain {
-                let dimensions: [u32; 2] = surface.window().inner_size().into();
-                let (new_swapchain, new_images) =
-                    match swapchain.recreate_with_dimensions(dimensions) {
-                        Ok(r) => r,
-                        Err(SwapchainCreationError::UnsupportedDimensions) => return,
-                        Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
-                    };
-
-                swapchain = new_swapchain;
-                let (new_pipeline, new_framebuffers) = window_size_dependent_setup(
-                    device.clone(),
-                    &vs,
-                    &fs,
-                    &new_images,
-                    render_pass.clone(),
-                );
-                pipeline = new_pipeline;
-                framebuffers = new_framebuffers;
-                recreate_swapchain = false;
-            }
-
-            let uniform_buffer_subbuffer = {
-                let uniform_data = Uniforms {
-                    model: rotate(&identity(), rotation, &vec3(1.0, 1.0, 0.0)),
-                    view: translate(&identity(), &vec3(0.0, 0.0, -4.0)),
-                    projection: perspective(1.6, 1.0, 0.01, 1000.0)
-                };
-
-                uniform_buffer.next(uniform_data).unwrap()
-            };
-
-            rotation += 0.01;
-
-            let layout = pipeline.descriptor_set_layout(0).unwrap();
-            let set = Arc::new(
-                PersistentDescriptorSet::start(layout.clone())
-                    .add_buffer(uniform_buffer_subbuffer)
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            );
-
-            let (image_num, suboptimal, acquire_future) =
-                match swapchain::acquire_next_image(swapchain.clone(), None) {
-                    Ok(r) => r,
-                    Err(AcquireError::OutOfDate) => {
-                        recreate_swapchain = true;
-                        return;
-                    }
-                    Err(e) => panic!("Failed to acquire next image: {:?}", e),
-                };
-
-            if suboptimal {
-                recreate_swapchain = true;
-            }
-
-            let command_buffer =
-                AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
-                    .unwrap()
-                    .begin_render_pass(
-                        framebuffers[image_num].clone(),
-                        false,
-                        vec![[0.0, 0.0, 1.0, 1.0].into(), 1f32.into()],
-                    )
-                    .unwrap()
-                    .draw_indexed(
-                        pipeline.clone(),
-                        &DynamicState::none(),
-                        vertex_buffer.clone(),
-                        index_buffer.clone(),
-                        set.clone(),
-                        (),
-                    )
-                    .unwrap()
-                    .end_render_pass()
-                    .unwrap()
-                    .build()
-                    .unwrap();
-
-            let future = previous_frame_end
-                .take()
-                .unwrap()
-                .join(acquire_future)
-                .then_execute(queue.clone(), command_buffer)
-                .unwrap()
-                .then_swapchain_present(queue.clone(), swapchain.clone(), image_num)
-                .then_signal_fence_and_flush();
-
-            match future {
-                Ok(future) => {
-                    previous_frame_end = Some(Box::new(future) as Box<_>);
-                }
-                Err(FlushError::OutOfDate) => {
-                    recreate_swapchain = true;
-                    previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<_>);
-                }
-                Err(e) => {
-                    println!("Failed to flush future: {:?}", e);
-                    previous_frame_end = Some(Box::new(sync::now(device.clone())) as Box<_>);
-                }
-            }
-        }
-        _ => (),
-    });
-}
-
-/// This method is called once during initialization, then again whenever the window is resized
-fn window_size_dependent_setup(
-    device: Arc<Device>,
-    vs: &vs::Shader,
-    fs: &fs::Shader,
-    images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
-) -> (
-    Arc<
-        GraphicsPipeline<
-            SingleBufferDefinition<Vertex>,
-            Box<dyn PipelineLayoutAbstract + Send + Sync>,
-            Arc<dyn RenderPassAbstract + Send + Sync>,
-        >,
-    >,
-    Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
-) {
-    let dimensions = images[0].dimensions();
-
-    let depth_buffer =
-        AttachmentImage::transient(device.clone(), dimensions, Format::D16Unorm).unwrap();
-
-    let framebuffers = images
-        .iter()
-        .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .add(depth_buffer.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
-        })
-        .collect::<Vec<_>>();
-
-    // In the triangle example we use a dynamic viewport, as its a simple example.
-    // However in the teapot example, we recreate the pipelines with a hardcoded viewport instead.
-    // This allows the driver to optimize things, at the cost of slower window resizes.
-    // https://computergraphics.stackexchange.com/questions/5742/vulkan-best-way-of-updating-pipeline-viewport
-    let pipeline = Arc::new(
-        GraphicsPipeline::start()
-            .vertex_input(SingleBufferDefinition::<Vertex>::new())
-            .vertex_shader(vs.main_entry_point(), ())
-            .triangle_list()
-            .viewports_dynamic_scissors_irrelevant(1)
-            .viewports(std::iter::once(Viewport {
-                origin: [0.0, 0.0],
-                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-                depth_range: 0.0..1.0,
-            }))
-            .depth_stencil(DepthStencil {
-                depth_write: true,
-                depth_compare: Compare::LessOrEqual,
-                depth_bounds_test: DepthBounds::Disabled,
-                stencil_front: Default::default(),
-                stencil_back: Default::default(),
-            })
-            .fragment_shader(fs.main_entry_point(), ())
-            .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-            .build(device.clone())
-            .unwrap(),
-    );
-
-    (pipeline, framebuffers)
-}
-
-mod vs {
-    vulkano_shaders::shader! {
-        ty: "vertex",
-        src: "
-                #version 450
-                layout(set = 0, binding = 0) uniform Data {
-                    mat4 model;
-                    mat4 view;
-                    mat4 projection;
-                } ubo;
-                layout(location = 0) in vec3 position;
-                layout(location = 1) in vec3 color;
-
-                layout(location = 0) out vec3 v_color;
-                void main() {
-                    mat4 mvp = ubo.projection * ubo.view * ubo.model;
-                    v_color = color;
-                    gl_Position = mvp * vec4(position, 1.0);
-                }
-            "
-    }
-}
-
-mod fs {
-    vulkano_shaders::shader! {
-        ty: "fragment",
-        src: "
-                #version 450
-                layout(location = 0) in vec3 v_color;
-                layout(location = 0) out vec4 f_color;
-                void main() {
-                    f_color = vec4(v_color, 1.0);
-                }
-            "
-    }
-}
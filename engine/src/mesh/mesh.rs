@@ -1,4 +1,4 @@
-use glm::{rotate_vec3, vec3, Vec3};
+use glm::{rotate_vec3, vec2, vec3, Vec2, Vec3};
 use num::Num;
 use std::f32::consts::PI;
 
@@ -7,6 +7,7 @@ pub struct Mesh {
     pub vertices: Vec<Vec3>,
     pub normals: Vec<Vec3>,
     pub colors: Vec<Vec3>,
+    pub tex_coords: Vec<Vec2>,
     pub indices: Vec<u16>,
 }
 
@@ -20,6 +21,7 @@ impl Mesh {
                 .collect(),
             normals: self.normals.iter().cloned().collect(),
             colors: self.colors.iter().cloned().collect(),
+            tex_coords: self.tex_coords.iter().cloned().collect(),
             indices: self.indices.iter().cloned().collect(),
         }
     }
@@ -33,6 +35,7 @@ impl Mesh {
                 .collect(),
             normals: self.normals.iter().cloned().collect(),
             colors: self.colors.iter().cloned().collect(),
+            tex_coords: self.tex_coords.iter().cloned().collect(),
             indices: self.indices.iter().cloned().collect(),
         }
     }
@@ -58,6 +61,7 @@ impl Mesh {
                 .collect(),
             normals: self.normals.iter().cloned().collect(),
             colors: self.colors.iter().cloned().collect(),
+            tex_coords: self.tex_coords.iter().cloned().collect(),
             indices: self.indices.iter().cloned().collect(),
         }
     }
@@ -72,6 +76,7 @@ impl Mesh {
             vertices: [m1.vertices, m2.vertices].concat(),
             normals: [m1.normals, m2.normals].concat(),
             colors: [m1.colors, m2.colors].concat(),
+            tex_coords: [m1.tex_coords, m2.tex_coords].concat(),
             indices: [
                 m1.indices,
                 m2.indices
@@ -88,6 +93,7 @@ impl Mesh {
             vertices: self.vertices.iter().cloned().collect(),
             normals: self.normals.iter().cloned().collect(),
             colors: std::vec::from_elem(color.clone(), self.vertices.len()),
+            tex_coords: self.tex_coords.iter().cloned().collect(),
             indices: self.indices.iter().cloned().collect(),
         }
     }
@@ -111,6 +117,7 @@ impl Mesh {
                     std::vec::from_elem(vec3(1.0, 0.0, 1.0), 3)
                 }
             },
+            tex_coords: vec![vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0)],
             indices: vec![0, 1, 2],
         };
     }
@@ -0,0 +1,385 @@
+//! A dynamic bounding volume hierarchy, for finding what is inside a view without testing
+//! everything.
+//!
+//! Leaves store their bounds grown by a margin, so objects moving a little stay inside and don't
+//! touch the tree at all; objects that leave theirs are taken out and inserted again. Insertion
+//! puts a leaf next to the node that grows the tree's surface area the least.
+
+use super::culling::{Aabb, Frustum};
+
+/// How much leaf bounds are grown by.
+pub const BVH_MARGIN: f32 = 0.1;
+
+/// A leaf of a `Bvh`, valid until it is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BvhProxy(usize);
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    bounds: Aabb,
+    parent: Option<usize>,
+    children: Option<[usize; 2]>,
+    /// `Some` for leaves only.
+    data: Option<T>,
+}
+
+pub struct Bvh<T> {
+    nodes: Vec<Node<T>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    leaf_count: usize,
+}
+
+impl<T> Bvh<T> {
+    pub fn new() -> Bvh<T> {
+        Bvh {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            leaf_count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaf_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+
+    pub fn insert(&mut self, bounds: Aabb, data: T) -> BvhProxy {
+        let leaf = self.allocate(Node {
+            bounds: bounds.expanded(BVH_MARGIN),
+            parent: None,
+            children: None,
+            data: Some(data),
+        });
+        self.insert_leaf(leaf);
+        self.leaf_count += 1;
+        BvhProxy(leaf)
+    }
+
+    pub fn remove(&mut self, proxy: BvhProxy) -> T {
+        self.remove_leaf(proxy.0);
+        self.leaf_count -= 1;
+        self.free.push(proxy.0);
+        self.nodes[proxy.0].data.take().unwrap()
+    }
+
+    /// Moves a leaf to `bounds`. Returns whether the tree had to change, which it only does when
+    /// `bounds` leave the leaf's grown bounds.
+    pub fn update(&mut self, proxy: BvhProxy, bounds: Aabb) -> bool {
+        if self.nodes[proxy.0].bounds.contains(&bounds) {
+            return false;
+        }
+        self.remove_leaf(proxy.0);
+        self.nodes[proxy.0].bounds = bounds.expanded(BVH_MARGIN);
+        self.insert_leaf(proxy.0);
+        true
+    }
+
+    pub fn get(&self, proxy: BvhProxy) -> &T {
+        self.nodes[proxy.0].data.as_ref().unwrap()
+    }
+
+    /// The leaf's grown bounds.
+    pub fn bounds(&self, proxy: BvhProxy) -> Aabb {
+        self.nodes[proxy.0].bounds
+    }
+
+    /// Calls `visit` with every leaf whose bounds may be inside `frustum`, and returns how many
+    /// nodes were tested.
+    pub fn query_frustum<F>(&self, frustum: &Frustum, mut visit: F) -> usize
+    where
+        F: FnMut(&T),
+    {
+        let mut tests = 0;
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            tests += 1;
+            if !frustum.intersects(&node.bounds) {
+                continue;
+            }
+            match node.children {
+                Some(children) => stack.extend_from_slice(&children),
+                None => visit(node.data.as_ref().unwrap()),
+            }
+        }
+        tests
+    }
+
+    fn allocate(&mut self, node: Node<T>) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                self.nodes[leaf].parent = None;
+                self.root = Some(leaf);
+                return;
+            }
+        };
+
+        let bounds = self.nodes[leaf].bounds;
+        let sibling = self.find_sibling(root, &bounds);
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            bounds: bounds.merged(&self.nodes[sibling].bounds),
+            parent: old_parent,
+            children: Some([sibling, leaf]),
+            data: None,
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            Some(old_parent) => self.replace_child(old_parent, sibling, parent),
+            None => self.root = Some(parent),
+        }
+        self.refit(old_parent);
+    }
+
+    /// Walks down from `root` to the node that, paired with a new leaf of `bounds`, adds the least
+    /// surface area to the tree.
+    fn find_sibling(&self, root: usize, bounds: &Aabb) -> usize {
+        let mut index = root;
+        while let Some(children) = self.nodes[index].children {
+            let area = self.nodes[index].bounds.surface_area();
+            let merged_area = self.nodes[index].bounds.merged(bounds).surface_area();
+
+            // Pairing with this node makes a new parent; going further down grows this node
+            let cost_here = 2.0 * merged_area;
+            let inherited_cost = 2.0 * (merged_area - area);
+
+            let child_cost = |child: usize| {
+                let node = &self.nodes[child];
+                let merged = node.bounds.merged(bounds).surface_area();
+                match node.children {
+                    Some(_) => merged - node.bounds.surface_area() + inherited_cost,
+                    None => merged + inherited_cost,
+                }
+            };
+            let (cost_left, cost_right) = (child_cost(children[0]), child_cost(children[1]));
+
+            if cost_here < cost_left && cost_here < cost_right {
+                break;
+            }
+            index = if cost_left < cost_right {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+        index
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        let parent = match self.nodes[leaf].parent {
+            Some(parent) => parent,
+            None => {
+                self.root = None;
+                return;
+            }
+        };
+
+        let children = self.nodes[parent].children.unwrap();
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => self.replace_child(grandparent, parent, sibling),
+            None => self.root = Some(sibling),
+        }
+        self.nodes[parent].children = None;
+        self.free.push(parent);
+        self.nodes[leaf].parent = None;
+        self.refit(grandparent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let Some(ref mut children) = self.nodes[parent].children {
+            for child in children.iter_mut() {
+                if *child == old {
+                    *child = new;
+                }
+            }
+        }
+    }
+
+    /// Recomputes the bounds of `index` and everything above it.
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(i) = index {
+            let children = self.nodes[i].children.unwrap();
+            self.nodes[i].bounds = self.nodes[children[0]]
+                .bounds
+                .merged(&self.nodes[children[1]].bounds);
+            index = self.nodes[i].parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::{identity, perspective, vec3, Mat4, Vec3};
+
+    fn cube(center: Vec3) -> Aabb {
+        Aabb::new(center - vec3(0.5, 0.5, 0.5), center + vec3(0.5, 0.5, 0.5))
+    }
+
+    /// A camera at the origin looking down -z.
+    fn camera() -> Frustum {
+        let view: Mat4 = identity();
+        Frustum::from_view_projection(&(perspective(1.0, 1.0, 0.1, 100.0) * view))
+    }
+
+    fn query(bvh: &Bvh<u32>, frustum: &Frustum) -> Vec<u32> {
+        let mut found = Vec::new();
+        bvh.query_frustum(frustum, |&data| found.push(data));
+        found.sort();
+        found
+    }
+
+    /// Every node's bounds contain its children's, and parents and children point at each other.
+    fn check_tree(bvh: &Bvh<u32>) {
+        let mut leaves = 0;
+        let mut stack: Vec<usize> = bvh.root.into_iter().collect();
+        if let Some(root) = bvh.root {
+            assert_eq!(bvh.nodes[root].parent, None);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &bvh.nodes[index];
+            match node.children {
+                Some(children) => {
+                    for &child in &children {
+                        assert_eq!(bvh.nodes[child].parent, Some(index));
+                        assert!(node.bounds.contains(&bvh.nodes[child].bounds));
+                        stack.push(child);
+                    }
+                }
+                None => leaves += 1,
+            }
+        }
+        assert_eq!(leaves, bvh.len());
+    }
+
+    /// A row of cubes along x at depth 10, numbered from x = -20.
+    fn row(bvh: &mut Bvh<u32>) -> Vec<BvhProxy> {
+        (0..41)
+            .map(|i| bvh.insert(cube(vec3(i as f32 - 20.0, 0.0, -10.0)), i))
+            .collect()
+    }
+
+    #[test]
+    fn queries_find_what_the_frustum_sees() {
+        let mut bvh = Bvh::new();
+        row(&mut bvh);
+        bvh.insert(cube(vec3(0.0, 0.0, 10.0)), 100);
+        bvh.insert(cube(vec3(0.0, 0.0, -200.0)), 101);
+        check_tree(&bvh);
+
+        let frustum = camera();
+        let expected: Vec<u32> = (0..41)
+            .filter(|&i| {
+                frustum.intersects(&cube(vec3(i as f32 - 20.0, 0.0, -10.0)).expanded(BVH_MARGIN))
+            })
+            .collect();
+        assert_eq!(query(&bvh, &frustum), expected);
+        assert!(expected.contains(&20));
+        assert!(!expected.contains(&0) && !expected.contains(&40));
+    }
+
+    #[test]
+    fn queries_skip_subtrees_outside_the_frustum() {
+        let mut bvh = Bvh::new();
+        for i in 0..64 {
+            bvh.insert(cube(vec3(i as f32 * 3.0, 0.0, 50.0)), i);
+        }
+        let tests = bvh.query_frustum(&camera(), |_| panic!("Everything is behind the camera"));
+        assert!(tests < 2 * bvh.len() - 1);
+    }
+
+    #[test]
+    fn small_moves_stay_in_the_leaf() {
+        let mut bvh = Bvh::new();
+        let proxies = row(&mut bvh);
+        assert!(!bvh.update(proxies[20], cube(vec3(0.05, 0.0, -10.0))));
+        assert!(bvh.update(proxies[20], cube(vec3(0.5, 0.0, -10.0))));
+        check_tree(&bvh);
+    }
+
+    #[test]
+    fn queries_follow_moved_leaves() {
+        let mut bvh = Bvh::new();
+        let proxies = row(&mut bvh);
+        let frustum = camera();
+
+        // The middle cube goes behind the camera, and the leftmost one from out of view into it
+        bvh.update(proxies[20], cube(vec3(0.0, 0.0, 10.0)));
+        check_tree(&bvh);
+        assert!(!query(&bvh, &frustum).contains(&20));
+
+        bvh.update(proxies[0], cube(vec3(0.0, 0.0, -50.0)));
+        check_tree(&bvh);
+        assert!(query(&bvh, &frustum).contains(&0));
+        assert_eq!(
+            bvh.bounds(proxies[0]),
+            cube(vec3(0.0, 0.0, -50.0)).expanded(BVH_MARGIN)
+        );
+        assert_eq!(*bvh.get(proxies[0]), 0);
+    }
+
+    #[test]
+    fn removed_leaves_are_not_found() {
+        let mut bvh = Bvh::new();
+        let proxies = row(&mut bvh);
+        let frustum = camera();
+
+        assert_eq!(bvh.remove(proxies[20]), 20);
+        assert_eq!(bvh.remove(proxies[19]), 19);
+        check_tree(&bvh);
+        assert_eq!(bvh.len(), 39);
+        let found = query(&bvh, &frustum);
+        assert!(!found.contains(&20) && !found.contains(&19));
+        assert!(found.contains(&21));
+
+        // Freed nodes are reused
+        let node_count = bvh.nodes.len();
+        let proxy = bvh.insert(cube(vec3(0.0, 0.0, -10.0)), 200);
+        check_tree(&bvh);
+        assert_eq!(bvh.nodes.len(), node_count);
+        assert!(query(&bvh, &frustum).contains(&200));
+        assert_eq!(*bvh.get(proxy), 200);
+    }
+
+    #[test]
+    fn removing_everything_empties_the_tree() {
+        let mut bvh = Bvh::new();
+        for proxy in row(&mut bvh) {
+            bvh.remove(proxy);
+            check_tree(&bvh);
+        }
+        assert!(bvh.is_empty());
+        assert_eq!(
+            bvh.query_frustum(&camera(), |_| panic!("The tree is empty")),
+            0
+        );
+    }
+}
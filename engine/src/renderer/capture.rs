@@ -0,0 +1,117 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use utils::img::save_as_image;
+use vulkano::format::Format;
+
+/// Saves the next `remaining` frames as `frame_00000.png`, `frame_00001.png`, ... in `dir`.
+pub struct FrameSequence {
+    dir: PathBuf,
+    next_index: u32,
+    remaining: u32,
+}
+
+impl FrameSequence {
+    pub fn new(dir: &Path, frame_count: u32) -> FrameSequence {
+        FrameSequence {
+            dir: dir.to_path_buf(),
+            next_index: 0,
+            remaining: frame_count,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining == 0
+    }
+
+    pub fn next_path(&mut self) -> PathBuf {
+        let path = self.dir.join(format!("frame_{:05}.png", self.next_index));
+        self.next_index += 1;
+        self.remaining -= 1;
+        path
+    }
+}
+
+/// A swapchain format `to_rgba8` can't convert, like 10 bit or floating point HDR formats.
+#[derive(Clone, Copy, Debug)]
+pub struct UnsupportedFormat(pub Format);
+
+impl fmt::Display for UnsupportedFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Capturing frames with format {:?} is not supported",
+            self.0
+        )
+    }
+}
+
+/// Turns the raw bytes of a captured image into RGBA8 rows that can be saved as PNG.
+///
+/// 8 bit sRGB and UNORM images both hold the bytes the display shows, so only the channel order
+/// needs fixing. The packed ABGR formats keep red in the lowest byte, which comes first on the
+/// little endian machines Vulkan runs on. Alpha is forced to opaque since swapchains don't keep
+/// meaningful alpha.
+pub fn to_rgba8(format: Format, data: &[u8]) -> Result<Vec<u8>, UnsupportedFormat> {
+    let swap_red_blue = match format {
+        Format::R8G8B8A8Unorm
+        | Format::R8G8B8A8Srgb
+        | Format::A8B8G8R8UnormPack32
+        | Format::A8B8G8R8SrgbPack32 => false,
+        Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => true,
+        _ => return Err(UnsupportedFormat(format)),
+    };
+
+    let mut rgba = Vec::with_capacity(data.len());
+    for pixel in data.chunks(4) {
+        if swap_red_blue {
+            rgba.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+        } else {
+            rgba.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+        }
+    }
+    Ok(rgba)
+}
+
+/// Encodes and writes the PNG on another thread so capturing doesn't stall the frame for long.
+pub fn save_in_background(path: PathBuf, pixels: Vec<u8>, dimensions: [u32; 2]) {
+    thread::spawn(move || {
+        save_as_image(&path, &pixels, dimensions[0], dimensions[1], 4);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: [u8; 8] = [10, 20, 30, 40, 50, 60, 70, 80];
+
+    #[test]
+    fn rgba_and_packed_abgr_keep_their_channel_order() {
+        for &format in &[
+            Format::R8G8B8A8Unorm,
+            Format::R8G8B8A8Srgb,
+            Format::A8B8G8R8UnormPack32,
+            Format::A8B8G8R8SrgbPack32,
+        ] {
+            assert_eq!(
+                to_rgba8(format, &PIXELS).unwrap(),
+                vec![10, 20, 30, 255, 50, 60, 70, 255]
+            );
+        }
+    }
+
+    #[test]
+    fn bgra_swaps_red_and_blue() {
+        assert_eq!(
+            to_rgba8(Format::B8G8R8A8Srgb, &PIXELS).unwrap(),
+            vec![30, 20, 10, 255, 70, 60, 50, 255]
+        );
+    }
+
+    #[test]
+    fn other_formats_are_errors() {
+        assert!(to_rgba8(Format::A2B10G10R10UnormPack32, &PIXELS).is_err());
+        assert!(to_rgba8(Format::R16G16B16A16Sfloat, &PIXELS).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use vulkano::format::Format;
+use vulkano::swapchain::{Capabilities, ColorSpace, PresentMode, SupportedPresentModes};
+use winit::dpi::LogicalSize;
+use winit::event_loop::EventLoop;
+use winit::window::{Fullscreen, WindowBuilder};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VSync {
+    /// Frames wait for the display's refresh, so they never tear.
+    On,
+    /// Frames are shown as soon as they are done: with mailbox presentation where the device has
+    /// it, which doesn't tear either, or immediately otherwise. Falls back to `On`.
+    Off,
+    /// Like `On`, except that a late frame is shown right away instead of waiting for the next
+    /// refresh. Falls back to `On`.
+    Adaptive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    /// A window covering the primary monitor, without changing its video mode.
+    Borderless,
+    /// Takes over the primary monitor in the video mode closest to the window size. Falls back to
+    /// `Borderless` if the monitor lists no video modes.
+    Fullscreen,
+}
+
+/// How `Renderer::with_config` sets up the window and swapchain. Choices the device or monitor
+/// doesn't support fall back to the closest one that is.
+#[derive(Clone, Debug)]
+pub struct RendererConfig {
+    pub title: String,
+    /// In logical pixels. Also picks the video mode in `WindowMode::Fullscreen`.
+    pub window_size: [u32; 2],
+    pub window_mode: WindowMode,
+    pub vsync: VSync,
+    /// Swapchain images, clamped to what the surface allows. `None` uses the surface's minimum.
+    pub image_count: Option<u32>,
+    /// Pick an sRGB swapchain format, so shader output is gamma corrected when written, as the
+    /// engine's shaders expect. Without one the surface's first format is used.
+    pub prefer_srgb: bool,
+    /// Samples per pixel of the scene: 1 for no MSAA, or 2, 4 or 8. Lowered to the most the
+    /// device supports.
+    pub msaa_samples: u32,
+}
+
+impl Default for RendererConfig {
+    fn default() -> RendererConfig {
+        RendererConfig {
+            title: "Engine".to_string(),
+            window_size: [1024, 768],
+            window_mode: WindowMode::Windowed,
+            vsync: VSync::On,
+            image_count: None,
+            prefer_srgb: true,
+            msaa_samples: 1,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn window_builder(&self, event_loop: &EventLoop<()>) -> WindowBuilder {
+        let [width, height] = self.window_size;
+        let builder = WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(LogicalSize::new(width, height));
+
+        let monitor = event_loop.primary_monitor();
+        let fullscreen = match self.window_mode {
+            WindowMode::Windowed => None,
+            WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            WindowMode::Fullscreen => {
+                let closest = monitor.video_modes().min_by_key(|mode| {
+                    let mode_size = mode.size();
+                    let dw = mode_size.width as i64 - width as i64;
+                    let dh = mode_size.height as i64 - height as i64;
+                    // Closest size first, then the highest refresh rate and bit depth
+                    (
+                        dw * dw + dh * dh,
+                        -(mode.refresh_rate() as i64),
+                        -(mode.bit_depth() as i64),
+                    )
+                });
+                match closest {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => Some(Fullscreen::Borderless(monitor)),
+                }
+            }
+        };
+        builder.with_fullscreen(fullscreen)
+    }
+}
+
+pub fn choose_present_mode(vsync: VSync, supported: &SupportedPresentModes) -> PresentMode {
+    let preferred: &[PresentMode] = match vsync {
+        VSync::On => &[],
+        VSync::Off => &[PresentMode::Mailbox, PresentMode::Immediate],
+        VSync::Adaptive => &[PresentMode::Relaxed],
+    };
+    // Every device supports fifo
+    preferred
+        .iter()
+        .cloned()
+        .find(|&mode| supported.supports(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+/// An sRGB format in the sRGB color space if `prefer_srgb` and the surface has one, otherwise the
+/// surface's first format.
+pub fn choose_format(capabilities: &Capabilities, prefer_srgb: bool) -> (Format, ColorSpace) {
+    let formats = &capabilities.supported_formats;
+    let srgb = formats.iter().cloned().find(|&(format, color_space)| {
+        color_space == ColorSpace::SrgbNonLinear
+            && match format {
+                Format::B8G8R8A8Srgb | Format::R8G8B8A8Srgb | Format::A8B8G8R8SrgbPack32 => true,
+                _ => false,
+            }
+    });
+    match srgb {
+        Some(format) if prefer_srgb => format,
+        _ => formats[0],
+    }
+}
+
+pub fn choose_image_count(requested: Option<u32>, capabilities: &Capabilities) -> u32 {
+    let count = requested
+        .unwrap_or(capabilities.min_image_count)
+        .max(capabilities.min_image_count);
+    match capabilities.max_image_count {
+        Some(max) => count.min(max),
+        None => count,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vulkano::image::ImageUsage;
+    use vulkano::swapchain::{
+        SupportedCompositeAlpha, SupportedSurfaceTransforms, SurfaceTransform,
+    };
+
+    fn capabilities(
+        formats: Vec<(Format, ColorSpace)>,
+        min_image_count: u32,
+        max_image_count: Option<u32>,
+    ) -> Capabilities {
+        Capabilities {
+            min_image_count: min_image_count,
+            max_image_count: max_image_count,
+            current_extent: Some([800, 600]),
+            min_image_extent: [1, 1],
+            max_image_extent: [4096, 4096],
+            max_image_array_layers: 1,
+            supported_transforms: SupportedSurfaceTransforms::none(),
+            current_transform: SurfaceTransform::Identity,
+            supported_composite_alpha: SupportedCompositeAlpha::none(),
+            supported_usage_flags: ImageUsage::none(),
+            supported_formats: formats,
+            present_modes: SupportedPresentModes::none(),
+        }
+    }
+
+    fn present_modes(immediate: bool, mailbox: bool, relaxed: bool) -> SupportedPresentModes {
+        SupportedPresentModes {
+            immediate: immediate,
+            mailbox: mailbox,
+            relaxed: relaxed,
+            fifo: true,
+            ..SupportedPresentModes::none()
+        }
+    }
+
+    #[test]
+    fn vsync_off_prefers_mailbox_then_immediate() {
+        let all = present_modes(true, true, true);
+        assert_eq!(choose_present_mode(VSync::On, &all), PresentMode::Fifo);
+        assert_eq!(choose_present_mode(VSync::Off, &all), PresentMode::Mailbox);
+        assert_eq!(
+            choose_present_mode(VSync::Off, &present_modes(true, false, false)),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            choose_present_mode(VSync::Adaptive, &all),
+            PresentMode::Relaxed
+        );
+    }
+
+    #[test]
+    fn unsupported_present_modes_fall_back_to_fifo() {
+        let fifo_only = present_modes(false, false, false);
+        assert_eq!(
+            choose_present_mode(VSync::Off, &fifo_only),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            choose_present_mode(VSync::Adaptive, &present_modes(true, true, false)),
+            PresentMode::Fifo
+        );
+    }
+
+    #[test]
+    fn srgb_formats_are_preferred() {
+        let caps = capabilities(
+            vec![
+                (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+                // sRGB, but not in the color space the shaders write for
+                (Format::B8G8R8A8Srgb, ColorSpace::ExtendedSrgbLinear),
+                (Format::R8G8B8A8Srgb, ColorSpace::SrgbNonLinear),
+            ],
+            2,
+            None,
+        );
+        assert_eq!(
+            choose_format(&caps, true),
+            (Format::R8G8B8A8Srgb, ColorSpace::SrgbNonLinear)
+        );
+        assert_eq!(
+            choose_format(&caps, false),
+            (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear)
+        );
+    }
+
+    #[test]
+    fn without_an_srgb_format_the_first_is_used() {
+        let caps = capabilities(
+            vec![
+                (Format::A2B10G10R10UnormPack32, ColorSpace::SrgbNonLinear),
+                (Format::B8G8R8A8Unorm, ColorSpace::SrgbNonLinear),
+            ],
+            2,
+            None,
+        );
+        assert_eq!(
+            choose_format(&caps, true),
+            (Format::A2B10G10R10UnormPack32, ColorSpace::SrgbNonLinear)
+        );
+    }
+
+    #[test]
+    fn image_counts_are_clamped_to_the_surface() {
+        let caps = capabilities(vec![], 2, Some(4));
+        assert_eq!(choose_image_count(None, &caps), 2);
+        assert_eq!(choose_image_count(Some(3), &caps), 3);
+        assert_eq!(choose_image_count(Some(1), &caps), 2);
+        assert_eq!(choose_image_count(Some(8), &caps), 4);
+
+        // No maximum
+        let caps = capabilities(vec![], 3, None);
+        assert_eq!(choose_image_count(Some(8), &caps), 8);
+        assert_eq!(choose_image_count(Some(0), &caps), 3);
+    }
+}
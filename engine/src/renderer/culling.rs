@@ -0,0 +1,293 @@
+use glm::{vec3, vec4, Mat4, Vec3, Vec4};
+
+/// An axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min: min, max: max }
+    }
+
+    /// The smallest box around `points`, or an empty box at the origin without points.
+    pub fn from_points(points: &[Vec3]) -> Aabb {
+        let first = match points.first() {
+            Some(first) => *first,
+            None => return Aabb::new(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0)),
+        };
+        points.iter().fold(Aabb::new(first, first), |bounds, p| {
+            Aabb::new(
+                vec3(
+                    bounds.min.x.min(p.x),
+                    bounds.min.y.min(p.y),
+                    bounds.min.z.min(p.z),
+                ),
+                vec3(
+                    bounds.max.x.max(p.x),
+                    bounds.max.y.max(p.y),
+                    bounds.max.z.max(p.z),
+                ),
+            )
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The box around both boxes.
+    pub fn merged(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            vec3(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            vec3(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        )
+    }
+
+    /// Grown by `margin` on every side.
+    pub fn expanded(&self, margin: f32) -> Aabb {
+        let margin = vec3(margin, margin, margin);
+        Aabb::new(self.min - margin, self.max + margin)
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.min.y <= other.max.y
+            && self.min.z <= other.max.z
+            && self.max.x >= other.min.x
+            && self.max.y >= other.min.y
+            && self.max.z >= other.min.z
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// The box around this one after `transform`, e.g. a mesh's bounds moved into world space.
+    pub fn transformed(&self, transform: &Mat4) -> Aabb {
+        let center = self.center();
+        let extents = self.extents();
+        let center = transform * vec4(center.x, center.y, center.z, 1.0);
+        let extent = |row: usize| {
+            transform[(row, 0)].abs() * extents.x
+                + transform[(row, 1)].abs() * extents.y
+                + transform[(row, 2)].abs() * extents.z
+        };
+        let extents = vec3(extent(0), extent(1), extent(2));
+        let center = vec3(center.x, center.y, center.z);
+        Aabb::new(center - extents, center + extents)
+    }
+}
+
+/// The six planes of a camera's view volume, pointing inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// The frustum of `projection * view`. The near plane is taken at -w, where OpenGL style
+    /// projections put it, which for the 0 to 1 depth of Vulkan style ones is a little further
+    /// back and only lets a few more boxes through.
+    pub fn from_view_projection(view_projection: &Mat4) -> Frustum {
+        let row = |i: usize| {
+            vec4(
+                view_projection[(i, 0)],
+                view_projection[(i, 1)],
+                view_projection[(i, 2)],
+                view_projection[(i, 3)],
+            )
+        };
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Frustum {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    /// Whether any of `bounds` may be inside. Boxes near the frustum's edges that are actually
+    /// outside can still count as inside, but nothing inside is ever reported outside.
+    pub fn intersects(&self, bounds: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal
+            let corner = vec3(
+                if plane.x >= 0.0 {
+                    bounds.max.x
+                } else {
+                    bounds.min.x
+                },
+                if plane.y >= 0.0 {
+                    bounds.max.y
+                } else {
+                    bounds.min.y
+                },
+                if plane.z >= 0.0 {
+                    bounds.max.z
+                } else {
+                    bounds.min.z
+                },
+            );
+            plane.x * corner.x + plane.y * corner.y + plane.z * corner.z + plane.w >= 0.0
+        })
+    }
+}
+
+/// How culling went for the last gathered view.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CullingStats {
+    /// Objects that could have been drawn.
+    pub total: usize,
+    pub visible: usize,
+    /// Out of view, but drawn into the shadow maps of a light that sees them.
+    pub shadow_casters: usize,
+    /// Neither visible nor casting a visible shadow.
+    pub culled: usize,
+    /// Bounding boxes tested against the frustums of the camera and the shadow maps, BVH nodes
+    /// included.
+    pub tests: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::{identity, ortho_rh_zo, perspective, rotate_y, translate};
+
+    fn cube(center: Vec3, half_size: f32) -> Aabb {
+        let half = vec3(half_size, half_size, half_size);
+        Aabb::new(center - half, center + half)
+    }
+
+    /// A camera at the origin looking down -z, seeing from 0.1 to 100.
+    fn camera() -> Frustum {
+        let view: Mat4 = identity();
+        Frustum::from_view_projection(&(perspective(1.0, 1.0, 0.1, 100.0) * view))
+    }
+
+    #[test]
+    fn from_points_bounds_all_points() {
+        let bounds = Aabb::from_points(&[
+            vec3(1.0, -2.0, 3.0),
+            vec3(-1.0, 4.0, 0.0),
+            vec3(0.0, 0.0, -5.0),
+        ]);
+        assert_eq!(
+            bounds,
+            Aabb::new(vec3(-1.0, -2.0, -5.0), vec3(1.0, 4.0, 3.0))
+        );
+        assert_eq!(
+            Aabb::from_points(&[]),
+            Aabb::new(vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn merging_containing_and_intersecting() {
+        let a = Aabb::new(vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0));
+        let b = Aabb::new(vec3(0.5, 0.5, 0.5), vec3(2.0, 2.0, 2.0));
+        let c = Aabb::new(vec3(3.0, 0.0, 0.0), vec3(4.0, 1.0, 1.0));
+
+        let merged = a.merged(&b);
+        assert_eq!(merged, Aabb::new(vec3(0.0, 0.0, 0.0), vec3(2.0, 2.0, 2.0)));
+        assert!(merged.contains(&a) && merged.contains(&b));
+        assert!(!a.contains(&b));
+        assert!(a.intersects(&b) && b.intersects(&a));
+        assert!(!a.intersects(&c));
+        assert_eq!(a.expanded(0.5), cube(vec3(0.5, 0.5, 0.5), 1.0));
+        assert_eq!(a.surface_area(), 6.0);
+    }
+
+    #[test]
+    fn transformed_bounds_follow_translation_and_rotation() {
+        let bounds = Aabb::new(vec3(-1.0, -2.0, -3.0), vec3(1.0, 2.0, 3.0));
+
+        let moved = bounds.transformed(&translate(&identity(), &vec3(10.0, 0.0, 0.0)));
+        assert_eq!(
+            moved,
+            Aabb::new(vec3(9.0, -2.0, -3.0), vec3(11.0, 2.0, 3.0))
+        );
+
+        // A quarter turn around y swaps the extents along x and z
+        let turned = bounds.transformed(&rotate_y(&identity(), ::std::f32::consts::FRAC_PI_2));
+        assert!((turned.min - vec3(-3.0, -2.0, -1.0)).norm() < 1e-5);
+        assert!((turned.max - vec3(3.0, 2.0, 1.0)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn boxes_inside_the_frustum_intersect_it() {
+        let frustum = camera();
+        assert!(frustum.intersects(&cube(vec3(0.0, 0.0, -10.0), 1.0)));
+        assert!(frustum.intersects(&cube(vec3(3.0, -3.0, -50.0), 1.0)));
+        // Bigger than the whole view
+        assert!(frustum.intersects(&cube(vec3(0.0, 0.0, 0.0), 500.0)));
+    }
+
+    #[test]
+    fn boxes_outside_the_frustum_dont_intersect_it() {
+        let frustum = camera();
+        assert!(
+            !frustum.intersects(&cube(vec3(0.0, 0.0, 10.0), 1.0)),
+            "behind"
+        );
+        assert!(
+            !frustum.intersects(&cube(vec3(0.0, 0.0, -200.0), 1.0)),
+            "beyond far"
+        );
+        assert!(
+            !frustum.intersects(&cube(vec3(-50.0, 0.0, -10.0), 1.0)),
+            "left"
+        );
+        assert!(
+            !frustum.intersects(&cube(vec3(50.0, 0.0, -10.0), 1.0)),
+            "right"
+        );
+        assert!(
+            !frustum.intersects(&cube(vec3(0.0, 50.0, -10.0), 1.0)),
+            "above"
+        );
+        assert!(
+            !frustum.intersects(&cube(vec3(0.0, -50.0, -10.0), 1.0)),
+            "below"
+        );
+    }
+
+    #[test]
+    fn boxes_crossing_a_plane_intersect_the_frustum() {
+        let frustum = camera();
+        // The left plane is at x = -tan(0.5) * 10, about -5.5, at this depth
+        assert!(frustum.intersects(&Aabb::new(vec3(-20.0, -1.0, -11.0), vec3(-5.0, 1.0, -9.0))));
+        assert!(frustum.intersects(&Aabb::new(vec3(-1.0, -1.0, -110.0), vec3(1.0, 1.0, -90.0))));
+        assert!(frustum.intersects(&Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 5.0))));
+    }
+
+    #[test]
+    fn orthographic_frustums_are_boxes() {
+        let frustum = Frustum::from_view_projection(&ortho_rh_zo(-5.0, 5.0, -5.0, 5.0, 0.0, 20.0));
+        assert!(frustum.intersects(&cube(vec3(4.0, -4.0, -19.0), 0.5)));
+        assert!(frustum.intersects(&cube(vec3(5.5, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects(&cube(vec3(7.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects(&cube(vec3(0.0, 0.0, -22.0), 1.0)));
+    }
+}
@@ -0,0 +1,444 @@
+//! Immediate mode debug drawing, for seeing collision shapes, paths or camera frustums.
+//!
+//! Anything can call these functions during a frame; the renderer collects what was drawn and
+//! draws it as lines over the scene. Each call takes an optional duration in seconds: `None` draws
+//! for the next frame only, `Some(seconds)` keeps drawing until that much time has passed.
+//!
+//! Debug drawing is off until `set_enabled(true)`, and while off every call returns right away.
+
+use drawing::Color4;
+use glm::{cross, inverse, length, normalize, vec3, vec4, Mat4, Vec3};
+use renderer::culling::Aabb;
+
+use std::f32::consts::PI;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Line segments circles and spheres are made of.
+const CIRCLE_SEGMENTS: usize = 32;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref PRIMITIVES: Mutex<DebugFrame> = Mutex::new(DebugFrame::default());
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugLine {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub color: Color4,
+    expires: Option<Instant>,
+}
+
+/// Text drawn at a point in the world, centered on where the point lands on screen.
+#[derive(Clone, Debug)]
+pub struct DebugText {
+    pub position: Vec3,
+    pub text: String,
+    pub color: Color4,
+    expires: Option<Instant>,
+}
+
+/// Everything to draw in one frame.
+#[derive(Clone, Debug, Default)]
+pub struct DebugFrame {
+    pub lines: Vec<DebugLine>,
+    pub texts: Vec<DebugText>,
+}
+
+/// Turning debug drawing off also drops everything still waiting to be drawn.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled {
+        let mut primitives = PRIMITIVES.lock().unwrap();
+        primitives.lines.clear();
+        primitives.texts.clear();
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn line(from: Vec3, to: Vec3, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    add_lines(&[(from, to)], color, duration);
+}
+
+/// A line from `from` to `to` with a head at `to`.
+pub fn arrow(from: Vec3, to: Vec3, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let length = length(&(to - from));
+    if length == 0.0 {
+        return;
+    }
+    let direction = (to - from) / length;
+    let (side, up) = perpendiculars(&direction);
+    let head_length = length * 0.2;
+    let back = to - direction * head_length;
+    let spread = head_length * 0.5;
+    add_lines(
+        &[
+            (from, to),
+            (to, back + side * spread),
+            (to, back - side * spread),
+            (to, back + up * spread),
+            (to, back - up * spread),
+        ],
+        color,
+        duration,
+    );
+}
+
+pub fn aabb(bounds: &Aabb, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let (min, max) = (bounds.min, bounds.max);
+    let corners = [
+        vec3(min.x, min.y, min.z),
+        vec3(max.x, min.y, min.z),
+        vec3(max.x, max.y, min.z),
+        vec3(min.x, max.y, min.z),
+        vec3(min.x, min.y, max.z),
+        vec3(max.x, min.y, max.z),
+        vec3(max.x, max.y, max.z),
+        vec3(min.x, max.y, max.z),
+    ];
+    add_lines(&box_edges(&corners), color, duration);
+}
+
+/// A box of `half_extents` around the origin of `transform`, rotated and placed by it.
+pub fn oriented_box(transform: &Mat4, half_extents: Vec3, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let h = half_extents;
+    let corner = |x: f32, y: f32, z: f32| {
+        let p = transform * vec4(x * h.x, y * h.y, z * h.z, 1.0);
+        vec3(p.x, p.y, p.z)
+    };
+    let corners = [
+        corner(-1.0, -1.0, -1.0),
+        corner(1.0, -1.0, -1.0),
+        corner(1.0, 1.0, -1.0),
+        corner(-1.0, 1.0, -1.0),
+        corner(-1.0, -1.0, 1.0),
+        corner(1.0, -1.0, 1.0),
+        corner(1.0, 1.0, 1.0),
+        corner(-1.0, 1.0, 1.0),
+    ];
+    add_lines(&box_edges(&corners), color, duration);
+}
+
+/// A circle around `center` in the plane facing `normal`.
+pub fn circle(center: Vec3, normal: Vec3, radius: f32, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let (side, up) = perpendiculars(&normalize(&normal));
+    add_lines(&circle_segments(center, side, up, radius), color, duration);
+}
+
+/// Drawn as three circles, one around each axis.
+pub fn sphere(center: Vec3, radius: f32, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let (x, y, z) = (
+        vec3(1.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(0.0, 0.0, 1.0),
+    );
+    let mut segments = circle_segments(center, x, y, radius);
+    segments.extend(circle_segments(center, y, z, radius));
+    segments.extend(circle_segments(center, z, x, radius));
+    add_lines(&segments, color, duration);
+}
+
+/// The frustum a camera with `view_projection` sees, from the near to the far plane.
+pub fn frustum(view_projection: &Mat4, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    let inverse_view_projection = inverse(view_projection);
+    let corner = |x: f32, y: f32, z: f32| {
+        let p = inverse_view_projection * vec4(x, y, z, 1.0);
+        vec3(p.x, p.y, p.z) / p.w
+    };
+    let corners = [
+        corner(-1.0, -1.0, -1.0),
+        corner(1.0, -1.0, -1.0),
+        corner(1.0, 1.0, -1.0),
+        corner(-1.0, 1.0, -1.0),
+        corner(-1.0, -1.0, 1.0),
+        corner(1.0, -1.0, 1.0),
+        corner(1.0, 1.0, 1.0),
+        corner(-1.0, 1.0, 1.0),
+    ];
+    add_lines(&box_edges(&corners), color, duration);
+}
+
+/// Text centered on where `position` lands on screen, not drawn while it is behind the camera.
+pub fn text(position: Vec3, text: &str, color: Color4, duration: Option<f32>) {
+    if !is_enabled() {
+        return;
+    }
+    PRIMITIVES.lock().unwrap().texts.push(DebugText {
+        position: position,
+        text: text.to_string(),
+        color: color,
+        expires: expiry(duration),
+    });
+}
+
+/// Takes what to draw this frame. Primitives without a duration, or whose duration ran out, are
+/// not drawn again. Called by the renderer once per frame.
+pub fn take_frame() -> DebugFrame {
+    if !is_enabled() {
+        return DebugFrame::default();
+    }
+    let mut primitives = PRIMITIVES.lock().unwrap();
+    let frame = mem::replace(&mut *primitives, DebugFrame::default());
+
+    let now = Instant::now();
+    let lasts = |expires: Option<Instant>| expires.map_or(false, |expires| expires > now);
+    primitives.lines = frame
+        .lines
+        .iter()
+        .filter(|line| lasts(line.expires))
+        .cloned()
+        .collect();
+    primitives.texts = frame
+        .texts
+        .iter()
+        .filter(|text| lasts(text.expires))
+        .cloned()
+        .collect();
+    frame
+}
+
+fn expiry(duration: Option<f32>) -> Option<Instant> {
+    duration.map(|seconds| Instant::now() + Duration::from_secs_f32(seconds.max(0.0)))
+}
+
+fn add_lines(segments: &[(Vec3, Vec3)], color: Color4, duration: Option<f32>) {
+    let expires = expiry(duration);
+    let mut primitives = PRIMITIVES.lock().unwrap();
+    primitives
+        .lines
+        .extend(segments.iter().map(|&(from, to)| DebugLine {
+            from: from,
+            to: to,
+            color: color,
+            expires: expires,
+        }));
+}
+
+/// The 12 edges of a box whose corners go around one face and then the opposite one.
+fn box_edges(corners: &[Vec3; 8]) -> Vec<(Vec3, Vec3)> {
+    let mut edges = Vec::with_capacity(12);
+    for i in 0..4 {
+        let next = (i + 1) % 4;
+        edges.push((corners[i], corners[next]));
+        edges.push((corners[i + 4], corners[next + 4]));
+        edges.push((corners[i], corners[i + 4]));
+    }
+    edges
+}
+
+fn circle_segments(center: Vec3, side: Vec3, up: Vec3, radius: f32) -> Vec<(Vec3, Vec3)> {
+    let point = |i: usize| {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+        center + (side * angle.cos() + up * angle.sin()) * radius
+    };
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| (point(i), point(i + 1)))
+        .collect()
+}
+
+/// Two unit vectors perpendicular to `direction` and to each other.
+fn perpendiculars(direction: &Vec3) -> (Vec3, Vec3) {
+    let helper = if direction.y.abs() < 0.9 {
+        vec3(0.0, 1.0, 0.0)
+    } else {
+        vec3(1.0, 0.0, 0.0)
+    };
+    let side = normalize(&cross(direction, &helper));
+    let up = cross(&side, direction);
+    (side, up)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::{dot, translation};
+    use std::sync::MutexGuard;
+    use std::thread;
+
+    lazy_static! {
+        /// Debug drawing is global, so the tests that draw take turns.
+        static ref LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    /// Holds the lock with debug drawing on and nothing queued.
+    fn enabled() -> MutexGuard<'static, ()> {
+        let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_enabled(false);
+        set_enabled(true);
+        guard
+    }
+
+    fn red() -> Color4 {
+        vec4(1.0, 0.0, 0.0, 1.0)
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        length(&(a - b)) < 1e-4
+    }
+
+    #[test]
+    fn boxes_have_twelve_edges() {
+        let corners = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(1.0, 1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            vec3(1.0, 0.0, 1.0),
+            vec3(1.0, 1.0, 1.0),
+            vec3(0.0, 1.0, 1.0),
+        ];
+        let edges = box_edges(&corners);
+        assert_eq!(edges.len(), 12);
+        for (i, &(a, b)) in edges.iter().enumerate() {
+            // Every edge of a unit cube is one long and along one axis
+            assert_eq!(length(&(b - a)), 1.0, "{:?}", (a, b));
+            let same = |&(c, d): &(Vec3, Vec3)| (a, b) == (c, d) || (a, b) == (d, c);
+            assert!(!edges[i + 1..].iter().any(same), "{:?}", (a, b));
+        }
+        for corner in &corners {
+            let touching = edges
+                .iter()
+                .filter(|&&(a, b)| a == *corner || b == *corner)
+                .count();
+            assert_eq!(touching, 3, "{:?}", corner);
+        }
+    }
+
+    #[test]
+    fn circles_are_closed_loops() {
+        let center = vec3(1.0, 2.0, 3.0);
+        let (side, up) = perpendiculars(&vec3(0.0, 0.0, 1.0));
+        let segments = circle_segments(center, side, up, 2.0);
+        assert_eq!(segments.len(), CIRCLE_SEGMENTS);
+        assert!(close(segments[CIRCLE_SEGMENTS - 1].1, segments[0].0));
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+        for &(from, _) in &segments {
+            assert!((length(&(from - center)) - 2.0).abs() < 1e-4);
+            assert!((from.z - center.z).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn perpendiculars_are_at_right_angles() {
+        for direction in &[
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            normalize(&vec3(1.0, 2.0, -3.0)),
+        ] {
+            let (side, up) = perpendiculars(direction);
+            assert!((length(&side) - 1.0).abs() < 1e-5);
+            assert!((length(&up) - 1.0).abs() < 1e-5);
+            assert!(dot(&side, direction).abs() < 1e-5);
+            assert!(dot(&up, direction).abs() < 1e-5);
+            assert!(dot(&side, &up).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn shapes_add_their_lines() {
+        let _guard = enabled();
+        let origin = vec3(0.0, 0.0, 0.0);
+        let bounds = Aabb {
+            min: origin,
+            max: vec3(1.0, 1.0, 1.0),
+        };
+        aabb(&bounds, red(), None);
+        oriented_box(&translation(&vec3(1.0, 0.0, 0.0)), bounds.max, red(), None);
+        sphere(origin, 1.0, red(), None);
+        circle(origin, vec3(0.0, 1.0, 0.0), 1.0, red(), None);
+        arrow(origin, vec3(0.0, 0.0, 1.0), red(), None);
+        // A zero length arrow has no direction to draw in
+        arrow(origin, origin, red(), None);
+        text(origin, "origin", red(), None);
+
+        let frame = take_frame();
+        assert_eq!(
+            frame.lines.len(),
+            12 + 12 + 3 * CIRCLE_SEGMENTS + CIRCLE_SEGMENTS + 5
+        );
+        assert_eq!(frame.texts.len(), 1);
+        assert_eq!(frame.texts[0].text, "origin");
+        assert_eq!(frame.lines[0].color, red());
+    }
+
+    #[test]
+    fn primitives_last_until_their_duration_ends() {
+        let _guard = enabled();
+        let origin = vec3(0.0, 0.0, 0.0);
+        line(origin, vec3(1.0, 0.0, 0.0), red(), None);
+        line(origin, vec3(2.0, 0.0, 0.0), red(), Some(0.05));
+        line(origin, vec3(3.0, 0.0, 0.0), red(), Some(60.0));
+        text(origin, "once", red(), None);
+        text(origin, "long", red(), Some(60.0));
+
+        let ends =
+            |frame: &DebugFrame| -> Vec<f32> { frame.lines.iter().map(|l| l.to.x).collect() };
+        let frame = take_frame();
+        assert_eq!(ends(&frame), vec![1.0, 2.0, 3.0]);
+        assert_eq!(frame.texts.len(), 2);
+
+        // One frame primitives are gone, the others are drawn again
+        let frame = take_frame();
+        assert_eq!(ends(&frame), vec![2.0, 3.0]);
+        assert_eq!(frame.texts.len(), 1);
+        assert_eq!(frame.texts[0].text, "long");
+
+        thread::sleep(Duration::from_millis(100));
+        // Drawn once more, since it was still going at the last frame
+        assert_eq!(ends(&take_frame()), vec![2.0, 3.0]);
+        assert_eq!(ends(&take_frame()), vec![3.0]);
+    }
+
+    #[test]
+    fn turning_off_drops_the_queue() {
+        let _guard = enabled();
+        let origin = vec3(0.0, 0.0, 0.0);
+        line(origin, vec3(1.0, 0.0, 0.0), red(), Some(60.0));
+        text(origin, "kept", red(), Some(60.0));
+
+        set_enabled(false);
+        assert!(!is_enabled());
+        // Nothing is queued while off
+        sphere(origin, 1.0, red(), None);
+        text(origin, "off", red(), None);
+        assert!(take_frame().lines.is_empty());
+
+        set_enabled(true);
+        let frame = take_frame();
+        assert!(frame.lines.is_empty());
+        assert!(frame.texts.is_empty());
+        set_enabled(false);
+    }
+}
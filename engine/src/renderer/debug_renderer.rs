@@ -0,0 +1,179 @@
+use glm::{vec2, vec4, Mat4};
+use renderer::debug_draw::{DebugLine, DebugText};
+use shader::{ShaderError, ShaderProgram};
+use text::{layout_text, LayoutOptions, TextRenderer};
+
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::CpuBufferPool;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::Device;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+
+pub const DEBUG_SHADER: &str = "debug";
+
+#[derive(Default, Clone, Debug)]
+pub struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+vulkano::impl_vertex!(DebugVertex, position, color);
+
+/// The push constants of debug.vs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugPushConstants {
+    view_projection: [[f32; 4]; 4],
+}
+
+type DebugPipeline = GraphicsPipeline<
+    SingleBufferDefinition<DebugVertex>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+/// Draws what `debug_draw` collected, all lines in one draw call. They are drawn in the final
+/// post-processing pass without depth testing, so nothing in the scene hides them.
+pub struct DebugRenderer {
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: ShaderProgram,
+    pipeline: Arc<DebugPipeline>,
+    vertex_pool: CpuBufferPool<DebugVertex>,
+}
+
+impl DebugRenderer {
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> DebugRenderer {
+        let program = match ShaderProgram::create_program(device.clone(), DEBUG_SHADER) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the debug shader:\n{}", e),
+        };
+        let pipeline = match create_pipeline(device.clone(), render_pass.clone(), &program) {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the debug pipeline:\n{}", e),
+        };
+
+        DebugRenderer {
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            device: device,
+            render_pass: render_pass,
+            program: program,
+            pipeline: pipeline,
+        }
+    }
+
+    /// Recompiles debug.vs and debug.fs, keeping the current pipeline if that fails.
+    pub fn reload_shader(&mut self) -> Result<(), ShaderError> {
+        let program = self.program.reload(self.device.clone(), DEBUG_SHADER)?;
+        self.pipeline = create_pipeline(self.device.clone(), self.render_pass.clone(), &program)?;
+        self.program = program;
+        Ok(())
+    }
+
+    /// Records `lines` into the current render pass.
+    pub fn draw(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        lines: &[DebugLine],
+        view_projection: &Mat4,
+        dimensions: [u32; 2],
+    ) -> AutoCommandBufferBuilder {
+        // A buffer can't be empty
+        if lines.is_empty() {
+            return builder;
+        }
+
+        let vertices: Vec<_> = lines
+            .iter()
+            .flat_map(|line| {
+                let color = line.color.into();
+                vec![
+                    DebugVertex {
+                        position: line.from.into(),
+                        color: color,
+                    },
+                    DebugVertex {
+                        position: line.to.into(),
+                        color: color,
+                    },
+                ]
+            })
+            .collect();
+        let vertex_buffer = self.vertex_pool.chunk(vertices).unwrap();
+
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+
+        let push_constants = DebugPushConstants {
+            view_projection: (*view_projection).into(),
+        };
+
+        builder
+            .draw(
+                self.pipeline.clone(),
+                &dynamic_state,
+                vertex_buffer,
+                (),
+                push_constants,
+            )
+            .unwrap()
+    }
+
+    /// Queues `texts` into `text_renderer`, centered on where each lands on screen. Text behind
+    /// the camera is skipped.
+    pub fn queue_texts(
+        &self,
+        text_renderer: &mut TextRenderer,
+        texts: &[DebugText],
+        view_projection: &Mat4,
+        dimensions: [u32; 2],
+    ) {
+        for text in texts {
+            let clip =
+                view_projection * vec4(text.position.x, text.position.y, text.position.z, 1.0);
+            if clip.w <= 0.0 {
+                continue;
+            }
+            let screen = vec2(
+                (clip.x / clip.w + 1.0) * 0.5 * dimensions[0] as f32,
+                (clip.y / clip.w + 1.0) * 0.5 * dimensions[1] as f32,
+            );
+            let options = LayoutOptions {
+                color: text.color,
+                ..LayoutOptions::default()
+            };
+            let layout = layout_text(text_renderer.font(), &text.text, &options);
+            text_renderer.queue_layout(&layout, screen - layout.size * 0.5);
+        }
+    }
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<DebugPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(SingleBufferDefinition::<DebugVertex>::new())
+        .vertex_shader(program.vertex_entry_point(), ())
+        .line_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(program.fragment_entry_point(), ())
+        .depth_stencil_disabled()
+        .blend_alpha_blending()
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
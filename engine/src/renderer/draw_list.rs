@@ -0,0 +1,80 @@
+use drawing::Color4;
+use glm::{identity, vec4, Mat4};
+
+use super::light::Light;
+use super::resources::{MaterialHandle, MeshHandle};
+
+#[derive(Clone, Debug)]
+pub struct DrawCommand {
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub transform: Mat4,
+    pub tint: Color4,
+}
+
+/// Everything the renderer should draw this frame, as seen from one camera.
+#[derive(Clone, Debug)]
+pub struct DrawList {
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub commands: Vec<DrawCommand>,
+    /// Only drawn into shadow maps: objects out of the camera's view that can still throw a
+    /// shadow into it.
+    pub shadow_casters: Vec<DrawCommand>,
+    pub lights: Vec<Light>,
+}
+
+impl DrawList {
+    pub fn new(view: Mat4, projection: Mat4) -> DrawList {
+        DrawList {
+            view: view,
+            projection: projection,
+            commands: Vec::new(),
+            shadow_casters: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn empty() -> DrawList {
+        DrawList::new(identity(), identity())
+    }
+
+    pub fn draw(&mut self, mesh: MeshHandle, material: MaterialHandle, transform: Mat4) {
+        self.draw_tinted(mesh, material, transform, vec4(1.0, 1.0, 1.0, 1.0));
+    }
+
+    pub fn draw_tinted(
+        &mut self,
+        mesh: MeshHandle,
+        material: MaterialHandle,
+        transform: Mat4,
+        tint: Color4,
+    ) {
+        self.commands.push(DrawCommand {
+            mesh: mesh,
+            material: material,
+            transform: transform,
+            tint: tint,
+        });
+    }
+
+    /// Draws the mesh into the shadow maps only.
+    pub fn cast_shadow(&mut self, mesh: MeshHandle, material: MaterialHandle, transform: Mat4) {
+        self.shadow_casters.push(DrawCommand {
+            mesh: mesh,
+            material: material,
+            transform: transform,
+            tint: vec4(1.0, 1.0, 1.0, 1.0),
+        });
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.shadow_casters.clear();
+        self.lights.clear();
+    }
+}
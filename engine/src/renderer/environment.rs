@@ -0,0 +1,263 @@
+//! Environment maps for image based lighting in pbr.fs.
+//!
+//! The six faces of a cube map are prefiltered on the CPU into a small cube map whose mip levels
+//! get blurrier with roughness: level 0 is the sharp environment for mirror-like surfaces, and the
+//! last level is close to the diffuse irradiance.
+
+use glm::{dot, normalize, vec3, Vec3};
+use image::Image;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::device::Queue;
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImageLayout, ImageUsage, ImmutableImage, MipmapsCount};
+use vulkano::sync::GpuFuture;
+
+/// Texels per side of each face at mip level 0.
+pub const ENVIRONMENT_SIZE: u32 = 64;
+/// The blurred levels are filtered from a copy of the environment this small, which keeps
+/// prefiltering down to a fraction of a second.
+const FILTER_SOURCE_SIZE: u32 = 16;
+
+/// A prefiltered cube map ready for upload. Each level holds the six faces in Vulkan's order,
+/// +X, -X, +Y, -Y, +Z, -Z, as RGBA8 sRGB rows.
+pub struct PrefilteredEnvironment {
+    pub size: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl PrefilteredEnvironment {
+    /// Prefilters `faces`, given in Vulkan's face order. Faces have to be square and the same size.
+    pub fn new(faces: &[Image]) -> PrefilteredEnvironment {
+        assert_eq!(faces.len(), 6, "A cube map needs six faces");
+
+        let sharp: Vec<Vec<Vec3>> = faces
+            .iter()
+            .map(|face| resample_face(face, ENVIRONMENT_SIZE))
+            .collect();
+        let source: Vec<Vec<Vec3>> = faces
+            .iter()
+            .map(|face| resample_face(face, FILTER_SOURCE_SIZE))
+            .collect();
+        let source_texels = cube_texels(FILTER_SOURCE_SIZE);
+
+        let level_count = 32 - ENVIRONMENT_SIZE.leading_zeros();
+        let mut levels = vec![encode_faces(&sharp)];
+        for level in 1..level_count {
+            let size = ENVIRONMENT_SIZE >> level;
+            let roughness = level as f32 / (level_count - 1) as f32;
+            let filtered: Vec<Vec<Vec3>> = (0..6)
+                .map(|face| {
+                    (0..size * size)
+                        .map(|i| {
+                            let normal = texel_direction(face, i % size, i / size, size);
+                            filter_lobe(&normal, roughness, &source, &source_texels)
+                        })
+                        .collect()
+                })
+                .collect();
+            levels.push(encode_faces(&filtered));
+        }
+
+        PrefilteredEnvironment {
+            size: ENVIRONMENT_SIZE,
+            levels: levels,
+        }
+    }
+
+    /// The same color in every direction, for scenes without an environment.
+    pub fn uniform(color: [u8; 4]) -> PrefilteredEnvironment {
+        PrefilteredEnvironment {
+            size: 1,
+            levels: vec![color.iter().cloned().cycle().take(6 * 4).collect()],
+        }
+    }
+
+    /// Uploads every level into a cube map. The returned future has to finish before drawing.
+    pub fn upload(&self, queue: Arc<Queue>) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+        let device = queue.device().clone();
+        let usage = ImageUsage {
+            transfer_destination: true,
+            sampled: true,
+            ..ImageUsage::none()
+        };
+        let (image, initialization) = ImmutableImage::uninitialized(
+            device.clone(),
+            Dimensions::Cubemap { size: self.size },
+            Format::R8G8B8A8Srgb,
+            MipmapsCount::Specific(self.levels.len() as u32),
+            usage,
+            ImageLayout::ShaderReadOnlyOptimal,
+            Some(queue.family()),
+        )
+        .unwrap();
+        let initialization = Arc::new(initialization);
+
+        let mut builder =
+            AutoCommandBufferBuilder::primary_one_time_submit(device.clone(), queue.family())
+                .unwrap();
+        for (level, data) in self.levels.iter().enumerate() {
+            let size = (self.size >> level).max(1);
+            let buffer = CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::transfer_source(),
+                false,
+                data.iter().cloned(),
+            )
+            .unwrap();
+            builder = builder
+                .copy_buffer_to_image_dimensions(
+                    buffer,
+                    initialization.clone(),
+                    [0, 0, 0],
+                    [size, size, 1],
+                    0,
+                    6,
+                    level as u32,
+                )
+                .unwrap();
+        }
+        let future = builder.build().unwrap().execute(queue).unwrap();
+        (image, Box::new(future))
+    }
+}
+
+/// A texel of the filter source: its direction and the solid angle it covers.
+struct CubeTexel {
+    face: usize,
+    index: usize,
+    direction: Vec3,
+    solid_angle: f32,
+}
+
+fn cube_texels(size: u32) -> Vec<CubeTexel> {
+    let mut texels = Vec::new();
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let (s, t) = face_coordinates(x, y, size);
+                let texel_area = (2.0 / size as f32) * (2.0 / size as f32);
+                texels.push(CubeTexel {
+                    face: face as usize,
+                    index: (y * size + x) as usize,
+                    direction: texel_direction(face, x, y, size),
+                    solid_angle: texel_area / (1.0 + s * s + t * t).powf(1.5),
+                });
+            }
+        }
+    }
+    texels
+}
+
+/// Averages the environment around `normal`, weighted by a lobe that widens with roughness:
+/// cos^n with n picked to roughly match GGX, down to plain cosine weighting at roughness 1.
+fn filter_lobe(normal: &Vec3, roughness: f32, source: &[Vec<Vec3>], texels: &[CubeTexel]) -> Vec3 {
+    let alpha = (roughness * roughness).max(0.01);
+    let exponent = (2.0 / (alpha * alpha) - 2.0).max(1.0);
+
+    let mut sum = vec3(0.0, 0.0, 0.0);
+    let mut total_weight = 0.0;
+    for texel in texels {
+        let cos = dot(normal, &texel.direction);
+        if cos <= 0.0 {
+            continue;
+        }
+        let weight = cos.powf(exponent) * texel.solid_angle;
+        sum += source[texel.face][texel.index] * weight;
+        total_weight += weight;
+    }
+    if total_weight > 0.0 {
+        sum / total_weight
+    } else {
+        sum
+    }
+}
+
+/// Texel center in the face's -1 to 1 coordinates.
+fn face_coordinates(x: u32, y: u32, size: u32) -> (f32, f32) {
+    (
+        (x as f32 + 0.5) / size as f32 * 2.0 - 1.0,
+        (y as f32 + 0.5) / size as f32 * 2.0 - 1.0,
+    )
+}
+
+/// The direction a cube map texel is seen in, following the face layout in the Vulkan spec.
+fn texel_direction(face: u32, x: u32, y: u32, size: u32) -> Vec3 {
+    let (s, t) = face_coordinates(x, y, size);
+    let direction = match face {
+        0 => vec3(1.0, -t, -s),
+        1 => vec3(-1.0, -t, s),
+        2 => vec3(s, 1.0, t),
+        3 => vec3(s, -1.0, -t),
+        4 => vec3(s, -t, 1.0),
+        _ => vec3(-s, -t, -1.0),
+    };
+    normalize(&direction)
+}
+
+/// Scales a face down to `size` by averaging the texels each output texel covers, in linear space.
+fn resample_face(face: &Image, size: u32) -> Vec<Vec3> {
+    assert_eq!(face.width, face.height, "Cube map faces must be square");
+
+    let mut texels = Vec::with_capacity((size * size) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let range = |i: u32| {
+                let start = i * face.width / size;
+                let end = ((i + 1) * face.width / size).max(start + 1);
+                start..end
+            };
+
+            let mut sum = vec3(0.0, 0.0, 0.0);
+            let mut count = 0.0;
+            for source_y in range(y) {
+                for source_x in range(x) {
+                    let i = ((source_y * face.width + source_x) * 4) as usize;
+                    sum += vec3(
+                        srgb_to_linear(face.data[i]),
+                        srgb_to_linear(face.data[i + 1]),
+                        srgb_to_linear(face.data[i + 2]),
+                    );
+                    count += 1.0;
+                }
+            }
+            texels.push(sum / count);
+        }
+    }
+    texels
+}
+
+fn encode_faces(faces: &[Vec<Vec3>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for face in faces {
+        for texel in face {
+            data.extend_from_slice(&[
+                linear_to_srgb(texel.x),
+                linear_to_srgb(texel.y),
+                linear_to_srgb(texel.z),
+                255,
+            ]);
+        }
+    }
+    data
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.max(0.0).min(1.0);
+    let srgb = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
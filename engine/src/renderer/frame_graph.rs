@@ -0,0 +1,108 @@
+use super::graph::{ImageDesc, ImageSize, Load, PassDecl, PassId, RenderGraphBuilder, ResourceId};
+use super::shadow::{SHADOW_ATLAS_SIZE, SHADOW_FORMAT};
+use super::target::{DEPTH_FORMAT, HDR_FORMAT};
+
+/// The passes a frame is drawn with, and the images passed between them: shadows, the scene,
+/// three bloom passes and the final post-processing pass, which text is drawn in too.
+#[derive(Clone, Copy, Debug)]
+pub struct FramePasses {
+    pub shadows: PassId,
+    pub scene: PassId,
+    pub bloom_threshold: PassId,
+    pub bloom_blur_horizontal: PassId,
+    pub bloom_blur_vertical: PassId,
+    pub post: PassId,
+    pub shadow_atlas: ResourceId,
+    /// The finished scene, resolved if multisampled.
+    pub scene_color: ResourceId,
+    /// At half resolution. The blurred bloom ends up in the first.
+    pub bloom: [ResourceId; 2],
+}
+
+impl FramePasses {
+    pub fn declare(graph: &mut RenderGraphBuilder, msaa_samples: u32) -> FramePasses {
+        let shadow_atlas = graph.image(
+            "shadow atlas",
+            ImageDesc::new(
+                SHADOW_FORMAT,
+                ImageSize::Fixed([SHADOW_ATLAS_SIZE, SHADOW_ATLAS_SIZE]),
+            ),
+        );
+        let scene_color = graph.image("scene", ImageDesc::new(HDR_FORMAT, ImageSize::Target));
+        let depth = graph.image(
+            "depth",
+            ImageDesc::new(DEPTH_FORMAT, ImageSize::Target).samples(msaa_samples),
+        );
+        let bloom = [
+            graph.image("bloom", ImageDesc::new(HDR_FORMAT, ImageSize::Scaled(0.5))),
+            graph.image(
+                "half blurred bloom",
+                ImageDesc::new(HDR_FORMAT, ImageSize::Scaled(0.5)),
+            ),
+        ];
+
+        // The atlas is cleared even without shadow casting lights so it can always be sampled
+        let shadows =
+            graph.add_pass(PassDecl::new("shadows").depth(shadow_atlas, Load::Clear(1f32.into())));
+
+        let background = Load::Clear([0.0, 0.0, 1.0, 1.0].into());
+        let scene = if msaa_samples > 1 {
+            let multisampled = graph.image(
+                "multisampled scene",
+                ImageDesc::new(HDR_FORMAT, ImageSize::Target).samples(msaa_samples),
+            );
+            PassDecl::new("scene")
+                .color(multisampled, background)
+                .resolve(scene_color)
+        } else {
+            PassDecl::new("scene").color(scene_color, background)
+        };
+        let scene = graph.add_pass(
+            scene
+                .depth(depth, Load::Clear(1f32.into()))
+                .read(shadow_atlas),
+        );
+
+        let bloom_threshold = graph.add_pass(
+            PassDecl::new("bloom threshold")
+                .read(scene_color)
+                .color(bloom[0], Load::DontCare),
+        );
+        let bloom_blur_horizontal = graph.add_pass(
+            PassDecl::new("horizontal bloom blur")
+                .read(bloom[0])
+                .color(bloom[1], Load::DontCare),
+        );
+        let bloom_blur_vertical = graph.add_pass(
+            PassDecl::new("vertical bloom blur")
+                .read(bloom[1])
+                .color(bloom[0], Load::DontCare),
+        );
+
+        let target = graph.target();
+        let post = graph.add_pass(
+            PassDecl::new("post")
+                .read(scene_color)
+                .read(bloom[0])
+                .color(target, Load::DontCare),
+        );
+
+        FramePasses {
+            shadows: shadows,
+            scene: scene,
+            bloom_threshold: bloom_threshold,
+            bloom_blur_horizontal: bloom_blur_horizontal,
+            bloom_blur_vertical: bloom_blur_vertical,
+            post: post,
+            shadow_atlas: shadow_atlas,
+            scene_color: scene_color,
+            bloom: bloom,
+        }
+    }
+
+    pub fn is_bloom(&self, pass: PassId) -> bool {
+        pass == self.bloom_threshold
+            || pass == self.bloom_blur_horizontal
+            || pass == self.bloom_blur_vertical
+    }
+}
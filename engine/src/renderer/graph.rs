@@ -0,0 +1,1020 @@
+//! A render graph: passes declare the images they draw into and sample, and the graph works out
+//! the rest.
+//!
+//! Building a graph orders the passes so every image is written before it is read, gives each
+//! image a Vulkan image, sharing one between images of the same kind that are never needed at the
+//! same time, and makes a render pass per pass whose attachments start and end in the layouts
+//! their neighbouring passes need, so no extra transitions are recorded between passes. Images
+//! sized after the target are recreated with the framebuffers on `resize`; render passes, and so
+//! the pipelines made for them, stay.
+//!
+//! The target itself is imported rather than allocated: it is the swapchain image being drawn, so
+//! passes drawing into it get a framebuffer per swapchain image.
+
+use std::fmt;
+use std::sync::Arc;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::format::{ClearValue, Format, FormatTy};
+use vulkano::framebuffer::{
+    AttachmentDescription, Framebuffer, FramebufferAbstract, LoadOp, PassDependencyDescription,
+    PassDescription, RenderPass, RenderPassAbstract, RenderPassCreationError, RenderPassDesc,
+    RenderPassDescClearValues, StoreOp,
+};
+use vulkano::image::attachment::AttachmentImage;
+use vulkano::image::{ImageLayout, ImageUsage, ImageViewAccess};
+
+/// Most attachments a pass can have.
+pub const MAX_ATTACHMENTS: usize = 4;
+
+/// An image of a graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImageSize {
+    /// The target's size.
+    Target,
+    /// The target's size times a factor, at least one pixel.
+    Scaled(f32),
+    Fixed([u32; 2]),
+}
+
+impl ImageSize {
+    fn dimensions(&self, target: [u32; 2]) -> [u32; 2] {
+        match *self {
+            ImageSize::Target => target,
+            ImageSize::Scaled(factor) => [
+                ((target[0] as f32 * factor) as u32).max(1),
+                ((target[1] as f32 * factor) as u32).max(1),
+            ],
+            ImageSize::Fixed(size) => size,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageDesc {
+    pub format: Format,
+    pub size: ImageSize,
+    pub samples: u32,
+}
+
+impl ImageDesc {
+    pub fn new(format: Format, size: ImageSize) -> ImageDesc {
+        ImageDesc {
+            format: format,
+            size: size,
+            samples: 1,
+        }
+    }
+
+    pub fn samples(mut self, samples: u32) -> ImageDesc {
+        self.samples = samples;
+        self
+    }
+}
+
+/// What a pass does with an attachment's previous contents.
+#[derive(Clone, Copy, Debug)]
+pub enum Load {
+    Clear(ClearValue),
+    /// Keep what earlier passes drew.
+    Load,
+    /// The pass overwrites every pixel, so the previous contents don't matter.
+    DontCare,
+}
+
+/// A pass's use of an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Access {
+    Color,
+    Depth,
+    Resolve,
+    Sampled,
+}
+
+/// One pass of a graph, with the images it uses. Every pass has to draw into at least one
+/// attachment.
+#[derive(Clone, Debug)]
+pub struct PassDecl {
+    name: String,
+    colors: Vec<(ResourceId, Load)>,
+    depth: Option<(ResourceId, Load)>,
+    resolves: Vec<ResourceId>,
+    reads: Vec<ResourceId>,
+}
+
+impl PassDecl {
+    pub fn new(name: &str) -> PassDecl {
+        PassDecl {
+            name: name.to_string(),
+            colors: Vec::new(),
+            depth: None,
+            resolves: Vec::new(),
+            reads: Vec::new(),
+        }
+    }
+
+    pub fn color(mut self, image: ResourceId, load: Load) -> PassDecl {
+        self.colors.push((image, load));
+        self
+    }
+
+    pub fn depth(mut self, image: ResourceId, load: Load) -> PassDecl {
+        self.depth = Some((image, load));
+        self
+    }
+
+    /// Resolves the multisampled color attachment added before it into `image`.
+    pub fn resolve(mut self, image: ResourceId) -> PassDecl {
+        self.resolves.push(image);
+        self
+    }
+
+    /// Samples `image` in the pass's shaders.
+    pub fn read(mut self, image: ResourceId) -> PassDecl {
+        self.reads.push(image);
+        self
+    }
+
+    /// The pass's images in attachment order, then the ones it samples.
+    fn accesses(&self) -> Vec<(ResourceId, Access, Option<Load>)> {
+        let mut accesses: Vec<_> = self
+            .colors
+            .iter()
+            .map(|&(id, load)| (id, Access::Color, Some(load)))
+            .collect();
+        accesses.extend(
+            self.depth
+                .iter()
+                .map(|&(id, load)| (id, Access::Depth, Some(load))),
+        );
+        accesses.extend(self.resolves.iter().map(|&id| (id, Access::Resolve, None)));
+        accesses.extend(self.reads.iter().map(|&id| (id, Access::Sampled, None)));
+        accesses
+    }
+
+    fn writes(&self, resource: ResourceId) -> bool {
+        self.accesses()
+            .iter()
+            .any(|&(id, access, _)| id == resource && access != Access::Sampled)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ResourceDecl {
+    name: String,
+    /// `None` for the target.
+    desc: Option<ImageDesc>,
+}
+
+#[derive(Debug)]
+pub enum GraphError {
+    /// The named passes depend on each other in a circle.
+    Cycle(Vec<String>),
+    /// The named pass has no attachments to draw into.
+    NoAttachments(String),
+    /// The named pass has more than `MAX_ATTACHMENTS` attachments.
+    TooManyAttachments(String),
+    RenderPass(String, RenderPassCreationError),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GraphError::Cycle(ref passes) => {
+                write!(f, "Passes depend on each other: {}", passes.join(", "))
+            }
+            GraphError::NoAttachments(ref pass) => write!(f, "Pass '{}' draws into nothing", pass),
+            GraphError::TooManyAttachments(ref pass) => write!(
+                f,
+                "Pass '{}' has more than {} attachments",
+                pass, MAX_ATTACHMENTS
+            ),
+            GraphError::RenderPass(ref pass, ref e) => {
+                write!(f, "Could not create the render pass of '{}': {}", pass, e)
+            }
+        }
+    }
+}
+
+/// Collects the images and passes of a graph, in any order.
+pub struct RenderGraphBuilder {
+    resources: Vec<ResourceDecl>,
+    passes: Vec<PassDecl>,
+    target: ResourceId,
+}
+
+impl RenderGraphBuilder {
+    pub fn new() -> RenderGraphBuilder {
+        RenderGraphBuilder {
+            resources: vec![ResourceDecl {
+                name: "target".to_string(),
+                desc: None,
+            }],
+            passes: Vec::new(),
+            target: ResourceId(0),
+        }
+    }
+
+    /// The image presented at the end of the frame.
+    pub fn target(&self) -> ResourceId {
+        self.target
+    }
+
+    pub fn image(&mut self, name: &str, desc: ImageDesc) -> ResourceId {
+        self.resources.push(ResourceDecl {
+            name: name.to_string(),
+            desc: Some(desc),
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, pass: PassDecl) -> PassId {
+        self.passes.push(pass);
+        PassId(self.passes.len() - 1)
+    }
+
+    /// Creates the render passes, and the images and framebuffers for a target of `dimensions`
+    /// with `target_images`, in `target_format`.
+    pub fn build(
+        self,
+        device: Arc<Device>,
+        target_format: Format,
+        dimensions: [u32; 2],
+        target_images: Vec<Arc<dyn ImageViewAccess + Send + Sync>>,
+    ) -> Result<RenderGraph, GraphError> {
+        let order = self.order()?;
+        let physical = self.alias(&order);
+
+        let mut render_passes = vec![None; self.passes.len()];
+        for (position, &pass) in order.iter().enumerate() {
+            let desc = self.pass_desc(&order, position, target_format);
+            let render_pass = RenderPass::new(device.clone(), desc)
+                .map_err(|e| GraphError::RenderPass(self.passes[pass.0].name.clone(), e))?;
+            render_passes[pass.0] =
+                Some(Arc::new(render_pass) as Arc<dyn RenderPassAbstract + Send + Sync>);
+        }
+
+        let mut graph = RenderGraph {
+            device: device,
+            resources: self.resources,
+            passes: self.passes,
+            render_passes: render_passes,
+            order: order,
+            physical: physical,
+            images: Vec::new(),
+            framebuffers: Vec::new(),
+        };
+        graph.resize(dimensions, target_images);
+        Ok(graph)
+    }
+
+    /// The passes in the order they run: a pass reading an image comes after the pass writing
+    /// it, which is the last one added before the reader that writes it, or any writer added
+    /// after when there is none. Passes writing an image again wait for the passes reading its
+    /// previous contents. Otherwise passes keep the order they were added in, and passes whose
+    /// images never reach the target are left out.
+    fn order(&self) -> Result<Vec<PassId>, GraphError> {
+        let count = self.passes.len();
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); count];
+        for (i, pass) in self.passes.iter().enumerate() {
+            if pass.colors.is_empty() && pass.depth.is_none() {
+                return Err(GraphError::NoAttachments(pass.name.clone()));
+            }
+            if pass.colors.len() + pass.depth.iter().count() + pass.resolves.len() > MAX_ATTACHMENTS
+            {
+                return Err(GraphError::TooManyAttachments(pass.name.clone()));
+            }
+            for (id, access, _) in pass.accesses() {
+                dependencies[i].extend(self.dependencies_of(i, id, access));
+            }
+        }
+
+        // Passes leading to the target, found by walking dependencies back from its writers
+        let mut needed = vec![false; count];
+        let mut stack: Vec<usize> = (0..count)
+            .filter(|&i| self.passes[i].writes(self.target))
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !needed[i] {
+                needed[i] = true;
+                stack.extend_from_slice(&dependencies[i]);
+            }
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let mut done: Vec<bool> = needed.iter().map(|&needed| !needed).collect();
+        while done.iter().any(|&done| !done) {
+            let next = (0..count)
+                .find(|&i| !done[i] && dependencies[i].iter().all(|&j| done[j]))
+                .ok_or_else(|| {
+                    GraphError::Cycle(
+                        (0..count)
+                            .filter(|&i| !done[i])
+                            .map(|i| self.passes[i].name.clone())
+                            .collect(),
+                    )
+                })?;
+            done[next] = true;
+            order.push(PassId(next));
+        }
+        Ok(order)
+    }
+
+    /// The passes pass `i` has to run after because of its `access` to `resource`.
+    fn dependencies_of(&self, i: usize, resource: ResourceId, access: Access) -> Vec<usize> {
+        let writers: Vec<usize> = (0..self.passes.len())
+            .filter(|&j| j != i && self.passes[j].writes(resource))
+            .collect();
+        let earlier_writer = writers.iter().cloned().filter(|&j| j < i).last();
+        match (access, earlier_writer) {
+            (Access::Sampled, Some(writer)) => vec![writer],
+            (Access::Sampled, None) => writers,
+            (_, _) => {
+                // Earlier writes, and the reads of what they wrote
+                let mut dependencies: Vec<usize> =
+                    writers.iter().cloned().filter(|&j| j < i).collect();
+                dependencies.extend((0..i).filter(|&j| {
+                    self.passes[j].reads.contains(&resource) && writers.iter().any(|&w| w < j)
+                }));
+                dependencies
+            }
+        }
+    }
+
+    /// Which physical image each resource uses. Images with the same description and usage share
+    /// one when the passes using them don't overlap in `order`.
+    fn alias(&self, order: &[PassId]) -> Vec<Option<usize>> {
+        let mut physical: Vec<Option<usize>> = vec![None; self.resources.len()];
+        // Per physical image: its description, whether it is sampled, and the last pass using it
+        let mut slots: Vec<(ImageDesc, bool, usize)> = Vec::new();
+
+        for (position, &pass) in order.iter().enumerate() {
+            for (id, _, _) in self.passes[pass.0].accesses() {
+                let desc = match self.resources[id.0].desc {
+                    Some(desc) => desc,
+                    None => continue,
+                };
+                let last_use = self.last_use(order, id);
+                if let Some(slot) = physical[id.0] {
+                    slots[slot].2 = slots[slot].2.max(last_use);
+                    continue;
+                }
+                let sampled = self.is_sampled(id);
+                let free = slots
+                    .iter()
+                    .position(|&(slot_desc, slot_sampled, slot_last)| {
+                        slot_desc == desc && slot_sampled == sampled && slot_last < position
+                    });
+                physical[id.0] = Some(match free {
+                    Some(slot) => {
+                        slots[slot].2 = last_use;
+                        slot
+                    }
+                    None => {
+                        slots.push((desc, sampled, last_use));
+                        slots.len() - 1
+                    }
+                });
+            }
+        }
+        physical
+    }
+
+    fn is_sampled(&self, resource: ResourceId) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.reads.contains(&resource))
+    }
+
+    /// Position in `order` of the last pass using `resource`.
+    fn last_use(&self, order: &[PassId], resource: ResourceId) -> usize {
+        order
+            .iter()
+            .rposition(|pass| {
+                self.passes[pass.0]
+                    .accesses()
+                    .iter()
+                    .any(|&(id, _, _)| id == resource)
+            })
+            .unwrap()
+    }
+
+    /// The use of `resource` by the first pass after `position` that uses it.
+    fn next_access(
+        &self,
+        order: &[PassId],
+        position: usize,
+        resource: ResourceId,
+    ) -> Option<Access> {
+        order[position + 1..]
+            .iter()
+            .filter_map(|pass| {
+                let accesses = self.passes[pass.0].accesses();
+                accesses
+                    .iter()
+                    .find(|&&(id, _, _)| id == resource)
+                    .map(|&(_, access, _)| access)
+            })
+            .next()
+    }
+
+    /// The layout `resource` was left in by the last pass before `position` that used it.
+    fn previous_layout(
+        &self,
+        order: &[PassId],
+        position: usize,
+        resource: ResourceId,
+    ) -> ImageLayout {
+        for (i, pass) in order[..position].iter().enumerate().rev() {
+            let accesses = self.passes[pass.0].accesses();
+            if let Some(&(_, access, _)) = accesses.iter().find(|&&(id, _, _)| id == resource) {
+                if access == Access::Sampled {
+                    return ImageLayout::ShaderReadOnlyOptimal;
+                }
+                return self.final_layout(order, i, resource, access);
+            }
+        }
+        ImageLayout::Undefined
+    }
+
+    /// Attachments are left ready to be sampled when the next pass using them samples them.
+    fn final_layout(
+        &self,
+        order: &[PassId],
+        position: usize,
+        resource: ResourceId,
+        access: Access,
+    ) -> ImageLayout {
+        match self.next_access(order, position, resource) {
+            Some(Access::Sampled) => ImageLayout::ShaderReadOnlyOptimal,
+            _ => attachment_layout(access),
+        }
+    }
+
+    fn pass_desc(&self, order: &[PassId], position: usize, target_format: Format) -> GraphPassDesc {
+        let pass = &self.passes[order[position].0];
+        let mut attachments = Vec::new();
+        let mut subpass = PassDescription {
+            color_attachments: Vec::new(),
+            depth_stencil: None,
+            input_attachments: Vec::new(),
+            resolve_attachments: Vec::new(),
+            preserve_attachments: Vec::new(),
+        };
+
+        for (id, access, load) in pass.accesses() {
+            if access == Access::Sampled {
+                continue;
+            }
+            let (format, samples) = match self.resources[id.0].desc {
+                Some(desc) => (desc.format, desc.samples),
+                None => (target_format, 1),
+            };
+            let load = match load {
+                Some(Load::Clear(_)) => LoadOp::Clear,
+                Some(Load::Load) => LoadOp::Load,
+                Some(Load::DontCare) | None => LoadOp::DontCare,
+            };
+            // Later passes, or presenting for the target, need what is drawn here
+            let is_target = self.resources[id.0].desc.is_none();
+            let store = if is_target || self.next_access(order, position, id).is_some() {
+                StoreOp::Store
+            } else {
+                StoreOp::DontCare
+            };
+            // Previous contents that aren't loaded can start out undefined, which skips the
+            // transition from wherever they were
+            let initial_layout = if load == LoadOp::Load {
+                self.previous_layout(order, position, id)
+            } else {
+                ImageLayout::Undefined
+            };
+            let layout = attachment_layout(access);
+            let index = attachments.len();
+            attachments.push(AttachmentDescription {
+                format: format,
+                samples: samples,
+                load: load,
+                store: store,
+                stencil_load: load,
+                stencil_store: store,
+                initial_layout: initial_layout,
+                final_layout: if is_target {
+                    layout
+                } else {
+                    self.final_layout(order, position, id, access)
+                },
+            });
+            match access {
+                Access::Color => subpass.color_attachments.push((index, layout)),
+                Access::Depth => subpass.depth_stencil = Some((index, layout)),
+                Access::Resolve => subpass.resolve_attachments.push((index, layout)),
+                Access::Sampled => unreachable!(),
+            }
+        }
+        GraphPassDesc {
+            attachments: attachments,
+            subpass: subpass,
+        }
+    }
+}
+
+fn attachment_layout(access: Access) -> ImageLayout {
+    match access {
+        Access::Depth => ImageLayout::DepthStencilAttachmentOptimal,
+        _ => ImageLayout::ColorAttachmentOptimal,
+    }
+}
+
+/// The single subpass render pass of a graph pass.
+struct GraphPassDesc {
+    attachments: Vec<AttachmentDescription>,
+    subpass: PassDescription,
+}
+
+unsafe impl RenderPassDesc for GraphPassDesc {
+    fn num_attachments(&self) -> usize {
+        self.attachments.len()
+    }
+
+    fn attachment_desc(&self, num: usize) -> Option<AttachmentDescription> {
+        self.attachments.get(num).cloned()
+    }
+
+    fn num_subpasses(&self) -> usize {
+        1
+    }
+
+    fn subpass_desc(&self, num: usize) -> Option<PassDescription> {
+        if num == 0 {
+            Some(self.subpass.clone())
+        } else {
+            None
+        }
+    }
+
+    fn num_dependencies(&self) -> usize {
+        0
+    }
+
+    fn dependency_desc(&self, _: usize) -> Option<PassDependencyDescription> {
+        None
+    }
+}
+
+unsafe impl RenderPassDescClearValues<Vec<ClearValue>> for GraphPassDesc {
+    fn convert_clear_values(
+        &self,
+        values: Vec<ClearValue>,
+    ) -> Box<dyn Iterator<Item = ClearValue>> {
+        Box::new(values.into_iter())
+    }
+}
+
+/// A built graph. Record a frame by going through `order`, calling `begin_pass` before drawing
+/// each pass and ending its render pass after.
+pub struct RenderGraph {
+    device: Arc<Device>,
+    resources: Vec<ResourceDecl>,
+    passes: Vec<PassDecl>,
+    /// By pass; `None` for passes left out of `order`.
+    render_passes: Vec<Option<Arc<dyn RenderPassAbstract + Send + Sync>>>,
+    order: Vec<PassId>,
+    /// By resource, the index of its image in `images`; `None` for the target.
+    physical: Vec<Option<usize>>,
+    images: Vec<Arc<AttachmentImage>>,
+    /// By pass; one per target image for passes drawing into the target.
+    framebuffers: Vec<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>>,
+}
+
+impl RenderGraph {
+    pub fn order(&self) -> &[PassId] {
+        &self.order
+    }
+
+    /// For creating the pass's pipelines. It never changes.
+    pub fn render_pass(&self, pass: PassId) -> Arc<dyn RenderPassAbstract + Send + Sync> {
+        match self.render_passes[pass.0] {
+            Some(ref render_pass) => render_pass.clone(),
+            None => panic!(
+                "Pass '{}' never runs, nothing uses what it draws",
+                self.passes[pass.0].name
+            ),
+        }
+    }
+
+    /// The image behind `resource`, for sampling it. Panics for the target, and images no pass
+    /// that runs uses.
+    pub fn image(&self, resource: ResourceId) -> Arc<AttachmentImage> {
+        match self.physical[resource.0] {
+            Some(index) => self.images[index].clone(),
+            None => panic!(
+                "'{}' has no image in the graph",
+                self.resources[resource.0].name
+            ),
+        }
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &str {
+        &self.passes[pass.0].name
+    }
+
+    /// How many images the graph allocated, fewer than its resources when some share one.
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Recreates the images sized after the target, and every framebuffer, for a target of
+    /// `dimensions` with `target_images`.
+    pub fn resize(
+        &mut self,
+        dimensions: [u32; 2],
+        target_images: Vec<Arc<dyn ImageViewAccess + Send + Sync>>,
+    ) {
+        let mut images: Vec<Option<Arc<AttachmentImage>>> = vec![None; self.physical_count()];
+        for (resource, physical) in self.resources.iter().zip(self.physical.iter()) {
+            let (desc, index) = match (resource.desc, *physical) {
+                (Some(desc), Some(index)) => (desc, index),
+                _ => continue,
+            };
+            if images[index].is_some() {
+                continue;
+            }
+            // Fixed size images keep their contents across resizes
+            let kept = match (desc.size, self.images.get(index)) {
+                (ImageSize::Fixed(_), Some(image)) => Some(image.clone()),
+                _ => None,
+            };
+            images[index] = Some(kept.unwrap_or_else(|| {
+                self.create_image(desc, dimensions, self.is_sampled_physical(index))
+            }));
+        }
+        self.images = images.into_iter().map(|image| image.unwrap()).collect();
+
+        self.framebuffers = (0..self.passes.len())
+            .map(|pass| match self.render_passes[pass] {
+                Some(_) => self.create_framebuffers(PassId(pass), &target_images),
+                None => Vec::new(),
+            })
+            .collect();
+    }
+
+    /// Begins `pass`'s render pass, with the framebuffer of target image `image_num` if it draws
+    /// into the target.
+    pub fn begin_pass(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pass: PassId,
+        image_num: usize,
+    ) -> AutoCommandBufferBuilder {
+        let framebuffers = &self.framebuffers[pass.0];
+        let framebuffer = framebuffers[image_num.min(framebuffers.len() - 1)].clone();
+        let clear_values = self.passes[pass.0]
+            .accesses()
+            .iter()
+            .filter(|&&(_, access, _)| access != Access::Sampled)
+            .map(|&(_, _, load)| match load {
+                Some(Load::Clear(value)) => value,
+                _ => ClearValue::None,
+            })
+            .collect();
+        builder
+            .begin_render_pass(framebuffer, false, clear_values)
+            .unwrap()
+    }
+
+    fn physical_count(&self) -> usize {
+        self.physical
+            .iter()
+            .filter_map(|&index| index)
+            .max()
+            .map_or(0, |max| max + 1)
+    }
+
+    fn is_sampled_physical(&self, index: usize) -> bool {
+        self.physical
+            .iter()
+            .enumerate()
+            .filter(|&(_, &physical)| physical == Some(index))
+            .any(|(resource, _)| {
+                self.passes
+                    .iter()
+                    .any(|pass| pass.reads.contains(&ResourceId(resource)))
+            })
+    }
+
+    fn create_image(
+        &self,
+        desc: ImageDesc,
+        dimensions: [u32; 2],
+        sampled: bool,
+    ) -> Arc<AttachmentImage> {
+        let dimensions = desc.size.dimensions(dimensions);
+        // Images that are only drawn into within passes never need memory of their own on tiled
+        // GPUs
+        let usage = ImageUsage {
+            sampled: sampled,
+            transient_attachment: !sampled,
+            color_attachment: desc.format.ty() != FormatTy::Depth,
+            depth_stencil_attachment: desc.format.ty() == FormatTy::Depth,
+            ..ImageUsage::none()
+        };
+        if desc.samples > 1 {
+            AttachmentImage::multisampled_with_usage(
+                self.device.clone(),
+                dimensions,
+                desc.samples,
+                desc.format,
+                usage,
+            )
+            .unwrap()
+        } else {
+            AttachmentImage::with_usage(self.device.clone(), dimensions, desc.format, usage)
+                .unwrap()
+        }
+    }
+
+    fn create_framebuffers(
+        &self,
+        pass: PassId,
+        target_images: &[Arc<dyn ImageViewAccess + Send + Sync>],
+    ) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+        let attachments: Vec<ResourceId> = self.passes[pass.0]
+            .accesses()
+            .iter()
+            .filter(|&&(_, access, _)| access != Access::Sampled)
+            .map(|&(id, _, _)| id)
+            .collect();
+        let draws_target = attachments.iter().any(|&id| self.physical[id.0].is_none());
+        let framebuffer_count = if draws_target { target_images.len() } else { 1 };
+
+        (0..framebuffer_count)
+            .map(|i| {
+                let images = attachments
+                    .iter()
+                    .map(|&id| match self.physical[id.0] {
+                        Some(index) => {
+                            self.images[index].clone() as Arc<dyn ImageViewAccess + Send + Sync>
+                        }
+                        None => target_images[i].clone(),
+                    })
+                    .collect();
+                create_framebuffer(self.render_pass(pass), images)
+            })
+            .collect()
+    }
+}
+
+/// Framebuffers are typed by their number of attachments, so each count is built on its own.
+fn create_framebuffer(
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    images: Vec<Arc<dyn ImageViewAccess + Send + Sync>>,
+) -> Arc<dyn FramebufferAbstract + Send + Sync> {
+    let framebuffer = Framebuffer::start(render_pass);
+    match images.len() {
+        1 => Arc::new(framebuffer.add(images[0].clone()).unwrap().build().unwrap()),
+        2 => Arc::new(
+            framebuffer
+                .add(images[0].clone())
+                .unwrap()
+                .add(images[1].clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ),
+        3 => Arc::new(
+            framebuffer
+                .add(images[0].clone())
+                .unwrap()
+                .add(images[1].clone())
+                .unwrap()
+                .add(images[2].clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ),
+        4 => Arc::new(
+            framebuffer
+                .add(images[0].clone())
+                .unwrap()
+                .add(images[1].clone())
+                .unwrap()
+                .add(images[2].clone())
+                .unwrap()
+                .add(images[3].clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ),
+        count => unreachable!("{} attachments", count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color() -> ImageDesc {
+        ImageDesc::new(Format::R16G16B16A16Sfloat, ImageSize::Target)
+    }
+
+    fn clear() -> Load {
+        Load::Clear(ClearValue::Float([0.0, 0.0, 0.0, 1.0]))
+    }
+
+    fn names(graph: &RenderGraphBuilder, order: &[PassId]) -> Vec<String> {
+        order
+            .iter()
+            .map(|pass| graph.passes[pass.0].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn passes_run_after_the_passes_drawing_what_they_read() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let scene = graph.image("scene", color());
+        let bloom = graph.image("bloom", color());
+        graph.add_pass(
+            PassDecl::new("post")
+                .color(target, Load::DontCare)
+                .read(scene)
+                .read(bloom),
+        );
+        graph.add_pass(PassDecl::new("bloom").color(bloom, clear()).read(scene));
+        graph.add_pass(PassDecl::new("scene").color(scene, clear()));
+
+        let order = graph.order().unwrap();
+        assert_eq!(names(&graph, &order), ["scene", "bloom", "post"]);
+    }
+
+    #[test]
+    fn passes_not_reaching_the_target_are_left_out() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let scene = graph.image("scene", color());
+        let unused = graph.image("unused", color());
+        graph.add_pass(PassDecl::new("scene").color(scene, clear()));
+        graph.add_pass(PassDecl::new("unused").color(unused, clear()).read(scene));
+        graph.add_pass(PassDecl::new("post").color(target, clear()).read(scene));
+
+        let order = graph.order().unwrap();
+        assert_eq!(names(&graph, &order), ["scene", "post"]);
+    }
+
+    #[test]
+    fn rewriting_an_image_waits_for_readers_of_its_previous_contents() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let a = graph.image("a", color());
+        let b = graph.image("b", color());
+        graph.add_pass(
+            PassDecl::new("present")
+                .color(target, clear())
+                .read(a)
+                .read(b),
+        );
+        graph.add_pass(PassDecl::new("draw a").color(a, clear()));
+        graph.add_pass(PassDecl::new("copy a to b").color(b, clear()).read(a));
+        graph.add_pass(PassDecl::new("draw over a").color(a, Load::Load));
+
+        let order = graph.order().unwrap();
+        assert_eq!(
+            names(&graph, &order),
+            ["draw a", "copy a to b", "draw over a", "present"]
+        );
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let a = graph.image("a", color());
+        let b = graph.image("b", color());
+        graph.add_pass(PassDecl::new("first").color(a, clear()).read(b));
+        graph.add_pass(PassDecl::new("second").color(b, clear()).read(a));
+        graph.add_pass(PassDecl::new("present").color(target, clear()).read(a));
+
+        match graph.order() {
+            Err(GraphError::Cycle(passes)) => {
+                assert!(passes.contains(&"first".to_string()));
+                assert!(passes.contains(&"second".to_string()));
+            }
+            other => panic!("Expected a cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn passes_need_one_to_four_attachments() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let a = graph.image("a", color());
+        graph.add_pass(PassDecl::new("nothing").read(a));
+        match graph.order() {
+            Err(GraphError::NoAttachments(pass)) => assert_eq!(pass, "nothing"),
+            other => panic!("Expected no attachments, got {:?}", other),
+        }
+
+        let mut graph = RenderGraphBuilder::new();
+        let images: Vec<ResourceId> = (0..MAX_ATTACHMENTS)
+            .map(|i| graph.image(&i.to_string(), color()))
+            .collect();
+        let mut pass = PassDecl::new("too many").color(target, clear());
+        for &image in &images {
+            pass = pass.color(image, clear());
+        }
+        graph.add_pass(pass);
+        match graph.order() {
+            Err(GraphError::TooManyAttachments(pass)) => assert_eq!(pass, "too many"),
+            other => panic!("Expected too many attachments, got {:?}", other),
+        }
+    }
+
+    /// A chain of passes each sampling what the one before drew.
+    fn chain(graph: &mut RenderGraphBuilder, descs: &[ImageDesc]) -> Vec<ResourceId> {
+        let target = graph.target();
+        let images: Vec<ResourceId> = descs
+            .iter()
+            .enumerate()
+            .map(|(i, &desc)| graph.image(&format!("image {}", i), desc))
+            .collect();
+        for (i, &image) in images.iter().enumerate() {
+            let mut pass = PassDecl::new(&format!("pass {}", i)).color(image, clear());
+            if i > 0 {
+                pass = pass.read(images[i - 1]);
+            }
+            graph.add_pass(pass);
+        }
+        graph.add_pass(
+            PassDecl::new("present")
+                .color(target, clear())
+                .read(*images.last().unwrap()),
+        );
+        images
+    }
+
+    #[test]
+    fn images_whose_lifetimes_dont_overlap_share_memory() {
+        let mut graph = RenderGraphBuilder::new();
+        let images = chain(&mut graph, &[color(), color(), color(), color()]);
+        let order = graph.order().unwrap();
+        let physical = graph.alias(&order);
+
+        // Each image lives from the pass drawing it to the next pass, which samples it
+        assert_eq!(physical[images[0].0], physical[images[2].0]);
+        assert_eq!(physical[images[1].0], physical[images[3].0]);
+        assert_ne!(physical[images[0].0], physical[images[1].0]);
+        assert_eq!(physical[graph.target().0], None);
+    }
+
+    #[test]
+    fn images_of_different_kinds_dont_share_memory() {
+        let mut graph = RenderGraphBuilder::new();
+        let half = ImageDesc::new(Format::R16G16B16A16Sfloat, ImageSize::Scaled(0.5));
+        let ldr = ImageDesc::new(Format::R8G8B8A8Unorm, ImageSize::Target);
+        let images = chain(&mut graph, &[color(), half, color(), ldr, half]);
+        let order = graph.order().unwrap();
+        let physical = graph.alias(&order);
+
+        assert_eq!(physical[images[0].0], physical[images[2].0]);
+        assert_eq!(physical[images[1].0], physical[images[4].0]);
+        let distinct = [images[0], images[1], images[3]];
+        for a in &distinct {
+            for b in &distinct {
+                if a != b {
+                    assert_ne!(physical[a.0], physical[b.0]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_and_attachment_only_images_dont_share_memory() {
+        let mut graph = RenderGraphBuilder::new();
+        let target = graph.target();
+        let depth_desc = ImageDesc::new(Format::D16Unorm, ImageSize::Target);
+        let clear_depth = Load::Clear(ClearValue::Depth(1.0));
+        let shadow = graph.image("shadow", depth_desc);
+        let lit = graph.image("lit", color());
+        let depth = graph.image("depth", depth_desc);
+        graph.add_pass(PassDecl::new("shadow").depth(shadow, clear_depth));
+        graph.add_pass(PassDecl::new("lighting").color(lit, clear()).read(shadow));
+        // Free to reuse the shadow map's memory by lifetime, but it is transient
+        graph.add_pass(
+            PassDecl::new("scene")
+                .color(target, clear())
+                .depth(depth, clear_depth)
+                .read(lit),
+        );
+        let order = graph.order().unwrap();
+        let physical = graph.alias(&order);
+
+        assert_ne!(physical[shadow.0], physical[depth.0]);
+    }
+}
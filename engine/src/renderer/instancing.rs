@@ -0,0 +1,99 @@
+//! Instanced drawing: draw commands that share a mesh and material become one draw call, with the
+//! transform and tint of each command in a per instance vertex buffer.
+
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::CpuBufferPoolChunk;
+use vulkano::buffer::BufferSlice;
+use vulkano::memory::pool::StdMemoryPool;
+
+use super::draw_list::DrawCommand;
+use super::resources::{GpuMaterial, MaterialHandle, MeshHandle};
+
+/// The per instance attributes every mesh shader reads, see default.vs.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InstanceData {
+    pub instance_model: [[f32; 4]; 4],
+    pub instance_tint: [f32; 4],
+}
+vulkano::impl_vertex!(InstanceData, instance_model, instance_tint);
+
+pub type InstanceBuffer = CpuBufferPoolChunk<InstanceData, Arc<StdMemoryPool>>;
+
+/// Instances `first_instance..first_instance + instance_count` of a frame, all drawing `mesh` with
+/// `material`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawBatch {
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub first_instance: usize,
+    pub instance_count: usize,
+}
+
+impl DrawBatch {
+    /// This batch's part of the frame's instance buffer.
+    pub fn instances(
+        &self,
+        buffer: &Arc<InstanceBuffer>,
+    ) -> BufferSlice<[InstanceData], Arc<InstanceBuffer>> {
+        BufferSlice::from_typed_buffer_access(buffer.clone())
+            .slice(self.first_instance..self.first_instance + self.instance_count)
+            .unwrap()
+    }
+}
+
+/// The draw commands of a frame, grouped into batches.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceBatches {
+    /// Every batch's instances, one batch after the other.
+    pub instances: Vec<InstanceData>,
+    /// Ordered by shader, then material, so consecutive batches share pipelines and descriptor sets.
+    pub batches: Vec<DrawBatch>,
+}
+
+impl InstanceBatches {
+    /// Groups commands with the same mesh and material into one batch.
+    pub fn new(commands: &[DrawCommand], materials: &[GpuMaterial]) -> InstanceBatches {
+        InstanceBatches::build(commands, materials, true)
+    }
+
+    /// One batch per command, for comparing against drawing without instancing.
+    pub fn unbatched(commands: &[DrawCommand], materials: &[GpuMaterial]) -> InstanceBatches {
+        InstanceBatches::build(commands, materials, false)
+    }
+
+    fn build(commands: &[DrawCommand], materials: &[GpuMaterial], merge: bool) -> InstanceBatches {
+        let mut sorted: Vec<&DrawCommand> = commands.iter().collect();
+        sorted.sort_by_key(|c| (materials[c.material.0].shader, c.material, c.mesh));
+
+        let mut result = InstanceBatches {
+            instances: Vec::with_capacity(sorted.len()),
+            batches: Vec::new(),
+        };
+        for command in sorted {
+            let index = result.instances.len();
+            result.instances.push(InstanceData {
+                instance_model: command.transform.into(),
+                instance_tint: command.tint.into(),
+            });
+
+            if let Some(batch) = result.batches.last_mut() {
+                if merge && batch.mesh == command.mesh && batch.material == command.material {
+                    batch.instance_count += 1;
+                    continue;
+                }
+            }
+            result.batches.push(DrawBatch {
+                mesh: command.mesh,
+                material: command.material,
+                first_instance: index,
+                instance_count: 1,
+            });
+        }
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+}
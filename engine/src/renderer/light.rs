@@ -0,0 +1,178 @@
+use drawing::Color3;
+use glm::{normalize, vec3, vec4, Mat4, Vec3};
+use specs::{Component, Join, VecStorage, World, WorldExt};
+
+use super::draw_list::DrawList;
+use super::shadow::ShadowSettings;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Point,
+    /// Full strength inside `inner_angle` of the direction, fading out towards `outer_angle`.
+    /// Both in radians.
+    Spot {
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    /// Lights everything from one direction, like the sun. Position and range are ignored.
+    Directional,
+}
+
+/// A dynamic light, as a component of the entity it lights the scene from.
+/// Position and direction are in world space.
+#[derive(Clone, Debug)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Color3,
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub range: f32,
+    pub position: Vec3,
+    pub direction: Vec3,
+    /// Only directional and spot lights cast shadows.
+    pub shadow: Option<ShadowSettings>,
+}
+
+impl Component for Light {
+    type Storage = VecStorage<Self>;
+}
+
+impl Light {
+    pub fn point(position: Vec3, color: Color3, intensity: f32, range: f32) -> Light {
+        Light {
+            kind: LightKind::Point,
+            color: color,
+            intensity: intensity,
+            range: range,
+            position: position,
+            direction: vec3(0.0, -1.0, 0.0),
+            shadow: None,
+        }
+    }
+
+    pub fn spot(
+        position: Vec3,
+        direction: Vec3,
+        color: Color3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Light {
+        Light {
+            kind: LightKind::Spot {
+                inner_angle: inner_angle,
+                outer_angle: outer_angle,
+            },
+            color: color,
+            intensity: intensity,
+            range: range,
+            position: position,
+            direction: normalize(&direction),
+            shadow: None,
+        }
+    }
+
+    pub fn directional(direction: Vec3, color: Color3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional,
+            color: color,
+            intensity: intensity,
+            range: 0.0,
+            position: vec3(0.0, 0.0, 0.0),
+            direction: normalize(&direction),
+            shadow: None,
+        }
+    }
+
+    pub fn with_shadows(mut self, settings: ShadowSettings) -> Light {
+        self.shadow = Some(settings);
+        self
+    }
+}
+
+/// Adds the light of every entity that has one to `draw_list`.
+pub fn gather_lights(world: &World, draw_list: &mut DrawList) {
+    for light in world.read_storage::<Light>().join() {
+        draw_list.add_light(light.clone());
+    }
+}
+
+/// `Light` as default.fs reads it from the light buffer, with std430 layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuLight {
+    position: [f32; 3],
+    range: f32,
+    direction: [f32; 3],
+    kind: u32,
+    color: [f32; 3],
+    intensity: f32,
+    cos_inner: f32,
+    cos_outer: f32,
+    shadow_index: i32,
+    cascade_count: i32,
+    depth_bias: f32,
+    slope_bias: f32,
+    /// std430 rounds the size of a struct up to its largest member, a vec3 aligned to 16 bytes.
+    _padding: [f32; 2],
+}
+
+impl GpuLight {
+    /// Moves the light into view space, where default.fs does its lighting.
+    pub fn new(light: &Light, view: &Mat4) -> GpuLight {
+        let position = view * vec4(light.position.x, light.position.y, light.position.z, 1.0);
+        let direction = view * vec4(light.direction.x, light.direction.y, light.direction.z, 0.0);
+        let direction = normalize(&vec3(direction.x, direction.y, direction.z));
+
+        let (kind, cos_inner, cos_outer) = match light.kind {
+            LightKind::Point => (0, 1.0, 1.0),
+            LightKind::Spot {
+                inner_angle,
+                outer_angle,
+            } => (1, inner_angle.cos(), outer_angle.cos()),
+            LightKind::Directional => (2, 1.0, 1.0),
+        };
+
+        GpuLight {
+            position: [position.x, position.y, position.z],
+            range: light.range,
+            direction: [direction.x, direction.y, direction.z],
+            kind: kind,
+            color: [light.color.x, light.color.y, light.color.z],
+            intensity: light.intensity,
+            cos_inner: cos_inner,
+            cos_outer: cos_outer,
+            shadow_index: -1,
+            cascade_count: 0,
+            depth_bias: 0.0,
+            slope_bias: 0.0,
+            _padding: [0.0; 2],
+        }
+    }
+
+    /// Points the light at its cascades in the shadow buffer.
+    pub fn set_shadow(
+        &mut self,
+        first_cascade: usize,
+        cascade_count: usize,
+        settings: &ShadowSettings,
+    ) {
+        self.shadow_index = first_cascade as i32;
+        self.cascade_count = cascade_count as i32;
+        self.depth_bias = settings.depth_bias;
+        self.slope_bias = settings.slope_bias;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn gpu_light_matches_the_std430_array_stride() {
+        assert_eq!(mem::size_of::<GpuLight>(), 80);
+        assert_eq!(mem::size_of::<GpuLight>() % 16, 0);
+    }
+}
@@ -0,0 +1,304 @@
+//! Material files, one `key = value` per line with `#` starting a comment:
+//!
+//! ```text
+//! shader = default
+//! diffuse = crate.png
+//! normal = crate_normal.png
+//! color = 1.0 0.9 0.9 1.0
+//! float smoothness = 32.0
+//! vec3 specularColor = 1.0 1.0 1.0
+//! ```
+//!
+//! Texture paths are relative to the material file; `metallic_roughness` names the texture of the
+//! same name for pbr.fs. Lines starting with a type (bool, int, float, vec2, vec3 or vec4) set a
+//! member of the shader's `MaterialParams` block.
+
+use drawing::Color4;
+use glm::{vec2, vec3, vec4};
+use shader::{ShaderError, UniformError, UniformValue};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Shader(ShaderError),
+    Param(UniformError),
+    /// The shader doesn't take the frame data, material bindings and instance inputs that
+    /// default.vs and default.fs do.
+    UnsupportedShader(String),
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MaterialError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            MaterialError::Parse {
+                ref path,
+                line,
+                ref message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            MaterialError::Shader(ref e) => write!(f, "{}", e),
+            MaterialError::Param(ref e) => write!(f, "{}", e),
+            MaterialError::UnsupportedShader(ref name) => write!(
+                f,
+                "shader '{}' does not take the bindings and inputs of default.vs and default.fs",
+                name
+            ),
+        }
+    }
+}
+
+impl From<ShaderError> for MaterialError {
+    fn from(e: ShaderError) -> MaterialError {
+        MaterialError::Shader(e)
+    }
+}
+
+impl From<UniformError> for MaterialError {
+    fn from(e: UniformError) -> MaterialError {
+        MaterialError::Param(e)
+    }
+}
+
+/// A material file as written, with texture paths rather than uploaded textures.
+/// Everything left out keeps the value from `Material::for_shader` for the named shader.
+#[derive(Clone, Debug, Default)]
+pub struct MaterialDesc {
+    pub shader: Option<String>,
+    pub diffuse: Option<PathBuf>,
+    pub normal: Option<PathBuf>,
+    pub emissive: Option<PathBuf>,
+    pub metallic_roughness: Option<PathBuf>,
+    pub color: Option<Color4>,
+    pub params: Vec<(String, UniformValue)>,
+}
+
+impl MaterialDesc {
+    pub fn load(path: &Path) -> Result<MaterialDesc, MaterialError> {
+        match fs::read_to_string(path) {
+            Ok(source) => MaterialDesc::parse(&source, path),
+            Err(e) => Err(MaterialError::Io(path.to_path_buf(), e)),
+        }
+    }
+
+    /// `path` is only used to resolve texture paths and to point at errors.
+    pub fn parse(source: &str, path: &Path) -> Result<MaterialDesc, MaterialError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut desc = MaterialDesc::default();
+
+        for (i, line) in source.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |message: String| MaterialError::Parse {
+                path: path.to_path_buf(),
+                line: i + 1,
+                message: message,
+            };
+
+            let (key, value) = match line.find('=') {
+                Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+                None => return Err(error(format!("expected `key = value`, got `{}`", line))),
+            };
+
+            let words: Vec<&str> = key.split_whitespace().collect();
+            match words.as_slice() {
+                ["shader"] => desc.shader = Some(value.to_string()),
+                ["diffuse"] => desc.diffuse = Some(dir.join(value)),
+                ["normal"] => desc.normal = Some(dir.join(value)),
+                ["emissive"] => desc.emissive = Some(dir.join(value)),
+                ["metallic_roughness"] => desc.metallic_roughness = Some(dir.join(value)),
+                ["color"] => {
+                    let c = parse_floats(value, 4).map_err(&error)?;
+                    desc.color = Some(vec4(c[0], c[1], c[2], c[3]));
+                }
+                [ty, name] => {
+                    let param = parse_param(ty, value).map_err(&error)?;
+                    desc.params.push((name.to_string(), param));
+                }
+                _ => return Err(error(format!("unknown key `{}`", key))),
+            }
+        }
+        Ok(desc)
+    }
+}
+
+fn parse_param(ty: &str, value: &str) -> Result<UniformValue, String> {
+    match ty {
+        "bool" => match value {
+            "true" => Ok(UniformValue::Bool(true)),
+            "false" => Ok(UniformValue::Bool(false)),
+            _ => Err(format!("expected true or false, got `{}`", value)),
+        },
+        "int" => value
+            .parse()
+            .map(UniformValue::Int)
+            .map_err(|_| format!("expected an integer, got `{}`", value)),
+        "float" => parse_floats(value, 1).map(|v| UniformValue::Float(v[0])),
+        "vec2" => parse_floats(value, 2).map(|v| UniformValue::Vec2(vec2(v[0], v[1]))),
+        "vec3" => parse_floats(value, 3).map(|v| UniformValue::Vec3(vec3(v[0], v[1], v[2]))),
+        "vec4" => parse_floats(value, 4).map(|v| UniformValue::Vec4(vec4(v[0], v[1], v[2], v[3]))),
+        _ => Err(format!("unknown parameter type `{}`", ty)),
+    }
+}
+
+fn parse_floats(value: &str, count: usize) -> Result<Vec<f32>, String> {
+    let floats: Result<Vec<f32>, _> = value.split_whitespace().map(|v| v.parse()).collect();
+    match floats {
+        Ok(ref floats) if floats.len() == count => Ok(floats.clone()),
+        _ => Err(format!("expected {} numbers, got `{}`", count, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader::{ScalarKind, UniformType};
+
+    fn parse(source: &str) -> Result<MaterialDesc, MaterialError> {
+        MaterialDesc::parse(source, Path::new("assets/materials/crate.mat"))
+    }
+
+    /// The line and message of a parse error.
+    fn parse_error(source: &str) -> (usize, String) {
+        match parse(source) {
+            Err(MaterialError::Parse {
+                ref path,
+                line,
+                ref message,
+            }) => {
+                assert_eq!(path, Path::new("assets/materials/crate.mat"));
+                (line, message.clone())
+            }
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(desc) => panic!("expected a parse error, got {:?}", desc),
+        }
+    }
+
+    #[test]
+    fn parses_every_parameter_type() {
+        let desc = parse(
+            "# A crate\n\
+             shader = pbr\n\
+             color = 1.0 0.5 0.25 1.0  # tinted\n\
+             bool useNormalMap = true\n\
+             bool twoSided=false\n\
+             int layers = -3\n\
+             float smoothness = 32.0\n\
+             vec2 uvScale = 2 4\n\
+             vec3 specularColor = 1.0 0.9 0.8\n\
+             vec4 rimColor = 0.1 0.2 0.3 0.4\n",
+        )
+        .unwrap();
+        assert_eq!(desc.shader, Some("pbr".to_string()));
+        assert_eq!(desc.color, Some(vec4(1.0, 0.5, 0.25, 1.0)));
+        assert_eq!(
+            desc.params,
+            vec![
+                ("useNormalMap".to_string(), UniformValue::Bool(true)),
+                ("twoSided".to_string(), UniformValue::Bool(false)),
+                ("layers".to_string(), UniformValue::Int(-3)),
+                ("smoothness".to_string(), UniformValue::Float(32.0)),
+                ("uvScale".to_string(), UniformValue::Vec2(vec2(2.0, 4.0))),
+                (
+                    "specularColor".to_string(),
+                    UniformValue::Vec3(vec3(1.0, 0.9, 0.8))
+                ),
+                (
+                    "rimColor".to_string(),
+                    UniformValue::Vec4(vec4(0.1, 0.2, 0.3, 0.4))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn texture_paths_are_relative_to_the_material() {
+        let desc = parse(
+            "diffuse = crate.png\n\
+             normal = textures/crate_normal.png\n\
+             emissive = ../glow.png\n\
+             metallic_roughness = crate_mr.png\n",
+        )
+        .unwrap();
+        let dir = Path::new("assets/materials");
+        assert_eq!(desc.diffuse, Some(dir.join("crate.png")));
+        assert_eq!(desc.normal, Some(dir.join("textures/crate_normal.png")));
+        assert_eq!(desc.emissive, Some(dir.join("../glow.png")));
+        assert_eq!(desc.metallic_roughness, Some(dir.join("crate_mr.png")));
+
+        let desc = MaterialDesc::parse("diffuse = crate.png", Path::new("crate.mat")).unwrap();
+        assert_eq!(desc.diffuse, Some(PathBuf::from("crate.png")));
+    }
+
+    #[test]
+    fn left_out_keys_stay_empty() {
+        let desc = parse("\n   # nothing here\n\n").unwrap();
+        assert_eq!(desc.shader, None);
+        assert_eq!(desc.diffuse, None);
+        assert_eq!(desc.color, None);
+        assert!(desc.params.is_empty());
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        assert_eq!(
+            parse_error("shader = default\n\ndiffuse crate.png\n"),
+            (
+                3,
+                "expected `key = value`, got `diffuse crate.png`".to_string()
+            )
+        );
+        assert_eq!(
+            parse_error("# comment\nshiny = yes"),
+            (2, "unknown key `shiny`".to_string())
+        );
+        assert_eq!(
+            parse_error("color = 1 1 1"),
+            (1, "expected 4 numbers, got `1 1 1`".to_string())
+        );
+        assert_eq!(
+            parse_error("shader = default\nbool twoSided = yes"),
+            (2, "expected true or false, got `yes`".to_string())
+        );
+        assert_eq!(
+            parse_error("int layers = 1.5"),
+            (1, "expected an integer, got `1.5`".to_string())
+        );
+        assert_eq!(
+            parse_error("vec3 specularColor = 1 one 1"),
+            (1, "expected 3 numbers, got `1 one 1`".to_string())
+        );
+        assert_eq!(
+            parse_error("\n\n\nmat4 model = 1"),
+            (4, "unknown parameter type `mat4`".to_string())
+        );
+    }
+
+    #[test]
+    fn param_errors_name_the_uniform() {
+        let error = MaterialError::from(UniformError::TypeMismatch {
+            name: "smoothness".to_string(),
+            declared: UniformType::Scalar(ScalarKind::Float),
+            given: UniformType::Vector(ScalarKind::Float, 3),
+        });
+        assert_eq!(
+            error.to_string(),
+            "uniform `smoothness` is a float in the shader, but was given a vec3"
+        );
+    }
+}
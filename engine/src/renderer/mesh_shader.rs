@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::pipeline::depth_stencil::*;
+use vulkano::pipeline::vertex::OneVertexOneInstanceDefinition;
+use vulkano::pipeline::GraphicsPipeline;
+
+use super::instancing::InstanceData;
+use super::material::MaterialError;
+use super::resources::Vertex;
+use shader::{DescriptorKind, ImageDimensions, ShaderError, ShaderProgram};
+
+/// Meshes are always drawn instanced, with `Vertex` per vertex and `InstanceData` per instance.
+pub type MeshPipeline = GraphicsPipeline<
+    OneVertexOneInstanceDefinition<Vertex, InstanceData>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+/// The optional bindings a material shader declares, on top of the ones every shader has.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialLayout {
+    /// Material textures in set 1 after the `MaterialParams` block: diffuse, normal and emissive,
+    /// optionally followed by metallic-roughness.
+    pub texture_count: u32,
+    /// Whether the prefiltered environment map is bound at set 0, binding 4.
+    pub environment: bool,
+}
+
+/// A shader materials can use, and the pipeline drawing meshes with it.
+pub struct MeshShader {
+    pub name: String,
+    pub layout: MaterialLayout,
+    pub program: ShaderProgram,
+    pub pipeline: Arc<MeshPipeline>,
+}
+
+impl MeshShader {
+    pub fn load(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        name: &str,
+    ) -> Result<MeshShader, MaterialError> {
+        let program = ShaderProgram::create_program(device.clone(), name)?;
+        let layout = match material_layout(&program) {
+            Some(layout) => layout,
+            None => return Err(MaterialError::UnsupportedShader(name.to_string())),
+        };
+        Ok(MeshShader {
+            name: name.to_string(),
+            layout: layout,
+            pipeline: create_mesh_pipeline(device, render_pass, &program)?,
+            program: program,
+        })
+    }
+
+    /// Recompiles the shader, keeping the current pipeline if that fails.
+    pub fn reload(
+        &mut self,
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> Result<(), ShaderError> {
+        let program = self.program.reload(device.clone(), &self.name)?;
+        self.pipeline = create_mesh_pipeline(device, render_pass, &program)?;
+        self.program = program;
+        Ok(())
+    }
+}
+
+/// Every material shader has, like default.vs and default.fs, the `FrameData` block, the light and
+/// shadow buffers and the shadow atlas in set 0, the `MaterialParams` block followed by the
+/// diffuse, normal and emissive textures in set 1, and the per instance `instance_model` and
+/// `instance_tint` inputs instead of push constants. `None` if the program doesn't.
+fn material_layout(program: &ShaderProgram) -> Option<MaterialLayout> {
+    let kind = |set: u32, binding: u32| {
+        program
+            .descriptors()
+            .iter()
+            .find(|d| d.set == set && d.binding == binding)
+            .map(|d| d.kind.clone())
+    };
+    let is_block = |set, binding| match kind(set, binding) {
+        Some(DescriptorKind::UniformBuffer(_)) => true,
+        _ => false,
+    };
+    let is_storage_buffer = |binding| match kind(0, binding) {
+        Some(DescriptorKind::StorageBuffer(_)) => true,
+        _ => false,
+    };
+    let is_texture = |set, binding| match kind(set, binding) {
+        Some(DescriptorKind::CombinedImageSampler(ImageDimensions::TwoDimensional)) => true,
+        _ => false,
+    };
+    let has_input = |name: &str, location_count: u32| {
+        program.vertex_inputs().iter().any(|input| {
+            input.name == name
+                && input.format == Format::R32G32B32A32Sfloat
+                && input.location_count == location_count
+        })
+    };
+
+    let has_required = is_block(0, 0)
+        && is_storage_buffer(1)
+        && is_storage_buffer(2)
+        && is_texture(0, 3)
+        && is_block(1, 0)
+        && (1..4).all(|binding| is_texture(1, binding))
+        && has_input("instance_model", 4)
+        && has_input("instance_tint", 1)
+        && program.push_constants().is_none();
+    if !has_required {
+        return None;
+    }
+
+    let layout = MaterialLayout {
+        texture_count: if is_texture(1, 4) { 4 } else { 3 },
+        environment: match kind(0, 4) {
+            Some(DescriptorKind::CombinedImageSampler(ImageDimensions::Cube)) => true,
+            _ => false,
+        },
+    };
+    let binding_count = 4 + layout.environment as usize + 1 + layout.texture_count as usize;
+    if program.descriptors().len() == binding_count {
+        Some(layout)
+    } else {
+        None
+    }
+}
+
+// The viewport is dynamic so the pipeline, and the descriptor sets made from its layout,
+// survive window resizes.
+fn create_mesh_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<MeshPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+        .vertex_shader(program.vertex_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .depth_stencil(DepthStencil {
+            depth_write: true,
+            depth_compare: Compare::LessOrEqual,
+            depth_bounds_test: DepthBounds::Disabled,
+            stencil_front: Default::default(),
+            stencil_back: Default::default(),
+        })
+        .fragment_shader(program.fragment_entry_point(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
@@ -0,0 +1,1146 @@
+pub mod bvh;
+pub mod capture;
+pub mod config;
+pub mod culling;
+pub mod debug_draw;
+pub mod debug_renderer;
+pub mod draw_list;
+pub mod environment;
+mod frame_graph;
+pub mod graph;
+pub mod instancing;
+pub mod light;
+pub mod material;
+mod mesh_shader;
+pub mod post;
+pub mod resources;
+pub mod shadow;
+pub mod stats;
+pub mod target;
+pub mod visibility;
+
+pub use self::bvh::*;
+pub use self::capture::*;
+pub use self::config::*;
+pub use self::culling::*;
+pub use self::debug_renderer::*;
+pub use self::draw_list::*;
+pub use self::environment::*;
+pub use self::graph::*;
+pub use self::instancing::*;
+pub use self::light::*;
+pub use self::material::*;
+pub use self::post::*;
+pub use self::resources::*;
+pub use self::shadow::*;
+pub use self::stats::*;
+pub use self::target::*;
+pub use self::visibility::*;
+
+use self::frame_graph::FramePasses;
+use self::mesh_shader::MeshShader;
+use gui::{GuiRenderer, GUI_SHADER};
+use hud::Hud;
+use image::{self, Image};
+use mesh::mesh::Mesh;
+use shader::{ShaderWatcher, UniformBlock, UniformValue};
+use text::{Font, TextRenderer, TEXT_SDF_SHADER, TEXT_SHADER};
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, DeviceCreationError, DeviceExtensions, Queue};
+use vulkano::format::Format;
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::instance::{Instance, InstanceCreationError, InstanceExtensions, PhysicalDevice};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::swapchain;
+use vulkano::swapchain::{
+    AcquireError, FullscreenExclusive, SurfaceTransform, Swapchain, SwapchainCreationError,
+};
+use vulkano::sync;
+use vulkano::sync::{FlushError, GpuFuture};
+use vulkano_win::VkSurfaceBuild;
+use winit::{event_loop::EventLoop, window::Window};
+
+/// Draw commands batched for instancing, with the buffer holding their instances. The buffer is
+/// `None` when there are no commands.
+struct BatchedCommands {
+    batches: Vec<DrawBatch>,
+    instance_buffer: Option<Arc<InstanceBuffer>>,
+}
+
+/// How many frames `render_to_pixels` renders before giving up on reading one back.
+const CAPTURE_ATTEMPTS: u32 = 3;
+
+/// Ambient color of scenes that never call `set_environment`: a dim grey, in sRGB.
+const DEFAULT_ENVIRONMENT: [u8; 4] = [90, 90, 90, 255];
+
+/// Owns the Vulkan device, the window's swapchain and every GPU resource registered with it.
+///
+/// Meshes, textures and materials are registered once and referred to by handle afterwards.
+/// Each frame the caller builds a `DrawList` and hands it to `render`.
+///
+/// A renderer made with `new_headless` draws into an offscreen image instead of a window, which
+/// `render_to_pixels` reads back. That works on software drivers like lavapipe, for machines without
+/// a GPU: point `VK_ICD_FILENAMES` at its ICD json to run the golden tests, e.g.
+/// `VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json cargo test -p engine --test golden -- --ignored`.
+pub struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    target: RenderTarget,
+    graph: RenderGraph,
+    passes: FramePasses,
+    msaa_samples: u32,
+    post: PostProcessor,
+    /// Shaders used by registered materials, loaded the first time a material names them.
+    shaders: Vec<MeshShader>,
+    /// Only windowed renderers watch for shader edits.
+    shader_watcher: Option<ShaderWatcher>,
+    sampler: Arc<Sampler>,
+    light_pool: CpuBufferPool<GpuLight>,
+    instance_pool: CpuBufferPool<InstanceData>,
+    /// Off only to compare against drawing every command on its own.
+    instancing: bool,
+    shadows: ShadowRenderer,
+    white_texture: GpuTexture,
+    flat_normal_texture: GpuTexture,
+    black_texture: GpuTexture,
+    /// Prefiltered cube map that shaders like pbr.fs light their ambient with.
+    environment: GpuTexture,
+    environment_sampler: Arc<Sampler>,
+
+    meshes: Vec<GpuMesh>,
+    textures: Vec<GpuTexture>,
+    materials: Vec<GpuMaterial>,
+
+    text: TextRenderer,
+    debug: DebugRenderer,
+    gui: GuiRenderer,
+
+    recreate_swapchain: bool,
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+    capture_next_frame: bool,
+    capture_buffer: Option<Arc<CpuAccessibleBuffer<[u8]>>>,
+    captured_frame: Option<Vec<u8>>,
+    pending_screenshot: Option<PathBuf>,
+    frame_sequence: Option<FrameSequence>,
+    stats: RenderStats,
+}
+
+#[derive(Debug)]
+pub enum HeadlessError {
+    Instance(InstanceCreationError),
+    NoDevice,
+    Device(DeviceCreationError),
+}
+
+impl Renderer {
+    /// Opens a window with the default `RendererConfig`.
+    pub fn new(event_loop: &EventLoop<()>) -> Renderer {
+        Renderer::with_config(event_loop, &RendererConfig::default())
+    }
+
+    pub fn with_config(event_loop: &EventLoop<()>, config: &RendererConfig) -> Renderer {
+        let required_extensions = vulkano_win::required_extensions();
+        let instance = Instance::new(None, &required_extensions, None).unwrap();
+        let physical = PhysicalDevice::enumerate(&instance).next().unwrap();
+        println!(
+            "Using device: {} (type: {:?})",
+            physical.name(),
+            physical.ty()
+        );
+
+        let surface = config
+            .window_builder(event_loop)
+            .build_vk_surface(event_loop, instance.clone())
+            .unwrap();
+        let dimensions: [u32; 2] = surface.window().inner_size().into();
+
+        let queue_family = physical
+            .queue_families()
+            .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
+            .unwrap();
+
+        // Exclusive fullscreen needs an extension only some platforms have; without it the driver
+        // decides, as it does for the other window modes
+        let fullscreen_exclusive = config.window_mode == WindowMode::Fullscreen
+            && DeviceExtensions::supported_by_device(physical).ext_full_screen_exclusive;
+        let device_ext = DeviceExtensions {
+            khr_swapchain: true,
+            ext_full_screen_exclusive: fullscreen_exclusive,
+            ..DeviceExtensions::none()
+        };
+
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &device_ext,
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .unwrap();
+
+        let queue = queues.next().unwrap();
+
+        let (swapchain, images) = {
+            let caps = surface.capabilities(physical).unwrap();
+            let usage = caps.supported_usage_flags;
+            let (format, color_space) = choose_format(&caps, config.prefer_srgb);
+            let image_count = choose_image_count(config.image_count, &caps);
+            let present_mode = choose_present_mode(config.vsync, &caps.present_modes);
+            let alpha = caps.supported_composite_alpha.iter().next().unwrap();
+            println!(
+                "Swapchain: {:?}, {} images, {:?} presentation",
+                format, image_count, present_mode
+            );
+
+            Swapchain::new(
+                device.clone(),
+                surface.clone(),
+                image_count,
+                format,
+                dimensions,
+                1,
+                usage,
+                &queue,
+                SurfaceTransform::Identity,
+                alpha,
+                present_mode,
+                if fullscreen_exclusive {
+                    FullscreenExclusive::Allowed
+                } else {
+                    FullscreenExclusive::Default
+                },
+                true,
+                color_space,
+            )
+            .unwrap()
+        };
+
+        let supported_samples = physical.limits().framebuffer_color_sample_counts()
+            & physical.limits().framebuffer_depth_sample_counts();
+        let msaa_samples = choose_sample_count(config.msaa_samples, supported_samples);
+        if msaa_samples != config.msaa_samples {
+            println!(
+                "{}x MSAA is not supported, using {}x",
+                config.msaa_samples, msaa_samples
+            );
+        }
+
+        let target = RenderTarget::Window {
+            surface: surface,
+            swapchain: swapchain,
+            images: images,
+        };
+        let mut renderer = Renderer::from_target(device, queue, target, msaa_samples);
+        renderer.shader_watcher = match ShaderWatcher::new() {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                println!(
+                    "Could not watch the shader directory, hot reload is off: {:?}",
+                    e
+                );
+                None
+            }
+        };
+        renderer
+    }
+
+    /// Creates a renderer without a window that draws into a `dimensions` sized offscreen image.
+    pub fn new_headless(dimensions: [u32; 2]) -> Result<Renderer, HeadlessError> {
+        let instance = Instance::new(None, &InstanceExtensions::none(), None)
+            .map_err(HeadlessError::Instance)?;
+        let physical = PhysicalDevice::enumerate(&instance)
+            .find(|p| p.queue_families().any(|q| q.supports_graphics()))
+            .ok_or(HeadlessError::NoDevice)?;
+        println!(
+            "Using device: {} (type: {:?})",
+            physical.name(),
+            physical.ty()
+        );
+
+        let queue_family = physical
+            .queue_families()
+            .find(|q| q.supports_graphics())
+            .unwrap();
+
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &DeviceExtensions::none(),
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .map_err(HeadlessError::Device)?;
+
+        let queue = queues.next().unwrap();
+        let target = RenderTarget::offscreen(device.clone(), dimensions);
+        Ok(Renderer::from_target(device, queue, target, 1))
+    }
+
+    fn from_target(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        target: RenderTarget,
+        msaa_samples: u32,
+    ) -> Renderer {
+        let mut graph = RenderGraphBuilder::new();
+        let passes = FramePasses::declare(&mut graph, msaa_samples);
+        let graph = match graph.build(
+            device.clone(),
+            target.format(),
+            target.dimensions(),
+            target.images(),
+        ) {
+            Ok(graph) => graph,
+            Err(e) => panic!("Failed to build the render graph:\n{}", e),
+        };
+
+        let default_shader = match MeshShader::load(
+            device.clone(),
+            graph.render_pass(passes.scene),
+            &Material::default().shader,
+        ) {
+            Ok(shader) => shader,
+            Err(e) => panic!("Failed to load the default shader:\n{}", e),
+        };
+
+        let (white_texture, white_future) =
+            solid_texture([255, 255, 255, 255], Format::R8G8B8A8Srgb, queue.clone());
+        let (flat_normal_texture, flat_normal_future) =
+            solid_texture([128, 128, 255, 255], Format::R8G8B8A8Unorm, queue.clone());
+        let (black_texture, black_future) =
+            solid_texture([0, 0, 0, 255], Format::R8G8B8A8Srgb, queue.clone());
+        let (environment, environment_future) =
+            PrefilteredEnvironment::uniform(DEFAULT_ENVIRONMENT).upload(queue.clone());
+        // The bloom passes' render passes are all alike, so pipelines made for one work in all
+        let (post, lut_future) = PostProcessor::new(
+            device.clone(),
+            queue.clone(),
+            graph.render_pass(passes.bloom_threshold),
+            graph.render_pass(passes.post),
+            black_texture.clone(),
+        );
+        let upload_future = white_future
+            .join(flat_normal_future)
+            .join(black_future)
+            .join(environment_future)
+            .join(lut_future);
+
+        let text = TextRenderer::new(device.clone(), graph.render_pass(passes.post), Font::new());
+        let debug = DebugRenderer::new(device.clone(), graph.render_pass(passes.post));
+        let gui = GuiRenderer::new(device.clone(), graph.render_pass(passes.post));
+
+        Renderer {
+            sampler: Sampler::simple_repeat_linear_no_mipmap(device.clone()),
+            light_pool: CpuBufferPool::new(
+                device.clone(),
+                BufferUsage {
+                    storage_buffer: true,
+                    ..BufferUsage::none()
+                },
+            ),
+            instance_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            instancing: true,
+            shadows: ShadowRenderer::new(device.clone(), graph.render_pass(passes.shadows)),
+            white_texture: white_texture,
+            flat_normal_texture: flat_normal_texture,
+            black_texture: black_texture,
+            environment: environment,
+            environment_sampler: Sampler::new(
+                device.clone(),
+                Filter::Linear,
+                Filter::Linear,
+                MipmapMode::Linear,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                0.0,
+                1.0,
+                0.0,
+                1000.0,
+            )
+            .unwrap(),
+            previous_frame_end: Some(Box::new(upload_future) as Box<dyn GpuFuture>),
+
+            device: device,
+            queue: queue,
+            target: target,
+            graph: graph,
+            passes: passes,
+            msaa_samples: msaa_samples,
+            post: post,
+            shaders: vec![default_shader],
+            shader_watcher: None,
+
+            meshes: Vec::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+
+            text: text,
+            debug: debug,
+            gui: gui,
+
+            recreate_swapchain: false,
+            capture_next_frame: false,
+            capture_buffer: None,
+            captured_frame: None,
+            pending_screenshot: None,
+            frame_sequence: None,
+            stats: RenderStats::default(),
+        }
+    }
+
+    /// `None` for headless renderers.
+    pub fn window(&self) -> Option<&Window> {
+        self.target.window()
+    }
+
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.target.dimensions()
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        let [width, height] = self.dimensions();
+        width as f32 / height as f32
+    }
+
+    /// Call when the window is resized; the swapchain is recreated before the next frame.
+    pub fn resize(&mut self) {
+        self.recreate_swapchain = true;
+    }
+
+    pub fn text(&mut self) -> &mut TextRenderer {
+        &mut self.text
+    }
+
+    /// Takes the ImGui output of the frame, see `gui::Ui::render`.
+    pub fn gui(&mut self) -> &mut GuiRenderer {
+        &mut self.gui
+    }
+
+    /// Lays out `hud` for the current window size and queues it to be drawn this frame, under
+    /// text and ImGui.
+    pub fn queue_hud(&mut self, hud: &mut Hud) {
+        hud.layout(self.dimensions());
+        let draw_data = hud.draw(&mut self.text);
+        self.gui.queue_hud(draw_data);
+    }
+
+    /// Uploads the font atlas of a `gui::Gui`, which the gui isn't drawn without.
+    pub fn set_gui_font(&mut self, atlas: &Image) {
+        let future = self.gui.set_font_atlas(atlas, self.queue.clone());
+        self.join_frame_future(future);
+    }
+
+    /// Samples per pixel of the scene, 1 without MSAA.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// Which post-processing effects run, changeable between frames.
+    pub fn post_settings(&mut self) -> &mut PostSettings {
+        self.post.settings()
+    }
+
+    /// Replaces the LUT that `PostSettings::color_grading` grades with. See
+    /// `PostProcessor::set_color_grading_lut` for the layout `lut` has to have. The current LUT
+    /// is kept if `lut` isn't laid out that way.
+    pub fn set_color_grading_lut(&mut self, lut: &Image) -> Result<(), LutError> {
+        let future = self.post.set_color_grading_lut(lut, self.queue.clone())?;
+        self.join_frame_future(future);
+        Ok(())
+    }
+
+    /// Counts from the last rendered frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Draw commands sharing a mesh and material are drawn with one instanced call. Turning that
+    /// off draws each command with its own call, which is only useful for comparison.
+    pub fn set_instancing(&mut self, enabled: bool) {
+        self.instancing = enabled;
+    }
+
+    pub fn register_mesh(&mut self, mesh: &Mesh) -> MeshHandle {
+        self.meshes.push(GpuMesh::new(self.device.clone(), mesh));
+        MeshHandle(self.meshes.len() - 1)
+    }
+
+    /// The bounds of a registered mesh, in the mesh's own space.
+    pub fn mesh_bounds(&self, mesh: MeshHandle) -> Aabb {
+        self.meshes[mesh.0].bounds
+    }
+
+    /// Uploads an RGBA8 image, as returned by `image::load_texture`.
+    pub fn register_texture(&mut self, image: &Image) -> TextureHandle {
+        self.upload_texture(image, Format::R8G8B8A8Srgb)
+    }
+
+    /// Uploads an RGBA8 image that holds data rather than colors, like a normal map, so it is
+    /// sampled without the sRGB conversion.
+    pub fn register_linear_texture(&mut self, image: &Image) -> TextureHandle {
+        self.upload_texture(image, Format::R8G8B8A8Unorm)
+    }
+
+    fn upload_texture(&mut self, image: &Image, format: Format) -> TextureHandle {
+        let (texture, future) = ImmutableImage::from_iter(
+            image.data.iter().cloned(),
+            Dimensions::Dim2d {
+                width: image.width,
+                height: image.height,
+            },
+            format,
+            self.queue.clone(),
+        )
+        .unwrap();
+        self.join_frame_future(Box::new(future));
+
+        self.textures.push(texture);
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// Fails if the material's shader doesn't compile, doesn't have the bindings every material
+    /// shader has (see default.fs) or doesn't declare one of the material's parameters.
+    pub fn register_material(
+        &mut self,
+        material: Material,
+    ) -> Result<MaterialHandle, MaterialError> {
+        let shader_index = self.mesh_shader(&material.shader)?;
+        let shader = &self.shaders[shader_index];
+
+        let params_binding = shader
+            .program
+            .descriptors()
+            .iter()
+            .find(|d| d.set == 1 && d.binding == 0)
+            .unwrap();
+        let mut params = UniformBlock::new(self.device.clone(), params_binding).unwrap();
+        params.set("color", &UniformValue::Vec4(material.color))?;
+        for &(ref name, ref value) in &material.params {
+            params.set(name, value)?;
+        }
+
+        let texture = |slot: Option<TextureHandle>, fallback: &GpuTexture| match slot {
+            Some(TextureHandle(i)) => self.textures[i].clone(),
+            None => fallback.clone(),
+        };
+        let diffuse = texture(material.diffuse, &self.white_texture);
+        let normal = texture(material.normal, &self.flat_normal_texture);
+        let emissive = texture(material.emissive, &self.black_texture);
+
+        let layout = shader.pipeline.descriptor_set_layout(1).unwrap();
+        let builder = PersistentDescriptorSet::start(layout.clone())
+            .add_buffer(params.upload())
+            .unwrap()
+            .add_sampled_image(diffuse, self.sampler.clone())
+            .unwrap()
+            .add_sampled_image(normal, self.sampler.clone())
+            .unwrap()
+            .add_sampled_image(emissive, self.sampler.clone())
+            .unwrap();
+        let descriptor_set: Arc<dyn DescriptorSet + Send + Sync> =
+            if shader.layout.texture_count > 3 {
+                let metallic_roughness = texture(material.metallic_roughness, &self.white_texture);
+                Arc::new(
+                    builder
+                        .add_sampled_image(metallic_roughness, self.sampler.clone())
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                )
+            } else {
+                Arc::new(builder.build().unwrap())
+            };
+
+        self.materials.push(GpuMaterial {
+            material: material,
+            shader: shader_index,
+            descriptor_set: descriptor_set,
+        });
+        Ok(MaterialHandle(self.materials.len() - 1))
+    }
+
+    /// Loads a material file, along with the textures it names. See `renderer::material` for the
+    /// format.
+    pub fn load_material(&mut self, path: &Path) -> Result<MaterialHandle, MaterialError> {
+        let desc = MaterialDesc::load(path)?;
+        let mut material = match desc.shader {
+            Some(ref shader) => Material::for_shader(shader),
+            None => Material::default(),
+        };
+
+        if let Some(color) = desc.color {
+            material.color = color;
+        }
+        for (name, value) in desc.params {
+            material.set_param(&name, value);
+        }
+
+        if let Some(ref path) = desc.diffuse {
+            material.diffuse = Some(self.register_texture(&load_image(path)?));
+        }
+        if let Some(ref path) = desc.normal {
+            material.normal = Some(self.register_linear_texture(&load_image(path)?));
+        }
+        if let Some(ref path) = desc.emissive {
+            material.emissive = Some(self.register_texture(&load_image(path)?));
+        }
+        if let Some(ref path) = desc.metallic_roughness {
+            material.metallic_roughness = Some(self.register_linear_texture(&load_image(path)?));
+        }
+
+        self.register_material(material)
+    }
+
+    /// Replaces the environment that image based lighting reflects, from the six faces of a cube
+    /// map in Vulkan's order: +X, -X, +Y, -Y, +Z, -Z. The faces are prefiltered on the CPU first,
+    /// which takes a moment.
+    pub fn set_environment(&mut self, faces: &[Image]) {
+        let (environment, future) = PrefilteredEnvironment::new(faces).upload(self.queue.clone());
+        self.join_frame_future(future);
+        self.environment = environment;
+    }
+
+    pub fn render(&mut self, draw_list: &DrawList) {
+        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+        self.reload_changed_shaders();
+
+        if self.recreate_swapchain && !self.recreate_target() {
+            return;
+        }
+
+        if let Some(atlas_upload) = self.text.upload_atlas(self.queue.clone()) {
+            self.join_frame_future(atlas_upload);
+        }
+
+        self.captured_frame = None;
+        if self.pending_screenshot.is_some() || self.frame_sequence.is_some() {
+            self.capture_next_frame = true;
+        }
+
+        let (image_num, acquire_future) = match self.target {
+            RenderTarget::Window { ref swapchain, .. } => {
+                match swapchain::acquire_next_image(swapchain.clone(), None) {
+                    Ok((image_num, suboptimal, acquire_future)) => {
+                        if suboptimal {
+                            self.recreate_swapchain = true;
+                        }
+                        (image_num, Some(acquire_future))
+                    }
+                    Err(AcquireError::OutOfDate) => {
+                        self.recreate_swapchain = true;
+                        return;
+                    }
+                    Err(e) => panic!("Failed to acquire next image: {:?}", e),
+                }
+            }
+            RenderTarget::Offscreen { .. } => (0, None),
+        };
+
+        let builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )
+        .unwrap();
+        let scene = self.batch_commands(&draw_list.commands);
+        let casters = self.batch_commands(&draw_list.shadow_casters);
+        let shadow_frame =
+            ShadowFrame::new(&draw_list.lights, &draw_list.view, &draw_list.projection);
+        let (builder, shadow_draw_calls) = self.draw_graph(
+            builder,
+            image_num,
+            draw_list,
+            &shadow_frame,
+            &scene,
+            &casters,
+        );
+        let builder = self.record_capture(builder, image_num);
+        self.stats = RenderStats {
+            commands: draw_list.commands.len(),
+            draw_calls: scene.batches.len(),
+            shadow_draw_calls: shadow_draw_calls,
+        };
+        let command_buffer = builder.build().unwrap();
+
+        let frame_start = match acquire_future {
+            Some(acquire_future) => {
+                Box::new(self.previous_frame_end.take().unwrap().join(acquire_future))
+                    as Box<dyn GpuFuture>
+            }
+            None => self.previous_frame_end.take().unwrap(),
+        };
+        let after_draw = frame_start
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap();
+        let future = match self.target {
+            RenderTarget::Window { ref swapchain, .. } => Box::new(
+                after_draw.then_swapchain_present(self.queue.clone(), swapchain.clone(), image_num),
+            ) as Box<dyn GpuFuture>,
+            RenderTarget::Offscreen { .. } => Box::new(after_draw) as Box<dyn GpuFuture>,
+        }
+        .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                if let Some(buffer) = self.capture_buffer.take() {
+                    future.wait(None).unwrap();
+                    let data = buffer.read().unwrap();
+                    match to_rgba8(self.target.format(), &data) {
+                        Ok(pixels) => self.captured_frame = Some(pixels),
+                        // Later frames would fail the same way, so the captures are dropped
+                        Err(e) => {
+                            println!("{}", e);
+                            self.pending_screenshot = None;
+                            self.frame_sequence = None;
+                        }
+                    }
+                }
+                self.previous_frame_end = Some(Box::new(future) as Box<_>);
+                self.save_captured_frame();
+            }
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                self.capture_buffer = None;
+                self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+            }
+            Err(e) => {
+                println!("Failed to flush future: {:?}", e);
+                self.capture_buffer = None;
+                self.previous_frame_end = Some(Box::new(sync::now(self.device.clone())) as Box<_>);
+            }
+        }
+    }
+
+    /// Saves the next rendered frame as a PNG at `path`.
+    pub fn save_screenshot(&mut self, path: &Path) {
+        self.pending_screenshot = Some(path.to_path_buf());
+    }
+
+    /// Saves each of the next `frame_count` frames as a numbered PNG in `dir`.
+    pub fn capture_frames(&mut self, dir: &Path, frame_count: u32) {
+        self.frame_sequence = Some(FrameSequence::new(dir, frame_count));
+    }
+
+    pub fn is_capturing_frames(&self) -> bool {
+        self.frame_sequence.is_some()
+    }
+
+    fn save_captured_frame(&mut self) {
+        let pixels = match self.captured_frame {
+            Some(ref pixels) => pixels,
+            None => return,
+        };
+        let dimensions = self.target.dimensions();
+
+        if let Some(path) = self.pending_screenshot.take() {
+            save_in_background(path, pixels.clone(), dimensions);
+        }
+
+        let sequence_done = match self.frame_sequence {
+            Some(ref mut sequence) => {
+                save_in_background(sequence.next_path(), pixels.clone(), dimensions);
+                sequence.is_done()
+            }
+            None => false,
+        };
+        if sequence_done {
+            self.frame_sequence = None;
+        }
+    }
+
+    /// Renders `draw_list` and reads the result back as tightly packed RGBA8 rows.
+    /// Works for windowed renderers too, where it returns the frame that was presented. Frames
+    /// dropped because the swapchain was out of date are rendered again on the recreated one.
+    pub fn render_to_pixels(&mut self, draw_list: &DrawList) -> Vec<u8> {
+        for _ in 0..CAPTURE_ATTEMPTS {
+            self.capture_next_frame = true;
+            self.render(draw_list);
+            if let Some(pixels) = self.captured_frame.take() {
+                return pixels;
+            }
+        }
+        panic!(
+            "No frame could be captured in {} attempts, see the log for why",
+            CAPTURE_ATTEMPTS
+        );
+    }
+
+    fn recreate_target(&mut self) -> bool {
+        let dimensions: [u32; 2] = match self.target.window() {
+            Some(window) => window.inner_size().into(),
+            None => {
+                self.recreate_swapchain = false;
+                return true;
+            }
+        };
+
+        if let RenderTarget::Window {
+            ref mut swapchain,
+            ref mut images,
+            ..
+        } = self.target
+        {
+            let (new_swapchain, new_images) = match swapchain.recreate_with_dimensions(dimensions) {
+                Ok(r) => r,
+                Err(SwapchainCreationError::UnsupportedDimensions) => return false,
+                Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
+            };
+            *swapchain = new_swapchain;
+            *images = new_images;
+        }
+
+        self.graph.resize(dimensions, self.target.images());
+        self.recreate_swapchain = false;
+        true
+    }
+
+    fn batch_commands(&self, commands: &[DrawCommand]) -> BatchedCommands {
+        let batches = if self.instancing {
+            InstanceBatches::new(commands, &self.materials)
+        } else {
+            InstanceBatches::unbatched(commands, &self.materials)
+        };
+        // A buffer can't be empty, so without instances there is none and nothing is drawn
+        let instance_buffer = if batches.is_empty() {
+            None
+        } else {
+            Some(Arc::new(
+                self.instance_pool
+                    .chunk(batches.instances.iter().cloned())
+                    .unwrap(),
+            ))
+        };
+        BatchedCommands {
+            batches: batches.batches,
+            instance_buffer: instance_buffer,
+        }
+    }
+
+    /// Records every pass of the graph, in its order, drawing into target image `image_num`.
+    /// `casters` are only drawn into the shadow atlas. Returns the number of draw calls of the
+    /// shadow pass as well.
+    fn draw_graph(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        image_num: usize,
+        draw_list: &DrawList,
+        shadow_frame: &ShadowFrame,
+        scene: &BatchedCommands,
+        casters: &BatchedCommands,
+    ) -> (AutoCommandBufferBuilder, usize) {
+        let passes = self.passes;
+        let bloom = self.post.settings().bloom;
+        let dimensions = self.dimensions();
+        let mut builder = builder;
+        let mut shadow_draw_calls = 0;
+
+        for pass in self.graph.order().to_vec() {
+            // Without bloom the post pass doesn't sample the bloom images
+            if passes.is_bloom(pass) && !bloom {
+                continue;
+            }
+            builder = self.graph.begin_pass(builder, pass, image_num);
+            builder = if pass == passes.shadows {
+                let mut builder = builder;
+                for commands in &[scene, casters] {
+                    let (next, draw_calls) = self.shadows.draw(
+                        builder,
+                        shadow_frame,
+                        &self.meshes,
+                        &commands.batches,
+                        commands.instance_buffer.as_ref(),
+                    );
+                    builder = next;
+                    shadow_draw_calls += draw_calls;
+                }
+                builder
+            } else if pass == passes.scene {
+                self.draw_scene(
+                    builder,
+                    draw_list,
+                    shadow_frame,
+                    &scene.batches,
+                    scene.instance_buffer.as_ref(),
+                )
+            } else if pass == passes.bloom_threshold {
+                let bloom_size = self.graph.image(passes.bloom[0]).dimensions();
+                let scene = self.graph.image(passes.scene_color);
+                self.post.draw_bloom_threshold(builder, scene, bloom_size)
+            } else if pass == passes.bloom_blur_horizontal {
+                let source = self.graph.image(passes.bloom[0]);
+                self.post.draw_bloom_blur(builder, source, false)
+            } else if pass == passes.bloom_blur_vertical {
+                let source = self.graph.image(passes.bloom[1]);
+                self.post.draw_bloom_blur(builder, source, true)
+            } else if pass == passes.post {
+                let scene = self.graph.image(passes.scene_color);
+                let bloom = self.graph.image(passes.bloom[0]);
+                let builder = self.post.draw_composite(builder, scene, bloom, dimensions);
+                // Debug lines, the HUD, text and the gui go last so they are drawn over the
+                // post-processed scene
+                let view_projection = draw_list.projection * draw_list.view;
+                let debug_frame = debug_draw::take_frame();
+                let builder =
+                    self.debug
+                        .draw(builder, &debug_frame.lines, &view_projection, dimensions);
+                self.debug.queue_texts(
+                    &mut self.text,
+                    &debug_frame.texts,
+                    &view_projection,
+                    dimensions,
+                );
+                let builder =
+                    self.gui
+                        .draw_hud(builder, dimensions, &self.textures, &self.white_texture);
+                let builder = self.text.draw(builder, dimensions);
+                self.gui
+                    .draw(builder, dimensions, &self.textures, &self.white_texture)
+            } else {
+                builder
+            };
+            builder = builder.end_render_pass().unwrap();
+        }
+        (builder, shadow_draw_calls)
+    }
+
+    /// Records the scene into the begun scene pass, drawing each batch with its instances from
+    /// `instance_buffer`.
+    fn draw_scene(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        draw_list: &DrawList,
+        shadow_frame: &ShadowFrame,
+        batches: &[DrawBatch],
+        instance_buffer: Option<&Arc<InstanceBuffer>>,
+    ) -> AutoCommandBufferBuilder {
+        // A buffer can't be empty, so without lights a zeroed one goes in that lightCount skips
+        let mut lights: Vec<GpuLight> = draw_list
+            .lights
+            .iter()
+            .zip(shadow_frame.light_cascades.iter())
+            .map(|(light, cascades)| {
+                let mut gpu_light = GpuLight::new(light, &draw_list.view);
+                if let (Some((first, count)), Some(ref settings)) = (*cascades, light.shadow) {
+                    gpu_light.set_shadow(first, count, settings);
+                }
+                gpu_light
+            })
+            .collect();
+        if lights.is_empty() {
+            lights.push(GpuLight::default());
+        }
+        let light_buffer = Arc::new(self.light_pool.chunk(lights).unwrap());
+        let shadow_buffer = self.shadows.cascade_buffer(shadow_frame);
+
+        // The frame data is the same for every shader, but each one has its own set 0 layout
+        let mut frame_sets: Vec<Arc<dyn DescriptorSet + Send + Sync>> = Vec::new();
+        for shader in &mut self.shaders {
+            let program = &mut shader.program;
+            program.set_mat4("frame.view", &draw_list.view).unwrap();
+            program
+                .set_mat4("frame.projection", &draw_list.projection)
+                .unwrap();
+            program
+                .set_int("frame.lightCount", draw_list.lights.len() as i32)
+                .unwrap();
+
+            let layout = shader.pipeline.descriptor_set_layout(0).unwrap();
+            let builder = PersistentDescriptorSet::start(layout.clone())
+                .add_buffer(program.uniform_buffer(0, 0).unwrap())
+                .unwrap()
+                .add_buffer(light_buffer.clone())
+                .unwrap()
+                .add_buffer(shadow_buffer.clone())
+                .unwrap()
+                .add_sampled_image(
+                    self.graph.image(self.passes.shadow_atlas),
+                    self.shadows.sampler(),
+                )
+                .unwrap();
+            frame_sets.push(if shader.layout.environment {
+                Arc::new(
+                    builder
+                        .add_sampled_image(
+                            self.environment.clone(),
+                            self.environment_sampler.clone(),
+                        )
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                )
+            } else {
+                Arc::new(builder.build().unwrap())
+            });
+        }
+
+        let dimensions = self.dimensions();
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+
+        let mut builder = builder;
+
+        // Batches come sorted by shader and material, so each pipeline and descriptor set is
+        // bound only once
+        if let Some(instance_buffer) = instance_buffer {
+            for batch in batches {
+                let mesh = &self.meshes[batch.mesh.0];
+                let material = &self.materials[batch.material.0];
+                builder = builder
+                    .draw_indexed(
+                        self.shaders[material.shader].pipeline.clone(),
+                        &dynamic_state,
+                        (mesh.vertex_buffer.clone(), batch.instances(instance_buffer)),
+                        mesh.index_buffer.clone(),
+                        (
+                            frame_sets[material.shader].clone(),
+                            material.descriptor_set.clone(),
+                        ),
+                        (),
+                    )
+                    .unwrap();
+            }
+        }
+
+        builder
+    }
+
+    /// Copies the rendered image into a CPU visible buffer if a capture was asked for.
+    fn record_capture(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        image_num: usize,
+    ) -> AutoCommandBufferBuilder {
+        if !self.capture_next_frame {
+            return builder;
+        }
+        self.capture_next_frame = false;
+
+        let [width, height] = self.dimensions();
+        let buffer = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::transfer_destination(),
+            false,
+            (0..width * height * 4).map(|_| 0u8),
+        )
+        .unwrap();
+        self.capture_buffer = Some(buffer.clone());
+
+        match self.target {
+            RenderTarget::Window { ref images, .. } => builder
+                .copy_image_to_buffer(images[image_num].clone(), buffer)
+                .unwrap(),
+            RenderTarget::Offscreen { ref image } => {
+                builder.copy_image_to_buffer(image.clone(), buffer).unwrap()
+            }
+        }
+    }
+
+    /// Rebuilds the pipelines of shaders that were edited on disk. When a shader fails to compile
+    /// the error is logged and the previous pipeline stays in use.
+    fn reload_changed_shaders(&mut self) {
+        let mut changed = match self.shader_watcher {
+            Some(ref watcher) => watcher.changed_programs(),
+            None => return,
+        };
+        // Only the material shaders include files, and any of them might include this one
+        if changed.iter().any(|name| name.ends_with(".glsl")) {
+            changed.retain(|name| !name.ends_with(".glsl"));
+            for shader in &self.shaders {
+                if !changed.contains(&shader.name) {
+                    changed.push(shader.name.clone());
+                }
+            }
+        }
+
+        for name in changed {
+            let result = if name == TEXT_SHADER {
+                self.text.reload_shader()
+            } else if name == TEXT_SDF_SHADER {
+                self.text.reload_sdf_shader()
+            } else if name == GUI_SHADER {
+                self.gui.reload_shader()
+            } else if name == DEBUG_SHADER {
+                self.debug.reload_shader()
+            } else if name == SHADOW_SHADER {
+                self.shadows.reload_shader()
+            } else if let Some(result) = self.post.reload_shader(&name) {
+                result
+            } else if let Some(shader) = self.shaders.iter_mut().find(|s| s.name == name) {
+                shader.reload(
+                    self.device.clone(),
+                    self.graph.render_pass(self.passes.scene),
+                )
+            } else {
+                continue;
+            };
+            match result {
+                Ok(()) => println!("Reloaded shader '{}'", name),
+                Err(e) => println!(
+                    "Failed to reload shader '{}', keeping the previous one:\n{}",
+                    name, e
+                ),
+            }
+        }
+    }
+
+    /// Index of shader `name` in `shaders`, loading it if no material used it yet.
+    fn mesh_shader(&mut self, name: &str) -> Result<usize, MaterialError> {
+        if let Some(index) = self.shaders.iter().position(|s| s.name == name) {
+            return Ok(index);
+        }
+        let shader = MeshShader::load(
+            self.device.clone(),
+            self.graph.render_pass(self.passes.scene),
+            name,
+        )?;
+        self.shaders.push(shader);
+        Ok(self.shaders.len() - 1)
+    }
+
+    /// Makes the next frame wait for `future`, used for uploads that have to finish before drawing.
+    fn join_frame_future(&mut self, future: Box<dyn GpuFuture>) {
+        let frame_end = self.previous_frame_end.take().unwrap().join(future);
+        self.previous_frame_end = Some(Box::new(frame_end) as Box<dyn GpuFuture>);
+    }
+}
+
+/// A 1x1 texture standing in for a texture slot a material leaves empty.
+fn solid_texture(
+    pixel: [u8; 4],
+    format: Format,
+    queue: Arc<Queue>,
+) -> (GpuTexture, impl GpuFuture) {
+    ImmutableImage::from_iter(
+        pixel.iter().cloned(),
+        Dimensions::Dim2d {
+            width: 1,
+            height: 1,
+        },
+        format,
+        queue,
+    )
+    .unwrap()
+}
+
+// `image::load_texture` panics on missing files, which a typo in a material file shouldn't do.
+fn load_image(path: &Path) -> Result<Image, MaterialError> {
+    if !path.is_file() {
+        return Err(MaterialError::Io(
+            path.to_path_buf(),
+            io::Error::new(io::ErrorKind::NotFound, "texture not found"),
+        ));
+    }
+    Ok(image::load_texture(path))
+}
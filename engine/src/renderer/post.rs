@@ -0,0 +1,560 @@
+//! Post-processing of the HDR scene image into the final one.
+//!
+//! Bloom first: what is brighter than a threshold is copied into a half resolution image and
+//! blurred there. A last pass over the whole target then adds the bloom to the scene and applies
+//! exposure, tonemapping, a vignette and color grading from a LUT. Each is a pass of the
+//! renderer's graph, which owns the images between them.
+
+use image::Image;
+use shader::{ShaderError, ShaderProgram};
+use std::fmt;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::attachment::AttachmentImage;
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::pipeline::vertex::{BufferlessDefinition, BufferlessVertices};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+
+use super::resources::GpuTexture;
+
+pub const BLOOM_THRESHOLD_SHADER: &str = "bloom_threshold";
+pub const BLOOM_BLUR_SHADER: &str = "bloom_blur";
+pub const POST_SHADER: &str = "post";
+/// Size of the LUT used until `PostProcessor::set_color_grading_lut` is called.
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+/// A color grading LUT that isn't laid out as `PostProcessor::set_color_grading_lut` expects.
+#[derive(Debug, PartialEq)]
+pub enum LutError {
+    /// The strip has to be as wide as its height squared.
+    Size { width: u32, height: u32 },
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LutError::Size { width, height } => write!(
+                f,
+                "a color grading LUT has to be as wide as its height squared, got {}x{}",
+                width, height
+            ),
+        }
+    }
+}
+
+/// Which effects run, and how strongly. Every effect can be switched between frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostSettings {
+    /// Scales the scene's colors before tonemapping.
+    pub exposure: f32,
+    /// ACES filmic tonemapping. Without it colors above 1 are clipped.
+    pub tonemapping: bool,
+    pub bloom: bool,
+    /// Brightness above which pixels start to bloom.
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub vignette: bool,
+    /// How much the corners are darkened, from 0 to 1.
+    pub vignette_strength: f32,
+    /// Grades colors with the LUT given to `Renderer::set_color_grading_lut`.
+    pub color_grading: bool,
+}
+
+impl Default for PostSettings {
+    fn default() -> PostSettings {
+        PostSettings {
+            exposure: 1.0,
+            tonemapping: true,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+            vignette: false,
+            vignette_strength: 0.4,
+            color_grading: false,
+        }
+    }
+}
+
+/// The push constants of bloom_threshold.fs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ThresholdPushConstants {
+    texel_size: [f32; 2],
+    threshold: f32,
+}
+
+/// The push constants of bloom_blur.fs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BlurPushConstants {
+    direction: [f32; 2],
+}
+
+/// The push constants of post.fs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PostPushConstants {
+    exposure: f32,
+    bloom_intensity: f32,
+    vignette_strength: f32,
+    tonemapping: i32,
+    color_grading: i32,
+}
+
+type PostPipeline = GraphicsPipeline<
+    BufferlessDefinition,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+/// The shader and pipeline of one fullscreen pass.
+struct PostPass {
+    name: &'static str,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: ShaderProgram,
+    pipeline: Arc<PostPipeline>,
+}
+
+impl PostPass {
+    fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        name: &'static str,
+    ) -> PostPass {
+        let program = match ShaderProgram::create_program(device.clone(), name) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the {} shader:\n{}", name, e),
+        };
+        let pipeline = match create_pipeline(device, render_pass.clone(), &program) {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the {} pipeline:\n{}", name, e),
+        };
+        PostPass {
+            name: name,
+            render_pass: render_pass,
+            program: program,
+            pipeline: pipeline,
+        }
+    }
+
+    fn reload(&mut self, device: Arc<Device>) -> Result<(), ShaderError> {
+        let program = self.program.reload(device.clone(), self.name)?;
+        self.pipeline = create_pipeline(device, self.render_pass.clone(), &program)?;
+        self.program = program;
+        Ok(())
+    }
+}
+
+/// Draws the post-processing passes, and owns their pipelines and the color grading LUT.
+pub struct PostProcessor {
+    device: Arc<Device>,
+    settings: PostSettings,
+    threshold: PostPass,
+    blur: PostPass,
+    composite: PostPass,
+    /// Bound in place of the bloom image while bloom is off.
+    black_texture: GpuTexture,
+    lut: GpuTexture,
+    sampler: Arc<Sampler>,
+}
+
+impl PostProcessor {
+    /// `bloom_pass` draws into an HDR image, `final_pass` into the target. The returned future
+    /// uploads the identity LUT.
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        bloom_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        final_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        black_texture: GpuTexture,
+    ) -> (PostProcessor, Box<dyn GpuFuture>) {
+        let (lut, lut_future) = upload_lut(
+            identity_lut(IDENTITY_LUT_SIZE),
+            IDENTITY_LUT_SIZE,
+            queue.clone(),
+        );
+
+        let post = PostProcessor {
+            settings: PostSettings::default(),
+            threshold: PostPass::new(device.clone(), bloom_pass.clone(), BLOOM_THRESHOLD_SHADER),
+            blur: PostPass::new(device.clone(), bloom_pass, BLOOM_BLUR_SHADER),
+            composite: PostPass::new(device.clone(), final_pass, POST_SHADER),
+            sampler: Sampler::new(
+                device.clone(),
+                Filter::Linear,
+                Filter::Linear,
+                MipmapMode::Nearest,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                SamplerAddressMode::ClampToEdge,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            )
+            .unwrap(),
+            device: device,
+            black_texture: black_texture,
+            lut: lut,
+        };
+        (post, lut_future)
+    }
+
+    pub fn settings(&mut self) -> &mut PostSettings {
+        &mut self.settings
+    }
+
+    /// Replaces the color grading LUT with `lut`, a strip of square slices laid out left to right
+    /// as in Unreal or Unity: a `size` cubed LUT is `size * size` pixels wide and `size` high, blue
+    /// grows from slice to slice, red to the right and green downwards within a slice. Colors are
+    /// in sRGB. Returns the upload to wait for.
+    pub fn set_color_grading_lut(
+        &mut self,
+        lut: &Image,
+        queue: Arc<Queue>,
+    ) -> Result<Box<dyn GpuFuture>, LutError> {
+        let data = lut_from_strip(lut)?;
+        let (lut, future) = upload_lut(data, lut.height, queue);
+        self.lut = lut;
+        Ok(future)
+    }
+
+    /// Recompiles the shader of the pass named `name`, keeping the current pipeline if that
+    /// fails. `None` if no pass uses that shader.
+    pub fn reload_shader(&mut self, name: &str) -> Option<Result<(), ShaderError>> {
+        let device = self.device.clone();
+        [&mut self.threshold, &mut self.blur, &mut self.composite]
+            .iter_mut()
+            .find(|pass| pass.name == name)
+            .map(|pass| pass.reload(device))
+    }
+
+    /// Draws the bright parts of `scene` into the begun bloom pass, whose image is `dimensions`
+    /// big.
+    pub fn draw_bloom_threshold(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        scene: Arc<AttachmentImage>,
+        dimensions: [u32; 2],
+    ) -> AutoCommandBufferBuilder {
+        let [width, height] = scene.dimensions();
+        let push_constants = ThresholdPushConstants {
+            texel_size: [1.0 / width as f32, 1.0 / height as f32],
+            threshold: self.settings.bloom_threshold,
+        };
+        self.draw_pass(
+            builder,
+            &self.threshold,
+            self.source_set(&self.threshold, scene),
+            dimensions,
+            push_constants,
+        )
+    }
+
+    /// Draws `source` blurred horizontally, or vertically, into the begun bloom pass, whose image
+    /// is as big as `source`.
+    pub fn draw_bloom_blur(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        source: Arc<AttachmentImage>,
+        vertical: bool,
+    ) -> AutoCommandBufferBuilder {
+        let dimensions = source.dimensions();
+        let push_constants = BlurPushConstants {
+            direction: if vertical {
+                [0.0, 1.0 / dimensions[1] as f32]
+            } else {
+                [1.0 / dimensions[0] as f32, 0.0]
+            },
+        };
+        self.draw_pass(
+            builder,
+            &self.blur,
+            self.source_set(&self.blur, source),
+            dimensions,
+            push_constants,
+        )
+    }
+
+    /// Draws the final image from `scene` and the blurred `bloom` into the begun final pass.
+    /// `bloom` is only used with `PostSettings::bloom` on.
+    pub fn draw_composite(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        scene: Arc<AttachmentImage>,
+        bloom: Arc<AttachmentImage>,
+        dimensions: [u32; 2],
+    ) -> AutoCommandBufferBuilder {
+        let settings = self.settings;
+        let layout = self.composite.pipeline.descriptor_set_layout(0).unwrap();
+        let set = PersistentDescriptorSet::start(layout.clone())
+            .add_sampled_image(scene, self.sampler.clone())
+            .unwrap();
+        let set: Arc<dyn DescriptorSet + Send + Sync> = if settings.bloom {
+            Arc::new(
+                set.add_sampled_image(bloom, self.sampler.clone())
+                    .unwrap()
+                    .add_sampled_image(self.lut.clone(), self.sampler.clone())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+        } else {
+            Arc::new(
+                set.add_sampled_image(self.black_texture.clone(), self.sampler.clone())
+                    .unwrap()
+                    .add_sampled_image(self.lut.clone(), self.sampler.clone())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            )
+        };
+
+        let push_constants = PostPushConstants {
+            exposure: settings.exposure,
+            bloom_intensity: if settings.bloom {
+                settings.bloom_intensity
+            } else {
+                0.0
+            },
+            vignette_strength: if settings.vignette {
+                settings.vignette_strength
+            } else {
+                0.0
+            },
+            tonemapping: settings.tonemapping as i32,
+            color_grading: settings.color_grading as i32,
+        };
+        self.draw_pass(builder, &self.composite, set, dimensions, push_constants)
+    }
+
+    fn source_set(
+        &self,
+        pass: &PostPass,
+        source: Arc<AttachmentImage>,
+    ) -> Arc<dyn DescriptorSet + Send + Sync> {
+        let layout = pass.pipeline.descriptor_set_layout(0).unwrap();
+        Arc::new(
+            PersistentDescriptorSet::start(layout.clone())
+                .add_sampled_image(source, self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// Draws a fullscreen triangle over an image of `dimensions` with `pass`.
+    fn draw_pass<Pc>(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        pass: &PostPass,
+        set: Arc<dyn DescriptorSet + Send + Sync>,
+        dimensions: [u32; 2],
+        push_constants: Pc,
+    ) -> AutoCommandBufferBuilder {
+        builder
+            .draw(
+                pass.pipeline.clone(),
+                &viewport_state(dimensions),
+                BufferlessVertices {
+                    vertices: 3,
+                    instances: 1,
+                },
+                set,
+                push_constants,
+            )
+            .unwrap()
+    }
+}
+
+fn viewport_state(dimensions: [u32; 2]) -> DynamicState {
+    DynamicState {
+        viewports: Some(vec![Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+            depth_range: 0.0..1.0,
+        }]),
+        ..DynamicState::none()
+    }
+}
+
+/// RGBA8 texels of a `size` cubed LUT that leaves colors as they are, red varying fastest.
+fn identity_lut(size: u32) -> Vec<u8> {
+    let level = |i: u32| (i * 255 / (size - 1)) as u8;
+    let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                data.extend_from_slice(&[level(r), level(g), level(b), 255]);
+            }
+        }
+    }
+    data
+}
+
+/// The texels of a strip LUT, see `PostProcessor::set_color_grading_lut`, in the order of
+/// `identity_lut`.
+fn lut_from_strip(strip: &Image) -> Result<Vec<u8>, LutError> {
+    let size = strip.height;
+    if size == 0 || strip.width != size * size {
+        return Err(LutError::Size {
+            width: strip.width,
+            height: strip.height,
+        });
+    }
+    let mut data = Vec::with_capacity(strip.data.len());
+    for b in 0..size {
+        for g in 0..size {
+            let row = (g * strip.width + b * size) as usize * 4;
+            data.extend_from_slice(&strip.data[row..row + size as usize * 4]);
+        }
+    }
+    Ok(data)
+}
+
+fn upload_lut(data: Vec<u8>, size: u32, queue: Arc<Queue>) -> (GpuTexture, Box<dyn GpuFuture>) {
+    let (lut, future) = ImmutableImage::from_iter(
+        data.into_iter(),
+        Dimensions::Dim3d {
+            width: size,
+            height: size,
+            depth: size,
+        },
+        Format::R8G8B8A8Unorm,
+        queue,
+    )
+    .unwrap();
+    (lut, Box::new(future))
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<PostPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(BufferlessDefinition)
+        .vertex_shader(program.vertex_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(program.fragment_entry_point(), ())
+        .depth_stencil_disabled()
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The texel of `lut` for red `r`, green `g` and blue `b`, each in 0..size.
+    fn texel(lut: &[u8], size: u32, r: u32, g: u32, b: u32) -> &[u8] {
+        let i = ((b * size + g) * size + r) as usize * 4;
+        &lut[i..i + 4]
+    }
+
+    #[test]
+    fn identity_lut_leaves_colors_alone() {
+        let lut = identity_lut(4);
+        assert_eq!(lut.len(), 4 * 4 * 4 * 4);
+        assert_eq!(texel(&lut, 4, 0, 0, 0), &[0, 0, 0, 255]);
+        assert_eq!(texel(&lut, 4, 3, 0, 0), &[255, 0, 0, 255]);
+        assert_eq!(texel(&lut, 4, 0, 3, 0), &[0, 255, 0, 255]);
+        assert_eq!(texel(&lut, 4, 0, 0, 3), &[0, 0, 255, 255]);
+        assert_eq!(texel(&lut, 4, 1, 2, 3), &[85, 170, 255, 255]);
+        assert_eq!(texel(&lut, 4, 3, 3, 3), &[255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn strips_are_read_slice_by_slice() {
+        // Each texel of a 2 cubed strip holds its own coordinates
+        let size = 2;
+        let mut data = Vec::new();
+        for g in 0..size {
+            for x in 0..size * size {
+                let (b, r) = (x / size, x % size);
+                data.extend_from_slice(&[r as u8, g as u8, b as u8, 255]);
+            }
+        }
+        let strip = Image {
+            data: data,
+            width: size * size,
+            height: size,
+        };
+        let lut = lut_from_strip(&strip).unwrap();
+        assert_eq!(lut.len(), strip.data.len());
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    assert_eq!(
+                        texel(&lut, size, r, g, b),
+                        &[r as u8, g as u8, b as u8, 255]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn an_identity_strip_gives_the_identity_lut() {
+        let size = 16;
+        let identity = identity_lut(size);
+        // Lay the identity out as a strip: slice b is the square at x = b * size
+        let mut data = vec![0; identity.len()];
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let i = ((g * size * size + b * size + r) * 4) as usize;
+                    data[i..i + 4].copy_from_slice(texel(&identity, size, r, g, b));
+                }
+            }
+        }
+        let strip = Image {
+            data: data,
+            width: size * size,
+            height: size,
+        };
+        assert_eq!(lut_from_strip(&strip), Ok(identity));
+    }
+
+    #[test]
+    fn strips_of_the_wrong_size_are_errors() {
+        let strip = |width: u32, height: u32| Image {
+            data: vec![0; (width * height * 4) as usize],
+            width: width,
+            height: height,
+        };
+        assert_eq!(
+            lut_from_strip(&strip(32, 4)),
+            Err(LutError::Size {
+                width: 32,
+                height: 4,
+            })
+        );
+        assert_eq!(
+            lut_from_strip(&strip(0, 0)),
+            Err(LutError::Size {
+                width: 0,
+                height: 0,
+            })
+        );
+        assert!(lut_from_strip(&strip(16, 4)).is_ok());
+        assert_eq!(
+            LutError::Size {
+                width: 32,
+                height: 4,
+            }
+            .to_string(),
+            "a color grading LUT has to be as wide as its height squared, got 32x4"
+        );
+    }
+}
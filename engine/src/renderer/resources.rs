@@ -0,0 +1,141 @@
+use drawing::Color4;
+use glm::{vec2, vec3, vec4};
+use mesh::mesh::Mesh;
+use shader::UniformValue;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::descriptor::descriptor_set::DescriptorSet;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::ImmutableImage;
+
+use super::culling::Aabb;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MeshHandle(pub(crate) usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureHandle(pub(crate) usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MaterialHandle(pub(crate) usize);
+
+#[derive(Default, Clone, Debug)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub color: [f32; 3],
+    pub tex_coord: [f32; 2],
+}
+vulkano::impl_vertex!(Vertex, position, normal, color, tex_coord);
+
+pub struct GpuMesh {
+    pub vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>>,
+    pub index_buffer: Arc<CpuAccessibleBuffer<[u16]>>,
+    pub bounds: Aabb,
+}
+
+impl GpuMesh {
+    pub fn new(device: Arc<Device>, mesh: &Mesh) -> GpuMesh {
+        let vertices = (0..mesh.vertices.len()).map(|i| {
+            let v = mesh.vertices[i];
+            let n = mesh.normals.get(i).cloned().unwrap_or(vec3(0.0, 0.0, 1.0));
+            let c = mesh.colors.get(i).cloned().unwrap_or(vec3(1.0, 1.0, 1.0));
+            let t = mesh.tex_coords.get(i).cloned().unwrap_or(vec2(0.0, 0.0));
+            Vertex {
+                position: [v.x, v.y, v.z],
+                normal: [n.x, n.y, n.z],
+                color: [c.x, c.y, c.z],
+                tex_coord: [t.x, t.y],
+            }
+        });
+        let vertex_buffer =
+            CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false, vertices)
+                .unwrap();
+
+        let indices = mesh.indices.iter().cloned();
+        let index_buffer =
+            CpuAccessibleBuffer::from_iter(device, BufferUsage::all(), false, indices).unwrap();
+
+        GpuMesh {
+            vertex_buffer: vertex_buffer,
+            index_buffer: index_buffer,
+            bounds: Aabb::from_points(&mesh.vertices),
+        }
+    }
+}
+
+pub type GpuTexture = Arc<ImmutableImage<Format>>;
+
+/// How a mesh surface looks: the shader it is drawn with, its textures and its parameters.
+///
+/// `shader` names a program in the engine's shader directory. `color` and `params` are written by
+/// name into that shader's `MaterialParams` block. Missing textures are white for diffuse and
+/// metallic-roughness, flat for normal and black for emissive. Metallic-roughness is only used by
+/// shaders that declare it, like pbr.fs, which reads roughness from green and metallic from blue
+/// as glTF does.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub shader: String,
+    pub diffuse: Option<TextureHandle>,
+    pub normal: Option<TextureHandle>,
+    pub emissive: Option<TextureHandle>,
+    pub metallic_roughness: Option<TextureHandle>,
+    pub color: Color4,
+    pub params: Vec<(String, UniformValue)>,
+}
+
+impl Material {
+    /// A material with the default parameters of `shader`, for the engine's own shaders "default"
+    /// and "pbr". Other shaders start without parameters, so their blocks start zeroed.
+    pub fn for_shader(shader: &str) -> Material {
+        let params = match shader {
+            "default" => vec![
+                ("specularColor", UniformValue::Vec3(vec3(1.0, 0.5, 0.5))),
+                ("smoothness", UniformValue::Float(10.0)),
+                ("ambientAmount", UniformValue::Float(0.1)),
+                ("emissiveStrength", UniformValue::Float(1.0)),
+            ],
+            "pbr" => vec![
+                ("metallic", UniformValue::Float(0.0)),
+                ("roughness", UniformValue::Float(0.5)),
+                ("emissiveStrength", UniformValue::Float(1.0)),
+                ("environmentStrength", UniformValue::Float(1.0)),
+            ],
+            _ => Vec::new(),
+        };
+        Material {
+            shader: shader.to_string(),
+            diffuse: None,
+            normal: None,
+            emissive: None,
+            metallic_roughness: None,
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            params: params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        }
+    }
+
+    /// Sets parameter `name`, replacing an earlier value.
+    pub fn set_param(&mut self, name: &str, value: UniformValue) {
+        match self.params.iter_mut().find(|p| p.0 == name) {
+            Some(param) => param.1 = value,
+            None => self.params.push((name.to_string(), value)),
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::for_shader("default")
+    }
+}
+
+pub struct GpuMaterial {
+    pub material: Material,
+    /// Index of the material's shader in the renderer.
+    pub shader: usize,
+    pub descriptor_set: Arc<dyn DescriptorSet + Send + Sync>,
+}
@@ -0,0 +1,385 @@
+//! Shadow maps for directional and spot lights.
+//!
+//! Every frame each shadow casting light gets tiles of one shared depth atlas: a spot light one,
+//! a directional light one per cascade. The tiles are rendered in a depth only pass before the
+//! scene, and default.fs samples them with PCF.
+
+use glm::{inverse, look_at, normalize, ortho_rh_zo, perspective_rh_zo, vec3, vec4, Mat4, Vec3};
+use shader::{ShaderError, ShaderProgram};
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::{CpuBufferPool, CpuBufferPoolChunk};
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::memory::pool::StdMemoryPool;
+use vulkano::pipeline::depth_stencil::*;
+use vulkano::pipeline::vertex::OneVertexOneInstanceDefinition;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+
+use super::instancing::{DrawBatch, InstanceBuffer, InstanceData};
+use super::light::{Light, LightKind};
+use super::mesh_shader::MeshPipeline;
+use super::resources::{GpuMesh, Vertex};
+
+pub const SHADOW_SHADER: &str = "shadow";
+pub const SHADOW_ATLAS_SIZE: u32 = 4096;
+pub const SHADOW_TILE_SIZE: u32 = 1024;
+pub const MAX_CASCADES: u32 = 4;
+pub const SHADOW_FORMAT: Format = Format::D16Unorm;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// Bias for surfaces facing the light, in the light's 0 to 1 depth range.
+    pub depth_bias: f32,
+    /// Bias added as surfaces turn away from the light, up to this much at grazing angles.
+    pub slope_bias: f32,
+    /// Directional lights only. Clamped to `MAX_CASCADES`.
+    pub cascade_count: u32,
+    /// Directional lights only: how far from the camera shadows reach.
+    pub max_distance: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> ShadowSettings {
+        ShadowSettings {
+            depth_bias: 0.0005,
+            slope_bias: 0.003,
+            cascade_count: 3,
+            max_distance: 50.0,
+        }
+    }
+}
+
+/// `ShadowCascade` as default.fs reads it from the shadow buffer, with std430 layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuShadowCascade {
+    matrix: [[f32; 4]; 4],
+    atlas_rect: [f32; 4],
+    split_depth: f32,
+    _padding: [f32; 3],
+}
+
+pub type ShadowBuffer = CpuBufferPoolChunk<GpuShadowCascade, Arc<StdMemoryPool>>;
+
+/// One atlas tile to render this frame.
+pub struct ShadowView {
+    pub view_projection: Mat4,
+    pub viewport: Viewport,
+}
+
+/// Which tiles of the atlas each light of a draw list got this frame.
+pub struct ShadowFrame {
+    pub views: Vec<ShadowView>,
+    pub cascades: Vec<GpuShadowCascade>,
+    /// Per light, the index of its first cascade and how many it has.
+    pub light_cascades: Vec<Option<(usize, usize)>>,
+}
+
+impl ShadowFrame {
+    /// Lights are given tiles in order until the atlas is full; the rest cast no shadows.
+    pub fn new(lights: &[Light], view: &Mat4, projection: &Mat4) -> ShadowFrame {
+        let tiles_per_row = SHADOW_ATLAS_SIZE / SHADOW_TILE_SIZE;
+        let tile_count = (tiles_per_row * tiles_per_row) as usize;
+        let inverse_view = inverse(view);
+
+        let mut frame = ShadowFrame {
+            views: Vec::new(),
+            cascades: Vec::new(),
+            light_cascades: Vec::new(),
+        };
+        for light in lights {
+            let splits = match (light.shadow, light.kind) {
+                (Some(settings), LightKind::Directional) => {
+                    directional_cascades(light, &settings, view, projection)
+                }
+                (Some(_), LightKind::Spot { .. }) => {
+                    vec![(spot_view_projection(light), ::std::f32::MAX)]
+                }
+                _ => Vec::new(),
+            };
+            if splits.is_empty() || frame.views.len() + splits.len() > tile_count {
+                frame.light_cascades.push(None);
+                continue;
+            }
+
+            frame
+                .light_cascades
+                .push(Some((frame.cascades.len(), splits.len())));
+            for (view_projection, split_depth) in splits {
+                let tile = frame.views.len() as u32;
+                let origin = [
+                    ((tile % tiles_per_row) * SHADOW_TILE_SIZE) as f32,
+                    ((tile / tiles_per_row) * SHADOW_TILE_SIZE) as f32,
+                ];
+                let size = SHADOW_TILE_SIZE as f32;
+                let atlas_size = SHADOW_ATLAS_SIZE as f32;
+
+                frame.cascades.push(GpuShadowCascade {
+                    matrix: (view_projection * inverse_view).into(),
+                    atlas_rect: [
+                        origin[0] / atlas_size,
+                        origin[1] / atlas_size,
+                        size / atlas_size,
+                        size / atlas_size,
+                    ],
+                    split_depth: split_depth,
+                    _padding: [0.0; 3],
+                });
+                frame.views.push(ShadowView {
+                    view_projection: view_projection,
+                    viewport: Viewport {
+                        origin: origin,
+                        dimensions: [size, size],
+                        depth_range: 0.0..1.0,
+                    },
+                });
+            }
+        }
+        frame
+    }
+}
+
+/// Splits the camera frustum, up to `max_distance`, into cascades that get closer together near
+/// the camera, and fits an orthographic projection around each. Returns the projections with the
+/// view space depth each cascade reaches to.
+fn directional_cascades(
+    light: &Light,
+    settings: &ShadowSettings,
+    view: &Mat4,
+    projection: &Mat4,
+) -> Vec<(Mat4, f32)> {
+    let inverse_projection = inverse(projection);
+    let inverse_view = inverse(view);
+    let view_corner = |x: f32, y: f32, z: f32| {
+        let corner = inverse_projection * vec4(x, y, z, 1.0);
+        vec3(corner.x, corner.y, corner.z) / corner.w
+    };
+    let xy = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let near_corners: Vec<Vec3> = xy.iter().map(|&(x, y)| view_corner(x, y, -1.0)).collect();
+    let far_corners: Vec<Vec3> = xy.iter().map(|&(x, y)| view_corner(x, y, 1.0)).collect();
+    let near = -near_corners[0].z;
+    let far = -far_corners[0].z;
+    let shadow_far = far.min(settings.max_distance);
+
+    let count = settings.cascade_count.max(1).min(MAX_CASCADES);
+    let mut cascades = Vec::new();
+    let mut start = near;
+    for i in 1..count + 1 {
+        // Mostly logarithmic, so near cascades are small, blended with even splits
+        let t = i as f32 / count as f32;
+        let even = near + (shadow_far - near) * t;
+        let logarithmic = near * (shadow_far / near).powf(t);
+        let end = even + (logarithmic - even) * 0.75;
+
+        // The slice of the frustum between depths start and end, in world space
+        let mut corners = Vec::new();
+        for depth in &[start, end] {
+            let t = (depth - near) / (far - near);
+            for (n, f) in near_corners.iter().zip(far_corners.iter()) {
+                let corner = n + (f - n) * t;
+                let world = inverse_view * vec4(corner.x, corner.y, corner.z, 1.0);
+                corners.push(vec3(world.x, world.y, world.z));
+            }
+        }
+
+        // Fitting a sphere rather than a box keeps the projection the same size as the camera turns
+        let center = corners.iter().fold(vec3(0.0, 0.0, 0.0), |sum, c| sum + c) / 8.0;
+        let radius = corners
+            .iter()
+            .map(|c| (c - center).norm())
+            .fold(0.0, f32::max)
+            .max(0.01);
+
+        // Backed off by another radius so casters between the light and the slice are included
+        let eye = center - light.direction * radius * 2.0;
+        let light_view = look_at(&eye, &center, &up_vector(&light.direction));
+        let light_projection = ortho_rh_zo(-radius, radius, -radius, radius, 0.0, radius * 3.0);
+        cascades.push((light_projection * light_view, end));
+        start = end;
+    }
+    cascades
+}
+
+fn spot_view_projection(light: &Light) -> Mat4 {
+    let outer_angle = match light.kind {
+        LightKind::Spot { outer_angle, .. } => outer_angle,
+        _ => unreachable!(),
+    };
+    let light_view = look_at(
+        &light.position,
+        &(light.position + light.direction),
+        &up_vector(&light.direction),
+    );
+    let near = (light.range * 0.01).max(0.05);
+    let fov = (outer_angle * 2.0).min(3.0);
+    perspective_rh_zo(1.0, fov, near, light.range) * light_view
+}
+
+/// Any up vector works for a light, as long as it isn't parallel to the light's direction.
+fn up_vector(direction: &Vec3) -> Vec3 {
+    if normalize(direction).y.abs() > 0.99 {
+        vec3(1.0, 0.0, 0.0)
+    } else {
+        vec3(0.0, 1.0, 0.0)
+    }
+}
+
+/// The push constants of shadow.vs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ShadowPushConstants {
+    light_view_projection: [[f32; 4]; 4],
+}
+
+/// Renders the shadow atlas, and owns what default.fs needs to sample it besides the atlas itself,
+/// which is an image of the renderer's graph.
+pub struct ShadowRenderer {
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    sampler: Arc<Sampler>,
+    program: ShaderProgram,
+    pipeline: Arc<MeshPipeline>,
+    cascade_pool: CpuBufferPool<GpuShadowCascade>,
+}
+
+impl ShadowRenderer {
+    /// `render_pass` draws into the atlas, a `SHADOW_ATLAS_SIZE` square image in `SHADOW_FORMAT`.
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> ShadowRenderer {
+        let program = match ShaderProgram::create_program(device.clone(), SHADOW_SHADER) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the shadow shader:\n{}", e),
+        };
+        let pipeline = match create_pipeline(device.clone(), render_pass.clone(), &program) {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the shadow pipeline:\n{}", e),
+        };
+
+        // Depths are compared in the shader, so they must not be filtered
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        ShadowRenderer {
+            cascade_pool: CpuBufferPool::new(
+                device.clone(),
+                BufferUsage {
+                    storage_buffer: true,
+                    ..BufferUsage::none()
+                },
+            ),
+            device: device,
+            render_pass: render_pass,
+            sampler: sampler,
+            program: program,
+            pipeline: pipeline,
+        }
+    }
+
+    /// Recompiles shadow.vs and shadow.fs, keeping the current pipeline if that fails.
+    pub fn reload_shader(&mut self) -> Result<(), ShaderError> {
+        let program = self.program.reload(self.device.clone(), SHADOW_SHADER)?;
+        self.pipeline = create_pipeline(self.device.clone(), self.render_pass.clone(), &program)?;
+        self.program = program;
+        Ok(())
+    }
+
+    pub fn sampler(&self) -> Arc<Sampler> {
+        self.sampler.clone()
+    }
+
+    /// The cascades of `frame`, for default.fs's shadow buffer.
+    pub fn cascade_buffer(&self, frame: &ShadowFrame) -> Arc<ShadowBuffer> {
+        // A buffer can't be empty, and no light refers to this placeholder
+        let cascades = if frame.cascades.is_empty() {
+            vec![GpuShadowCascade::default()]
+        } else {
+            frame.cascades.clone()
+        };
+        Arc::new(self.cascade_pool.chunk(cascades).unwrap())
+    }
+
+    /// Draws every batch into every tile of `frame` in the begun shadow pass, and returns how many
+    /// draw calls that took. `instance_buffer` holds the batches' instances; it is `None` when
+    /// there is nothing to draw.
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        frame: &ShadowFrame,
+        meshes: &[GpuMesh],
+        batches: &[DrawBatch],
+        instance_buffer: Option<&Arc<InstanceBuffer>>,
+    ) -> (AutoCommandBufferBuilder, usize) {
+        let mut builder = builder;
+        let mut draw_calls = 0;
+
+        if let Some(instance_buffer) = instance_buffer {
+            for view in &frame.views {
+                let dynamic_state = DynamicState {
+                    viewports: Some(vec![view.viewport.clone()]),
+                    ..DynamicState::none()
+                };
+                let push_constants = ShadowPushConstants {
+                    light_view_projection: view.view_projection.into(),
+                };
+                for batch in batches {
+                    let mesh = &meshes[batch.mesh.0];
+                    builder = builder
+                        .draw_indexed(
+                            self.pipeline.clone(),
+                            &dynamic_state,
+                            (mesh.vertex_buffer.clone(), batch.instances(instance_buffer)),
+                            mesh.index_buffer.clone(),
+                            (),
+                            push_constants,
+                        )
+                        .unwrap();
+                    draw_calls += 1;
+                }
+            }
+        }
+
+        (builder, draw_calls)
+    }
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<MeshPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(OneVertexOneInstanceDefinition::<Vertex, InstanceData>::new())
+        .vertex_shader(program.vertex_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .depth_stencil(DepthStencil {
+            depth_write: true,
+            depth_compare: Compare::LessOrEqual,
+            depth_bounds_test: DepthBounds::Disabled,
+            stencil_front: Default::default(),
+            stencil_back: Default::default(),
+        })
+        .fragment_shader(program.fragment_entry_point(), ())
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
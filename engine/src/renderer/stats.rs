@@ -0,0 +1,11 @@
+/// What the renderer did for the last frame, from `Renderer::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    /// Draw commands in the draw list.
+    pub commands: usize,
+    /// Instanced draw calls of the scene pass, one per mesh and material pair.
+    pub draw_calls: usize,
+    /// Draw calls of the shadow pass, over all tiles of the shadow atlas, shadow casters out of
+    /// view included.
+    pub shadow_draw_calls: usize,
+}
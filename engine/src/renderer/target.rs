@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::attachment::AttachmentImage;
+use vulkano::image::{ImageUsage, ImageViewAccess, SwapchainImage};
+use vulkano::swapchain::{Surface, Swapchain};
+use winit::window::Window;
+
+/// Format of offscreen targets. sRGB so the pixels read back look like they do in a window.
+pub const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8Srgb;
+pub const DEPTH_FORMAT: Format = Format::D16Unorm;
+/// The scene is drawn in linear color that can go past 1, for post-processing to bring back down.
+pub const HDR_FORMAT: Format = Format::R16G16B16A16Sfloat;
+
+/// What the renderer draws into: the window's swapchain, or an image that is never presented.
+pub enum RenderTarget {
+    Window {
+        surface: Arc<Surface<Window>>,
+        swapchain: Arc<Swapchain<Window>>,
+        images: Vec<Arc<SwapchainImage<Window>>>,
+    },
+    Offscreen {
+        image: Arc<AttachmentImage>,
+    },
+}
+
+impl RenderTarget {
+    pub fn offscreen(device: Arc<Device>, dimensions: [u32; 2]) -> RenderTarget {
+        let usage = ImageUsage {
+            color_attachment: true,
+            transfer_source: true,
+            ..ImageUsage::none()
+        };
+        RenderTarget::Offscreen {
+            image: AttachmentImage::with_usage(device, dimensions, OFFSCREEN_FORMAT, usage)
+                .unwrap(),
+        }
+    }
+
+    pub fn format(&self) -> Format {
+        match *self {
+            RenderTarget::Window { ref swapchain, .. } => swapchain.format(),
+            RenderTarget::Offscreen { .. } => OFFSCREEN_FORMAT,
+        }
+    }
+
+    pub fn dimensions(&self) -> [u32; 2] {
+        match *self {
+            RenderTarget::Window { ref swapchain, .. } => swapchain.dimensions(),
+            RenderTarget::Offscreen { ref image } => AttachmentImage::dimensions(image),
+        }
+    }
+
+    pub fn window(&self) -> Option<&Window> {
+        match *self {
+            RenderTarget::Window { ref surface, .. } => Some(surface.window()),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// The swapchain images, or the offscreen image, for the render graph to draw into.
+    pub fn images(&self) -> Vec<Arc<dyn ImageViewAccess + Send + Sync>> {
+        match *self {
+            RenderTarget::Window { ref images, .. } => images
+                .iter()
+                .map(|image| image.clone() as Arc<dyn ImageViewAccess + Send + Sync>)
+                .collect(),
+            RenderTarget::Offscreen { ref image } => {
+                vec![image.clone() as Arc<dyn ImageViewAccess + Send + Sync>]
+            }
+        }
+    }
+}
+
+/// The largest sample count up to `requested` that `supported`, a bit mask of sample counts as
+/// Vulkan reports them, contains.
+pub fn choose_sample_count(requested: u32, supported: u32) -> u32 {
+    let mut samples = 64;
+    while samples > 1 && (samples > requested || supported & samples == 0) {
+        samples /= 2;
+    }
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sample count bits as Vulkan reports them, one bit per supported count.
+    const UP_TO_8: u32 = 1 | 2 | 4 | 8;
+
+    #[test]
+    fn supported_sample_counts_are_kept() {
+        for &samples in &[1, 2, 4, 8] {
+            assert_eq!(choose_sample_count(samples, UP_TO_8), samples);
+        }
+    }
+
+    #[test]
+    fn unsupported_sample_counts_are_lowered() {
+        assert_eq!(choose_sample_count(16, UP_TO_8), 8);
+        assert_eq!(choose_sample_count(64, UP_TO_8), 8);
+        assert_eq!(choose_sample_count(8, 1 | 2 | 4), 4);
+        // Counts in between are skipped, never raised past the request
+        assert_eq!(choose_sample_count(8, 1 | 2 | 16), 2);
+        assert_eq!(choose_sample_count(3, UP_TO_8), 2);
+        assert_eq!(choose_sample_count(4, 1), 1);
+    }
+
+    #[test]
+    fn no_msaa_is_one_sample() {
+        assert_eq!(choose_sample_count(0, UP_TO_8), 1);
+        assert_eq!(choose_sample_count(1, UP_TO_8), 1);
+        assert_eq!(choose_sample_count(1000, 0), 1);
+    }
+}
@@ -0,0 +1,174 @@
+use drawing::Color4;
+use glm::{vec4, Mat4};
+use specs::storage::ComponentEvent;
+use specs::{Component, Entity, FlaggedStorage, Join, ReaderId, VecStorage, World, WorldExt};
+use std::collections::HashSet;
+
+use super::bvh::{Bvh, BvhProxy};
+use super::culling::{Aabb, CullingStats, Frustum};
+use super::draw_list::DrawList;
+use super::resources::{MaterialHandle, MeshHandle};
+use super::shadow::ShadowFrame;
+
+/// A mesh drawn with its entity. The transform is in world space.
+///
+/// The storage is flagged, so `VisibilityIndex` only looks at renderables that were inserted,
+/// removed or borrowed mutably since its last update.
+#[derive(Clone, Debug)]
+pub struct Renderable {
+    pub mesh: MeshHandle,
+    pub material: MaterialHandle,
+    pub transform: Mat4,
+    pub tint: Color4,
+}
+
+impl Component for Renderable {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+impl Renderable {
+    pub fn new(mesh: MeshHandle, material: MaterialHandle, transform: Mat4) -> Renderable {
+        Renderable {
+            mesh: mesh,
+            material: material,
+            transform: transform,
+            tint: vec4(1.0, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// The world bounds of every `Renderable` in a BVH, for adding only the visible ones to a draw
+/// list, along with the ones out of view that are inside a shadow casting light's view.
+pub struct VisibilityIndex {
+    bvh: Bvh<Entity>,
+    /// By entity id.
+    proxies: Vec<Option<BvhProxy>>,
+    reader: ReaderId<ComponentEvent>,
+    /// Renderables that were in the world before the index, added by the first update.
+    existing: Vec<u32>,
+    stats: CullingStats,
+}
+
+impl VisibilityIndex {
+    /// Starts listening for changes to renderables; the ones already in `world` are added too.
+    pub fn new(world: &mut World) -> VisibilityIndex {
+        world.register::<Renderable>();
+        let mut renderables = world.write_storage::<Renderable>();
+        let existing = (&world.entities(), &renderables)
+            .join()
+            .map(|(entity, _)| entity.id())
+            .collect();
+
+        VisibilityIndex {
+            bvh: Bvh::new(),
+            proxies: Vec::new(),
+            reader: renderables.register_reader(),
+            existing: existing,
+            stats: CullingStats::default(),
+        }
+    }
+
+    /// Brings the BVH up to date with the renderables that changed since the last update.
+    /// `mesh_bounds` gives the local bounds of a mesh, like `Renderer::mesh_bounds`.
+    pub fn update<F>(&mut self, world: &World, mesh_bounds: F)
+    where
+        F: Fn(MeshHandle) -> Aabb,
+    {
+        let entities = world.entities();
+        let renderables = world.read_storage::<Renderable>();
+        let events: Vec<ComponentEvent> = self
+            .existing
+            .drain(..)
+            .map(ComponentEvent::Inserted)
+            .chain(renderables.channel().read(&mut self.reader).cloned())
+            .collect();
+
+        for event in events {
+            let id = match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => id,
+                ComponentEvent::Removed(id) => {
+                    if let Some(proxy) = self.proxy_mut(id).take() {
+                        self.bvh.remove(proxy);
+                    }
+                    continue;
+                }
+            };
+
+            // Renderables removed again before this update leave nothing to look at
+            let entity = entities.entity(id);
+            let bounds = match renderables.get(entity) {
+                Some(renderable) => mesh_bounds(renderable.mesh).transformed(&renderable.transform),
+                None => continue,
+            };
+            let proxy = *self.proxy_mut(id);
+            match proxy {
+                Some(proxy) => {
+                    self.bvh.update(proxy, bounds);
+                }
+                None => *self.proxy_mut(id) = Some(self.bvh.insert(bounds, entity)),
+            }
+        }
+    }
+
+    /// Adds the renderables that may be in view of `draw_list`'s camera to it, and as shadow
+    /// casters the ones out of view that may be in view of one of its shadow casting lights.
+    /// Add the lights first.
+    pub fn gather_visible(&mut self, world: &World, draw_list: &mut DrawList) {
+        let renderables = world.read_storage::<Renderable>();
+        let frustum = Frustum::from_view_projection(&(draw_list.projection * draw_list.view));
+
+        let mut visible = HashSet::new();
+        let mut tests = self.bvh.query_frustum(&frustum, |entity| {
+            if let Some(renderable) = renderables.get(*entity) {
+                draw_list.draw_tinted(
+                    renderable.mesh,
+                    renderable.material,
+                    renderable.transform,
+                    renderable.tint,
+                );
+                visible.insert(*entity);
+            }
+        });
+
+        // The same tiles the renderer will give the lights
+        let shadow_frame =
+            ShadowFrame::new(&draw_list.lights, &draw_list.view, &draw_list.projection);
+        let mut casters = HashSet::new();
+        for view in &shadow_frame.views {
+            let frustum = Frustum::from_view_projection(&view.view_projection);
+            tests += self.bvh.query_frustum(&frustum, |entity| {
+                if visible.contains(entity) || !casters.insert(*entity) {
+                    return;
+                }
+                if let Some(renderable) = renderables.get(*entity) {
+                    draw_list.cast_shadow(
+                        renderable.mesh,
+                        renderable.material,
+                        renderable.transform,
+                    );
+                }
+            });
+        }
+
+        self.stats = CullingStats {
+            total: self.bvh.len(),
+            visible: visible.len(),
+            shadow_casters: casters.len(),
+            culled: self.bvh.len() - visible.len() - casters.len(),
+            tests: tests,
+        };
+    }
+
+    /// Culling statistics of the last `gather_visible`.
+    pub fn stats(&self) -> CullingStats {
+        self.stats
+    }
+
+    fn proxy_mut(&mut self, id: u32) -> &mut Option<BvhProxy> {
+        let id = id as usize;
+        if id >= self.proxies.len() {
+            self.proxies.resize(id + 1, None);
+        }
+        &mut self.proxies[id]
+    }
+}
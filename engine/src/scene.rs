@@ -0,0 +1,782 @@
+//! Scene files, XML with one element per entity and one child element per component:
+//!
+//! ```text
+//! <Scene>
+//!     <Entity Name="crate">
+//!         <Renderable Mesh="box" Material="crate" Position="0,1,0" Rotation="0,45,0" Scale="1,1,1" Tint="1,1,1,1"/>
+//!     </Entity>
+//!     <Entity Name="sun">
+//!         <Light Kind="directional" Color="1,0.95,0.9" Intensity="0.6" Direction="-0.5,-1,-0.5">
+//!             <Shadows DepthBias="0.0005" SlopeBias="0.003" Cascades="3" MaxDistance="50"/>
+//!         </Light>
+//!     </Entity>
+//!     <Entity Name="camera">
+//!         <Camera Position="0,2,6" Yaw="0" Pitch="-15" Fov="57" Near="0.01" Far="1000"/>
+//!     </Entity>
+//! </Scene>
+//! ```
+//!
+//! Meshes and materials are named through `SceneAssets`. Rotations, spot light angles and camera
+//! angles are in degrees. Attributes left out keep the values of `Renderable::new`, the `Light`
+//! constructors and `Camera::new`.
+
+use camera::Camera;
+use drawing::Color3;
+use glm::{identity, normalize, vec3, vec4, Vec3};
+use renderer::{Light, LightKind, MaterialHandle, MeshHandle, Renderable, ShadowSettings};
+use specs::{Builder, Component, Entity, Join, VecStorage, World, WorldExt};
+use transform::Transform;
+use xml::common::Position;
+use xml::reader::{EventReader, XmlEvent};
+
+use std::f32::consts::PI;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// What an entity is called in the inspector and in scene files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Name(pub String);
+
+impl Component for Name {
+    type Storage = VecStorage<Self>;
+}
+
+/// Registers the components scene files hold.
+pub fn register_components(world: &mut World) {
+    world.register::<Name>();
+    world.register::<Renderable>();
+    world.register::<Light>();
+    world.register::<Camera>();
+}
+
+/// The names scene files refer to meshes and materials by.
+#[derive(Clone, Debug, Default)]
+pub struct SceneAssets {
+    meshes: Vec<(String, MeshHandle)>,
+    materials: Vec<(String, MaterialHandle)>,
+}
+
+impl SceneAssets {
+    pub fn new() -> SceneAssets {
+        SceneAssets::default()
+    }
+
+    pub fn add_mesh(&mut self, name: &str, mesh: MeshHandle) {
+        self.meshes.push((name.to_string(), mesh));
+    }
+
+    pub fn add_material(&mut self, name: &str, material: MaterialHandle) {
+        self.materials.push((name.to_string(), material));
+    }
+
+    pub fn meshes(&self) -> &[(String, MeshHandle)] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[(String, MaterialHandle)] {
+        &self.materials
+    }
+
+    pub fn mesh(&self, name: &str) -> Option<MeshHandle> {
+        self.meshes.iter().find(|m| m.0 == name).map(|m| m.1)
+    }
+
+    pub fn material(&self, name: &str) -> Option<MaterialHandle> {
+        self.materials.iter().find(|m| m.0 == name).map(|m| m.1)
+    }
+
+    pub fn mesh_name(&self, mesh: MeshHandle) -> Option<&str> {
+        self.meshes
+            .iter()
+            .find(|m| m.1 == mesh)
+            .map(|m| m.0.as_str())
+    }
+
+    pub fn material_name(&self, material: MaterialHandle) -> Option<&str> {
+        self.materials
+            .iter()
+            .find(|m| m.1 == material)
+            .map(|m| m.0.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, io::Error),
+    Xml(PathBuf, xml::reader::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// Saving an entity whose mesh or material has no name in the `SceneAssets`.
+    UnnamedAsset(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            SceneError::Xml(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            SceneError::Parse {
+                ref path,
+                line,
+                ref message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            SceneError::UnnamedAsset(ref entity) => write!(
+                f,
+                "the mesh or material of '{}' has no name in the scene assets",
+                entity
+            ),
+        }
+    }
+}
+
+/// The components of one `<Entity>`, held until the whole file has parsed.
+struct SceneEntity {
+    name: String,
+    renderable: Option<Renderable>,
+    light: Option<Light>,
+    camera: Option<Camera>,
+}
+
+/// Adds the entities of the scene file at `path` to `world` and returns them.
+pub fn load_scene(
+    world: &mut World,
+    path: &Path,
+    assets: &SceneAssets,
+) -> Result<Vec<Entity>, SceneError> {
+    let file = File::open(path).map_err(|e| SceneError::Io(path.to_path_buf(), e))?;
+    let mut reader = EventReader::new(BufReader::new(file));
+
+    // Everything is parsed before anything is added, so a broken file adds nothing
+    let mut entities: Vec<SceneEntity> = Vec::new();
+    let mut in_entity = false;
+    loop {
+        let event = reader
+            .next()
+            .map_err(|e| SceneError::Xml(path.to_path_buf(), e))?;
+        let line = reader.position().row as usize + 1;
+        let error = |message: String| SceneError::Parse {
+            path: path.to_path_buf(),
+            line: line,
+            message: message,
+        };
+
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let attribute = |key: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == key)
+                        .map(|a| a.value.as_str())
+                };
+                match (name.local_name.as_str(), in_entity) {
+                    ("Scene", false) => {}
+                    ("Entity", false) => {
+                        let name = attribute("Name").unwrap_or("").to_string();
+                        entities.push(SceneEntity {
+                            name: name,
+                            renderable: None,
+                            light: None,
+                            camera: None,
+                        });
+                        in_entity = true;
+                    }
+                    (element, true) => {
+                        let entity = entities.last_mut().unwrap();
+                        match element {
+                            "Renderable" => {
+                                entity.renderable =
+                                    Some(parse_renderable(&attribute, assets).map_err(&error)?);
+                            }
+                            "Light" => {
+                                entity.light = Some(parse_light(&attribute).map_err(&error)?)
+                            }
+                            "Camera" => {
+                                entity.camera = Some(parse_camera(&attribute).map_err(&error)?)
+                            }
+                            "Shadows" => match entity.light {
+                                Some(ref mut light) => {
+                                    light.shadow = Some(parse_shadows(&attribute).map_err(&error)?);
+                                }
+                                None => {
+                                    return Err(error("Shadows has to be inside a Light".into()))
+                                }
+                            },
+                            _ => return Err(error(format!("unknown component `{}`", element))),
+                        }
+                    }
+                    (element, false) => {
+                        return Err(error(format!("unexpected element `{}`", element)))
+                    }
+                }
+            }
+            XmlEvent::EndElement { ref name } if name.local_name == "Entity" => in_entity = false,
+            XmlEvent::EndDocument => break,
+            _ => {}
+        }
+    }
+
+    Ok(entities
+        .into_iter()
+        .map(|entity| {
+            let mut builder = world.create_entity().with(Name(entity.name));
+            if let Some(renderable) = entity.renderable {
+                builder = builder.with(renderable);
+            }
+            if let Some(light) = entity.light {
+                builder = builder.with(light);
+            }
+            if let Some(camera) = entity.camera {
+                builder = builder.with(camera);
+            }
+            builder.build()
+        })
+        .collect())
+}
+
+/// Writes every entity with a name, renderable, light or camera in `world` to `path`.
+pub fn save_scene(world: &World, path: &Path, assets: &SceneAssets) -> Result<(), SceneError> {
+    let entities = world.entities();
+    let names = world.read_storage::<Name>();
+    let renderables = world.read_storage::<Renderable>();
+    let lights = world.read_storage::<Light>();
+    let cameras = world.read_storage::<Camera>();
+
+    let mut xml = String::from("<Scene>\n");
+    for (entity, name, renderable, light, camera) in (
+        &entities,
+        names.maybe(),
+        renderables.maybe(),
+        lights.maybe(),
+        cameras.maybe(),
+    )
+        .join()
+    {
+        if name.is_none() && renderable.is_none() && light.is_none() && camera.is_none() {
+            continue;
+        }
+        let name = match name {
+            Some(name) => name.0.clone(),
+            None => format!("Entity {}", entity.id()),
+        };
+
+        xml.push_str(&format!("\t<Entity Name=\"{}\">\n", escape(&name)));
+        if let Some(renderable) = renderable {
+            let mesh = assets.mesh_name(renderable.mesh);
+            let material = assets.material_name(renderable.material);
+            let (mesh, material) = match (mesh, material) {
+                (Some(mesh), Some(material)) => (mesh, material),
+                _ => return Err(SceneError::UnnamedAsset(name)),
+            };
+            let transform = Transform::from_matrix(&renderable.transform);
+            xml.push_str(&format!(
+                "\t\t<Renderable Mesh=\"{}\" Material=\"{}\" Position=\"{}\" Rotation=\"{}\" \
+                 Scale=\"{}\" Tint=\"{}\"/>\n",
+                escape(mesh),
+                escape(material),
+                join(transform.position.as_slice()),
+                join((transform.rotation * (180.0 / PI)).as_slice()),
+                join(transform.scale.as_slice()),
+                join(renderable.tint.as_slice()),
+            ));
+        }
+        if let Some(light) = light {
+            xml.push_str(&light_element(light));
+        }
+        if let Some(camera) = camera {
+            xml.push_str(&format!(
+                "\t\t<Camera Position=\"{}\" Yaw=\"{}\" Pitch=\"{}\" Fov=\"{}\" Near=\"{}\" \
+                 Far=\"{}\"/>\n",
+                join(camera.position.as_slice()),
+                camera.yaw * (180.0 / PI),
+                camera.pitch * (180.0 / PI),
+                camera.fov * (180.0 / PI),
+                camera.near,
+                camera.far,
+            ));
+        }
+        xml.push_str("\t</Entity>\n");
+    }
+    xml.push_str("</Scene>\n");
+
+    fs::write(path, xml).map_err(|e| SceneError::Io(path.to_path_buf(), e))
+}
+
+fn parse_renderable<'a, A>(attribute: &A, assets: &SceneAssets) -> Result<Renderable, String>
+where
+    A: Fn(&str) -> Option<&'a str>,
+{
+    let mesh = attribute("Mesh").ok_or("a renderable needs a Mesh")?;
+    let material = attribute("Material").ok_or("a renderable needs a Material")?;
+    let mesh = assets
+        .mesh(mesh)
+        .ok_or_else(|| format!("unknown mesh `{}`", mesh))?;
+    let material = assets
+        .material(material)
+        .ok_or_else(|| format!("unknown material `{}`", material))?;
+
+    let mut transform = Transform::new();
+    if let Some(position) = attribute("Position") {
+        transform.position = parse_vec3(position)?;
+    }
+    if let Some(rotation) = attribute("Rotation") {
+        transform.rotation = parse_vec3(rotation)? * (PI / 180.0);
+    }
+    if let Some(scale) = attribute("Scale") {
+        transform.scale = parse_vec3(scale)?;
+    }
+
+    let mut renderable = Renderable::new(mesh, material, identity());
+    renderable.transform = transform.matrix();
+    if let Some(tint) = attribute("Tint") {
+        let t = parse_floats(tint, 4)?;
+        renderable.tint = vec4(t[0], t[1], t[2], t[3]);
+    }
+    Ok(renderable)
+}
+
+fn parse_light<'a, A>(attribute: &A) -> Result<Light, String>
+where
+    A: Fn(&str) -> Option<&'a str>,
+{
+    let color: Color3 = vec3(1.0, 1.0, 1.0);
+    let origin = vec3(0.0, 0.0, 0.0);
+    let down = vec3(0.0, -1.0, 0.0);
+    let mut light = match attribute("Kind").unwrap_or("point") {
+        "point" => Light::point(origin, color, 1.0, 10.0),
+        "spot" => Light::spot(origin, down, color, 1.0, 10.0, PI / 9.0, PI / 6.0),
+        "directional" => Light::directional(down, color, 1.0),
+        kind => return Err(format!("unknown light kind `{}`", kind)),
+    };
+
+    if let Some(color) = attribute("Color") {
+        light.color = parse_vec3(color)?;
+    }
+    if let Some(intensity) = attribute("Intensity") {
+        light.intensity = parse_floats(intensity, 1)?[0];
+    }
+    if let Some(range) = attribute("Range") {
+        light.range = parse_floats(range, 1)?[0];
+    }
+    if let Some(position) = attribute("Position") {
+        light.position = parse_vec3(position)?;
+    }
+    if let Some(direction) = attribute("Direction") {
+        light.direction = normalize(&parse_vec3(direction)?);
+    }
+    if let LightKind::Spot {
+        ref mut inner_angle,
+        ref mut outer_angle,
+    } = light.kind
+    {
+        if let Some(angle) = attribute("InnerAngle") {
+            *inner_angle = parse_floats(angle, 1)?[0] * (PI / 180.0);
+        }
+        if let Some(angle) = attribute("OuterAngle") {
+            *outer_angle = parse_floats(angle, 1)?[0] * (PI / 180.0);
+        }
+    }
+    Ok(light)
+}
+
+fn parse_camera<'a, A>(attribute: &A) -> Result<Camera, String>
+where
+    A: Fn(&str) -> Option<&'a str>,
+{
+    let mut camera = Camera::new(vec3(0.0, 0.0, 0.0));
+    if let Some(position) = attribute("Position") {
+        camera.position = parse_vec3(position)?;
+    }
+    if let Some(yaw) = attribute("Yaw") {
+        camera.yaw = parse_floats(yaw, 1)?[0] * (PI / 180.0);
+    }
+    if let Some(pitch) = attribute("Pitch") {
+        camera.pitch = parse_floats(pitch, 1)?[0] * (PI / 180.0);
+    }
+    if let Some(fov) = attribute("Fov") {
+        camera.fov = parse_floats(fov, 1)?[0] * (PI / 180.0);
+    }
+    if let Some(near) = attribute("Near") {
+        camera.near = parse_floats(near, 1)?[0];
+    }
+    if let Some(far) = attribute("Far") {
+        camera.far = parse_floats(far, 1)?[0];
+    }
+    Ok(camera)
+}
+
+fn parse_shadows<'a, A>(attribute: &A) -> Result<ShadowSettings, String>
+where
+    A: Fn(&str) -> Option<&'a str>,
+{
+    let mut settings = ShadowSettings::default();
+    if let Some(bias) = attribute("DepthBias") {
+        settings.depth_bias = parse_floats(bias, 1)?[0];
+    }
+    if let Some(bias) = attribute("SlopeBias") {
+        settings.slope_bias = parse_floats(bias, 1)?[0];
+    }
+    if let Some(count) = attribute("Cascades") {
+        settings.cascade_count = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("expected a whole number, got `{}`", count))?;
+    }
+    if let Some(distance) = attribute("MaxDistance") {
+        settings.max_distance = parse_floats(distance, 1)?[0];
+    }
+    Ok(settings)
+}
+
+fn light_element(light: &Light) -> String {
+    let (kind, angles) = match light.kind {
+        LightKind::Point => ("point", String::new()),
+        LightKind::Spot {
+            inner_angle,
+            outer_angle,
+        } => (
+            "spot",
+            format!(
+                " InnerAngle=\"{}\" OuterAngle=\"{}\"",
+                inner_angle * (180.0 / PI),
+                outer_angle * (180.0 / PI)
+            ),
+        ),
+        LightKind::Directional => ("directional", String::new()),
+    };
+    let mut element = format!(
+        "\t\t<Light Kind=\"{}\" Color=\"{}\" Intensity=\"{}\" Range=\"{}\" Position=\"{}\" \
+         Direction=\"{}\"{}",
+        kind,
+        join(light.color.as_slice()),
+        light.intensity,
+        light.range,
+        join(light.position.as_slice()),
+        join(light.direction.as_slice()),
+        angles
+    );
+    match light.shadow {
+        Some(ref shadow) => element.push_str(&format!(
+            ">\n\t\t\t<Shadows DepthBias=\"{}\" SlopeBias=\"{}\" Cascades=\"{}\" \
+             MaxDistance=\"{}\"/>\n\t\t</Light>\n",
+            shadow.depth_bias, shadow.slope_bias, shadow.cascade_count, shadow.max_distance
+        )),
+        None => element.push_str("/>\n"),
+    }
+    element
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    let v = parse_floats(value, 3)?;
+    Ok(vec3(v[0], v[1], v[2]))
+}
+
+/// Vectors are written as comma separated numbers, like `Size="5.0,5.0"`.
+fn parse_floats(value: &str, count: usize) -> Result<Vec<f32>, String> {
+    let floats: Result<Vec<f32>, _> = value.split(',').map(|v| v.trim().parse()).collect();
+    match floats {
+        Ok(ref floats) if floats.len() == count => Ok(floats.clone()),
+        _ => Err(format!("expected {} numbers, got `{}`", count, value)),
+    }
+}
+
+fn join(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    values.join(",")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::{Mat4, Vec4};
+    use std::{env, process};
+
+    fn assets() -> SceneAssets {
+        let mut assets = SceneAssets::new();
+        assets.add_mesh("box", MeshHandle(0));
+        assets.add_mesh("plane", MeshHandle(1));
+        assets.add_material("crate", MaterialHandle(2));
+        assets
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        register_components(&mut world);
+        world
+    }
+
+    /// A file in the temp directory unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("engine_scene_{}_{}.xml", name, process::id()))
+    }
+
+    /// Loads `xml` into a new world and returns the error's line and message.
+    fn load_error(name: &str, xml: &str) -> (usize, String) {
+        let path = temp_path(name);
+        fs::write(&path, xml).unwrap();
+        let mut world = world();
+        let result = load_scene(&mut world, &path, &assets());
+        fs::remove_file(&path).unwrap();
+        // A broken file adds nothing
+        assert_eq!(world.entities().join().count(), 0);
+        match result {
+            Err(SceneError::Parse { line, message, .. }) => (line, message),
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn vec4_close(a: &Vec4, b: &Vec4) {
+        assert_close(a.as_slice(), b.as_slice());
+    }
+
+    #[test]
+    fn save_then_load_gives_the_same_world() {
+        let mut transform = Transform::new();
+        transform.position = vec3(1.0, 2.0, -3.0);
+        transform.rotation = vec3(10.0, 20.0, 30.0) * (PI / 180.0);
+        transform.scale = vec3(1.0, 2.0, 0.5);
+        let mut renderable = Renderable::new(MeshHandle(1), MaterialHandle(2), transform.matrix());
+        renderable.tint = vec4(0.5, 0.25, 1.0, 0.75);
+
+        let spot = Light::spot(
+            vec3(0.0, 4.0, 1.0),
+            vec3(0.0, -1.0, 0.5),
+            vec3(1.0, 0.5, 0.25),
+            3.0,
+            12.0,
+            0.2,
+            0.4,
+        )
+        .with_shadows(ShadowSettings {
+            depth_bias: 0.001,
+            slope_bias: 0.01,
+            cascade_count: 2,
+            max_distance: 30.0,
+        });
+        let camera = Camera {
+            position: vec3(0.0, 2.0, 6.0),
+            yaw: 0.5,
+            pitch: -0.25,
+            fov: 1.2,
+            near: 0.1,
+            far: 500.0,
+        };
+
+        let mut world = world();
+        world
+            .create_entity()
+            .with(Name("crate & <friends>".to_string()))
+            .with(renderable.clone())
+            .build();
+        world
+            .create_entity()
+            .with(Name("spot".to_string()))
+            .with(spot.clone())
+            .build();
+        world
+            .create_entity()
+            .with(Name("camera".to_string()))
+            .with(camera)
+            .build();
+        // Unnamed entities get a name from their id
+        let lamp = world
+            .create_entity()
+            .with(Light::point(
+                vec3(1.0, 1.0, 1.0),
+                vec3(1.0, 1.0, 1.0),
+                2.0,
+                5.0,
+            ))
+            .build();
+
+        let path = temp_path("round_trip");
+        save_scene(&world, &path, &assets()).unwrap();
+        let mut loaded = self::world();
+        let entities = load_scene(&mut loaded, &path, &assets()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(entities.len(), 4);
+
+        let names = loaded.read_storage::<Name>();
+        let renderables = loaded.read_storage::<Renderable>();
+        let lights = loaded.read_storage::<Light>();
+        let cameras = loaded.read_storage::<Camera>();
+        let name = |i: usize| names.get(entities[i]).unwrap().0.clone();
+        assert_eq!(name(0), "crate & <friends>");
+        assert_eq!(name(1), "spot");
+        assert_eq!(name(2), "camera");
+        assert_eq!(name(3), format!("Entity {}", lamp.id()));
+
+        let loaded_renderable = renderables.get(entities[0]).unwrap();
+        assert_eq!(loaded_renderable.mesh, MeshHandle(1));
+        assert_eq!(loaded_renderable.material, MaterialHandle(2));
+        assert_close(
+            loaded_renderable.transform.as_slice(),
+            renderable.transform.as_slice(),
+        );
+        vec4_close(&loaded_renderable.tint, &renderable.tint);
+        assert!(lights.get(entities[0]).is_none());
+
+        let loaded_spot = lights.get(entities[1]).unwrap();
+        match (loaded_spot.kind, spot.kind) {
+            (
+                LightKind::Spot {
+                    inner_angle,
+                    outer_angle,
+                },
+                LightKind::Spot {
+                    inner_angle: inner,
+                    outer_angle: outer,
+                },
+            ) => assert_close(&[inner_angle, outer_angle], &[inner, outer]),
+            (kind, _) => panic!("expected a spot light, got {:?}", kind),
+        }
+        assert_close(loaded_spot.color.as_slice(), spot.color.as_slice());
+        assert_close(loaded_spot.position.as_slice(), spot.position.as_slice());
+        assert_close(loaded_spot.direction.as_slice(), spot.direction.as_slice());
+        assert_eq!(
+            (loaded_spot.intensity, loaded_spot.range),
+            (spot.intensity, spot.range)
+        );
+        assert_eq!(loaded_spot.shadow, spot.shadow);
+
+        let loaded_camera = cameras.get(entities[2]).unwrap();
+        assert_close(
+            loaded_camera.position.as_slice(),
+            camera.position.as_slice(),
+        );
+        assert_close(
+            &[
+                loaded_camera.yaw,
+                loaded_camera.pitch,
+                loaded_camera.fov,
+                loaded_camera.near,
+                loaded_camera.far,
+            ],
+            &[
+                camera.yaw,
+                camera.pitch,
+                camera.fov,
+                camera.near,
+                camera.far,
+            ],
+        );
+
+        let loaded_lamp = lights.get(entities[3]).unwrap();
+        assert_eq!(loaded_lamp.kind, LightKind::Point);
+        assert_eq!(loaded_lamp.shadow, None);
+    }
+
+    #[test]
+    fn left_out_attributes_keep_their_defaults() {
+        let path = temp_path("defaults");
+        fs::write(
+            &path,
+            "<Scene>\n\t<Entity>\n\t\t<Renderable Mesh=\"box\" Material=\"crate\"/>\n\t\t\
+             <Camera/>\n\t</Entity>\n</Scene>\n",
+        )
+        .unwrap();
+        let mut world = world();
+        let entities = load_scene(&mut world, &path, &assets()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let renderables = world.read_storage::<Renderable>();
+        let renderable = renderables.get(entities[0]).unwrap();
+        let identity: Mat4 = identity();
+        assert_eq!(renderable.transform, identity);
+        assert_eq!(renderable.tint, vec4(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(
+            world.read_storage::<Camera>().get(entities[0]),
+            Some(&Camera::new(vec3(0.0, 0.0, 0.0)))
+        );
+        assert_eq!(world.read_storage::<Name>().get(entities[0]).unwrap().0, "");
+    }
+
+    #[test]
+    fn unknown_components_are_errors() {
+        let (line, message) = load_error(
+            "unknown_component",
+            "<Scene>\n\t<Entity Name=\"a\">\n\t\t<Sprite Texture=\"a.png\"/>\n\t</Entity>\n</Scene>",
+        );
+        assert_eq!(line, 3);
+        assert_eq!(message, "unknown component `Sprite`");
+    }
+
+    #[test]
+    fn shadows_outside_a_light_are_errors() {
+        let (line, message) = load_error(
+            "stray_shadows",
+            "<Scene>\n\t<Entity Name=\"a\">\n\t\t<Renderable Mesh=\"box\" Material=\"crate\"/>\
+             \n\n\t\t<Shadows Cascades=\"2\"/>\n\t</Entity>\n</Scene>",
+        );
+        assert_eq!(line, 5);
+        assert_eq!(message, "Shadows has to be inside a Light");
+    }
+
+    #[test]
+    fn bad_vectors_are_errors() {
+        let (line, message) = load_error(
+            "bad_vector",
+            "<Scene>\n\t<Entity Name=\"a\">\n\t\t<Light Kind=\"point\"/>\n\t</Entity>\n\
+             \t<Entity Name=\"b\">\n\t\t<Camera Position=\"1,2\"/>\n\t</Entity>\n</Scene>",
+        );
+        assert_eq!(line, 6);
+        assert_eq!(message, "expected 3 numbers, got `1,2`");
+
+        let (_, message) = load_error(
+            "bad_number",
+            "<Scene><Entity><Light Intensity=\"bright\"/></Entity></Scene>",
+        );
+        assert_eq!(message, "expected 1 numbers, got `bright`");
+    }
+
+    #[test]
+    fn unknown_assets_are_errors() {
+        let (line, message) = load_error(
+            "unknown_mesh",
+            "<Scene>\n<Entity>\n<Renderable Mesh=\"sphere\" Material=\"crate\"/>\n</Entity>\n</Scene>",
+        );
+        assert_eq!((line, message.as_str()), (3, "unknown mesh `sphere`"));
+    }
+
+    #[test]
+    fn saving_an_unnamed_mesh_is_an_error() {
+        let mut world = world();
+        world
+            .create_entity()
+            .with(Name("a".to_string()))
+            .with(Renderable::new(
+                MeshHandle(7),
+                MaterialHandle(2),
+                identity(),
+            ))
+            .build();
+        let path = temp_path("unnamed_mesh");
+        match save_scene(&world, &path, &assets()) {
+            Err(SceneError::UnnamedAsset(ref name)) if name == "a" => (),
+            other => panic!("expected an unnamed asset error, got {:?}", other),
+        }
+        assert!(!path.exists());
+    }
+}
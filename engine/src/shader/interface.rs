@@ -0,0 +1,156 @@
+//! vulkano's descriptions of a shader's interface and pipeline layout, built from reflection
+//! instead of generated at compile time by `vulkano_shaders::shader!`.
+
+use std::borrow::Cow;
+use std::vec;
+use vulkano::descriptor::descriptor::{
+    DescriptorBufferDesc, DescriptorDesc, DescriptorDescTy, DescriptorImageDesc,
+    DescriptorImageDescArray, DescriptorImageDescDimensions, ShaderStages,
+};
+use vulkano::descriptor::pipeline_layout::{PipelineLayoutDesc, PipelineLayoutDescPcRange};
+use vulkano::pipeline::shader::{ShaderInterfaceDef, ShaderInterfaceDefEntry};
+
+use super::reflect::{DescriptorBinding, DescriptorKind, ImageDimensions, InterfaceVariable};
+
+/// The inputs or outputs of one shader stage.
+#[derive(Clone, Debug)]
+pub struct RuntimeInterface {
+    entries: Vec<ShaderInterfaceDefEntry>,
+}
+
+impl RuntimeInterface {
+    pub fn new(variables: &[InterfaceVariable]) -> RuntimeInterface {
+        RuntimeInterface {
+            entries: variables
+                .iter()
+                .map(|v| ShaderInterfaceDefEntry {
+                    location: v.location..v.location + v.location_count,
+                    format: v.format,
+                    name: Some(Cow::Owned(v.name.clone())),
+                })
+                .collect(),
+        }
+    }
+}
+
+unsafe impl ShaderInterfaceDef for RuntimeInterface {
+    type Iter = vec::IntoIter<ShaderInterfaceDefEntry>;
+
+    fn elements(&self) -> Self::Iter {
+        self.entries.clone().into_iter()
+    }
+}
+
+/// Descriptor sets and push constants of a whole program, shared by its vertex and fragment stage.
+#[derive(Clone, Debug)]
+pub struct RuntimeLayout {
+    sets: Vec<Vec<Option<DescriptorDesc>>>,
+    push_constants: Option<PipelineLayoutDescPcRange>,
+}
+
+impl RuntimeLayout {
+    /// Bindings missing from a set are left empty; sets with no bindings at all are too.
+    pub fn new(
+        descriptors: &[DescriptorBinding],
+        push_constants_size: Option<u32>,
+    ) -> RuntimeLayout {
+        let stages = ShaderStages {
+            vertex: true,
+            fragment: true,
+            ..ShaderStages::none()
+        };
+
+        let mut sets: Vec<Vec<Option<DescriptorDesc>>> = Vec::new();
+        for descriptor in descriptors {
+            let set = descriptor.set as usize;
+            let binding = descriptor.binding as usize;
+            if sets.len() <= set {
+                sets.resize(set + 1, Vec::new());
+            }
+            if sets[set].len() <= binding {
+                sets[set].resize(binding + 1, None);
+            }
+            sets[set][binding] = Some(DescriptorDesc {
+                ty: descriptor_ty(&descriptor.kind),
+                array_count: descriptor.array_count,
+                stages: stages,
+                readonly: true,
+            });
+        }
+
+        RuntimeLayout {
+            sets: sets,
+            push_constants: push_constants_size.map(|size| PipelineLayoutDescPcRange {
+                offset: 0,
+                size: size as usize,
+                stages: stages,
+            }),
+        }
+    }
+}
+
+unsafe impl PipelineLayoutDesc for RuntimeLayout {
+    fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    fn num_bindings_in_set(&self, set: usize) -> Option<usize> {
+        self.sets.get(set).map(|bindings| bindings.len())
+    }
+
+    fn descriptor(&self, set: usize, binding: usize) -> Option<DescriptorDesc> {
+        self.sets
+            .get(set)
+            .and_then(|bindings| bindings.get(binding))
+            .and_then(|desc| desc.clone())
+    }
+
+    fn num_push_constants_ranges(&self) -> usize {
+        if self.push_constants.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn push_constants_range(&self, num: usize) -> Option<PipelineLayoutDescPcRange> {
+        if num == 0 {
+            self.push_constants
+        } else {
+            None
+        }
+    }
+}
+
+fn descriptor_ty(kind: &DescriptorKind) -> DescriptorDescTy {
+    match *kind {
+        DescriptorKind::UniformBuffer(_) => DescriptorDescTy::Buffer(DescriptorBufferDesc {
+            dynamic: Some(false),
+            storage: false,
+        }),
+        DescriptorKind::StorageBuffer(_) => DescriptorDescTy::Buffer(DescriptorBufferDesc {
+            dynamic: Some(false),
+            storage: true,
+        }),
+        DescriptorKind::CombinedImageSampler(dimensions) => {
+            DescriptorDescTy::CombinedImageSampler(image_desc(dimensions))
+        }
+        DescriptorKind::SampledImage(dimensions) => DescriptorDescTy::Image(image_desc(dimensions)),
+        DescriptorKind::Sampler => DescriptorDescTy::Sampler,
+    }
+}
+
+fn image_desc(dimensions: ImageDimensions) -> DescriptorImageDesc {
+    DescriptorImageDesc {
+        sampled: true,
+        dimensions: match dimensions {
+            ImageDimensions::OneDimensional => DescriptorImageDescDimensions::OneDimensional,
+            ImageDimensions::TwoDimensional => DescriptorImageDescDimensions::TwoDimensional,
+            ImageDimensions::ThreeDimensional => DescriptorImageDescDimensions::ThreeDimensional,
+            ImageDimensions::Cube => DescriptorImageDescDimensions::Cube,
+        },
+        format: None,
+        multisampled: false,
+        array_layers: DescriptorImageDescArray::NonArrayed,
+    }
+}
@@ -0,0 +1,453 @@
+pub mod interface;
+pub mod reflect;
+pub mod uniforms;
+pub mod watcher;
+
+pub use self::interface::*;
+pub use self::reflect::*;
+pub use self::uniforms::*;
+pub use self::watcher::*;
+
+use std::ffi::CStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use utils::file::executable_dir;
+use vulkano::device::Device;
+use vulkano::pipeline::shader::{GraphicsEntryPoint, GraphicsShaderType, ShaderModule};
+use vulkano::pipeline::GraphicsPipelineCreationError;
+use vulkano::OomError;
+
+use glm::{vec2, vec3, vec4, Mat3, Mat4};
+
+/// Shipped games keep the shaders in a directory of this name next to the executable.
+const SHADERS_DIR: &'static str = "shaders";
+/// The shaders in the engine's source, used when there are none next to the executable, like when
+/// running with cargo during development.
+const SOURCE_SHADERS_PATH: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+const ENTRY_POINT: &'static [u8] = b"main\0";
+
+/// Entry point of a `ShaderProgram` stage, ready to hand to `GraphicsPipeline::start()`.
+pub type ShaderEntryPoint<'a> =
+    GraphicsEntryPoint<'a, (), RuntimeInterface, RuntimeInterface, RuntimeLayout>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Io(PathBuf, io::Error),
+    /// The compiler's output, where each error starts with `file:line:`.
+    Compile(String),
+    Reflect(ReflectError),
+    Module(OomError),
+    WrongStage {
+        expected: ShaderStage,
+        actual: ShaderStage,
+    },
+    /// The vertex and fragment shader declare different things at the same set and binding.
+    LayoutMismatch {
+        set: u32,
+        binding: u32,
+    },
+    Pipeline(GraphicsPipelineCreationError),
+    /// A reloaded program no longer fits the descriptor sets and push constants the renderer has.
+    LayoutChanged,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ShaderError::Io(ref path, ref e) => write!(f, "{}: {}", path.display(), e),
+            ShaderError::Compile(ref message) => write!(f, "{}", message.trim_end()),
+            ShaderError::Reflect(ref e) => write!(f, "SPIR-V reflection failed: {:?}", e),
+            ShaderError::Module(ref e) => write!(f, "could not create shader module: {:?}", e),
+            ShaderError::WrongStage { expected, actual } => {
+                write!(
+                    f,
+                    "expected a {:?} shader, got a {:?} shader",
+                    expected, actual
+                )
+            }
+            ShaderError::LayoutMismatch { set, binding } => write!(
+                f,
+                "vertex and fragment shader disagree about set {} binding {}",
+                set, binding
+            ),
+            ShaderError::Pipeline(ref e) => write!(f, "could not create pipeline: {:?}", e),
+            ShaderError::LayoutChanged => write!(
+                f,
+                "descriptors or push constants changed, which needs a restart"
+            ),
+        }
+    }
+}
+
+impl From<shaderc::Error> for ShaderError {
+    fn from(e: shaderc::Error) -> ShaderError {
+        match e {
+            shaderc::Error::CompilationError(_, message) => ShaderError::Compile(message),
+            e => ShaderError::Compile(e.to_string()),
+        }
+    }
+}
+
+impl From<ReflectError> for ShaderError {
+    fn from(e: ReflectError) -> ShaderError {
+        ShaderError::Reflect(e)
+    }
+}
+
+impl From<GraphicsPipelineCreationError> for ShaderError {
+    fn from(e: GraphicsPipelineCreationError) -> ShaderError {
+        ShaderError::Pipeline(e)
+    }
+}
+
+impl From<OomError> for ShaderError {
+    fn from(e: OomError) -> ShaderError {
+        ShaderError::Module(e)
+    }
+}
+
+lazy_static! {
+    static ref SHADERS_PATH: PathBuf = find_shaders_path();
+}
+
+/// Path of a file in the engine's shader directory, which doesn't depend on the working directory.
+pub fn shader_path(file_name: &str) -> PathBuf {
+    SHADERS_PATH.join(file_name)
+}
+
+fn find_shaders_path() -> PathBuf {
+    match executable_dir().map(|dir| dir.join(SHADERS_DIR)) {
+        Some(ref dir) if dir.is_dir() => dir.clone(),
+        _ => PathBuf::from(SOURCE_SHADERS_PATH),
+    }
+}
+
+/// Compiles GLSL to SPIR-V. `name` is what compiler errors call the source, usually its path.
+///
+/// `#include "file.glsl"` reads from the engine's shader directory, e.g. lighting.glsl for the
+/// material shaders.
+pub fn compile_glsl(source: &str, stage: ShaderStage, name: &str) -> Result<Vec<u32>, ShaderError> {
+    let mut compiler = shaderc::Compiler::new().expect("Failed to create shader compiler");
+    let mut options =
+        shaderc::CompileOptions::new().expect("Failed to create shader compiler options");
+    options.set_include_callback(|requested, _, _, _| {
+        let path = shader_path(requested);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(shaderc::ResolvedInclude {
+                resolved_name: path.display().to_string(),
+                content: content,
+            }),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    });
+    let kind = match stage {
+        ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+        ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+    };
+    let artifact = compiler.compile_into_spirv(source, kind, name, "main", Some(&options))?;
+    Ok(artifact.as_binary().to_vec())
+}
+
+/// A compiled shader stage together with what reflection found out about its interface.
+pub struct Shader {
+    device: Arc<Device>,
+    stage: ShaderStage,
+    module: Arc<ShaderModule>,
+    reflection: ShaderReflection,
+}
+
+impl Shader {
+    pub fn compile(
+        device: Arc<Device>,
+        stage: ShaderStage,
+        source: &str,
+        name: &str,
+    ) -> Result<Shader, ShaderError> {
+        let spirv = compile_glsl(source, stage, name)?;
+        let reflection = reflect(&spirv)?;
+        // The module is only used through entry points described by `reflection`, so vulkano's
+        // checks against the pipeline are checks against what the SPIR-V really declares
+        let module = unsafe { ShaderModule::from_words(device.clone(), &spirv)? };
+        Ok(Shader {
+            device: device,
+            stage: stage,
+            module: module,
+            reflection: reflection,
+        })
+    }
+
+    pub fn create_vertex_shader(device: Arc<Device>, vs: &str) -> Result<Shader, ShaderError> {
+        Shader::compile(device, ShaderStage::Vertex, vs, "vertex shader")
+    }
+
+    pub fn create_fragment_shader(device: Arc<Device>, fs: &str) -> Result<Shader, ShaderError> {
+        Shader::compile(device, ShaderStage::Fragment, fs, "fragment shader")
+    }
+
+    pub fn create_fragment_shader_from_path(
+        device: Arc<Device>,
+        path: &Path,
+    ) -> Result<Shader, ShaderError> {
+        Shader::from_path(device, ShaderStage::Fragment, path)
+    }
+
+    pub fn create_vertex_shader_from_path(
+        device: Arc<Device>,
+        path: &Path,
+    ) -> Result<Shader, ShaderError> {
+        Shader::from_path(device, ShaderStage::Vertex, path)
+    }
+
+    pub fn from_path(
+        device: Arc<Device>,
+        stage: ShaderStage,
+        path: &Path,
+    ) -> Result<Shader, ShaderError> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return Err(ShaderError::Io(path.to_path_buf(), e)),
+        };
+        Shader::compile(device, stage, &source, &path.display().to_string())
+    }
+
+    pub fn stage(&self) -> ShaderStage {
+        self.stage
+    }
+
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+}
+
+/// A vertex and fragment shader sharing one pipeline layout.
+///
+/// Vulkan has no program objects to bind; pass the entry points to a `GraphicsPipeline` instead.
+pub struct ShaderProgram {
+    vertex: Shader,
+    fragment: Shader,
+    layout: RuntimeLayout,
+    descriptors: Vec<DescriptorBinding>,
+    push_constants: Option<BlockLayout>,
+    uniform_blocks: Vec<UniformBlock>,
+}
+
+impl ShaderProgram {
+    pub fn new(vs: Shader, fs: Shader) -> Result<ShaderProgram, ShaderError> {
+        check_stage(&vs, ShaderStage::Vertex)?;
+        check_stage(&fs, ShaderStage::Fragment)?;
+
+        let mut descriptors: Vec<DescriptorBinding> = Vec::new();
+        for descriptor in vs
+            .reflection
+            .descriptors
+            .iter()
+            .chain(fs.reflection.descriptors.iter())
+        {
+            let existing = descriptors
+                .iter()
+                .find(|d| d.set == descriptor.set && d.binding == descriptor.binding)
+                .cloned();
+            match existing {
+                Some(ref existing) if existing.kind != descriptor.kind => {
+                    return Err(ShaderError::LayoutMismatch {
+                        set: descriptor.set,
+                        binding: descriptor.binding,
+                    })
+                }
+                Some(_) => (),
+                None => descriptors.push(descriptor.clone()),
+            }
+        }
+        descriptors.sort_by_key(|d| (d.set, d.binding));
+
+        // Both stages see the same push constant range, so the larger block covers the other
+        let push_constants = match (
+            vs.reflection.push_constants.clone(),
+            fs.reflection.push_constants.clone(),
+        ) {
+            (Some(a), Some(b)) => Some(if a.size >= b.size { a } else { b }),
+            (a, b) => a.or(b),
+        };
+
+        let uniform_blocks = descriptors
+            .iter()
+            .filter_map(|d| UniformBlock::new(vs.device.clone(), d))
+            .collect();
+
+        Ok(ShaderProgram {
+            uniform_blocks: uniform_blocks,
+            layout: RuntimeLayout::new(&descriptors, push_constants.as_ref().map(|pc| pc.size)),
+            vertex: vs,
+            fragment: fs,
+            descriptors: descriptors,
+            push_constants: push_constants,
+        })
+    }
+
+    pub fn from_fragments(
+        device: Arc<Device>,
+        vs: &str,
+        fs: &str,
+    ) -> Result<ShaderProgram, ShaderError> {
+        ShaderProgram::new(
+            Shader::create_vertex_shader(device.clone(), vs)?,
+            Shader::create_fragment_shader(device, fs)?,
+        )
+    }
+
+    /// Loads `<name>.vs` and `<name>.fs` from the engine's shader directory.
+    pub fn create_program(device: Arc<Device>, name: &str) -> Result<ShaderProgram, ShaderError> {
+        ShaderProgram::create_program_from_vert_frag(
+            device,
+            &format!("{}.vs", name),
+            &format!("{}.fs", name),
+        )
+    }
+
+    pub fn create_program_from_vert_frag(
+        device: Arc<Device>,
+        vert: &str,
+        frag: &str,
+    ) -> Result<ShaderProgram, ShaderError> {
+        ShaderProgram::new(
+            Shader::create_vertex_shader_from_path(device.clone(), &shader_path(vert))?,
+            Shader::create_fragment_shader_from_path(device, &shader_path(frag))?,
+        )
+    }
+
+    pub fn vertex_entry_point(&self) -> ShaderEntryPoint {
+        self.entry_point(&self.vertex, GraphicsShaderType::Vertex)
+    }
+
+    pub fn fragment_entry_point(&self) -> ShaderEntryPoint {
+        self.entry_point(&self.fragment, GraphicsShaderType::Fragment)
+    }
+
+    /// Every descriptor either stage uses, ordered by set and binding.
+    pub fn descriptors(&self) -> &[DescriptorBinding] {
+        &self.descriptors
+    }
+
+    pub fn push_constants(&self) -> Option<&BlockLayout> {
+        self.push_constants.as_ref()
+    }
+
+    /// The vertex shader's inputs, ordered by location.
+    pub fn vertex_inputs(&self) -> &[InterfaceVariable] {
+        &self.vertex.reflection.inputs
+    }
+
+    /// Loads program `name` again, for when its files changed on disk.
+    ///
+    /// Descriptor sets and push constant structs made for this program have to keep working with
+    /// the new one, so a reload that changes them is refused.
+    pub fn reload(&self, device: Arc<Device>, name: &str) -> Result<ShaderProgram, ShaderError> {
+        let program = ShaderProgram::create_program(device, name)?;
+        if program.descriptors != self.descriptors || program.push_constants != self.push_constants
+        {
+            return Err(ShaderError::LayoutChanged);
+        }
+        Ok(program)
+    }
+
+    fn entry_point<'a>(
+        &'a self,
+        shader: &'a Shader,
+        ty: GraphicsShaderType,
+    ) -> ShaderEntryPoint<'a> {
+        let name = CStr::from_bytes_with_nul(ENTRY_POINT).unwrap();
+        unsafe {
+            shader.module.graphics_entry_point(
+                name,
+                RuntimeInterface::new(&shader.reflection.inputs),
+                RuntimeInterface::new(&shader.reflection.outputs),
+                self.layout.clone(),
+                ty,
+            )
+        }
+    }
+
+    /// Uploads the values set so far for the uniform block at `set` and `binding`.
+    pub fn uniform_buffer(&self, set: u32, binding: u32) -> Result<UniformBuffer, UniformError> {
+        match self.uniform_blocks.iter().find(|b| b.is_at(set, binding)) {
+            Some(block) => Ok(block.upload()),
+            None => Err(UniformError::UnknownBlock {
+                set: set,
+                binding: binding,
+            }),
+        }
+    }
+
+    /// `name` is either a member name, like `view`, or qualified with its block, like `frame.view`
+    /// or `FrameData.view`. Unqualified names go to the first block that has such a member.
+    pub fn set(&mut self, name: &str, value: UniformValue) -> Result<(), UniformError> {
+        let (block_name, member_name) = match name.find('.') {
+            Some(i) => (Some(&name[..i]), &name[i + 1..]),
+            None => (None, name),
+        };
+
+        let block = self.uniform_blocks.iter_mut().find(|block| {
+            block_name.map_or(true, |b| block.is_named(b)) && block.member(member_name).is_some()
+        });
+        match block {
+            Some(block) => block.set(member_name, &value),
+            None => Err(UniformError::UnknownName(name.to_string())),
+        }
+    }
+
+    pub fn set_bool(&mut self, name: &str, val: bool) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Bool(val))
+    }
+
+    pub fn set_int(&mut self, name: &str, val: i32) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Int(val))
+    }
+
+    pub fn set_float(&mut self, name: &str, val: f32) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Float(val))
+    }
+
+    pub fn set_float2(&mut self, name: &str, val: (f32, f32)) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Vec2(vec2(val.0, val.1)))
+    }
+
+    pub fn set_float3(&mut self, name: &str, val: (f32, f32, f32)) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Vec3(vec3(val.0, val.1, val.2)))
+    }
+
+    pub fn set_float4(
+        &mut self,
+        name: &str,
+        val: (f32, f32, f32, f32),
+    ) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Vec4(vec4(val.0, val.1, val.2, val.3)))
+    }
+
+    pub fn set_mat3(&mut self, name: &str, val: &Mat3) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Mat3(*val))
+    }
+
+    pub fn set_mat4(&mut self, name: &str, val: &Mat4) -> Result<(), UniformError> {
+        self.set(name, UniformValue::Mat4(*val))
+    }
+}
+
+fn check_stage(shader: &Shader, expected: ShaderStage) -> Result<(), ShaderError> {
+    if shader.stage == expected {
+        Ok(())
+    } else {
+        Err(ShaderError::WrongStage {
+            expected: expected,
+            actual: shader.stage,
+        })
+    }
+}
@@ -0,0 +1,927 @@
+//! Just enough SPIR-V parsing to build Vulkan pipeline layouts and vertex interfaces for shaders
+//! compiled at runtime: input/output locations, descriptor bindings, and the layout of uniform and
+//! push constant blocks.
+
+use std::collections::HashMap;
+use std::fmt;
+use vulkano::format::Format;
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_OUTPUT: u32 = 3;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_1D: u32 = 0;
+const DIM_2D: u32 = 1;
+const DIM_3D: u32 = 2;
+const DIM_CUBE: u32 = 3;
+
+#[derive(Debug)]
+pub enum ReflectError {
+    InvalidHeader,
+    Truncated,
+    /// A variable uses a type the reflection doesn't understand, named by its SPIR-V id.
+    UnsupportedType(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarKind {
+    Float,
+    Int,
+    UInt,
+}
+
+/// Type of a member of a uniform or push constant block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniformType {
+    Scalar(ScalarKind),
+    Vector(ScalarKind, u32),
+    Matrix {
+        columns: u32,
+        rows: u32,
+        stride: u32,
+    },
+    /// Arrays and nested structs, which can only be written as raw bytes.
+    Other,
+}
+
+/// The GLSL name of the type, like `vec3` or `mat3x2`.
+impl fmt::Display for UniformType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = |kind: ScalarKind| match kind {
+            ScalarKind::Float => "",
+            ScalarKind::Int => "i",
+            ScalarKind::UInt => "u",
+        };
+        match *self {
+            UniformType::Scalar(ScalarKind::Float) => write!(f, "float"),
+            UniformType::Scalar(ScalarKind::Int) => write!(f, "int"),
+            UniformType::Scalar(ScalarKind::UInt) => write!(f, "uint"),
+            UniformType::Vector(kind, size) => write!(f, "{}vec{}", prefix(kind), size),
+            UniformType::Matrix { columns, rows, .. } if columns == rows => {
+                write!(f, "mat{}", columns)
+            }
+            UniformType::Matrix { columns, rows, .. } => write!(f, "mat{}x{}", columns, rows),
+            UniformType::Other => write!(f, "array or struct"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockMember {
+    pub name: String,
+    pub offset: u32,
+    pub size: u32,
+    pub ty: UniformType,
+}
+
+/// Memory layout of a uniform, storage or push constant block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockLayout {
+    pub name: String,
+    pub size: u32,
+    pub members: Vec<BlockMember>,
+}
+
+impl BlockLayout {
+    pub fn member(&self, name: &str) -> Option<&BlockMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageDimensions {
+    OneDimensional,
+    TwoDimensional,
+    ThreeDimensional,
+    Cube,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DescriptorKind {
+    UniformBuffer(BlockLayout),
+    StorageBuffer(BlockLayout),
+    CombinedImageSampler(ImageDimensions),
+    SampledImage(ImageDimensions),
+    Sampler,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    /// Name of the variable, or of the block for anonymous uniform blocks.
+    pub name: String,
+    pub kind: DescriptorKind,
+    pub array_count: u32,
+}
+
+/// A vertex input or stage output. Matrices take up one location per column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceVariable {
+    pub name: String,
+    pub location: u32,
+    pub location_count: u32,
+    pub format: Format,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub descriptors: Vec<DescriptorBinding>,
+    pub push_constants: Option<BlockLayout>,
+}
+
+impl ShaderReflection {
+    pub fn descriptor(&self, set: u32, binding: u32) -> Option<&DescriptorBinding> {
+        self.descriptors
+            .iter()
+            .find(|d| d.set == set && d.binding == binding)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum SpirvType {
+    Bool,
+    Int { signed: bool },
+    Float,
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, columns: u32 },
+    Image { dim: u32 },
+    Sampler,
+    SampledImage { image: u32 },
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Decorations {
+    location: Option<u32>,
+    binding: Option<u32>,
+    set: Option<u32>,
+    buffer_block: bool,
+    built_in: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct MemberDecorations {
+    offset: Option<u32>,
+    matrix_stride: Option<u32>,
+    built_in: bool,
+}
+
+struct Variable {
+    id: u32,
+    ty: u32,
+    storage: u32,
+}
+
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    member_decorations: HashMap<(u32, u32), MemberDecorations>,
+    variables: Vec<Variable>,
+}
+
+/// Reads the interface of a SPIR-V module holding a single entry point.
+pub fn reflect(words: &[u32]) -> Result<ShaderReflection, ReflectError> {
+    let module = parse(words)?;
+    let mut reflection = ShaderReflection::default();
+
+    for variable in &module.variables {
+        let pointee = match module.types.get(&variable.ty) {
+            Some(&SpirvType::Pointer { pointee }) => pointee,
+            _ => return Err(ReflectError::UnsupportedType(variable.ty)),
+        };
+        let decorations = module.decoration(variable.id);
+
+        match variable.storage {
+            STORAGE_INPUT | STORAGE_OUTPUT => {
+                if decorations.built_in || module.is_built_in_block(pointee) {
+                    continue;
+                }
+                let (format, location_count) = module.interface_format(pointee)?;
+                let interface = InterfaceVariable {
+                    name: module.name(variable.id),
+                    location: decorations.location.unwrap_or(0),
+                    location_count: location_count,
+                    format: format,
+                };
+                if variable.storage == STORAGE_INPUT {
+                    reflection.inputs.push(interface);
+                } else {
+                    reflection.outputs.push(interface);
+                }
+            }
+            STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                let (element, array_count) = match module.types.get(&pointee) {
+                    Some(&SpirvType::Array { element, length }) => (element, length),
+                    _ => (pointee, 1),
+                };
+                let type_decorations = module.decoration(element);
+                let kind = match module.types.get(&element) {
+                    Some(&SpirvType::SampledImage { image }) => match module.types.get(&image) {
+                        Some(&SpirvType::Image { dim }) => {
+                            DescriptorKind::CombinedImageSampler(image_dimensions(dim, image)?)
+                        }
+                        _ => return Err(ReflectError::UnsupportedType(image)),
+                    },
+                    Some(&SpirvType::Image { dim }) => {
+                        DescriptorKind::SampledImage(image_dimensions(dim, element)?)
+                    }
+                    Some(&SpirvType::Sampler) => DescriptorKind::Sampler,
+                    Some(&SpirvType::Struct { .. })
+                        if variable.storage == STORAGE_STORAGE_BUFFER
+                            || type_decorations.buffer_block =>
+                    {
+                        DescriptorKind::StorageBuffer(module.block_layout(element)?)
+                    }
+                    Some(&SpirvType::Struct { .. }) => {
+                        DescriptorKind::UniformBuffer(module.block_layout(element)?)
+                    }
+                    _ => return Err(ReflectError::UnsupportedType(element)),
+                };
+
+                let mut name = module.name(variable.id);
+                if name.is_empty() {
+                    name = module.name(element);
+                }
+                reflection.descriptors.push(DescriptorBinding {
+                    set: decorations.set.unwrap_or(0),
+                    binding: decorations.binding.unwrap_or(0),
+                    name: name,
+                    kind: kind,
+                    array_count: array_count,
+                });
+            }
+            STORAGE_PUSH_CONSTANT => {
+                reflection.push_constants = Some(module.block_layout(pointee)?);
+            }
+            _ => (),
+        }
+    }
+
+    reflection.inputs.sort_by_key(|v| v.location);
+    reflection.outputs.sort_by_key(|v| v.location);
+    reflection.descriptors.sort_by_key(|d| (d.set, d.binding));
+    Ok(reflection)
+}
+
+fn parse(words: &[u32]) -> Result<Module, ReflectError> {
+    if words.len() < HEADER_WORDS || words[0] != MAGIC {
+        return Err(ReflectError::InvalidHeader);
+    }
+
+    let mut module = Module::default();
+    let mut i = HEADER_WORDS;
+    while i < words.len() {
+        let word_count = (words[i] >> 16) as usize;
+        let opcode = words[i] & 0xffff;
+        if word_count == 0 || i + word_count > words.len() {
+            return Err(ReflectError::Truncated);
+        }
+        let operands = &words[i + 1..i + word_count];
+        i += word_count;
+
+        match opcode {
+            OP_NAME => {
+                module
+                    .names
+                    .insert(operands[0], parse_string(&operands[1..]));
+            }
+            OP_MEMBER_NAME => {
+                module
+                    .member_names
+                    .insert((operands[0], operands[1]), parse_string(&operands[2..]));
+            }
+            OP_TYPE_BOOL => {
+                module.types.insert(operands[0], SpirvType::Bool);
+            }
+            OP_TYPE_INT => {
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Int {
+                        signed: operands[2] != 0,
+                    },
+                );
+            }
+            OP_TYPE_FLOAT => {
+                module.types.insert(operands[0], SpirvType::Float);
+            }
+            OP_TYPE_VECTOR => {
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Vector {
+                        component: operands[1],
+                        count: operands[2],
+                    },
+                );
+            }
+            OP_TYPE_MATRIX => {
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Matrix {
+                        column: operands[1],
+                        columns: operands[2],
+                    },
+                );
+            }
+            OP_TYPE_IMAGE => {
+                module
+                    .types
+                    .insert(operands[0], SpirvType::Image { dim: operands[2] });
+            }
+            OP_TYPE_SAMPLER => {
+                module.types.insert(operands[0], SpirvType::Sampler);
+            }
+            OP_TYPE_SAMPLED_IMAGE => {
+                module
+                    .types
+                    .insert(operands[0], SpirvType::SampledImage { image: operands[1] });
+            }
+            OP_TYPE_ARRAY => {
+                // The length is the id of a constant, which is always declared before the array
+                let length = module.constants.get(&operands[2]).cloned().unwrap_or(1);
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Array {
+                        element: operands[1],
+                        length: length,
+                    },
+                );
+            }
+            OP_TYPE_RUNTIME_ARRAY => {
+                module.types.insert(operands[0], SpirvType::RuntimeArray);
+            }
+            OP_TYPE_STRUCT => {
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Struct {
+                        members: operands[1..].to_vec(),
+                    },
+                );
+            }
+            OP_TYPE_POINTER => {
+                module.types.insert(
+                    operands[0],
+                    SpirvType::Pointer {
+                        pointee: operands[2],
+                    },
+                );
+            }
+            OP_CONSTANT => {
+                module.constants.insert(operands[1], operands[2]);
+            }
+            OP_VARIABLE => {
+                module.variables.push(Variable {
+                    ty: operands[0],
+                    id: operands[1],
+                    storage: operands[2],
+                });
+            }
+            OP_DECORATE => {
+                let decorations = module.decorations.entry(operands[0]).or_default();
+                let value = operands.get(2).cloned();
+                match operands[1] {
+                    DECORATION_BUFFER_BLOCK => decorations.buffer_block = true,
+                    DECORATION_ARRAY_STRIDE => decorations.array_stride = value,
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    DECORATION_LOCATION => decorations.location = value,
+                    DECORATION_BINDING => decorations.binding = value,
+                    DECORATION_DESCRIPTOR_SET => decorations.set = value,
+                    _ => (),
+                }
+            }
+            OP_MEMBER_DECORATE => {
+                let decorations = module
+                    .member_decorations
+                    .entry((operands[0], operands[1]))
+                    .or_default();
+                let value = operands.get(3).cloned();
+                match operands[2] {
+                    DECORATION_OFFSET => decorations.offset = value,
+                    DECORATION_MATRIX_STRIDE => decorations.matrix_stride = value,
+                    DECORATION_BUILT_IN => decorations.built_in = true,
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(module)
+}
+
+/// Strings are nul terminated UTF-8, packed four bytes to a word in little endian order.
+fn parse_string(words: &[u32]) -> String {
+    let mut bytes = Vec::new();
+    'words: for word in words {
+        for shift in &[0, 8, 16, 24] {
+            let byte = (word >> shift) as u8;
+            if byte == 0 {
+                break 'words;
+            }
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn image_dimensions(dim: u32, image: u32) -> Result<ImageDimensions, ReflectError> {
+    match dim {
+        DIM_1D => Ok(ImageDimensions::OneDimensional),
+        DIM_2D => Ok(ImageDimensions::TwoDimensional),
+        DIM_3D => Ok(ImageDimensions::ThreeDimensional),
+        DIM_CUBE => Ok(ImageDimensions::Cube),
+        _ => Err(ReflectError::UnsupportedType(image)),
+    }
+}
+
+impl Module {
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    fn decoration(&self, id: u32) -> Decorations {
+        self.decorations.get(&id).cloned().unwrap_or_default()
+    }
+
+    fn member_decoration(&self, ty: u32, member: u32) -> MemberDecorations {
+        self.member_decorations
+            .get(&(ty, member))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// gl_PerVertex and friends, which are blocks made of built ins.
+    fn is_built_in_block(&self, ty: u32) -> bool {
+        match self.types.get(&ty) {
+            Some(&SpirvType::Struct { ref members }) => {
+                (0..members.len() as u32).any(|i| self.member_decoration(ty, i).built_in)
+            }
+            _ => false,
+        }
+    }
+
+    fn scalar_kind(&self, ty: u32) -> Option<ScalarKind> {
+        match self.types.get(&ty) {
+            Some(&SpirvType::Float) => Some(ScalarKind::Float),
+            Some(&SpirvType::Int { signed: true }) => Some(ScalarKind::Int),
+            Some(&SpirvType::Int { signed: false }) | Some(&SpirvType::Bool) => {
+                Some(ScalarKind::UInt)
+            }
+            _ => None,
+        }
+    }
+
+    fn interface_format(&self, ty: u32) -> Result<(Format, u32), ReflectError> {
+        let (kind, count, locations) = match self.types.get(&ty) {
+            Some(&SpirvType::Vector { component, count }) => {
+                (self.scalar_kind(component), count, 1)
+            }
+            Some(&SpirvType::Matrix { column, columns }) => match self.types.get(&column) {
+                Some(&SpirvType::Vector { component, count }) => {
+                    (self.scalar_kind(component), count, columns)
+                }
+                _ => (None, 0, 0),
+            },
+            _ => (self.scalar_kind(ty), 1, 1),
+        };
+
+        let format = match (kind, count) {
+            (Some(ScalarKind::Float), 1) => Format::R32Sfloat,
+            (Some(ScalarKind::Float), 2) => Format::R32G32Sfloat,
+            (Some(ScalarKind::Float), 3) => Format::R32G32B32Sfloat,
+            (Some(ScalarKind::Float), 4) => Format::R32G32B32A32Sfloat,
+            (Some(ScalarKind::Int), 1) => Format::R32Sint,
+            (Some(ScalarKind::Int), 2) => Format::R32G32Sint,
+            (Some(ScalarKind::Int), 3) => Format::R32G32B32Sint,
+            (Some(ScalarKind::Int), 4) => Format::R32G32B32A32Sint,
+            (Some(ScalarKind::UInt), 1) => Format::R32Uint,
+            (Some(ScalarKind::UInt), 2) => Format::R32G32Uint,
+            (Some(ScalarKind::UInt), 3) => Format::R32G32B32Uint,
+            (Some(ScalarKind::UInt), 4) => Format::R32G32B32A32Uint,
+            _ => return Err(ReflectError::UnsupportedType(ty)),
+        };
+        Ok((format, locations))
+    }
+
+    fn block_layout(&self, ty: u32) -> Result<BlockLayout, ReflectError> {
+        let members = match self.types.get(&ty) {
+            Some(&SpirvType::Struct { ref members }) => members,
+            _ => return Err(ReflectError::UnsupportedType(ty)),
+        };
+
+        let mut layout = BlockLayout {
+            name: self.name(ty),
+            size: 0,
+            members: Vec::with_capacity(members.len()),
+        };
+        for (i, &member_ty) in members.iter().enumerate() {
+            let decorations = self.member_decoration(ty, i as u32);
+            let offset = decorations.offset.unwrap_or(0);
+            let size = self.size_of(member_ty, decorations.matrix_stride)?;
+            layout.size = layout.size.max(offset + size);
+            layout.members.push(BlockMember {
+                name: self
+                    .member_names
+                    .get(&(ty, i as u32))
+                    .cloned()
+                    .unwrap_or_default(),
+                offset: offset,
+                size: size,
+                ty: self.uniform_type(member_ty, decorations.matrix_stride),
+            });
+        }
+        Ok(layout)
+    }
+
+    fn uniform_type(&self, ty: u32, matrix_stride: Option<u32>) -> UniformType {
+        match self.types.get(&ty) {
+            Some(&SpirvType::Vector { component, count }) => match self.scalar_kind(component) {
+                Some(kind) => UniformType::Vector(kind, count),
+                None => UniformType::Other,
+            },
+            Some(&SpirvType::Matrix { column, columns }) => match self.types.get(&column) {
+                Some(&SpirvType::Vector { count, .. }) => UniformType::Matrix {
+                    columns: columns,
+                    rows: count,
+                    stride: matrix_stride.unwrap_or(16),
+                },
+                _ => UniformType::Other,
+            },
+            _ => match self.scalar_kind(ty) {
+                Some(kind) => UniformType::Scalar(kind),
+                None => UniformType::Other,
+            },
+        }
+    }
+
+    /// Size in bytes of a 32 bit type inside a block. Runtime arrays count as empty.
+    fn size_of(&self, ty: u32, matrix_stride: Option<u32>) -> Result<u32, ReflectError> {
+        match self.types.get(&ty) {
+            Some(&SpirvType::Bool) | Some(&SpirvType::Int { .. }) | Some(&SpirvType::Float) => {
+                Ok(4)
+            }
+            Some(&SpirvType::Vector { count, .. }) => Ok(count * 4),
+            Some(&SpirvType::Matrix { columns, .. }) => Ok(columns * matrix_stride.unwrap_or(16)),
+            Some(&SpirvType::Array { element, length }) => {
+                let stride = match self.decoration(ty).array_stride {
+                    Some(stride) => stride,
+                    None => self.size_of(element, matrix_stride)?,
+                };
+                Ok(length * stride)
+            }
+            Some(&SpirvType::RuntimeArray) => Ok(0),
+            Some(&SpirvType::Struct { .. }) => Ok(self.block_layout(ty)?.size),
+            _ => Err(ReflectError::UnsupportedType(ty)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shader::{compile_glsl, ShaderStage};
+
+    fn reflect_glsl(stage: ShaderStage, source: &str) -> ShaderReflection {
+        let spirv = compile_glsl(source, stage, "test").unwrap();
+        reflect(&spirv).unwrap()
+    }
+
+    fn member(name: &str, offset: u32, size: u32, ty: UniformType) -> BlockMember {
+        BlockMember {
+            name: name.to_string(),
+            offset: offset,
+            size: size,
+            ty: ty,
+        }
+    }
+
+    fn matrix(size: u32) -> UniformType {
+        UniformType::Matrix {
+            columns: size,
+            rows: size,
+            stride: 16,
+        }
+    }
+
+    #[test]
+    fn vertex_inputs_and_outputs() {
+        let reflection = reflect_glsl(
+            ShaderStage::Vertex,
+            "#version 450
+            layout(location = 2) in mat4 model;
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec2 uv;
+            layout(location = 6) in ivec4 bones;
+            layout(location = 1) out vec3 v_color;
+            layout(location = 0) out vec2 v_uv;
+            void main() {
+                v_uv = uv;
+                v_color = vec3(bones.xyz);
+                gl_Position = model * vec4(position, 1.0);
+            }",
+        );
+
+        let inputs: Vec<_> = reflection
+            .inputs
+            .iter()
+            .map(|v| (v.name.as_str(), v.location, v.location_count, v.format))
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![
+                ("position", 0, 1, Format::R32G32B32Sfloat),
+                ("uv", 1, 1, Format::R32G32Sfloat),
+                ("model", 2, 4, Format::R32G32B32A32Sfloat),
+                ("bones", 6, 1, Format::R32G32B32A32Sint),
+            ]
+        );
+        // gl_Position is a built in and not part of the interface
+        let outputs: Vec<_> = reflection
+            .outputs
+            .iter()
+            .map(|v| (v.name.as_str(), v.location, v.format))
+            .collect();
+        assert_eq!(
+            outputs,
+            vec![
+                ("v_uv", 0, Format::R32G32Sfloat),
+                ("v_color", 1, Format::R32G32B32Sfloat),
+            ]
+        );
+        assert!(reflection.descriptors.is_empty());
+        assert!(reflection.push_constants.is_none());
+    }
+
+    #[test]
+    fn uniform_block_has_std140_offsets() {
+        let reflection = reflect_glsl(
+            ShaderStage::Vertex,
+            "#version 450
+            layout(set = 0, binding = 0) uniform FrameData {
+                mat4 view;
+                mat3 normal_matrix;
+                vec3 light;
+                float intensity;
+                vec2 size;
+                int count;
+                bool enabled;
+                vec4 colors[3];
+            } frame;
+            void main() {
+                vec3 n = frame.normal_matrix * frame.light * frame.intensity;
+                if (frame.enabled) {
+                    n += frame.colors[frame.count].xyz;
+                }
+                gl_Position = frame.view * vec4(n, frame.size.x);
+            }",
+        );
+
+        assert_eq!(reflection.descriptors.len(), 1);
+        let descriptor = reflection.descriptor(0, 0).unwrap();
+        assert_eq!(descriptor.name, "frame");
+        assert_eq!(descriptor.array_count, 1);
+        let layout = match descriptor.kind {
+            DescriptorKind::UniformBuffer(ref layout) => layout,
+            ref kind => panic!("expected a uniform buffer, got {:?}", kind),
+        };
+        assert_eq!(layout.name, "FrameData");
+        assert_eq!(
+            layout.members,
+            vec![
+                member("view", 0, 64, matrix(4)),
+                // Each column of a mat3 is padded out to a vec4
+                member("normal_matrix", 64, 48, matrix(3)),
+                member("light", 112, 12, UniformType::Vector(ScalarKind::Float, 3)),
+                // A float fits in the padding after a vec3
+                member("intensity", 124, 4, UniformType::Scalar(ScalarKind::Float)),
+                member("size", 128, 8, UniformType::Vector(ScalarKind::Float, 2)),
+                member("count", 136, 4, UniformType::Scalar(ScalarKind::Int)),
+                member("enabled", 140, 4, UniformType::Scalar(ScalarKind::UInt)),
+                member("colors", 144, 48, UniformType::Other),
+            ]
+        );
+        assert_eq!(layout.size, 192);
+        assert_eq!(layout.member("intensity").unwrap().offset, 124);
+        assert!(layout.member("missing").is_none());
+    }
+
+    #[test]
+    fn samplers_are_sorted_by_set_and_binding() {
+        let reflection = reflect_glsl(
+            ShaderStage::Fragment,
+            "#version 450
+            layout(set = 1, binding = 2) uniform sampler2D albedo;
+            layout(set = 1, binding = 0) uniform samplerCube environment;
+            layout(set = 0, binding = 3) uniform sampler2D shadow_maps[4];
+            layout(set = 0, binding = 1) uniform sampler3D lut;
+            layout(location = 0) in vec2 uv;
+            layout(location = 0) out vec4 color;
+            void main() {
+                color = texture(albedo, uv) + texture(environment, vec3(uv, 1.0))
+                    + texture(shadow_maps[2], uv) + texture(lut, vec3(uv, 0.5));
+            }",
+        );
+
+        let descriptors: Vec<_> = reflection
+            .descriptors
+            .iter()
+            .map(|d| {
+                (
+                    d.set,
+                    d.binding,
+                    d.name.as_str(),
+                    d.kind.clone(),
+                    d.array_count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            descriptors,
+            vec![
+                (
+                    0,
+                    1,
+                    "lut",
+                    DescriptorKind::CombinedImageSampler(ImageDimensions::ThreeDimensional),
+                    1
+                ),
+                (
+                    0,
+                    3,
+                    "shadow_maps",
+                    DescriptorKind::CombinedImageSampler(ImageDimensions::TwoDimensional),
+                    4
+                ),
+                (
+                    1,
+                    0,
+                    "environment",
+                    DescriptorKind::CombinedImageSampler(ImageDimensions::Cube),
+                    1
+                ),
+                (
+                    1,
+                    2,
+                    "albedo",
+                    DescriptorKind::CombinedImageSampler(ImageDimensions::TwoDimensional),
+                    1
+                ),
+            ]
+        );
+        assert_eq!(reflection.outputs[0].format, Format::R32G32B32A32Sfloat);
+    }
+
+    #[test]
+    fn push_constants_and_storage_buffers() {
+        let reflection = reflect_glsl(
+            ShaderStage::Fragment,
+            "#version 450
+            layout(push_constant) uniform Push {
+                mat4 model;
+                vec4 tint;
+                uint index;
+            } push;
+            struct Light {
+                vec4 position;
+                vec4 color;
+            };
+            layout(set = 2, binding = 1) readonly buffer Lights {
+                uint count;
+                Light lights[];
+            } lights;
+            layout(location = 0) out vec4 color;
+            void main() {
+                color = push.tint * lights.lights[push.index].color * float(lights.count)
+                    * push.model[0];
+            }",
+        );
+
+        let push = reflection.push_constants.clone().unwrap();
+        assert_eq!(push.name, "Push");
+        assert_eq!(
+            push.members,
+            vec![
+                member("model", 0, 64, matrix(4)),
+                member("tint", 64, 16, UniformType::Vector(ScalarKind::Float, 4)),
+                member("index", 80, 4, UniformType::Scalar(ScalarKind::UInt)),
+            ]
+        );
+        assert_eq!(push.size, 84);
+
+        let descriptor = reflection.descriptor(2, 1).unwrap();
+        assert_eq!(descriptor.name, "lights");
+        match descriptor.kind {
+            DescriptorKind::StorageBuffer(ref layout) => {
+                assert_eq!(layout.name, "Lights");
+                assert_eq!(layout.member("count").unwrap().offset, 0);
+                // Runtime arrays take no space of their own
+                let lights = layout.member("lights").unwrap();
+                assert_eq!((lights.offset, lights.size), (16, 0));
+                assert_eq!(layout.size, 16);
+            }
+            ref kind => panic!("expected a storage buffer, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn std430_matrix_strides() {
+        let reflection = reflect_glsl(
+            ShaderStage::Fragment,
+            "#version 450
+            layout(std430, set = 0, binding = 0) readonly buffer Bones {
+                mat3x2 small;
+                mat2 rotation;
+            } bones;
+            layout(location = 0) out vec4 color;
+            void main() {
+                color = vec4(bones.small[0], bones.rotation[1]);
+            }",
+        );
+
+        let layout = match reflection.descriptor(0, 0).unwrap().kind {
+            DescriptorKind::StorageBuffer(ref layout) => layout.clone(),
+            ref kind => panic!("expected a storage buffer, got {:?}", kind),
+        };
+        // std430 packs vec2 columns 8 bytes apart instead of rounding them up to 16
+        let small = layout.member("small").unwrap();
+        assert_eq!(
+            small.ty,
+            UniformType::Matrix {
+                columns: 3,
+                rows: 2,
+                stride: 8,
+            }
+        );
+        assert_eq!((small.offset, small.size), (0, 24));
+        let rotation = layout.member("rotation").unwrap();
+        assert_eq!(
+            rotation.ty,
+            UniformType::Matrix {
+                columns: 2,
+                rows: 2,
+                stride: 8,
+            }
+        );
+        assert_eq!((rotation.offset, rotation.size), (24, 16));
+    }
+
+    #[test]
+    fn broken_modules_are_errors() {
+        match reflect(&[]) {
+            Err(ReflectError::InvalidHeader) => (),
+            other => panic!("expected an invalid header, got {:?}", other),
+        }
+        match reflect(&[0xdead_beef, 0, 0, 0, 0]) {
+            Err(ReflectError::InvalidHeader) => (),
+            other => panic!("expected an invalid header, got {:?}", other),
+        }
+        // An OpName claiming four words with only two left
+        match reflect(&[MAGIC, 0x0001_0000, 0, 10, 0, (4 << 16) | OP_NAME, 1]) {
+            Err(ReflectError::Truncated) => (),
+            other => panic!("expected a truncated module, got {:?}", other),
+        }
+    }
+}
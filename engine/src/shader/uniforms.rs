@@ -0,0 +1,337 @@
+use glm::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use std::fmt;
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::{CpuBufferPool, CpuBufferPoolChunk};
+use vulkano::buffer::BufferUsage;
+use vulkano::device::Device;
+use vulkano::memory::pool::StdMemoryPool;
+
+use super::reflect::{
+    BlockLayout, BlockMember, DescriptorBinding, DescriptorKind, ScalarKind, UniformType,
+};
+
+/// The contents of a uniform block for one draw, ready to add to a descriptor set.
+pub type UniformBuffer = CpuBufferPoolChunk<u32, Arc<StdMemoryPool>>;
+
+#[derive(Debug)]
+pub enum UniformError {
+    UnknownName(String),
+    TypeMismatch {
+        name: String,
+        declared: UniformType,
+        given: UniformType,
+    },
+    UnknownBlock {
+        set: u32,
+        binding: u32,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UniformError::UnknownName(ref name) => {
+                write!(f, "the shader has no uniform `{}`", name)
+            }
+            UniformError::TypeMismatch {
+                ref name,
+                declared,
+                given,
+            } => write!(
+                f,
+                "uniform `{}` is a {} in the shader, but was given a {}",
+                name, declared, given
+            ),
+            UniformError::UnknownBlock { set, binding } => write!(
+                f,
+                "the shader has no uniform block at set {} binding {}",
+                set, binding
+            ),
+        }
+    }
+}
+
+/// A value for a member of a uniform block.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UniformValue {
+    /// Booleans are 32 bit unsigned ints inside uniform blocks.
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat3(Mat3),
+    Mat4(Mat4),
+}
+
+impl UniformValue {
+    pub fn ty(&self) -> UniformType {
+        match *self {
+            UniformValue::Bool(_) => UniformType::Scalar(ScalarKind::UInt),
+            UniformValue::Int(_) => UniformType::Scalar(ScalarKind::Int),
+            UniformValue::Float(_) => UniformType::Scalar(ScalarKind::Float),
+            UniformValue::Vec2(_) => UniformType::Vector(ScalarKind::Float, 2),
+            UniformValue::Vec3(_) => UniformType::Vector(ScalarKind::Float, 3),
+            UniformValue::Vec4(_) => UniformType::Vector(ScalarKind::Float, 4),
+            UniformValue::Mat3(_) => matrix_type(3),
+            UniformValue::Mat4(_) => matrix_type(4),
+        }
+    }
+
+    /// The value as 32 bit words, matrices column by column.
+    pub fn words(&self) -> Vec<u32> {
+        let floats = match *self {
+            UniformValue::Bool(v) => return vec![v as u32],
+            UniformValue::Int(v) => return vec![v as u32],
+            UniformValue::Float(v) => return vec![v.to_bits()],
+            UniformValue::Vec2(ref v) => v.as_slice(),
+            UniformValue::Vec3(ref v) => v.as_slice(),
+            UniformValue::Vec4(ref v) => v.as_slice(),
+            UniformValue::Mat3(ref v) => v.as_slice(),
+            UniformValue::Mat4(ref v) => v.as_slice(),
+        };
+        floats.iter().map(|f| f.to_bits()).collect()
+    }
+}
+
+/// CPU side copy of the contents of a uniform block, written one member at a time by name.
+///
+/// Every member of a std140 block is 4 byte aligned, so the copy is kept as 32 bit words.
+#[derive(Clone, Debug)]
+pub struct BlockData {
+    layout: BlockLayout,
+    words: Vec<u32>,
+}
+
+impl BlockData {
+    /// Starts out all zeros.
+    pub fn new(layout: BlockLayout) -> BlockData {
+        BlockData {
+            words: vec![0; (layout.size as usize + 3) / 4],
+            layout: layout,
+        }
+    }
+
+    pub fn layout(&self) -> &BlockLayout {
+        &self.layout
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    pub fn set(&mut self, name: &str, value: &UniformValue) -> Result<(), UniformError> {
+        let member = match self.layout.member(name) {
+            Some(member) => member.clone(),
+            None => return Err(UniformError::UnknownName(name.to_string())),
+        };
+        if !accepts(member.ty, value.ty()) {
+            return Err(UniformError::TypeMismatch {
+                name: name.to_string(),
+                declared: member.ty,
+                given: value.ty(),
+            });
+        }
+        self.write(&member, &value.words());
+        Ok(())
+    }
+
+    /// Writes `words` at the member's offset. Matrices are given column by column, and each column
+    /// goes `stride` bytes after the previous one.
+    fn write(&mut self, member: &BlockMember, words: &[u32]) {
+        let (column_len, stride) = match member.ty {
+            UniformType::Matrix { rows, stride, .. } => (rows as usize, stride as usize),
+            _ => (words.len(), 0),
+        };
+        for (column, values) in words.chunks(column_len.max(1)).enumerate() {
+            let start = (member.offset as usize + column * stride) / 4;
+            self.words[start..start + values.len()].copy_from_slice(values);
+        }
+    }
+}
+
+/// A uniform block of a shader, whose `BlockData` is uploaded at draw time.
+pub struct UniformBlock {
+    set: u32,
+    binding: u32,
+    name: String,
+    data: BlockData,
+    pool: CpuBufferPool<u32>,
+}
+
+impl UniformBlock {
+    /// `None` unless `descriptor` is a uniform buffer.
+    pub fn new(device: Arc<Device>, descriptor: &DescriptorBinding) -> Option<UniformBlock> {
+        let layout = match descriptor.kind {
+            DescriptorKind::UniformBuffer(ref layout) => layout.clone(),
+            _ => return None,
+        };
+        Some(UniformBlock {
+            set: descriptor.set,
+            binding: descriptor.binding,
+            name: descriptor.name.clone(),
+            data: BlockData::new(layout),
+            pool: CpuBufferPool::new(device, BufferUsage::uniform_buffer()),
+        })
+    }
+
+    pub fn is_at(&self, set: u32, binding: u32) -> bool {
+        self.set == set && self.binding == binding
+    }
+
+    /// Blocks are known both by their instance name and their block name, e.g. `frame` and
+    /// `FrameData` for `uniform FrameData { ... } frame;`.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.data.layout().name == name
+    }
+
+    pub fn member(&self, name: &str) -> Option<&BlockMember> {
+        self.data.layout().member(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: &UniformValue) -> Result<(), UniformError> {
+        self.data.set(name, value)
+    }
+
+    pub fn upload(&self) -> UniformBuffer {
+        self.pool.chunk(self.data.words().iter().cloned()).unwrap()
+    }
+}
+
+/// Whether a value of type `given` can be written to a member declared as `declared`.
+/// Matrix strides come from the block layout, so only their shape has to match.
+fn accepts(declared: UniformType, given: UniformType) -> bool {
+    match (declared, given) {
+        (
+            UniformType::Matrix {
+                columns: c1,
+                rows: r1,
+                ..
+            },
+            UniformType::Matrix {
+                columns: c2,
+                rows: r2,
+                ..
+            },
+        ) => c1 == c2 && r1 == r2,
+        (declared, given) => declared == given,
+    }
+}
+
+/// A square, column major matrix type as std140 lays it out.
+fn matrix_type(size: u32) -> UniformType {
+    UniformType::Matrix {
+        columns: size,
+        rows: size,
+        stride: 16,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::{mat3, vec3};
+    use shader::{compile_glsl, reflect, ShaderStage};
+
+    fn params() -> BlockData {
+        let spirv = compile_glsl(
+            "#version 450
+            layout(set = 0, binding = 0) uniform Params {
+                float roughness;
+                vec3 color;
+                mat3 rotation;
+                int mode;
+                bool enabled;
+            } params;
+            layout(location = 0) out vec4 out_color;
+            void main() {
+                out_color = vec4(params.rotation * params.color * params.roughness, 1.0);
+                if (params.enabled) {
+                    out_color.w = float(params.mode);
+                }
+            }",
+            ShaderStage::Fragment,
+            "params.fs",
+        )
+        .unwrap();
+        let reflection = reflect(&spirv).unwrap();
+        match reflection.descriptor(0, 0).unwrap().kind {
+            DescriptorKind::UniformBuffer(ref layout) => BlockData::new(layout.clone()),
+            ref kind => panic!("expected a uniform buffer, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn values_go_to_their_offsets() {
+        let mut data = params();
+        // roughness 0, color 16, rotation 32 with columns 16 bytes apart, mode 80, enabled 84
+        assert_eq!(data.words().len(), 22);
+        assert!(data.words().iter().all(|&w| w == 0));
+
+        data.set("roughness", &UniformValue::Float(0.5)).unwrap();
+        data.set("color", &UniformValue::Vec3(vec3(1.0, 2.0, 3.0)))
+            .unwrap();
+        data.set("mode", &UniformValue::Int(-2)).unwrap();
+        data.set("enabled", &UniformValue::Bool(true)).unwrap();
+        let words = data.words();
+        assert_eq!(words[0], 0.5f32.to_bits());
+        assert_eq!(
+            words[4..7],
+            [1.0f32.to_bits(), 2.0f32.to_bits(), 3.0f32.to_bits()]
+        );
+        assert_eq!(words[20], -2i32 as u32);
+        assert_eq!(words[21], 1);
+    }
+
+    #[test]
+    fn matrix_columns_follow_the_stride() {
+        let mut data = params();
+        let rotation = mat3(1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0);
+        data.set("rotation", &UniformValue::Mat3(rotation)).unwrap();
+
+        let floats: Vec<f32> = data.words()[8..20]
+            .iter()
+            .map(|&w| f32::from_bits(w))
+            .collect();
+        // Column major, each column padded to four floats
+        assert_eq!(
+            floats,
+            vec![1.0, 4.0, 7.0, 0.0, 2.0, 5.0, 8.0, 0.0, 3.0, 6.0, 9.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let mut data = params();
+        match data.set("metalness", &UniformValue::Float(1.0)) {
+            Err(UniformError::UnknownName(ref name)) if name == "metalness" => (),
+            other => panic!("expected an unknown name, got {:?}", other),
+        }
+        assert!(data.words().iter().all(|&w| w == 0));
+    }
+
+    #[test]
+    fn wrong_types_are_errors() {
+        let mut data = params();
+        match data.set("color", &UniformValue::Float(1.0)) {
+            Err(UniformError::TypeMismatch {
+                ref name,
+                declared,
+                given,
+            }) => {
+                assert_eq!(name, "color");
+                assert_eq!(declared, UniformType::Vector(ScalarKind::Float, 3));
+                assert_eq!(given, UniformType::Scalar(ScalarKind::Float));
+            }
+            other => panic!("expected a type mismatch, got {:?}", other),
+        }
+        // Ints and bools aren't floats, and a mat4 doesn't fit a mat3
+        assert!(data.set("roughness", &UniformValue::Int(1)).is_err());
+        assert!(data.set("mode", &UniformValue::Bool(true)).is_err());
+        assert!(data
+            .set("rotation", &UniformValue::Mat4(glm::identity()))
+            .is_err());
+        assert!(data.words().iter().all(|&w| w == 0));
+    }
+}
@@ -0,0 +1,52 @@
+use notify::{self, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use super::shader_path;
+
+/// Editors tend to save in several steps, so events are collected for a moment before reporting.
+const DEBOUNCE_DELAY_MS: u64 = 200;
+
+/// Watches the engine's shader directory for edits.
+pub struct ShaderWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<DebouncedEvent>,
+}
+
+impl ShaderWatcher {
+    pub fn new() -> notify::Result<ShaderWatcher> {
+        let (tx, rx) = channel();
+        let mut watcher = notify::watcher(tx, Duration::from_millis(DEBOUNCE_DELAY_MS))?;
+        watcher.watch(shader_path(""), RecursiveMode::NonRecursive)?;
+        Ok(ShaderWatcher {
+            _watcher: watcher,
+            events: rx,
+        })
+    }
+
+    /// Names of the programs whose files changed since the last call, e.g. "default" when
+    /// default.vs or default.fs was saved. Included files keep their extension, e.g.
+    /// "lighting.glsl", since any program could include them. Never blocks.
+    pub fn changed_programs(&self) -> Vec<String> {
+        let mut programs = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            let path = match event {
+                DebouncedEvent::Write(path)
+                | DebouncedEvent::Create(path)
+                | DebouncedEvent::Rename(_, path) => path,
+                _ => continue,
+            };
+            let name = match path.extension().and_then(|e| e.to_str()) {
+                Some("vs") | Some("fs") => path.file_stem(),
+                Some("glsl") => path.file_name(),
+                _ => None,
+            };
+            if let Some(name) = name.and_then(|s| s.to_str()) {
+                if !programs.iter().any(|p| p == name) {
+                    programs.push(name.to_string());
+                }
+            }
+        }
+        programs
+    }
+}
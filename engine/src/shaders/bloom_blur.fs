@@ -0,0 +1,28 @@
+#version 450
+
+//One direction of a separable gaussian blur of the bloom image
+layout (set = 0, binding = 0) uniform sampler2D source;
+
+layout (push_constant) uniform PushConstants {
+	//One texel along the blur direction
+	vec2 direction;
+} pc;
+
+layout (location = 0) in vec2 uv;
+
+layout (location = 0) out vec4 outColor;
+
+// 9 taps, in 5 fetches by sampling between texels
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main()
+{
+	vec3 color = texture(source, uv).rgb * weights[0];
+	for (int i = 1; i < 3; i++)
+	{
+		color += texture(source, uv + pc.direction * offsets[i]).rgb * weights[i];
+		color += texture(source, uv - pc.direction * offsets[i]).rgb * weights[i];
+	}
+	outColor = vec4(color, 1.0);
+}
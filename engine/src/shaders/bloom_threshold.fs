@@ -0,0 +1,32 @@
+#version 450
+
+//Downsamples the scene to half resolution, keeping only what is bright enough to bloom
+layout (set = 0, binding = 0) uniform sampler2D scene;
+
+layout (push_constant) uniform PushConstants {
+	vec2 texelSize;
+	float threshold;
+} pc;
+
+layout (location = 0) in vec2 uv;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+	// Four bilinear taps average the 4x4 scene pixels around this one
+	vec3 color = texture(scene, uv + pc.texelSize * vec2(-1.0, -1.0)).rgb;
+	color += texture(scene, uv + pc.texelSize * vec2(1.0, -1.0)).rgb;
+	color += texture(scene, uv + pc.texelSize * vec2(-1.0, 1.0)).rgb;
+	color += texture(scene, uv + pc.texelSize * vec2(1.0, 1.0)).rgb;
+	color *= 0.25;
+
+	// A soft knee fades pixels in around the threshold instead of switching them on
+	float brightness = max(color.r, max(color.g, color.b));
+	float knee = 0.5 * pc.threshold;
+	float soft = clamp(brightness - pc.threshold + knee, 0.0, 2.0 * knee);
+	soft = soft * soft / (4.0 * knee + 0.00001);
+	float contribution = max(soft, brightness - pc.threshold) / max(brightness, 0.00001);
+
+	outColor = vec4(color * contribution, 1.0);
+}
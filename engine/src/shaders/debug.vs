@@ -0,0 +1,17 @@
+#version 450
+
+//Debug lines are given in world space, see renderer/debug_draw.rs
+layout (push_constant) uniform PushConstants {
+    mat4 viewProjection;
+} pc;
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 color;
+
+layout (location = 0) out vec4 VertexColor;
+
+void main()
+{
+    VertexColor = color;
+    gl_Position = pc.viewProjection * vec4(position, 1.0);
+}
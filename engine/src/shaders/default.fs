@@ -1,53 +1,65 @@
-#version 330 core
+#version 450
 
-in vec3 n;
-in vec3 p;
-in vec2 texCoords;
+#extension GL_GOOGLE_include_directive : require
 
-uniform sampler2D diffuseMap;
+#include "lighting.glsl"
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+//Material shaders take their parameters and textures in set 1, in this order. See renderer/material.rs
+layout (set = 1, binding = 0) uniform MaterialParams {
+	vec4 color;
+	vec3 specularColor;
+	float smoothness;
+	float ambientAmount;
+	float emissiveStrength;
+} material;
+layout (set = 1, binding = 1) uniform sampler2D diffuseMap;
+layout (set = 1, binding = 2) uniform sampler2D normalMap;
+layout (set = 1, binding = 3) uniform sampler2D emissiveMap;
 
-const float ambientAmount = 0.1;
+layout (location = 0) in vec3 n;
+layout (location = 1) in vec3 p;
+layout (location = 2) in vec2 texCoords;
+layout (location = 3) in vec4 vertexColor;
 
-const float lightIntensity = 4.0;
 const vec3 ambientColor = vec3(1.0,1.0,1.0);
-const vec3 diffuseColor = vec3(1.0,1.0,1.0); //Diffuse
-const vec3 specularColor = vec3(1.0,0.5,0.5); //Specular
-const vec3 lightPosWorld = vec3(5,10,15);//In world space..?
 
-const float smoothness = 10.0;
-
-out vec4 color;
+layout (location = 0) out vec4 color;
 
 void main() {
+	//have to do 1-texCoords.y since obj and glsl disagree on the origin
+	vec2 uv = vec2(texCoords.x, 1.0-texCoords.y);
+
 	vec3 viewDir = normalize(-p); //since we are in view space
 	vec3 normal = normalize(n);
+	vec3 mapNormal = texture(normalMap, uv).xyz * 2.0 - 1.0;
+	normal = normalize(cotangentFrame(normal, p, uv) * mapNormal);
 
-	vec3 lightPosView = (view * vec4(lightPosWorld,1.0)).xyz;
-	vec3 lightDir = normalize(lightPosView - p);
+	vec4 albedo = texture(diffuseMap, uv) * vertexColor * material.color;
+	float specularNormalization = (material.smoothness + 8.0) / (8.0 * 3.14);
 
-	//specular
-	vec3 halfVec = normalize(lightDir + viewDir);
-	float h = clamp(dot(normal, halfVec), 0.0, 1.0);
-	float specAmount = pow(h, smoothness);
+	vec3 lighting = vec3(0.0);
+	for (int i = 0; i < frame.lightCount; i++) {
+		Light light = lightBuffer.lights[i];
 
-	//diffuse
-	float lightDist = length(lightPosView - p);
-	float attenuation = 1.0 / (1.0 + 0.1*lightDist + 0.01*pow(lightDist,2));
+		vec3 lightDir;
+		float attenuation = lightAttenuation(light, p, normal, lightDir);
 
-	float diffuseAmount = clamp(dot(lightDir, normal), 0.0, 1.0);
+		//specular
+		vec3 halfVec = normalize(lightDir + viewDir);
+		float h = clamp(dot(normal, halfVec), 0.0, 1.0);
+		float specAmount = pow(h, material.smoothness);
+		vec3 specular = specularNormalization * specAmount * material.specularColor;
 
-	//diffuseColor
-	//have to do 1-texCoords.y since obj and glsl disagree on the origin
-	vec3 diffuse = texture(diffuseMap, vec2(texCoords.x, 1.0-texCoords.y)).xyz * diffuseAmount;
-	vec3 specular = ((smoothness + 8.0) / (8.0 * 3.14)) * specAmount * specularColor;
-	vec3 ambient = ambientAmount * ambientColor;
-	vec3 Lo = ambient + (diffuse + specular) * attenuation * lightIntensity;
-
-	color = vec4(Lo, 1.0); 
-	//color = texture(diffuseMap, vec2(texCoords.x, 1.0 - texCoords.y));
-	//color = vec4(texCoords,0.0,1.0);
+		//diffuse
+		float diffuseAmount = clamp(dot(lightDir, normal), 0.0, 1.0);
+		vec3 diffuse = albedo.rgb * diffuseAmount;
+
+		lighting += (diffuse + specular) * light.color * light.intensity * attenuation;
+	}
+
+	vec3 ambient = material.ambientAmount * ambientColor * albedo.rgb;
+	vec3 emissive = texture(emissiveMap, uv).rgb * material.emissiveStrength;
+	vec3 Lo = ambient + lighting + emissive;
+
+	color = vec4(Lo, albedo.a);
 }
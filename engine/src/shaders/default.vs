@@ -1,23 +1,32 @@
-#version 330 core
+#version 450
+
+layout (set = 0, binding = 0) uniform FrameData {
+	mat4 view;
+	mat4 projection;
+	int lightCount;
+} frame;
 
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec3 normal;
-layout (location = 2) in vec3 tex_coords;
+layout (location = 2) in vec3 color;
+layout (location = 3) in vec2 tex_coord;
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
-uniform mat3 mv_inv;
+//Per instance, see renderer/instancing.rs
+layout (location = 4) in mat4 instance_model;
+layout (location = 8) in vec4 instance_tint;
 
-out vec3 n;
-out vec3 p;
-out vec2 texCoords;
+layout (location = 0) out vec3 n;
+layout (location = 1) out vec3 p;
+layout (location = 2) out vec2 texCoords;
+layout (location = 3) out vec4 vertexColor;
 
 void main()
 {
-	n = mv_inv * normal;
-	p = (view * model * vec4(position, 1.0)).xyz;
-	texCoords = tex_coords.xy;
+	mat4 modelView = frame.view * instance_model;
+	n = transpose(inverse(mat3(modelView))) * normal;
+	p = (modelView * vec4(position, 1.0)).xyz;
+	texCoords = tex_coord;
+	vertexColor = vec4(color, 1.0) * instance_tint;
 
-	gl_Position = projection * view * model * vec4(position, 1.0);
+	gl_Position = frame.projection * modelView * vec4(position, 1.0);
 }
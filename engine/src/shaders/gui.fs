@@ -1,11 +1,11 @@
-#version 330 core
+#version 450
 
-in vec2 tex_coords;
-in vec4 v_color;
+layout (location = 0) in vec2 tex_coords;
+layout (location = 1) in vec4 v_color;
 
-uniform sampler2D tex;
+layout (set = 0, binding = 1) uniform sampler2D tex;
 
-out vec4 color;
+layout (location = 0) out vec4 color;
 
 void main() {
 	color = v_color
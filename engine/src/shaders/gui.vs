@@ -1,13 +1,15 @@
-#version 330 core
+#version 450
+
+layout (set = 0, binding = 0) uniform Uniforms {
+	mat4 proj;
+};
 
 layout (location = 0) in vec2 pos;
 layout (location = 1) in vec2 uv;
 layout (location = 2) in vec4 color;
 
-uniform mat4 proj;
-
-out vec2 tex_coords;
-out vec4 v_color;
+layout (location = 0) out vec2 tex_coords;
+layout (location = 1) out vec4 v_color;
 
 void main()
 {
@@ -0,0 +1,129 @@
+//Frame data, lights and shadows in set 0, shared by the material shaders through #include "lighting.glsl".
+//Positions and directions are in view space
+
+layout (set = 0, binding = 0) uniform FrameData {
+	mat4 view;
+	mat4 projection;
+	int lightCount;
+} frame;
+
+//Filled in by the renderer each frame, see renderer/light.rs. Positions and directions are in view space
+struct Light {
+	vec3 position;
+	float range;
+	vec3 direction;
+	uint kind;
+	vec3 color;
+	float intensity;
+	float cosInner;
+	float cosOuter;
+	int shadowIndex; //first of the light's cascades, -1 if it casts no shadows
+	int cascadeCount;
+	float depthBias;
+	float slopeBias;
+};
+
+//One per tile of the shadow atlas. Directional lights have one for each cascade, spot lights one
+struct ShadowCascade {
+	mat4 matrix; //view space to the light's clip space
+	vec4 atlasRect; //offset and size of the tile, in atlas uv
+	float splitDepth; //view space depth the cascade reaches to
+};
+
+const uint LIGHT_POINT = 0u;
+const uint LIGHT_SPOT = 1u;
+const uint LIGHT_DIRECTIONAL = 2u;
+
+layout (std430, set = 0, binding = 1) readonly buffer Lights {
+	Light lights[];
+} lightBuffer;
+
+layout (std430, set = 0, binding = 2) readonly buffer Shadows {
+	ShadowCascade cascades[];
+} shadowBuffer;
+layout (set = 0, binding = 3) uniform sampler2D shadowAtlas;
+
+//Meshes have no tangents, so the tangent frame comes from screen space derivatives instead
+mat3 cotangentFrame(vec3 normal, vec3 pos, vec2 uv) {
+	vec3 dp1 = dFdx(pos);
+	vec3 dp2 = dFdy(pos);
+	vec2 duv1 = dFdx(uv);
+	vec2 duv2 = dFdy(uv);
+
+	vec3 dp2perp = cross(dp2, normal);
+	vec3 dp1perp = cross(normal, dp1);
+	vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+	vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+
+	float invmax = inversesqrt(max(max(dot(t,t), dot(b,b)), 0.000001));
+	return mat3(t * invmax, b * invmax, normal);
+}
+
+//How much of the light reaches pos, from 0 to 1. PCF over 3x3 shadow map texels
+float shadowFactor(Light light, vec3 pos, vec3 normal, vec3 lightDir) {
+	if (light.shadowIndex < 0) {
+		return 1.0;
+	}
+
+	float depth = -pos.z;
+	int index = light.shadowIndex;
+	for (int i = 0; i < light.cascadeCount - 1; i++) {
+		if (depth <= shadowBuffer.cascades[index].splitDepth) {
+			break;
+		}
+		index++;
+	}
+	ShadowCascade cascade = shadowBuffer.cascades[index];
+	if (depth > cascade.splitDepth) {
+		return 1.0;
+	}
+
+	vec4 lightClip = cascade.matrix * vec4(pos, 1.0);
+	vec3 ndc = lightClip.xyz / lightClip.w;
+	if (abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0 || ndc.z > 1.0) {
+		return 1.0;
+	}
+
+	//surfaces facing away from the light need more bias to not shadow themselves
+	float bias = max(light.slopeBias * (1.0 - dot(normal, lightDir)), light.depthBias);
+	vec2 texel = 1.0 / vec2(textureSize(shadowAtlas, 0));
+	vec2 uv = cascade.atlasRect.xy + (ndc.xy * 0.5 + 0.5) * cascade.atlasRect.zw;
+	//keeps the filter inside the tile
+	vec2 minUv = cascade.atlasRect.xy + texel;
+	vec2 maxUv = cascade.atlasRect.xy + cascade.atlasRect.zw - texel;
+
+	float lit = 0.0;
+	for (int x = -1; x <= 1; x++) {
+		for (int y = -1; y <= 1; y++) {
+			vec2 sampleUv = clamp(uv + vec2(x, y) * texel, minUv, maxUv);
+			float occluderDepth = texture(shadowAtlas, sampleUv).r;
+			lit += ndc.z - bias <= occluderDepth ? 1.0 : 0.0;
+		}
+	}
+	return lit / 9.0;
+}
+
+//Direction from pos to the light, and how much of the light's intensity reaches pos after falloff,
+//the spot cone and shadows
+float lightAttenuation(Light light, vec3 pos, vec3 normal, out vec3 lightDir) {
+	float attenuation = 1.0;
+	if (light.kind == LIGHT_DIRECTIONAL) {
+		lightDir = -light.direction;
+	} else {
+		vec3 toLight = light.position - pos;
+		float lightDist = length(toLight);
+		lightDir = toLight / lightDist;
+		attenuation = 1.0 / (1.0 + 0.1*lightDist + 0.01*pow(lightDist,2));
+
+		//fades out towards the range instead of cutting off at it
+		float rangeFade = clamp(1.0 - pow(lightDist / light.range, 4.0), 0.0, 1.0);
+		attenuation *= rangeFade * rangeFade;
+
+		if (light.kind == LIGHT_SPOT) {
+			float cosAngle = dot(-lightDir, light.direction);
+			attenuation *= smoothstep(light.cosOuter, light.cosInner, cosAngle);
+		}
+	}
+
+	return attenuation * shadowFactor(light, pos, normal, lightDir);
+}
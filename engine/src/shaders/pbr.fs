@@ -0,0 +1,112 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive : require
+
+#include "lighting.glsl"
+
+layout (set = 0, binding = 4) uniform samplerCube environmentMap; //prefiltered, see renderer/environment.rs
+
+//Metallic-roughness materials as in glTF. The factors multiply the textures
+layout (set = 1, binding = 0) uniform MaterialParams {
+	vec4 color; //base color
+	float metallic;
+	float roughness;
+	float emissiveStrength;
+	float environmentStrength;
+} material;
+layout (set = 1, binding = 1) uniform sampler2D diffuseMap; //base color
+layout (set = 1, binding = 2) uniform sampler2D normalMap;
+layout (set = 1, binding = 3) uniform sampler2D emissiveMap;
+layout (set = 1, binding = 4) uniform sampler2D metallicRoughnessMap; //roughness in g, metallic in b
+
+layout (location = 0) in vec3 n;
+layout (location = 1) in vec3 p;
+layout (location = 2) in vec2 texCoords;
+layout (location = 3) in vec4 vertexColor;
+
+const float PI = 3.14159265;
+
+layout (location = 0) out vec4 color;
+
+//GGX / Trowbridge-Reitz normal distribution
+float distributionGGX(float NdotH, float alpha) {
+	float a2 = alpha * alpha;
+	float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+//Smith geometry term with Schlick-GGX for each direction, k as remapped for direct lighting
+float geometrySmith(float NdotV, float NdotL, float roughness) {
+	float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+	float gv = NdotV / (NdotV * (1.0 - k) + k);
+	float gl = NdotL / (NdotL * (1.0 - k) + k);
+	return gv * gl;
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+	return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+//Analytic fit of the split sum BRDF lookup table, from Karis' "Physically Based Shading on Mobile"
+vec3 environmentBRDF(vec3 F0, float roughness, float NdotV) {
+	const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+	const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+	vec4 r = roughness * c0 + c1;
+	float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+	vec2 AB = vec2(-1.04, 1.04) * a004 + r.zw;
+	return F0 * AB.x + AB.y;
+}
+
+void main() {
+	//have to do 1-texCoords.y since obj and glsl disagree on the origin
+	vec2 uv = vec2(texCoords.x, 1.0-texCoords.y);
+
+	vec3 viewDir = normalize(-p); //since we are in view space
+	vec3 normal = normalize(n);
+	vec3 mapNormal = texture(normalMap, uv).xyz * 2.0 - 1.0;
+	normal = normalize(cotangentFrame(normal, p, uv) * mapNormal);
+
+	vec4 baseColor = texture(diffuseMap, uv) * vertexColor * material.color;
+	vec4 metallicRoughness = texture(metallicRoughnessMap, uv);
+	float metallic = clamp(material.metallic * metallicRoughness.b, 0.0, 1.0);
+	float roughness = clamp(material.roughness * metallicRoughness.g, 0.04, 1.0);
+	float alpha = roughness * roughness;
+
+	//dielectrics reflect about 4% head on, metals reflect their base color and don't diffuse
+	vec3 F0 = mix(vec3(0.04), baseColor.rgb, metallic);
+	vec3 diffuseColor = baseColor.rgb * (1.0 - metallic);
+	float NdotV = max(dot(normal, viewDir), 0.0001);
+
+	vec3 lighting = vec3(0.0);
+	for (int i = 0; i < frame.lightCount; i++) {
+		Light light = lightBuffer.lights[i];
+
+		vec3 lightDir;
+		float attenuation = lightAttenuation(light, p, normal, lightDir);
+
+		vec3 halfVec = normalize(lightDir + viewDir);
+		float NdotL = clamp(dot(normal, lightDir), 0.0, 1.0);
+		float NdotH = clamp(dot(normal, halfVec), 0.0, 1.0);
+		float VdotH = clamp(dot(viewDir, halfVec), 0.0, 1.0);
+
+		vec3 F = fresnelSchlick(VdotH, F0);
+		float D = distributionGGX(NdotH, alpha);
+		float G = geometrySmith(NdotV, NdotL, roughness);
+		vec3 specular = D * G * F / max(4.0 * NdotV * NdotL, 0.0001);
+		vec3 diffuse = (1.0 - F) * diffuseColor / PI;
+
+		lighting += (diffuse + specular) * light.color * light.intensity * attenuation * NdotL;
+	}
+
+	//image based ambient: the blurriest level is close to irradiance, rougher surfaces reflect blurrier levels
+	mat3 viewToWorld = transpose(mat3(frame.view));
+	vec3 worldNormal = viewToWorld * normal;
+	vec3 worldReflection = viewToWorld * reflect(-viewDir, normal);
+	float maxLod = float(textureQueryLevels(environmentMap) - 1);
+	vec3 irradiance = textureLod(environmentMap, worldNormal, maxLod).rgb;
+	vec3 reflection = textureLod(environmentMap, worldReflection, roughness * maxLod).rgb;
+	vec3 ambient = (diffuseColor * irradiance + reflection * environmentBRDF(F0, roughness, NdotV)) * material.environmentStrength;
+
+	vec3 emissive = texture(emissiveMap, uv).rgb * material.emissiveStrength;
+	color = vec4(ambient + lighting + emissive, baseColor.a);
+}
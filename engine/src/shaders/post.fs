@@ -0,0 +1,60 @@
+#version 450
+
+//Turns the HDR scene into the final image: bloom, exposure, tonemapping, vignette and color grading
+layout (set = 0, binding = 0) uniform sampler2D scene;
+layout (set = 0, binding = 1) uniform sampler2D bloom;
+layout (set = 0, binding = 2) uniform sampler3D colorLut;
+
+layout (push_constant) uniform PushConstants {
+	float exposure;
+	float bloomIntensity;
+	float vignetteStrength;
+	int tonemapping;
+	int colorGrading;
+} pc;
+
+layout (location = 0) in vec2 uv;
+
+layout (location = 0) out vec4 outColor;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+vec3 acesFilm(vec3 x)
+{
+	return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 linearToSrgb(vec3 color)
+{
+	vec3 low = color * 12.92;
+	vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+	return mix(high, low, lessThanEqual(color, vec3(0.0031308)));
+}
+
+vec3 srgbToLinear(vec3 color)
+{
+	vec3 low = color / 12.92;
+	vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+	return mix(high, low, lessThanEqual(color, vec3(0.04045)));
+}
+
+void main()
+{
+	vec3 color = texture(scene, uv).rgb + texture(bloom, uv).rgb * pc.bloomIntensity;
+	color *= pc.exposure;
+	color = pc.tonemapping != 0 ? acesFilm(color) : clamp(color, 0.0, 1.0);
+
+	// Darkens towards the corners
+	float fromCenter = length(uv - 0.5) * 1.41421356;
+	color *= 1.0 - pc.vignetteStrength * smoothstep(0.4, 1.0, fromCenter);
+
+	// LUTs are authored on sRGB colors; the sample is moved in half a texel from each edge so
+	// the end texels are hit exactly
+	if (pc.colorGrading != 0)
+	{
+		float size = float(textureSize(colorLut, 0).x);
+		vec3 coords = linearToSrgb(color) * ((size - 1.0) / size) + 0.5 / size;
+		color = srgbToLinear(texture(colorLut, coords).rgb);
+	}
+
+	outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,6 @@
+#version 450
+
+//Only depth is written, but a pipeline needs a fragment stage
+void main()
+{
+}
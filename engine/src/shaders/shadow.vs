@@ -0,0 +1,14 @@
+#version 450
+
+//Depth only pass into one tile of the shadow atlas, see renderer/shadow.rs
+layout (push_constant) uniform PushConstants {
+	mat4 lightViewProjection;
+} pc;
+
+layout (location = 0) in vec3 position;
+layout (location = 4) in mat4 instance_model;
+
+void main()
+{
+	gl_Position = pc.lightViewProjection * instance_model * vec4(position, 1.0);
+}
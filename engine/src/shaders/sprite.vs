@@ -1,11 +1,13 @@
-#version 330 core
+#version 450
+
+layout (set = 0, binding = 0) uniform Uniforms {
+    mat4 mvp;
+};
 
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec2 tex_coord;
 
-uniform mat4 mvp;
-
-out vec2 TexCoord;
+layout (location = 0) out vec2 TexCoord;
 
 void main()
 {
@@ -1,12 +1,14 @@
-#version 330 core
+#version 450
 
-uniform sampler2D font;
+layout (set = 0, binding = 0) uniform sampler2D font;
 
-in vec2 TexCoord;
-out vec4 color;
+layout (location = 0) in vec2 TexCoord;
+layout (location = 1) in vec4 VertexColor;
+
+layout (location = 0) out vec4 color;
 
 void main()
 {
     float x = texture(font,TexCoord).r;
-    color = vec4(x,x,x, 1.0);
+    color = vec4(VertexColor.rgb, VertexColor.a * x);
 }
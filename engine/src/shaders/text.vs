@@ -1,12 +1,20 @@
-#version 330 core
+#version 450
 
-layout (location = 0) in vec3 position;
+//Positions are in pixels from the top left corner of the window
+layout (push_constant) uniform PushConstants {
+    vec2 screenSize;
+} pc;
+
+layout (location = 0) in vec2 position;
 layout (location = 1) in vec2 tex_coord;
+layout (location = 2) in vec4 color;
 
-out vec2 TexCoord;
+layout (location = 0) out vec2 TexCoord;
+layout (location = 1) out vec4 VertexColor;
 
 void main()
 {
     TexCoord = tex_coord;
-    gl_Position = vec4(position, 1.0);
+    VertexColor = color;
+    gl_Position = vec4(position / pc.screenSize * 2.0 - 1.0, 0.0, 1.0);
 }
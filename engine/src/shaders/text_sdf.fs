@@ -0,0 +1,50 @@
+#version 450
+
+layout (set = 0, binding = 0) uniform sampler2D font;
+
+//Matches SdfParams in text/sdf.rs. Widths are in distance field units where the edge is at 0.5
+layout (std140, set = 0, binding = 1) uniform SdfParams {
+	vec4 textColor;
+	vec4 outlineColor;
+	vec4 shadowColor;
+	vec4 glowColor;
+	vec2 shadowOffset;
+	float outlineWidth;
+	float shadowSoftness;
+	float glowWidth;
+};
+
+layout (location = 0) in vec2 TexCoord;
+layout (location = 1) in vec4 VertexColor;
+
+layout (location = 0) out vec4 color;
+
+vec4 over(vec4 src, vec4 dst) {
+	float a = src.a + dst.a * (1.0 - src.a);
+	vec3 rgb = (src.rgb * src.a + dst.rgb * dst.a * (1.0 - src.a)) / max(a, 0.0001);
+	return vec4(rgb, a);
+}
+
+void main()
+{
+	float dist = texture(font, TexCoord).r;
+	//Half a screen pixel in distance units, so edges stay one pixel wide at any scale
+	float aa = max(fwidth(dist) * 0.5, 0.0001);
+
+	float fill = smoothstep(0.5 - aa, 0.5 + aa, dist);
+	float outlineEdge = 0.5 - outlineWidth;
+	float outline = smoothstep(outlineEdge - aa, outlineEdge + aa, dist);
+	float glow = glowWidth > 0.0 ? smoothstep(outlineEdge - glowWidth, outlineEdge, dist) : 0.0;
+
+	float shadowDist = texture(font, TexCoord - shadowOffset).r;
+	float shadow = smoothstep(outlineEdge - shadowSoftness - aa, outlineEdge + shadowSoftness + aa, shadowDist);
+
+	vec4 body = mix(outlineColor, textColor * VertexColor, fill);
+	body.a *= outline;
+
+	vec4 result = vec4(shadowColor.rgb, shadowColor.a * shadow);
+	result = over(vec4(glowColor.rgb, glowColor.a * glow), result);
+	result = over(body, result);
+
+	color = result;
+}
@@ -1,12 +1,14 @@
-#version 330 core
+#version 450
 
-uniform sampler2D tex0;
+layout (set = 0, binding = 0) uniform Uniforms {
+    vec3 spriteColor;
+};
 
-uniform vec3 spriteColor;
+layout (set = 0, binding = 1) uniform sampler2D tex0;
 
-in vec2 TexCoord;
+layout (location = 0) in vec2 TexCoord;
 
-out vec4 color;
+layout (location = 0) out vec4 color;
 
 void main()
 {
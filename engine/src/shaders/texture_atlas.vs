@@ -1,9 +1,9 @@
-#version 330 core
+#version 450
 
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec2 tex_coord;
 
-out vec2 TexCoord;
+layout (location = 0) out vec2 TexCoord;
 
 void main()
 {
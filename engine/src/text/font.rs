@@ -0,0 +1,432 @@
+use glm::{vec2, Vec2};
+use of::OrderedFloat;
+use rusttype::{self, point, Scale};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+const DEFAULT_FONT: &[u8] = include_bytes!("../../Roboto-Regular.ttf");
+const DEFAULT_PIXEL_SIZE: f32 = 14.0;
+
+const INITIAL_ATLAS_SIZE: u32 = 256;
+const MAX_ATLAS_SIZE: u32 = 8192;
+// Empty pixels kept between glyphs so linear filtering doesn't bleed neighbours in
+const GLYPH_PADDING: u32 = 1;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    InvalidFont(rusttype::Error),
+}
+
+impl From<io::Error> for FontError {
+    fn from(e: io::Error) -> FontError {
+        FontError::Io(e)
+    }
+}
+
+impl From<rusttype::Error> for FontError {
+    fn from(e: rusttype::Error) -> FontError {
+        FontError::InvalidFont(e)
+    }
+}
+
+/// A rasterized glyph living in the font's atlas.
+///
+/// `bearing` is the offset from the pen position on the baseline to the top left
+/// corner of the bitmap, with y pointing down.
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    pub advance: f32,
+    pub bearing: Vec2,
+    pub width: u32,
+    pub height: u32,
+    pub atlas_x: u32,
+    pub atlas_y: u32,
+}
+
+impl Glyph {
+    /// Texture coordinates (min, max) of the glyph in an atlas of the given size.
+    /// Computed on demand since the atlas may have grown since the glyph was added.
+    pub fn uv(&self, atlas_size: (u32, u32)) -> (Vec2, Vec2) {
+        let (w, h) = (atlas_size.0 as f32, atlas_size.1 as f32);
+        (
+            vec2(self.atlas_x as f32 / w, self.atlas_y as f32 / h),
+            vec2(
+                (self.atlas_x + self.width) as f32 / w,
+                (self.atlas_y + self.height) as f32 / h,
+            ),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LineMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor_x: u32,
+}
+
+/// Single channel coverage texture that glyphs are packed into shelf by shelf.
+/// When it runs out of room it doubles in size, keeping already placed glyphs where they are.
+pub struct GlyphAtlas {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    shelves: Vec<Shelf>,
+    version: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> GlyphAtlas {
+        GlyphAtlas {
+            width: width,
+            height: height,
+            data: vec![0; (width * height) as usize],
+            shelves: Vec::new(),
+            version: 0,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Bumped every time a glyph is added or the atlas grows, so renderers know when to re-upload.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn allocate(&mut self, width: u32, height: u32) -> (u32, u32) {
+        self.version += 1;
+        loop {
+            if let Some(pos) = self.try_allocate(width + GLYPH_PADDING, height + GLYPH_PADDING) {
+                return pos;
+            }
+            self.grow();
+        }
+    }
+
+    fn try_allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width {
+            return None;
+        }
+
+        let atlas_width = self.width;
+        let best_shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|s| s.height >= height && s.cursor_x + width <= atlas_width)
+            .min_by_key(|s| s.height - height);
+        if let Some(shelf) = best_shelf {
+            let pos = (shelf.cursor_x, shelf.y);
+            shelf.cursor_x += width;
+            return Some(pos);
+        }
+
+        let next_y = self.shelves.last().map(|s| s.y + s.height).unwrap_or(0);
+        if next_y + height > self.height {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y: next_y,
+            height: height,
+            cursor_x: width,
+        });
+        Some((0, next_y))
+    }
+
+    fn grow(&mut self) {
+        if self.width >= MAX_ATLAS_SIZE && self.height >= MAX_ATLAS_SIZE {
+            panic!("Glyph atlas exceeded {0}x{0} pixels", MAX_ATLAS_SIZE);
+        }
+
+        if self.width <= self.height {
+            let new_width = self.width * 2;
+            let mut data = vec![0; (new_width * self.height) as usize];
+            for y in 0..self.height as usize {
+                let src = y * self.width as usize;
+                let dst = y * new_width as usize;
+                data[dst..dst + self.width as usize]
+                    .copy_from_slice(&self.data[src..src + self.width as usize]);
+            }
+            self.data = data;
+            self.width = new_width;
+        } else {
+            self.height *= 2;
+            self.data.resize((self.width * self.height) as usize, 0);
+        }
+        self.version += 1;
+    }
+
+    pub fn write(&mut self, x: u32, y: u32, value: u8) {
+        self.data[(x + y * self.width) as usize] = value;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    c: char,
+    pixel_size: OrderedFloat<f32>,
+}
+
+/// A TrueType font that rasterizes glyphs into its atlas the first time they are asked for.
+pub struct Font {
+    font: rusttype::Font<'static>,
+    pixel_size: f32,
+    glyphs: HashMap<GlyphKey, Glyph>,
+    atlas: GlyphAtlas,
+}
+
+impl Font {
+    /// The bundled Roboto font at 14 pixels.
+    pub fn new() -> Font {
+        Font::from_bytes(DEFAULT_FONT.to_vec(), DEFAULT_PIXEL_SIZE).unwrap()
+    }
+
+    pub fn from_path(path: &Path, pixel_size: f32) -> Result<Font, FontError> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Font::from_bytes(bytes, pixel_size)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>, pixel_size: f32) -> Result<Font, FontError> {
+        let font = rusttype::Font::from_bytes(bytes)?;
+        Ok(Font {
+            font: font,
+            pixel_size: pixel_size,
+            glyphs: HashMap::new(),
+            atlas: GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE),
+        })
+    }
+
+    pub fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    pub fn inner(&self) -> &rusttype::Font<'static> {
+        &self.font
+    }
+
+    pub fn line_metrics(&self) -> LineMetrics {
+        self.line_metrics_sized(self.pixel_size)
+    }
+
+    pub fn line_metrics_sized(&self, pixel_size: f32) -> LineMetrics {
+        let v = self.font.v_metrics(Scale::uniform(pixel_size));
+        LineMetrics {
+            ascent: v.ascent,
+            descent: v.descent,
+            line_gap: v.line_gap,
+        }
+    }
+
+    pub fn glyph(&mut self, c: char) -> Glyph {
+        let size = self.pixel_size;
+        self.glyph_sized(c, size)
+    }
+
+    /// Looks up `c` at `pixel_size`, rasterizing it into the atlas if it isn't there yet.
+    pub fn glyph_sized(&mut self, c: char, pixel_size: f32) -> Glyph {
+        let key = GlyphKey {
+            c: c,
+            pixel_size: OrderedFloat(pixel_size),
+        };
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+        let glyph = self.rasterize(c, pixel_size);
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    /// Rasterizes every character of `s` up front, to avoid atlas uploads mid game.
+    pub fn preload(&mut self, s: &str) {
+        for c in s.chars() {
+            self.glyph(c);
+        }
+    }
+
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kerning_sized(first, second, self.pixel_size)
+    }
+
+    pub fn kerning_sized(&self, first: char, second: char, pixel_size: f32) -> f32 {
+        self.font
+            .pair_kerning(Scale::uniform(pixel_size), first, second)
+    }
+
+    fn rasterize(&mut self, c: char, pixel_size: f32) -> Glyph {
+        let scaled = self.font.glyph(c).scaled(Scale::uniform(pixel_size));
+        let advance = scaled.h_metrics().advance_width;
+        let positioned = scaled.positioned(point(0.0, 0.0));
+
+        let bounding_box = match positioned.pixel_bounding_box() {
+            Some(bb) => bb,
+            None => {
+                return Glyph {
+                    advance: advance,
+                    bearing: vec2(0.0, 0.0),
+                    width: 0,
+                    height: 0,
+                    atlas_x: 0,
+                    atlas_y: 0,
+                }
+            }
+        };
+
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+        let (atlas_x, atlas_y) = self.atlas.allocate(width, height);
+
+        let atlas = &mut self.atlas;
+        positioned.draw(|x, y, v| {
+            atlas.write(atlas_x + x, atlas_y + y, (v * 255.0) as u8);
+        });
+
+        Glyph {
+            advance: advance,
+            bearing: vec2(bounding_box.min.x as f32, bounding_box.min.y as f32),
+            width: width,
+            height: height,
+            atlas_x: atlas_x,
+            atlas_y: atlas_y,
+        }
+    }
+
+    pub fn total_size(&self) -> (u32, u32) {
+        self.atlas.size()
+    }
+
+    pub fn data(&self) -> Vec<u8> {
+        self.atlas.data().to_vec()
+    }
+
+    pub fn rgba_data(&self) -> Vec<u8> {
+        let data = self.atlas.data();
+
+        let mut ret = Vec::with_capacity(data.len() * 4);
+        for d in data {
+            for _ in 0..4 {
+                ret.push(*d);
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_packed_along_a_shelf() {
+        let mut atlas = GlyphAtlas::new(64, 64);
+        assert_eq!(atlas.allocate(10, 8), (0, 0));
+        assert_eq!(atlas.allocate(10, 8), (10 + GLYPH_PADDING, 0));
+        assert_eq!(atlas.allocate(5, 4), (2 * (10 + GLYPH_PADDING), 0));
+    }
+
+    #[test]
+    fn taller_glyphs_start_a_new_shelf() {
+        let mut atlas = GlyphAtlas::new(64, 64);
+        atlas.allocate(10, 8);
+        assert_eq!(atlas.allocate(10, 20), (0, 8 + GLYPH_PADDING));
+        // A short glyph goes on the shelf that wastes the least height
+        assert_eq!(atlas.allocate(10, 8), (10 + GLYPH_PADDING, 0));
+    }
+
+    #[test]
+    fn full_shelves_wrap_to_a_new_one() {
+        let mut atlas = GlyphAtlas::new(32, 64);
+        assert_eq!(atlas.allocate(15, 8), (0, 0));
+        assert_eq!(atlas.allocate(15, 8), (16, 0));
+        assert_eq!(atlas.allocate(15, 8), (0, 9));
+    }
+
+    #[test]
+    fn a_full_atlas_grows_and_keeps_its_pixels() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        let (x, y) = atlas.allocate(12, 12);
+        atlas.write(x + 3, y + 5, 200);
+        let version = atlas.version();
+
+        // Doesn't fit beside or below the first glyph, so the width doubles
+        assert_eq!(atlas.allocate(12, 12), (13, 0));
+        assert_eq!(atlas.size(), (32, 16));
+        assert!(atlas.version() > version);
+        assert_eq!(atlas.data().len(), 32 * 16);
+        assert_eq!(atlas.data()[(3 + 5 * 32) as usize], 200);
+
+        // Then the height, since it is now the smaller side
+        assert_eq!(atlas.allocate(12, 12), (0, 13));
+        assert_eq!(atlas.size(), (32, 32));
+        assert_eq!(atlas.data()[(3 + 5 * 32) as usize], 200);
+    }
+
+    #[test]
+    #[should_panic(expected = "Glyph atlas exceeded")]
+    fn glyphs_wider_than_the_largest_atlas_panic() {
+        let mut atlas = GlyphAtlas::new(MAX_ATLAS_SIZE / 2, MAX_ATLAS_SIZE / 2);
+        atlas.allocate(MAX_ATLAS_SIZE, 1);
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_once() {
+        let mut font = Font::new();
+        let a = font.glyph('A');
+        let version = font.atlas().version();
+        let again = font.glyph('A');
+        assert_eq!((a.atlas_x, a.atlas_y), (again.atlas_x, again.atlas_y));
+        assert_eq!(font.atlas().version(), version);
+    }
+
+    #[test]
+    fn glyph_metrics() {
+        let mut font = Font::new();
+        let a = font.glyph('A');
+        assert!(!a.is_empty());
+        assert!(a.advance > 0.0);
+        // The bitmap sits above the baseline, y pointing down
+        assert!(a.bearing.y < 0.0);
+        assert!(a.height as f32 <= font.line_metrics().line_height());
+
+        let (uv_min, uv_max) = a.uv(font.atlas().size());
+        assert!(uv_min.x >= 0.0 && uv_min.y >= 0.0);
+        assert!(uv_max.x <= 1.0 && uv_max.y <= 1.0);
+        assert!(uv_max.x > uv_min.x && uv_max.y > uv_min.y);
+
+        let space = font.glyph(' ');
+        assert!(space.is_empty());
+        assert!(space.advance > 0.0);
+
+        let large = font.glyph_sized('A', DEFAULT_PIXEL_SIZE * 2.0);
+        assert!(large.width > a.width);
+        assert!(large.advance > a.advance);
+    }
+}
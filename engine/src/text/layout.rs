@@ -0,0 +1,606 @@
+use drawing::Color4;
+use glm::{vec2, vec4, Vec2};
+
+use super::font::{Font, Glyph, LineMetrics};
+
+/// Anything that can hand out atlas glyphs for layout, like the bitmap `Font` or an `SdfFont`.
+pub trait GlyphSource {
+    fn pixel_size(&self) -> f32;
+    /// Metrics are in pixels at `pixel_size`; the atlas region is in atlas pixels.
+    fn glyph_sized(&mut self, c: char, pixel_size: f32) -> Glyph;
+    fn kerning_sized(&self, first: char, second: char, pixel_size: f32) -> f32;
+    fn line_metrics_sized(&self, pixel_size: f32) -> LineMetrics;
+    fn atlas_size(&self) -> (u32, u32);
+
+    /// How many screen pixels one atlas pixel covers when drawn at `pixel_size`.
+    fn glyph_scale(&self, _pixel_size: f32) -> f32 {
+        1.0
+    }
+}
+
+impl GlyphSource for Font {
+    fn pixel_size(&self) -> f32 {
+        Font::pixel_size(self)
+    }
+
+    fn glyph_sized(&mut self, c: char, pixel_size: f32) -> Glyph {
+        Font::glyph_sized(self, c, pixel_size)
+    }
+
+    fn kerning_sized(&self, first: char, second: char, pixel_size: f32) -> f32 {
+        Font::kerning_sized(self, first, second, pixel_size)
+    }
+
+    fn line_metrics_sized(&self, pixel_size: f32) -> LineMetrics {
+        Font::line_metrics_sized(self, pixel_size)
+    }
+
+    fn atlas_size(&self) -> (u32, u32) {
+        self.atlas().size()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug)]
+pub struct LayoutOptions {
+    /// Lines are wrapped at word boundaries to stay within this many pixels.
+    pub max_width: Option<f32>,
+    pub alignment: Alignment,
+    /// Multiplier applied to the font's line height.
+    pub line_spacing: f32,
+    pub color: Color4,
+    /// Interpret `[color=#rrggbb]..[/color]` and `[size=20]..[/size]` tags. `[[` is a literal `[`.
+    pub markup: bool,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> LayoutOptions {
+        LayoutOptions {
+            max_width: None,
+            alignment: Alignment::Left,
+            line_spacing: 1.0,
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            markup: false,
+        }
+    }
+}
+
+/// A textured rectangle in pixels, relative to the top left corner of the laid out text with y down.
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphQuad {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub color: Color4,
+}
+
+#[derive(Clone, Debug)]
+pub struct TextLayout {
+    pub quads: Vec<GlyphQuad>,
+    /// Width of the widest line and total height of all lines.
+    pub size: Vec2,
+    pub line_count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct StyledChar {
+    c: char,
+    color: Color4,
+    size: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Placed {
+    glyph: Glyph,
+    x: f32,
+    scale: f32,
+    color: Color4,
+}
+
+struct Line {
+    glyphs: Vec<Placed>,
+    width: f32,
+    ascent: f32,
+    height: f32,
+    /// The last character placed, which the next one is kerned against even across words.
+    last: Option<StyledChar>,
+}
+
+impl Line {
+    fn new() -> Line {
+        Line {
+            glyphs: Vec::new(),
+            width: 0.0,
+            ascent: 0.0,
+            height: 0.0,
+            last: None,
+        }
+    }
+}
+
+enum Token {
+    Word(Vec<StyledChar>),
+    Space(Vec<StyledChar>),
+    Newline(f32),
+}
+
+pub fn layout_text<F: GlyphSource>(
+    font: &mut F,
+    text: &str,
+    options: &LayoutOptions,
+) -> TextLayout {
+    let chars = if options.markup {
+        parse_markup(text, options.color, font.pixel_size())
+    } else {
+        text.chars()
+            .map(|c| StyledChar {
+                c: c,
+                color: options.color,
+                size: font.pixel_size(),
+            })
+            .collect()
+    };
+
+    let lines = break_lines(font, &tokenize(&chars), options);
+
+    let block_width = match (options.max_width, options.alignment) {
+        (Some(w), Alignment::Center) | (Some(w), Alignment::Right) => w,
+        _ => lines.iter().fold(0.0, |w, l| f32::max(w, l.width)),
+    };
+
+    let atlas_size = font.atlas_size();
+    let mut quads = Vec::new();
+    let mut size = vec2(0.0, 0.0);
+    let mut y = 0.0;
+    for line in &lines {
+        let x_offset = match options.alignment {
+            Alignment::Left => 0.0,
+            Alignment::Center => (block_width - line.width) / 2.0,
+            Alignment::Right => block_width - line.width,
+        };
+        let baseline = y + line.ascent;
+        for placed in &line.glyphs {
+            if placed.glyph.is_empty() {
+                continue;
+            }
+            let min = vec2(x_offset + placed.x, baseline) + placed.glyph.bearing;
+            let (uv_min, uv_max) = placed.glyph.uv(atlas_size);
+            quads.push(GlyphQuad {
+                min: min,
+                max: min
+                    + vec2(placed.glyph.width as f32, placed.glyph.height as f32) * placed.scale,
+                uv_min: uv_min,
+                uv_max: uv_max,
+                color: placed.color,
+            });
+        }
+        size.x = f32::max(size.x, line.width);
+        y += line.height * options.line_spacing;
+    }
+    size.y = y;
+
+    TextLayout {
+        quads: quads,
+        size: size,
+        line_count: lines.len(),
+    }
+}
+
+/// Measures `text` without keeping the quads around, for placing HUD elements.
+pub fn measure_text<F: GlyphSource>(font: &mut F, text: &str, options: &LayoutOptions) -> Vec2 {
+    layout_text(font, text, options).size
+}
+
+fn tokenize(chars: &[StyledChar]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Vec<StyledChar> = Vec::new();
+    let mut in_space = false;
+
+    for sc in chars {
+        if sc.c == '\n' {
+            flush_token(&mut tokens, &mut current, in_space);
+            tokens.push(Token::Newline(sc.size));
+            continue;
+        }
+        let is_space = sc.c.is_whitespace();
+        if is_space != in_space {
+            flush_token(&mut tokens, &mut current, in_space);
+            in_space = is_space;
+        }
+        current.push(*sc);
+    }
+    flush_token(&mut tokens, &mut current, in_space);
+    tokens
+}
+
+fn flush_token(tokens: &mut Vec<Token>, current: &mut Vec<StyledChar>, is_space: bool) {
+    if current.is_empty() {
+        return;
+    }
+    let chars = ::std::mem::replace(current, Vec::new());
+    tokens.push(if is_space {
+        Token::Space(chars)
+    } else {
+        Token::Word(chars)
+    });
+}
+
+fn break_lines<F: GlyphSource>(
+    font: &mut F,
+    tokens: &[Token],
+    options: &LayoutOptions,
+) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line::new();
+    // Spaces are only placed once a word follows them, so wrapped lines never end or start in whitespace
+    let mut pending_space: &[StyledChar] = &[];
+    // The size of the newline that started the current line, if nothing was placed on it since
+    let mut after_newline = None;
+
+    for token in tokens {
+        match *token {
+            Token::Newline(size) => {
+                grow_line_height(font, &mut line, size);
+                lines.push(::std::mem::replace(&mut line, Line::new()));
+                pending_space = &[];
+                after_newline = Some(size);
+            }
+            Token::Space(ref chars) => {
+                pending_space = &chars[..];
+            }
+            Token::Word(ref chars) => {
+                after_newline = None;
+                let space_width = measure(font, line.last, pending_space);
+                let word_prev = pending_space.last().cloned().or(line.last);
+                let word_width = measure(font, word_prev, chars);
+                if let Some(max_width) = options.max_width {
+                    let fits = line.width + space_width + word_width <= max_width;
+                    if !fits && !line.glyphs.is_empty() {
+                        lines.push(::std::mem::replace(&mut line, Line::new()));
+                        pending_space = &[];
+                    }
+                }
+                place(font, &mut line, pending_space);
+                pending_space = &[];
+
+                match options.max_width {
+                    Some(max_width) if word_width > max_width => {
+                        // A single word wider than the line has to be broken between characters
+                        for sc in chars {
+                            let w = measure(font, line.last, &[*sc]);
+                            if line.width + w > max_width && !line.glyphs.is_empty() {
+                                lines.push(::std::mem::replace(&mut line, Line::new()));
+                            }
+                            place(font, &mut line, &[*sc]);
+                        }
+                    }
+                    _ => place(font, &mut line, chars),
+                }
+            }
+        }
+    }
+    // Text ending in a newline ends in an empty line
+    if !line.glyphs.is_empty() || lines.is_empty() || after_newline.is_some() {
+        if line.height == 0.0 {
+            let size = after_newline.unwrap_or_else(|| font.pixel_size());
+            grow_line_height(font, &mut line, size);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// The width of `chars` placed after `prev`, including the kerning between them.
+fn measure<F: GlyphSource>(font: &mut F, prev: Option<StyledChar>, chars: &[StyledChar]) -> f32 {
+    let mut width = 0.0;
+    let mut prev = prev;
+    for sc in chars {
+        if let Some(p) = prev {
+            if p.size == sc.size {
+                width += font.kerning_sized(p.c, sc.c, sc.size);
+            }
+        }
+        width += font.glyph_sized(sc.c, sc.size).advance;
+        prev = Some(*sc);
+    }
+    width
+}
+
+fn place<F: GlyphSource>(font: &mut F, line: &mut Line, chars: &[StyledChar]) {
+    for sc in chars {
+        if let Some(p) = line.last {
+            if p.size == sc.size {
+                line.width += font.kerning_sized(p.c, sc.c, sc.size);
+            }
+        }
+        let glyph = font.glyph_sized(sc.c, sc.size);
+        line.glyphs.push(Placed {
+            glyph: glyph,
+            x: line.width,
+            scale: font.glyph_scale(sc.size),
+            color: sc.color,
+        });
+        line.width += glyph.advance;
+        grow_line_height(font, line, sc.size);
+        line.last = Some(*sc);
+    }
+}
+
+fn grow_line_height<F: GlyphSource>(font: &F, line: &mut Line, size: f32) {
+    let metrics = font.line_metrics_sized(size);
+    line.ascent = f32::max(line.ascent, metrics.ascent);
+    line.height = f32::max(line.height, metrics.line_height());
+}
+
+fn parse_markup(text: &str, color: Color4, size: f32) -> Vec<StyledChar> {
+    let mut colors = vec![color];
+    let mut sizes = vec![size];
+    let mut ret = Vec::new();
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("[[") {
+            rest = &rest[2..];
+        } else if c == '[' {
+            if let Some(end) = rest.find(']') {
+                let tag = &rest[1..end];
+                if apply_tag(tag, &mut colors, &mut sizes) {
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+            rest = &rest[1..];
+        } else {
+            rest = &rest[c.len_utf8()..];
+        }
+        ret.push(StyledChar {
+            c: c,
+            color: *colors.last().unwrap(),
+            size: *sizes.last().unwrap(),
+        });
+    }
+    ret
+}
+
+/// Returns false for anything that isn't a known tag, in which case it is drawn as text.
+fn apply_tag(tag: &str, colors: &mut Vec<Color4>, sizes: &mut Vec<f32>) -> bool {
+    match tag {
+        "/color" if colors.len() > 1 => {
+            colors.pop();
+            true
+        }
+        "/size" if sizes.len() > 1 => {
+            sizes.pop();
+            true
+        }
+        _ if tag.starts_with("color=") => match parse_color(&tag["color=".len()..]) {
+            Some(c) => {
+                colors.push(c);
+                true
+            }
+            None => false,
+        },
+        _ if tag.starts_with("size=") => match tag["size=".len()..].parse::<f32>() {
+            Ok(s) if s > 0.0 => {
+                sizes.push(s);
+                true
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+pub fn parse_color(s: &str) -> Option<Color4> {
+    let named = match s {
+        "white" => Some(vec4(1.0, 1.0, 1.0, 1.0)),
+        "black" => Some(vec4(0.0, 0.0, 0.0, 1.0)),
+        "red" => Some(vec4(1.0, 0.0, 0.0, 1.0)),
+        "green" => Some(vec4(0.0, 1.0, 0.0, 1.0)),
+        "blue" => Some(vec4(0.0, 0.0, 1.0, 1.0)),
+        "yellow" => Some(vec4(1.0, 1.0, 0.0, 1.0)),
+        _ => None,
+    };
+    if named.is_some() {
+        return named;
+    }
+
+    if !s.starts_with('#') || !s.is_ascii() {
+        return None;
+    }
+    let hex = &s[1..];
+    if hex.len() != 6 && hex.len() != 8 {
+        return None;
+    }
+    let mut channels = [1.0; 4];
+    for (i, channel) in channels.iter_mut().enumerate().take(hex.len() / 2) {
+        let byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        *channel = byte as f32 / 255.0;
+    }
+    Some(vec4(channels[0], channels[1], channels[2], channels[3]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every glyph advances 10 pixels at size 10 and lines are 10 pixels high, so positions can
+    /// be worked out by hand.
+    struct FixedFont;
+
+    impl GlyphSource for FixedFont {
+        fn pixel_size(&self) -> f32 {
+            10.0
+        }
+
+        fn glyph_sized(&mut self, c: char, pixel_size: f32) -> Glyph {
+            let size = if c.is_whitespace() { 0 } else { 6 };
+            Glyph {
+                advance: pixel_size,
+                bearing: vec2(1.0, -7.0),
+                width: size,
+                height: size,
+                atlas_x: 0,
+                atlas_y: 0,
+            }
+        }
+
+        fn kerning_sized(&self, first: char, second: char, _pixel_size: f32) -> f32 {
+            match (first, second) {
+                ('A', 'V') => -2.0,
+                ('T', ' ') | (' ', 'T') => -3.0,
+                _ => 0.0,
+            }
+        }
+
+        fn line_metrics_sized(&self, pixel_size: f32) -> LineMetrics {
+            LineMetrics {
+                ascent: pixel_size * 0.8,
+                descent: -pixel_size * 0.2,
+                line_gap: 0.0,
+            }
+        }
+
+        fn atlas_size(&self) -> (u32, u32) {
+            (64, 64)
+        }
+    }
+
+    fn layout(text: &str, options: &LayoutOptions) -> TextLayout {
+        layout_text(&mut FixedFont, text, options)
+    }
+
+    #[test]
+    fn single_line() {
+        let layout = layout("abc", &LayoutOptions::default());
+        assert_eq!(layout.line_count, 1);
+        assert_eq!(layout.size, vec2(30.0, 10.0));
+        assert_eq!(layout.quads.len(), 3);
+        // Bearing from the pen on the baseline, 8 pixels down
+        assert_eq!(layout.quads[1].min, vec2(11.0, 1.0));
+        assert_eq!(layout.quads[1].max, vec2(17.0, 7.0));
+    }
+
+    #[test]
+    fn kerning_within_and_across_words() {
+        assert_eq!(layout("AV", &LayoutOptions::default()).size.x, 18.0);
+        // T and the space are kerned on both sides of it
+        assert_eq!(layout("T T", &LayoutOptions::default()).size.x, 24.0);
+    }
+
+    #[test]
+    fn words_wrap_at_max_width() {
+        let options = LayoutOptions {
+            max_width: Some(50.0),
+            ..LayoutOptions::default()
+        };
+        let layout = layout("aa bb cc", &options);
+        assert_eq!(layout.line_count, 2);
+        assert_eq!(layout.size, vec2(50.0, 20.0));
+        // The wrapped line starts with the word, not the space before it
+        assert_eq!(layout.quads[4].min.x, 1.0);
+    }
+
+    #[test]
+    fn long_words_break_between_characters() {
+        let options = LayoutOptions {
+            max_width: Some(25.0),
+            ..LayoutOptions::default()
+        };
+        let layout = layout("aaaaa", &options);
+        assert_eq!(layout.line_count, 3);
+        assert_eq!(layout.size, vec2(20.0, 30.0));
+    }
+
+    #[test]
+    fn alignment_within_max_width() {
+        let center = LayoutOptions {
+            max_width: Some(100.0),
+            alignment: Alignment::Center,
+            ..LayoutOptions::default()
+        };
+        assert_eq!(layout("ab", &center).quads[0].min.x, 41.0);
+        let right = LayoutOptions {
+            alignment: Alignment::Right,
+            ..center
+        };
+        assert_eq!(layout("ab", &right).quads[0].min.x, 81.0);
+    }
+
+    #[test]
+    fn newlines() {
+        let options = LayoutOptions::default();
+        assert_eq!(layout("", &options).line_count, 1);
+        assert_eq!(layout("a\nb", &options).line_count, 2);
+        assert_eq!(layout("a\n\nb", &options).line_count, 3);
+
+        let trailing = layout("a\n", &options);
+        assert_eq!(trailing.line_count, 2);
+        assert_eq!(trailing.size.y, 20.0);
+        assert_eq!(layout("\n", &options).line_count, 2);
+    }
+
+    #[test]
+    fn line_spacing() {
+        let options = LayoutOptions {
+            line_spacing: 1.5,
+            ..LayoutOptions::default()
+        };
+        assert_eq!(layout("a\nb", &options).size.y, 30.0);
+    }
+
+    #[test]
+    fn markup_colors_and_sizes() {
+        let white = vec4(1.0, 1.0, 1.0, 1.0);
+        let chars = parse_markup("a[color=red]b[size=20]c[/size][/color]d", white, 10.0);
+        let styles: Vec<(char, Color4, f32)> =
+            chars.iter().map(|sc| (sc.c, sc.color, sc.size)).collect();
+        let red = vec4(1.0, 0.0, 0.0, 1.0);
+        assert_eq!(
+            styles,
+            vec![
+                ('a', white, 10.0),
+                ('b', red, 10.0),
+                ('c', red, 20.0),
+                ('d', white, 10.0),
+            ]
+        );
+
+        let options = LayoutOptions {
+            markup: true,
+            ..LayoutOptions::default()
+        };
+        let layout = layout("a[size=20]b", &options);
+        assert_eq!(layout.size, vec2(30.0, 20.0));
+    }
+
+    #[test]
+    fn markup_that_isnt_a_tag_is_text() {
+        let white = vec4(1.0, 1.0, 1.0, 1.0);
+        let text =
+            |s: &str| -> String { parse_markup(s, white, 10.0).iter().map(|sc| sc.c).collect() };
+        assert_eq!(text("[[color=red]"), "[color=red]");
+        assert_eq!(text("[unknown]x"), "[unknown]x");
+        assert_eq!(text("[/color]x"), "[/color]x");
+        assert_eq!(text("[color=nope]x"), "[color=nope]x");
+        assert_eq!(text("[size=-3]x"), "[size=-3]x");
+        assert_eq!(text("a[b"), "a[b");
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("green"), Some(vec4(0.0, 1.0, 0.0, 1.0)));
+        assert_eq!(parse_color("#ff0000"), Some(vec4(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(parse_color("#00ff0000"), Some(vec4(0.0, 1.0, 0.0, 0.0)));
+        assert_eq!(parse_color("#ff00"), None);
+        assert_eq!(parse_color("ff0000"), None);
+        assert_eq!(parse_color("#gg0000"), None);
+    }
+}
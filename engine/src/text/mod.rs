@@ -0,0 +1,57 @@
+pub mod font;
+pub mod layout;
+pub mod renderer;
+pub mod sdf;
+
+use glm::Vec2;
+
+pub use self::font::*;
+pub use self::layout::*;
+pub use self::renderer::*;
+pub use self::sdf::*;
+
+/// A piece of screen space text that keeps its layout until the string or options change.
+pub struct Text {
+    val: String,
+    pub position: Vec2,
+    options: LayoutOptions,
+    layout: Option<TextLayout>,
+}
+
+impl Text {
+    pub fn new(val: &str, position: Vec2) -> Text {
+        Text::with_options(val, position, LayoutOptions::default())
+    }
+
+    pub fn with_options(val: &str, position: Vec2, options: LayoutOptions) -> Text {
+        Text {
+            val: val.to_string(),
+            position: position,
+            options: options,
+            layout: None,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.val
+    }
+
+    pub fn set_value(&mut self, val: &str) {
+        if self.val != val {
+            self.val = val.to_string();
+            self.layout = None;
+        }
+    }
+
+    pub fn set_options(&mut self, options: LayoutOptions) {
+        self.options = options;
+        self.layout = None;
+    }
+
+    pub fn draw(&mut self, renderer: &mut TextRenderer) {
+        if self.layout.is_none() {
+            self.layout = Some(layout_text(renderer.font(), &self.val, &self.options));
+        }
+        renderer.queue_layout(self.layout.as_ref().unwrap(), self.position);
+    }
+}
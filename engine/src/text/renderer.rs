@@ -0,0 +1,421 @@
+use glm::{vec2, vec4, Vec2};
+use shader::{ShaderError, ShaderProgram, UniformValue};
+use std::sync::Arc;
+use vulkano::buffer::cpu_pool::CpuBufferPool;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, PersistentDescriptorSet};
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::pipeline::vertex::SingleBufferDefinition;
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+
+use super::font::{Font, GlyphAtlas};
+use super::layout::{layout_text, LayoutOptions, TextLayout};
+use super::sdf::{SdfFont, SdfParams, SdfStyle};
+
+#[derive(Default, Clone, Debug)]
+pub struct TextVertex {
+    position: [f32; 2],
+    tex_coord: [f32; 2],
+    color: [f32; 4],
+}
+vulkano::impl_vertex!(TextVertex, position, tex_coord, color);
+
+pub const TEXT_SHADER: &str = "text";
+pub const TEXT_SDF_SHADER: &str = "text_sdf";
+
+/// The push constants of text.vs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextPushConstants {
+    screen_size: [f32; 2],
+}
+
+type TextPipeline = GraphicsPipeline<
+    SingleBufferDefinition<TextVertex>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+/// Distance field text queued with one style and size, which share the shader parameters.
+struct SdfBatch {
+    params: SdfParams,
+    vertices: Vec<TextVertex>,
+}
+
+/// Draws laid out text in screen space, in pixels from the top left corner of the window.
+///
+/// Bitmap text uses the `Font` at the sizes it was rasterized at. Distance field text, queued with
+/// `queue_sdf_text`, stays crisp at any size and can have an outline, shadow and glow.
+///
+/// Text is queued during the frame and drawn in one go in the final post-processing pass,
+/// with depth testing off so it ends up on top of everything else.
+pub struct TextRenderer {
+    font: Font,
+    sdf_font: SdfFont,
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: ShaderProgram,
+    pipeline: Arc<TextPipeline>,
+    sdf_program: ShaderProgram,
+    sdf_pipeline: Arc<TextPipeline>,
+    vertex_pool: CpuBufferPool<TextVertex>,
+    sampler: Arc<Sampler>,
+    atlas_set: Option<Arc<dyn DescriptorSet + Send + Sync>>,
+    atlas_version: Option<u32>,
+    sdf_atlas: Option<Arc<ImmutableImage<Format>>>,
+    sdf_atlas_version: Option<u32>,
+    vertices: Vec<TextVertex>,
+    sdf_batches: Vec<SdfBatch>,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: Arc<Device>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+        font: Font,
+    ) -> TextRenderer {
+        let program = match ShaderProgram::create_program(device.clone(), TEXT_SHADER) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the text shader:\n{}", e),
+        };
+        let pipeline = match create_pipeline(device.clone(), render_pass.clone(), &program) {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the text pipeline:\n{}", e),
+        };
+        let sdf_program = match ShaderProgram::create_program(device.clone(), TEXT_SDF_SHADER) {
+            Ok(program) => program,
+            Err(e) => panic!("Failed to load the distance field text shader:\n{}", e),
+        };
+        let sdf_pipeline = match create_pipeline(device.clone(), render_pass.clone(), &sdf_program)
+        {
+            Ok(pipeline) => pipeline,
+            Err(e) => panic!("Failed to create the distance field text pipeline:\n{}", e),
+        };
+
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .unwrap();
+
+        TextRenderer {
+            sdf_font: SdfFont::new(&font),
+            font: font,
+            vertex_pool: CpuBufferPool::vertex_buffer(device.clone()),
+            device: device,
+            render_pass: render_pass,
+            program: program,
+            pipeline: pipeline,
+            sdf_program: sdf_program,
+            sdf_pipeline: sdf_pipeline,
+            sampler: sampler,
+            atlas_set: None,
+            atlas_version: None,
+            sdf_atlas: None,
+            sdf_atlas_version: None,
+            vertices: Vec::new(),
+            sdf_batches: Vec::new(),
+        }
+    }
+
+    pub fn font(&mut self) -> &mut Font {
+        &mut self.font
+    }
+
+    pub fn sdf_font(&mut self) -> &mut SdfFont {
+        &mut self.sdf_font
+    }
+
+    /// Recompiles text.vs and text.fs, keeping the current pipeline if that fails.
+    pub fn reload_shader(&mut self) -> Result<(), ShaderError> {
+        let program = self.program.reload(self.device.clone(), TEXT_SHADER)?;
+        self.pipeline = create_pipeline(self.device.clone(), self.render_pass.clone(), &program)?;
+        self.program = program;
+        Ok(())
+    }
+
+    /// Recompiles text_sdf.vs and text_sdf.fs, keeping the current pipeline if that fails.
+    pub fn reload_sdf_shader(&mut self) -> Result<(), ShaderError> {
+        let program = self
+            .sdf_program
+            .reload(self.device.clone(), TEXT_SDF_SHADER)?;
+        self.sdf_pipeline =
+            create_pipeline(self.device.clone(), self.render_pass.clone(), &program)?;
+        self.sdf_program = program;
+        Ok(())
+    }
+
+    /// Lays out `text` and queues it to be drawn with its top left corner at `position`.
+    pub fn queue_text(&mut self, text: &str, position: Vec2, options: &LayoutOptions) -> Vec2 {
+        let layout = layout_text(&mut self.font, text, options);
+        self.queue_layout(&layout, position);
+        layout.size
+    }
+
+    pub fn queue_layout(&mut self, layout: &TextLayout, position: Vec2) {
+        push_quads(&mut self.vertices, layout, position);
+    }
+
+    /// Lays out `text` with the distance field font at `pixel_size`, for `queue_sdf_layout`.
+    pub fn layout_sdf_text(
+        &mut self,
+        text: &str,
+        pixel_size: f32,
+        options: &LayoutOptions,
+    ) -> TextLayout {
+        self.sdf_font.set_pixel_size(pixel_size);
+        layout_text(&mut self.sdf_font, text, options)
+    }
+
+    /// Queues distance field text laid out by `layout_sdf_text` at `pixel_size`, drawn with
+    /// `style`. The color of the text is the style's color times the layout's.
+    pub fn queue_sdf_layout(
+        &mut self,
+        layout: &TextLayout,
+        position: Vec2,
+        pixel_size: f32,
+        style: &SdfStyle,
+    ) {
+        let params = self.sdf_font.shader_params(style, pixel_size);
+        let same_batch = match self.sdf_batches.last() {
+            Some(batch) => batch.params == params,
+            None => false,
+        };
+        if !same_batch {
+            self.sdf_batches.push(SdfBatch {
+                params: params,
+                vertices: Vec::new(),
+            });
+        }
+        push_quads(
+            &mut self.sdf_batches.last_mut().unwrap().vertices,
+            layout,
+            position,
+        );
+    }
+
+    /// Lays out `text` with the distance field font and queues it with its top left corner at
+    /// `position`. Returns the size of the laid out text.
+    pub fn queue_sdf_text(
+        &mut self,
+        text: &str,
+        position: Vec2,
+        pixel_size: f32,
+        options: &LayoutOptions,
+        style: &SdfStyle,
+    ) -> Vec2 {
+        let layout = self.layout_sdf_text(text, pixel_size, options);
+        self.queue_sdf_layout(&layout, position, pixel_size, style);
+        layout.size
+    }
+
+    /// Uploads the glyph atlases if glyphs were added since the last upload. The returned future
+    /// has to be joined into the frame before drawing.
+    pub fn upload_atlas(&mut self, queue: Arc<Queue>) -> Option<Box<dyn GpuFuture>> {
+        let bitmap = self.upload_bitmap_atlas(queue.clone());
+        let sdf = self.upload_sdf_atlas(queue);
+        match (bitmap, sdf) {
+            (Some(bitmap), Some(sdf)) => Some(Box::new(bitmap.join(sdf))),
+            (bitmap, sdf) => bitmap.or(sdf),
+        }
+    }
+
+    fn upload_sdf_atlas(&mut self, queue: Arc<Queue>) -> Option<Box<dyn GpuFuture>> {
+        let version = self.sdf_font.atlas().version();
+        if self.sdf_atlas_version == Some(version) {
+            return None;
+        }
+        let (image, future) = upload_image(self.sdf_font.atlas(), queue);
+        self.sdf_atlas = Some(image);
+        self.sdf_atlas_version = Some(version);
+        Some(future)
+    }
+
+    fn upload_bitmap_atlas(&mut self, queue: Arc<Queue>) -> Option<Box<dyn GpuFuture>> {
+        let version = self.font.atlas().version();
+        if self.atlas_version == Some(version) {
+            return None;
+        }
+        let (image, future) = upload_image(self.font.atlas(), queue);
+
+        let layout = self.pipeline.descriptor_set_layout(0).unwrap();
+        self.atlas_set = Some(Arc::new(
+            PersistentDescriptorSet::start(layout.clone())
+                .add_sampled_image(image, self.sampler.clone())
+                .unwrap()
+                .build()
+                .unwrap(),
+        ));
+        self.atlas_version = Some(version);
+        Some(future)
+    }
+
+    /// Records the queued text into the current render pass and clears the queue.
+    pub fn draw(
+        &mut self,
+        builder: AutoCommandBufferBuilder,
+        dimensions: [u32; 2],
+    ) -> AutoCommandBufferBuilder {
+        let dynamic_state = DynamicState {
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            ..DynamicState::none()
+        };
+
+        let push_constants = TextPushConstants {
+            screen_size: [dimensions[0] as f32, dimensions[1] as f32],
+        };
+
+        let mut builder = builder;
+        match self.atlas_set {
+            Some(ref set) if !self.vertices.is_empty() => {
+                let vertex_buffer = self.vertex_pool.chunk(self.vertices.drain(..)).unwrap();
+                builder = builder
+                    .draw(
+                        self.pipeline.clone(),
+                        &dynamic_state,
+                        vertex_buffer,
+                        set.clone(),
+                        push_constants,
+                    )
+                    .unwrap();
+            }
+            _ => self.vertices.clear(),
+        }
+
+        let batches = ::std::mem::replace(&mut self.sdf_batches, Vec::new());
+        let atlas = match self.sdf_atlas {
+            Some(ref atlas) => atlas.clone(),
+            None => return builder,
+        };
+        let layout = self.sdf_pipeline.descriptor_set_layout(0).unwrap().clone();
+        for batch in batches {
+            if batch.vertices.is_empty() {
+                continue;
+            }
+            set_sdf_params(&mut self.sdf_program, &batch.params);
+            let set = Arc::new(
+                PersistentDescriptorSet::start(layout.clone())
+                    .add_sampled_image(atlas.clone(), self.sampler.clone())
+                    .unwrap()
+                    .add_buffer(self.sdf_program.uniform_buffer(0, 1).unwrap())
+                    .unwrap()
+                    .build()
+                    .unwrap(),
+            );
+            let vertex_buffer = self.vertex_pool.chunk(batch.vertices).unwrap();
+            builder = builder
+                .draw(
+                    self.sdf_pipeline.clone(),
+                    &dynamic_state,
+                    vertex_buffer,
+                    set,
+                    push_constants,
+                )
+                .unwrap();
+        }
+        builder
+    }
+}
+
+/// Two triangles per glyph quad, moved by `position`.
+fn push_quads(vertices: &mut Vec<TextVertex>, layout: &TextLayout, position: Vec2) {
+    for quad in &layout.quads {
+        let min = quad.min + position;
+        let max = quad.max + position;
+        let color = [quad.color.x, quad.color.y, quad.color.z, quad.color.w];
+        let corners = [
+            (vec2(min.x, min.y), vec2(quad.uv_min.x, quad.uv_min.y)),
+            (vec2(max.x, min.y), vec2(quad.uv_max.x, quad.uv_min.y)),
+            (vec2(max.x, max.y), vec2(quad.uv_max.x, quad.uv_max.y)),
+            (vec2(min.x, max.y), vec2(quad.uv_min.x, quad.uv_max.y)),
+        ];
+        for &i in &[0, 1, 2, 0, 2, 3] {
+            let (pos, uv) = corners[i];
+            vertices.push(TextVertex {
+                position: [pos.x, pos.y],
+                tex_coord: [uv.x, uv.y],
+                color: color,
+            });
+        }
+    }
+}
+
+fn upload_image(
+    atlas: &GlyphAtlas,
+    queue: Arc<Queue>,
+) -> (Arc<ImmutableImage<Format>>, Box<dyn GpuFuture>) {
+    let (width, height) = atlas.size();
+    let (image, future) = ImmutableImage::from_iter(
+        atlas.data().iter().cloned(),
+        Dimensions::Dim2d {
+            width: width,
+            height: height,
+        },
+        Format::R8Unorm,
+        queue,
+    )
+    .unwrap();
+    (image, Box::new(future))
+}
+
+/// Fills the `SdfParams` block of text_sdf.fs.
+fn set_sdf_params(program: &mut ShaderProgram, params: &SdfParams) {
+    let color = |c: [f32; 4]| UniformValue::Vec4(vec4(c[0], c[1], c[2], c[3]));
+    let values = [
+        ("textColor", color(params.color)),
+        ("outlineColor", color(params.outline_color)),
+        ("shadowColor", color(params.shadow_color)),
+        ("glowColor", color(params.glow_color)),
+        (
+            "shadowOffset",
+            UniformValue::Vec2(vec2(params.shadow_offset[0], params.shadow_offset[1])),
+        ),
+        ("outlineWidth", UniformValue::Float(params.outline_width)),
+        (
+            "shadowSoftness",
+            UniformValue::Float(params.shadow_softness),
+        ),
+        ("glowWidth", UniformValue::Float(params.glow_width)),
+    ];
+    for &(name, value) in &values {
+        program.set(name, value).unwrap();
+    }
+}
+
+fn create_pipeline(
+    device: Arc<Device>,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    program: &ShaderProgram,
+) -> Result<Arc<TextPipeline>, ShaderError> {
+    let pipeline = GraphicsPipeline::start()
+        .vertex_input(SingleBufferDefinition::<TextVertex>::new())
+        .vertex_shader(program.vertex_entry_point(), ())
+        .triangle_list()
+        .viewports_dynamic_scissors_irrelevant(1)
+        .fragment_shader(program.fragment_entry_point(), ())
+        .depth_stencil_disabled()
+        .blend_alpha_blending()
+        .render_pass(Subpass::from(render_pass, 0).unwrap())
+        .build(device)?;
+    Ok(Arc::new(pipeline))
+}
@@ -0,0 +1,474 @@
+use drawing::Color4;
+use glm::{vec2, vec4, Vec2};
+use rusttype::{self, Contour, Point, Scale, Segment};
+use std::collections::HashMap;
+
+use super::font::{Font, Glyph, GlyphAtlas, LineMetrics};
+use super::layout::GlyphSource;
+
+/// Pixel size glyphs are stored at in the distance field atlas. Drawing at other sizes scales the quads.
+const SDF_BASE_SIZE: f32 = 48.0;
+/// How far from the outline, in atlas pixels, the distance field reaches before it saturates.
+const SDF_SPREAD: f32 = 6.0;
+/// Line segments each quadratic curve is flattened into before measuring distances.
+const CURVE_SUBDIVISIONS: usize = 8;
+const INITIAL_ATLAS_SIZE: u32 = 512;
+
+/// Outline, drop shadow and glow settings for distance field text. Widths are in pixels at the size
+/// the text is drawn at.
+#[derive(Clone, Copy, Debug)]
+pub struct SdfStyle {
+    pub color: Color4,
+    pub outline_width: f32,
+    pub outline_color: Color4,
+    pub shadow_offset: Vec2,
+    pub shadow_softness: f32,
+    pub shadow_color: Color4,
+    pub glow_width: f32,
+    pub glow_color: Color4,
+}
+
+impl Default for SdfStyle {
+    fn default() -> SdfStyle {
+        SdfStyle {
+            color: vec4(1.0, 1.0, 1.0, 1.0),
+            outline_width: 0.0,
+            outline_color: vec4(0.0, 0.0, 0.0, 1.0),
+            shadow_offset: vec2(0.0, 0.0),
+            shadow_softness: 0.0,
+            shadow_color: vec4(0.0, 0.0, 0.0, 0.0),
+            glow_width: 0.0,
+            glow_color: vec4(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// `SdfStyle` converted to distance field units, laid out to match the uniform block in text_sdf.fs.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SdfParams {
+    pub color: [f32; 4],
+    pub outline_color: [f32; 4],
+    pub shadow_color: [f32; 4],
+    pub glow_color: [f32; 4],
+    /// Shadow offset in texture coordinates.
+    pub shadow_offset: [f32; 2],
+    pub outline_width: f32,
+    pub shadow_softness: f32,
+    pub glow_width: f32,
+    pub _padding: [f32; 3],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct SdfKey(char);
+
+/// A font whose glyphs are stored as signed distance fields, so they stay crisp at any scale.
+/// Inside the outline the field is above 0.5, outside it is below, reaching 0 and 1 at `SDF_SPREAD`
+/// atlas pixels from the edge.
+pub struct SdfFont {
+    font: rusttype::Font<'static>,
+    pixel_size: f32,
+    glyphs: HashMap<SdfKey, Glyph>,
+    atlas: GlyphAtlas,
+}
+
+impl SdfFont {
+    pub fn new(font: &Font) -> SdfFont {
+        SdfFont {
+            font: font.inner().clone(),
+            pixel_size: font.pixel_size(),
+            glyphs: HashMap::new(),
+            atlas: GlyphAtlas::new(INITIAL_ATLAS_SIZE, INITIAL_ATLAS_SIZE),
+        }
+    }
+
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
+    /// The size text is laid out at, unless markup changes it.
+    pub fn set_pixel_size(&mut self, pixel_size: f32) {
+        self.pixel_size = pixel_size;
+    }
+
+    pub fn spread(&self) -> f32 {
+        SDF_SPREAD
+    }
+
+    /// Glyph at the atlas base size. Use `GlyphSource::glyph_sized` for metrics at other sizes.
+    pub fn base_glyph(&mut self, c: char) -> Glyph {
+        if let Some(glyph) = self.glyphs.get(&SdfKey(c)) {
+            return *glyph;
+        }
+        let glyph = self.generate(c);
+        self.glyphs.insert(SdfKey(c), glyph);
+        glyph
+    }
+
+    pub fn preload(&mut self, s: &str) {
+        for c in s.chars() {
+            self.base_glyph(c);
+        }
+    }
+
+    /// Converts `style` for text drawn at `pixel_size` into the values text_sdf.fs expects.
+    pub fn shader_params(&self, style: &SdfStyle, pixel_size: f32) -> SdfParams {
+        // One screen pixel at this size, in distance field units
+        let pixel = (SDF_BASE_SIZE / pixel_size) / (2.0 * SDF_SPREAD);
+        let (atlas_w, atlas_h) = self.atlas.size();
+        let atlas_scale = SDF_BASE_SIZE / pixel_size;
+        SdfParams {
+            color: style.color.into(),
+            outline_color: style.outline_color.into(),
+            shadow_color: style.shadow_color.into(),
+            glow_color: style.glow_color.into(),
+            shadow_offset: [
+                style.shadow_offset.x * atlas_scale / atlas_w as f32,
+                style.shadow_offset.y * atlas_scale / atlas_h as f32,
+            ],
+            outline_width: (style.outline_width * pixel).min(0.5),
+            shadow_softness: style.shadow_softness * pixel,
+            glow_width: (style.glow_width * pixel).min(0.5),
+            _padding: [0.0; 3],
+        }
+    }
+
+    fn generate(&mut self, c: char) -> Glyph {
+        let scaled = self.font.glyph(c).scaled(Scale::uniform(SDF_BASE_SIZE));
+        let advance = scaled.h_metrics().advance_width;
+        let (bounds, contours) = match (scaled.exact_bounding_box(), scaled.shape()) {
+            (Some(bounds), Some(contours)) => (bounds, contours),
+            _ => {
+                return Glyph {
+                    advance: advance,
+                    bearing: vec2(0.0, 0.0),
+                    width: 0,
+                    height: 0,
+                    atlas_x: 0,
+                    atlas_y: 0,
+                }
+            }
+        };
+
+        let origin = vec2(
+            bounds.min.x.floor() - SDF_SPREAD,
+            bounds.min.y.floor() - SDF_SPREAD,
+        );
+        let width = (bounds.max.x.ceil() - bounds.min.x.floor() + 2.0 * SDF_SPREAD) as u32;
+        let height = (bounds.max.y.ceil() - bounds.min.y.floor() + 2.0 * SDF_SPREAD) as u32;
+
+        let field = generate_sdf(&contours, origin, width, height, SDF_SPREAD);
+        let (atlas_x, atlas_y) = self.atlas.allocate(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                self.atlas
+                    .write(atlas_x + x, atlas_y + y, field[(x + y * width) as usize]);
+            }
+        }
+
+        Glyph {
+            advance: advance,
+            bearing: origin,
+            width: width,
+            height: height,
+            atlas_x: atlas_x,
+            atlas_y: atlas_y,
+        }
+    }
+}
+
+impl GlyphSource for SdfFont {
+    fn pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    fn glyph_sized(&mut self, c: char, pixel_size: f32) -> Glyph {
+        let scale = pixel_size / SDF_BASE_SIZE;
+        let glyph = self.base_glyph(c);
+        Glyph {
+            advance: glyph.advance * scale,
+            bearing: glyph.bearing * scale,
+            ..glyph
+        }
+    }
+
+    fn kerning_sized(&self, first: char, second: char, pixel_size: f32) -> f32 {
+        self.font
+            .pair_kerning(Scale::uniform(pixel_size), first, second)
+    }
+
+    fn line_metrics_sized(&self, pixel_size: f32) -> LineMetrics {
+        let v = self.font.v_metrics(Scale::uniform(pixel_size));
+        LineMetrics {
+            ascent: v.ascent,
+            descent: v.descent,
+            line_gap: v.line_gap,
+        }
+    }
+
+    fn atlas_size(&self) -> (u32, u32) {
+        self.atlas.size()
+    }
+
+    fn glyph_scale(&self, pixel_size: f32) -> f32 {
+        pixel_size / SDF_BASE_SIZE
+    }
+}
+
+/// Computes a `width` x `height` distance field for `contours`, where pixel (0, 0) starts at `origin`
+/// in y down glyph space. Contours are in rusttype's y up outline space.
+pub fn generate_sdf(
+    contours: &[Contour],
+    origin: Vec2,
+    width: u32,
+    height: u32,
+    spread: f32,
+) -> Vec<u8> {
+    let edges = flatten(contours);
+    let mut field = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            // Sample at the pixel center, flipping into the outline's y up space
+            let p = vec2(origin.x + x as f32 + 0.5, -(origin.y + y as f32 + 0.5));
+            let mut distance = edges
+                .iter()
+                .map(|&(a, b)| distance_to_segment(p, a, b))
+                .fold(::std::f32::MAX, f32::min);
+            if winding_number(p, &edges) == 0 {
+                distance = -distance;
+            }
+            let value = 0.5 + distance / (2.0 * spread);
+            field.push((value.max(0.0).min(1.0) * 255.0) as u8);
+        }
+    }
+    field
+}
+
+fn flatten(contours: &[Contour]) -> Vec<(Vec2, Vec2)> {
+    let to_vec = |p: Point<f32>| vec2(p.x, p.y);
+    let mut edges = Vec::new();
+    for contour in contours {
+        for segment in &contour.segments {
+            match *segment {
+                Segment::Line(ref line) => edges.push((to_vec(line.p[0]), to_vec(line.p[1]))),
+                Segment::Curve(ref curve) => {
+                    let (p0, p1, p2) = (to_vec(curve.p[0]), to_vec(curve.p[1]), to_vec(curve.p[2]));
+                    let mut prev = p0;
+                    for i in 1..=CURVE_SUBDIVISIONS {
+                        let t = i as f32 / CURVE_SUBDIVISIONS as f32;
+                        let mt = 1.0 - t;
+                        let next = p0 * (mt * mt) + p1 * (2.0 * mt * t) + p2 * (t * t);
+                        edges.push((prev, next));
+                        prev = next;
+                    }
+                }
+            }
+        }
+    }
+    edges
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len_sq = ab.dot(&ab);
+    let t = if len_sq > 0.0 {
+        ((p - a).dot(&ab) / len_sq).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+    (p - (a + ab * t)).norm()
+}
+
+/// Non-zero winding rule, which is what TrueType outlines are defined by.
+fn winding_number(p: Vec2, edges: &[(Vec2, Vec2)]) -> i32 {
+    let mut winding = 0;
+    for &(a, b) in edges {
+        let side = (b.x - a.x) * (p.y - a.y) - (p.x - a.x) * (b.y - a.y);
+        if a.y <= p.y {
+            if b.y > p.y && side > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= p.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusttype::{point, Curve, Line};
+    use shader::{compile_glsl, reflect, DescriptorKind, ShaderStage};
+    use std::mem;
+
+    /// A square outline from `min` to `max` in y up space, turning counterclockwise unless
+    /// `clockwise`.
+    fn square(min: f32, max: f32, clockwise: bool) -> Contour {
+        let mut corners = [
+            point(min, min),
+            point(max, min),
+            point(max, max),
+            point(min, max),
+        ];
+        if clockwise {
+            corners.reverse();
+        }
+        let segments = (0..4)
+            .map(|i| {
+                Segment::Line(Line {
+                    p: [corners[i], corners[(i + 1) % 4]],
+                })
+            })
+            .collect();
+        Contour { segments: segments }
+    }
+
+    /// The field of `contours` over -8..18 on both axes, one pixel per unit, with a spread of 4.
+    /// Pixel (x, y) is centered on (x - 7.5, 17.5 - y) in y up space.
+    fn field(contours: &[Contour]) -> Vec<u8> {
+        generate_sdf(contours, vec2(-8.0, -18.0), 26, 26, 4.0)
+    }
+
+    fn at(field: &[u8], x: f32, y: f32) -> u8 {
+        let column = (x + 7.5) as usize;
+        let row = (17.5 - y) as usize;
+        field[column + row * 26]
+    }
+
+    #[test]
+    fn inside_is_above_half_and_outside_below() {
+        let field = field(&[square(0.0, 10.0, false)]);
+        assert_eq!(field.len(), 26 * 26);
+        assert_eq!(at(&field, 4.5, 4.5), 255);
+        assert_eq!(at(&field, -7.5, 17.5), 0);
+        // Half a pixel from the edge on either side
+        assert_eq!(at(&field, 0.5, 4.5), 143);
+        assert_eq!(at(&field, -0.5, 4.5), 111);
+        assert_eq!(at(&field, 4.5, 9.5), 143);
+        assert_eq!(at(&field, 4.5, 10.5), 111);
+
+        // Which way the outline turns doesn't matter
+        assert_eq!(field, self::field(&[square(0.0, 10.0, true)]));
+    }
+
+    #[test]
+    fn distances_saturate_at_the_spread() {
+        let field = field(&[square(0.0, 10.0, false)]);
+        let row: Vec<u8> = (0..9).map(|i| at(&field, i as f32 - 7.5, 4.5)).collect();
+        assert_eq!(row, vec![0, 0, 0, 0, 15, 47, 79, 111, 143]);
+        assert_eq!(at(&field, 3.5, 4.5), 239);
+        assert_eq!(at(&field, 4.5, 4.5), 255);
+    }
+
+    #[test]
+    fn holes_turn_the_other_way() {
+        let contours = [square(0.0, 10.0, false), square(3.0, 7.0, true)];
+        let edges = flatten(&contours);
+        assert_eq!(edges.len(), 8);
+        assert_eq!(winding_number(vec2(1.5, 5.0), &edges), 1);
+        assert_eq!(winding_number(vec2(5.0, 5.0), &edges), 0);
+        assert_eq!(winding_number(vec2(-1.0, 5.0), &edges), 0);
+        assert_eq!(winding_number(vec2(5.0, 11.0), &edges), 0);
+
+        let field = field(&contours);
+        assert!(at(&field, 1.5, 5.5) > 128);
+        assert!(at(&field, 4.5, 4.5) < 128);
+        assert_eq!(at(&field, 5.5, 5.5), 79);
+
+        // Overlapping contours that turn the same way both fill
+        let overlapping = [square(0.0, 10.0, false), square(3.0, 7.0, false)];
+        assert_eq!(winding_number(vec2(5.0, 5.0), &flatten(&overlapping)), 2);
+    }
+
+    #[test]
+    fn curves_are_flattened_into_lines() {
+        let contour = Contour {
+            segments: vec![
+                Segment::Curve(Curve {
+                    p: [point(0.0, 0.0), point(5.0, 10.0), point(10.0, 0.0)],
+                }),
+                Segment::Line(Line {
+                    p: [point(10.0, 0.0), point(0.0, 0.0)],
+                }),
+            ],
+        };
+        let edges = flatten(&[contour]);
+        assert_eq!(edges.len(), CURVE_SUBDIVISIONS + 1);
+        assert_eq!(edges[0].0, vec2(0.0, 0.0));
+        assert_eq!(edges[CURVE_SUBDIVISIONS - 1].1, vec2(10.0, 0.0));
+        // The middle of the curve is halfway to the control point
+        assert_eq!(edges[CURVE_SUBDIVISIONS / 2].0, vec2(5.0, 5.0));
+        for pair in edges.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn params_are_in_distance_field_units() {
+        let font = SdfFont::new(&Font::new());
+        let style = SdfStyle {
+            color: vec4(1.0, 0.5, 0.0, 1.0),
+            outline_width: 1.5,
+            shadow_offset: vec2(2.0, -1.0),
+            shadow_softness: 3.0,
+            glow_width: 6.0,
+            ..SdfStyle::default()
+        };
+        // At half the base size a screen pixel is 2 atlas pixels, a sixth of the field's range
+        let params = font.shader_params(&style, SDF_BASE_SIZE / 2.0);
+        assert_eq!(params.color, [1.0, 0.5, 0.0, 1.0]);
+        assert_eq!(params.outline_width, 0.25);
+        assert_eq!(params.shadow_softness, 0.5);
+        // Widths past the edge of the field are cut off there
+        assert_eq!(params.glow_width, 0.5);
+        let size = INITIAL_ATLAS_SIZE as f32;
+        assert_eq!(params.shadow_offset, [4.0 / size, -2.0 / size]);
+    }
+
+    #[test]
+    fn params_match_the_shader_block() {
+        let spirv = compile_glsl(
+            include_str!("../shaders/text_sdf.fs"),
+            ShaderStage::Fragment,
+            "text_sdf.fs",
+        )
+        .unwrap();
+        let reflection = reflect(&spirv).unwrap();
+        let layout = match reflection.descriptor(0, 1).map(|d| d.kind.clone()) {
+            Some(DescriptorKind::UniformBuffer(layout)) => layout,
+            kind => panic!("expected a uniform buffer, got {:?}", kind),
+        };
+
+        let params = SdfParams {
+            color: [0.0; 4],
+            outline_color: [0.0; 4],
+            shadow_color: [0.0; 4],
+            glow_color: [0.0; 4],
+            shadow_offset: [0.0; 2],
+            outline_width: 0.0,
+            shadow_softness: 0.0,
+            glow_width: 0.0,
+            _padding: [0.0; 3],
+        };
+        let base = &params as *const SdfParams as usize;
+        let offset = |field: *const f32| (field as usize - base) as u32;
+        let fields = [
+            ("textColor", offset(params.color.as_ptr())),
+            ("outlineColor", offset(params.outline_color.as_ptr())),
+            ("shadowColor", offset(params.shadow_color.as_ptr())),
+            ("glowColor", offset(params.glow_color.as_ptr())),
+            ("shadowOffset", offset(params.shadow_offset.as_ptr())),
+            ("outlineWidth", offset(&params.outline_width)),
+            ("shadowSoftness", offset(&params.shadow_softness)),
+            ("glowWidth", offset(&params.glow_width)),
+        ];
+        assert_eq!(layout.members.len(), fields.len());
+        for &(name, offset) in &fields {
+            let member = layout.member(name).expect(name);
+            assert_eq!(member.offset, offset, "{}", name);
+        }
+        assert!(mem::size_of::<SdfParams>() as u32 >= layout.size);
+        assert_eq!(mem::size_of::<SdfParams>() % 16, 0);
+    }
+}
@@ -1,18 +1,111 @@
-#![allow(dead_code)]
 use glm::*;
 
+/// A transform split into the parts people edit: position, rotation as euler angles in radians,
+/// applied around x, then y, then z, and scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Transform {
-    position: Vec2,
-    rotation: Vec2,
-    scale: Vec2,
+    pub position: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
 }
 
 impl Transform {
     pub fn new() -> Transform {
         Transform {
-            position: vec2(0.0, 0.0),
-            rotation: vec2(0.0, 0.0),
-            scale: vec2(1.0, 1.0),
+            position: vec3(0.0, 0.0, 0.0),
+            rotation: vec3(0.0, 0.0, 0.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    /// Splits `matrix`, which has to be made of a translation, rotation and scale without shear.
+    pub fn from_matrix(matrix: &Mat4) -> Transform {
+        let column = |i: usize| vec3(matrix[(0, i)], matrix[(1, i)], matrix[(2, i)]);
+        let scale = vec3(length(&column(0)), length(&column(1)), length(&column(2)));
+        let r = |row: usize, col: usize| {
+            if scale[col] == 0.0 {
+                0.0
+            } else {
+                matrix[(row, col)] / scale[col]
+            }
+        };
+
+        let y = (-r(2, 0)).max(-1.0).min(1.0).asin();
+        // Looking straight up or down, x and z turn around the same axis, so z is left at 0
+        let (x, z) = if r(2, 0).abs() < 0.9999 {
+            (r(2, 1).atan2(r(2, 2)), r(1, 0).atan2(r(0, 0)))
+        } else {
+            ((-r(1, 2)).atan2(r(1, 1)), 0.0)
+        };
+
+        Transform {
+            position: column(3),
+            rotation: vec3(x, y, z),
+            scale: scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        translation(&self.position)
+            * rotation(self.rotation.z, &vec3(0.0, 0.0, 1.0))
+            * rotation(self.rotation.y, &vec3(0.0, 1.0, 0.0))
+            * rotation(self.rotation.x, &vec3(1.0, 0.0, 0.0))
+            * scaling(&self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn matrix_round_trips_through_from_matrix() {
+        let transform = Transform {
+            position: vec3(1.0, -2.0, 3.5),
+            rotation: vec3(0.3, -0.7, 1.2),
+            scale: vec3(2.0, 0.5, 1.5),
+        };
+        let split = Transform::from_matrix(&transform.matrix());
+        assert_close(split.position.as_slice(), transform.position.as_slice());
+        assert_close(split.rotation.as_slice(), transform.rotation.as_slice());
+        assert_close(split.scale.as_slice(), transform.scale.as_slice());
+    }
+
+    #[test]
+    fn identity_splits_into_nothing() {
+        let split = Transform::from_matrix(&Transform::new().matrix());
+        assert_close(split.position.as_slice(), &[0.0, 0.0, 0.0]);
+        assert_close(split.rotation.as_slice(), &[0.0, 0.0, 0.0]);
+        assert_close(split.scale.as_slice(), &[1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn gimbal_lock_keeps_the_matrix() {
+        // Turned a quarter around y, x and z turn around the same axis
+        for &y in &[FRAC_PI_2, -FRAC_PI_2] {
+            let transform = Transform {
+                position: vec3(0.0, 1.0, 0.0),
+                rotation: vec3(0.4, y, 0.25),
+                scale: vec3(1.0, 3.0, 1.0),
+            };
+            let split = Transform::from_matrix(&transform.matrix());
+            assert_eq!(split.rotation.z, 0.0);
+            assert!((split.rotation.y - y).abs() < 1e-3);
+            assert_close(split.scale.as_slice(), transform.scale.as_slice());
+            assert_close(split.matrix().as_slice(), transform.matrix().as_slice());
         }
     }
 }
@@ -1,4 +1,4 @@
-use std::env::current_dir;
+use std::env::{current_dir, current_exe};
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -45,6 +45,13 @@ pub fn path_of(asset_name: &str) -> PathBuf {
     }
 }
 
+/// The directory of the running executable, where files shipped with it are found.
+pub fn executable_dir() -> Option<PathBuf> {
+    current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
 pub fn abolute_path_from_relative(path: &Path) -> std::io::Result<PathBuf> {
     let path_buf = path.to_path_buf();
     let mut current_dir = current_dir()?;
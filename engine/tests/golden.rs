@@ -0,0 +1,94 @@
+//! Renders small scenes headless and compares them against the reference images in
+//! `tests/golden/`. Needs a Vulkan device, see `Renderer` for running them on lavapipe, and
+//! `engine::golden` for updating the references.
+//!
+//! The tests are ignored until their reference images are committed. Create them on lavapipe with
+//! `UPDATE_GOLDEN=1 cargo test -p engine --test golden -- --ignored`, check them by eye, commit
+//! them and drop the `#[ignore]`.
+
+extern crate engine;
+extern crate nalgebra_glm as glm;
+
+use engine::golden::{assert_golden_scene, golden_camera, golden_sun, GoldenSettings};
+use engine::mesh::mesh::Mesh;
+use engine::renderer::{DrawList, Light, Material, ShadowSettings};
+use glm::{identity, rotate_y, translate, vec3};
+
+/// Software and hardware rasterizers disagree on a few edge pixels.
+fn settings() -> GoldenSettings {
+    GoldenSettings {
+        tolerance: 4,
+        max_differing_pixels: 64,
+    }
+}
+
+/// `Mesh::create_box` spans 0 to 1 on x and y and -1 to 0 on z, this one is centered.
+fn unit_cube() -> Mesh {
+    Mesh::create_box(&None).translated(-0.5, -0.5, 0.5)
+}
+
+#[test]
+#[ignore]
+fn lit_cube() {
+    assert_golden_scene("lit_cube", settings(), |renderer| {
+        let cube = renderer.register_mesh(&unit_cube());
+        let material = renderer.register_material(Material::default()).unwrap();
+
+        let (view, projection) = golden_camera();
+        let mut draw_list = DrawList::new(view, projection);
+        draw_list.add_light(Light::directional(
+            vec3(-1.0, -2.0, -1.5),
+            vec3(1.0, 1.0, 1.0),
+            1.0,
+        ));
+        draw_list.add_light(Light::point(
+            vec3(2.0, 1.0, 2.0),
+            vec3(1.0, 0.5, 0.2),
+            2.0,
+            6.0,
+        ));
+        draw_list.draw(cube, material, rotate_y(&identity(), 0.4));
+        draw_list
+    });
+}
+
+/// A cube on a ground plane, lit by the shadow casting golden sun so it throws a shadow across
+/// the plane, next to a spot light's shadow of a smaller cube.
+#[test]
+#[ignore]
+fn shadows() {
+    assert_golden_scene("shadows", settings(), |renderer| {
+        let ground = renderer.register_mesh(&unit_cube().scaled(8.0, 0.1, 8.0));
+        let cube = renderer.register_mesh(&unit_cube());
+        let small_cube = renderer.register_mesh(&unit_cube().scaled(0.4, 0.4, 0.4));
+        let material = renderer.register_material(Material::default()).unwrap();
+
+        let (view, projection) = golden_camera();
+        let mut draw_list = DrawList::new(view, projection);
+        draw_list.add_light(golden_sun());
+        draw_list.add_light(
+            Light::spot(
+                vec3(-2.0, 3.0, 1.0),
+                vec3(0.3, -1.0, -0.2),
+                vec3(0.4, 0.6, 1.0),
+                3.0,
+                10.0,
+                0.3,
+                0.5,
+            )
+            .with_shadows(ShadowSettings::default()),
+        );
+        draw_list.draw(
+            ground,
+            material,
+            translate(&identity(), &vec3(0.0, -0.55, 0.0)),
+        );
+        draw_list.draw(cube, material, rotate_y(&identity(), 0.4));
+        draw_list.draw(
+            small_cube,
+            material,
+            translate(&identity(), &vec3(-1.5, -0.3, 0.5)),
+        );
+        draw_list
+    });
+}